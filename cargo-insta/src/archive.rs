@@ -0,0 +1,349 @@
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use insta::{MetaData, Snapshot, SnapshotContents};
+
+use crate::utils::err_msg;
+
+const BLOCK_SIZE: usize = 512;
+
+/// A file read from an archive.
+#[derive(Debug)]
+pub struct ArchiveFile {
+    /// The path relative to the workspace root.
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+}
+
+/// How an archive is compressed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Returns how the archive at the path is compressed.
+fn compression(path: &Path) -> Result<Compression, Box<dyn Error>> {
+    let name = path.file_name().and_then(|x| x.to_str()).unwrap_or("");
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Ok(Compression::Gzip)
+    } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+        Ok(Compression::Zstd)
+    } else if name.ends_with(".tar") {
+        Ok(Compression::None)
+    } else {
+        Err(err_msg(format!(
+            "unsupported archive {}: use a .tar, .tar.gz or .tar.zst file",
+            path.display()
+        )))
+    }
+}
+
+/// Runs the `zstd` command over the data.
+fn run_zstd(args: &[&str], data: Vec<u8>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut proc = Command::new("zstd")
+        .args(args)
+        .arg("-q")
+        .arg("-c")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            err_msg(format!(
+                "could not run zstd, it is needed for .tar.zst archives: {}",
+                err
+            ))
+        })?;
+    // feed stdin from another thread so that a full stdout pipe cannot
+    // block both processes.
+    let mut stdin = proc.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(&data));
+    let output = proc.wait_with_output()?;
+    writer.join().unwrap().ok();
+    if !output.status.success() {
+        return Err(err_msg(format!(
+            "zstd failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Writes an octal number into a header field including the terminator.
+fn write_octal(field: &mut [u8], value: u64) {
+    let len = field.len() - 1;
+    let digits = format!("{:0width$o}", value, width = len);
+    field[..len].copy_from_slice(digits.as_bytes());
+}
+
+fn read_octal(field: &[u8]) -> Result<u64, Box<dyn Error>> {
+    let digits = std::str::from_utf8(field)?.trim_matches(|c| c == '\0' || c == ' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    Ok(u64::from_str_radix(digits, 8)?)
+}
+
+fn checksum(header: &[u8]) -> u64 {
+    // the checksum field itself counts as spaces
+    header
+        .iter()
+        .enumerate()
+        .map(|(idx, &b)| if (148..156).contains(&idx) { b' ' } else { b } as u64)
+        .sum()
+}
+
+/// Builds a ustar header for a regular file.
+fn make_header(name: &str, size: u64) -> Result<[u8; BLOCK_SIZE], Box<dyn Error>> {
+    let mut header = [0; BLOCK_SIZE];
+    // long names are split into a prefix and a name at a slash
+    let (prefix, name) = if name.len() <= 100 {
+        ("", name)
+    } else {
+        match name[..name.len().min(156)].rfind('/') {
+            Some(idx) if name.len() - idx - 1 <= 100 => (&name[..idx], &name[idx + 1..]),
+            _ => return Err(err_msg(format!("path too long for archive: {}", name))),
+        }
+    };
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], size);
+    write_octal(&mut header[136..148], 0);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    let sum = checksum(&header);
+    write_octal(&mut header[148..155], sum);
+    header[155] = b' ';
+    Ok(header)
+}
+
+/// Writes files of the workspace into an archive.
+///
+/// The files are stored by their path relative to the workspace root.
+pub fn write_archive(
+    archive: &Path,
+    workspace_root: &Path,
+    files: &[PathBuf],
+) -> Result<(), Box<dyn Error>> {
+    let compression = compression(archive)?;
+    let mut rv = vec![];
+    for file in files {
+        let rel_path = file.strip_prefix(workspace_root).map_err(|_| {
+            err_msg(format!(
+                "cannot archive {} as it is outside of the workspace",
+                file.display()
+            ))
+        })?;
+        let name = rel_path
+            .components()
+            .map(|x| x.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let contents = fs::read(file)?;
+        rv.extend_from_slice(&make_header(&name, contents.len() as u64)?);
+        rv.extend_from_slice(&contents);
+        let padding = (BLOCK_SIZE - contents.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        rv.resize(rv.len() + padding, 0);
+    }
+    rv.resize(rv.len() + 2 * BLOCK_SIZE, 0);
+    let data = match compression {
+        Compression::None => rv,
+        Compression::Gzip => insta::gzip(&rv),
+        Compression::Zstd => run_zstd(&[], rv)?,
+    };
+    fs::write(archive, data)?;
+    Ok(())
+}
+
+/// Reads all files from an archive.
+pub fn read_archive(archive: &Path) -> Result<Vec<ArchiveFile>, Box<dyn Error>> {
+    let mut data = fs::read(archive)?;
+    match compression(archive)? {
+        Compression::None => {}
+        Compression::Gzip => data = insta::gunzip(&data)?,
+        Compression::Zstd => data = run_zstd(&["-d"], data)?,
+    }
+    let mut rv = vec![];
+    let mut offset = 0;
+    while offset + BLOCK_SIZE <= data.len() {
+        let header = &data[offset..offset + BLOCK_SIZE];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        if read_octal(&header[148..156])? != checksum(header) {
+            return Err(err_msg("invalid archive: header checksum mismatch"));
+        }
+        let size = read_octal(&header[124..136])? as usize;
+        let start = offset + BLOCK_SIZE;
+        let end = start + size;
+        if end > data.len() {
+            return Err(err_msg("invalid archive: truncated file"));
+        }
+        offset = end + (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+        // only regular files are of interest
+        if header[156] != b'0' && header[156] != 0 {
+            continue;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let field = &header[range];
+            let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+            String::from_utf8_lossy(&field[..len]).into_owned()
+        };
+        let (prefix, name) = (field(345..500), field(0..100));
+        let path = PathBuf::from(if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        });
+        let is_relative = path.components().all(|x| match x {
+            Component::Normal(_) | Component::CurDir => true,
+            _ => false,
+        });
+        if !is_relative {
+            return Err(err_msg(format!(
+                "invalid archive: refusing to extract {}",
+                path.display()
+            )));
+        }
+        rv.push(ArchiveFile {
+            path,
+            contents: data[start..end].to_vec(),
+        });
+    }
+    Ok(rv)
+}
+
+fn is_pending_snapshot(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|x| x.to_str())
+        .map_or(false, |name| {
+            name.ends_with(".snap.new") || name.ends_with(".pending-snap")
+        })
+}
+
+/// Returns the binary and blob files a pending snapshot file refers to.
+fn referenced_files(file: &ArchiveFile) -> Vec<PathBuf> {
+    let contents = match std::str::from_utf8(&file.contents) {
+        Ok(contents) if file.path.to_string_lossy().ends_with(".snap.new") => contents,
+        _ => return vec![],
+    };
+    let mut parts = contents.splitn(3, "---\n");
+    let header = match (parts.next(), parts.next(), parts.next()) {
+        (Some(""), Some(header), Some(_)) => header,
+        _ => return vec![],
+    };
+    let metadata: MetaData = match serde_yaml::from_str(header) {
+        Ok(metadata) => metadata,
+        Err(_) => return vec![],
+    };
+    let snapshot =
+        Snapshot::from_components(String::new(), None, metadata, SnapshotContents::from(""));
+    snapshot
+        .binary_file(&file.path)
+        .into_iter()
+        .chain(snapshot.blob_file(&file.path))
+        .collect()
+}
+
+/// Checks that an archive only holds pending snapshots.
+///
+/// Archives are typically downloaded from CI, so anything else, like
+/// sources or build configuration, is refused.  Binary and blob files are
+/// only allowed if a pending snapshot of the archive refers to them.
+pub fn check_pending_only(files: &[ArchiveFile]) -> Result<(), Box<dyn Error>> {
+    let referenced: Vec<PathBuf> = files.iter().flat_map(referenced_files).collect();
+    let refused: Vec<_> = files
+        .iter()
+        .filter(|x| !is_pending_snapshot(&x.path) && !referenced.contains(&x.path))
+        .map(|x| x.path.display().to_string())
+        .collect();
+    if refused.is_empty() {
+        Ok(())
+    } else {
+        Err(err_msg(format!(
+            "archive contains files that are not pending snapshots, nothing was \
+             extracted:\n  {}",
+            refused.join("\n  ")
+        )))
+    }
+}
+
+#[cfg(test)]
+fn raw_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut rv = vec![];
+    for &(name, contents) in entries {
+        rv.extend_from_slice(&make_header(name, contents.len() as u64).unwrap());
+        rv.extend_from_slice(contents);
+        rv.resize(
+            rv.len() + (BLOCK_SIZE - contents.len() % BLOCK_SIZE) % BLOCK_SIZE,
+            0,
+        );
+    }
+    rv.resize(rv.len() + 2 * BLOCK_SIZE, 0);
+    rv
+}
+
+#[test]
+fn test_archive_roundtrip() {
+    let root = std::env::temp_dir().join(format!("insta-archive-{}", std::process::id()));
+    let long_dir = "nested/".repeat(20);
+    let files = [
+        root.join("tests/snapshots/test__a.snap.new"),
+        root.join(&long_dir).join(".lib.rs.pending-snap"),
+    ];
+    for file in &files {
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, file.to_string_lossy().as_bytes()).unwrap();
+    }
+    let archive = root.join("pending.tar.gz");
+    write_archive(&archive, &root, &files).unwrap();
+    let read = read_archive(&archive);
+    fs::remove_dir_all(&root).ok();
+
+    let read = read.unwrap();
+    assert_eq!(read.len(), 2);
+    assert_eq!(read[0].path, Path::new("tests/snapshots/test__a.snap.new"));
+    assert_eq!(
+        read[1].path,
+        Path::new(&long_dir).join(".lib.rs.pending-snap")
+    );
+    assert_eq!(read[1].contents, files[1].to_string_lossy().as_bytes());
+    assert!(check_pending_only(&read).is_ok());
+}
+
+#[test]
+fn test_archive_path_filtering() {
+    let root = std::env::temp_dir().join(format!("insta-archive-paths-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let read = |name: &str, entries: &[(&str, &[u8])]| {
+        let archive = root.join(name);
+        fs::write(&archive, raw_archive(entries)).unwrap();
+        read_archive(&archive)
+    };
+    let escaping = read("escaping.tar", &[("../outside.snap.new", b"x")]);
+    let absolute = read("absolute.tar", &[("/tmp/outside.snap.new", b"x")]);
+    let nested = read("nested.tar", &[("tests/../../outside.snap.new", b"x")]);
+    let other = read(
+        "other.tar",
+        &[("tests/a.snap.new", b"x"), ("build.rs", b"fn main() {}")],
+    );
+    fs::remove_dir_all(&root).ok();
+
+    for rv in &[escaping, absolute, nested] {
+        let err = rv.as_ref().unwrap_err().to_string();
+        assert!(err.starts_with("invalid archive: refusing to extract"));
+    }
+    let err = check_pending_only(&other.unwrap()).unwrap_err().to_string();
+    assert!(err.ends_with("nothing was extracted:\n  build.rs"));
+    assert!(compression(Path::new("pending.zip")).is_err());
+}
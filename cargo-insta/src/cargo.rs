@@ -2,15 +2,24 @@ use std::collections::HashSet;
 use std::env;
 use std::error::Error;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::time::{Duration, SystemTime};
 
+use globset::GlobMatcher;
 use ignore::overrides::OverrideBuilder;
 use ignore::{DirEntry, WalkBuilder};
-use insta::{PendingInlineSnapshot, Snapshot};
-use serde::Deserialize;
+use insta::{
+    metadata_from_file, snapshot_variant_file, BlobBackend, BlobsConfig, DirBlobBackend,
+    InlineConfig, PendingInlineSnapshot, RustfmtMode, Snapshot, SnapshotContents,
+};
+use serde::{Deserialize, Serialize};
 
+use crate::hunks::HunkDiff;
 use crate::inline::FilePatcher;
+use crate::rustfmt::format_file;
+use crate::trash::Trash;
 use crate::utils::err_msg;
 
 #[derive(Deserialize, Clone, Debug)]
@@ -26,6 +35,8 @@ pub struct Package {
     id: String,
     manifest_path: PathBuf,
     targets: Vec<Target>,
+    #[serde(default)]
+    edition: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -33,19 +44,44 @@ pub struct Metadata {
     packages: Vec<Package>,
     workspace_members: Vec<String>,
     workspace_root: String,
+    target_directory: PathBuf,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Operation {
     Accept,
     Reject,
     Skip,
 }
 
+/// What the user decided while reviewing a snapshot.
+#[derive(Clone, Debug)]
+pub enum ReviewAction {
+    /// Apply an operation to the snapshot.
+    Decide(Operation),
+    /// Apply an operation to each hunk of the snapshot.
+    DecideHunks(Vec<Operation>),
+    /// Apply an operation to the remaining snapshots of the current file.
+    DecideFile(Operation),
+    /// Skip the remaining snapshots of the current package.
+    SkipPackage,
+    /// Accept the snapshot as a new variant.
+    AcceptVariant,
+    /// Revert the decision on the previous snapshot.
+    Undo,
+    /// Only review the remaining snapshots that match a pattern.
+    Search(Box<GlobMatcher>),
+}
+
 impl Metadata {
     pub fn workspace_root(&self) -> &Path {
         Path::new(&self.workspace_root)
     }
+
+    pub fn target_directory(&self) -> &Path {
+        &self.target_directory
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -61,11 +97,15 @@ pub enum SnapshotContainerKind {
 
 #[derive(Debug)]
 pub struct PendingSnapshot {
+    #[allow(dead_code)]
     pub id: usize,
     pub old: Option<Snapshot>,
     pub new: Snapshot,
     pub op: Operation,
+    pub hunks: Option<Vec<Operation>>,
     pub line: Option<u32>,
+    /// Accept the snapshot as a new variant instead of replacing the old one.
+    pub as_variant: bool,
 }
 
 impl PendingSnapshot {
@@ -83,6 +123,90 @@ impl PendingSnapshot {
         }
         rv
     }
+
+    /// Checks if the snapshot matches a review filter.
+    ///
+    /// The filter is matched against the snapshot name with `::` separated
+    /// modules (`parser__tests__basic` as `parser::tests::basic`) and against
+    /// the path of the snapshot or source file relative to the workspace root.
+    pub fn matches_filter(&self, filter: &GlobMatcher, target_file: &Path) -> bool {
+        if let Some(name) = self.new.snapshot_name() {
+            if filter.is_match(name.replace("__", "::")) {
+                return true;
+            }
+        }
+        filter.is_match(target_file)
+            || self
+                .new
+                .metadata()
+                .source()
+                .map_or(false, |source| filter.is_match(source))
+    }
+
+    /// Returns the hunks of the change if it can be reviewed hunk by hunk.
+    pub fn hunk_diff(&self) -> Option<HunkDiff> {
+        if self.new.contents().is_binary()
+            || self
+                .old
+                .as_ref()
+                .map_or(false, |x| x.contents().is_binary())
+        {
+            return None;
+        }
+        Some(HunkDiff::new(
+            self.old.as_ref().map_or("", |x| x.contents_str()),
+            self.new.contents_str(),
+        ))
+    }
+
+    /// Returns the variant the snapshot can be accepted as.
+    ///
+    /// This is the variant of a snapshot file that was written while the
+    /// variant did not have a snapshot file of its own.
+    pub fn new_variant(&self, target_file: &Path) -> Option<&str> {
+        let variant = self.new.metadata().variant()?;
+        let is_variant_file = target_file
+            .file_name()
+            .and_then(|x| x.to_str())
+            .map_or(false, |x| x.ends_with(&format!("@{}.snap", variant)));
+        if self.line.is_some() || is_variant_file {
+            None
+        } else {
+            Some(variant)
+        }
+    }
+
+    /// Records a decision per hunk.
+    ///
+    /// If all hunks were decided the same way this is the same as deciding
+    /// on the whole snapshot.  Otherwise the snapshot counts as accepted
+    /// and only the accepted hunks are written.
+    pub fn set_hunks(&mut self, hunks: Vec<Operation>) {
+        if hunks.iter().all(|x| Some(x) == hunks.first()) {
+            self.op = hunks.first().copied().unwrap_or(Operation::Skip);
+            self.hunks = None;
+        } else {
+            self.op = Operation::Accept;
+            self.hunks = Some(hunks);
+        }
+    }
+
+    /// Splits a hunk level decision into the contents to store and the
+    /// contents that stay pending (skipped hunks on top of accepted ones).
+    fn split_by_hunks(&self) -> Option<(SnapshotContents, Option<SnapshotContents>)> {
+        let hunks = self.hunks.as_ref()?;
+        let diff = self.hunk_diff()?;
+        let select = |keep: fn(Operation) -> bool| {
+            diff.apply(&hunks.iter().map(|&op| keep(op)).collect::<Vec<_>>())
+        };
+        let accepted = select(|op| op == Operation::Accept);
+        let pending = if hunks.contains(&Operation::Skip) {
+            Some(select(|op| op != Operation::Reject).into())
+        } else {
+            None
+        };
+        Some((accepted.into(), pending))
+    }
 }
 
 #[derive(Debug)]
@@ -92,6 +216,8 @@ pub struct SnapshotContainer {
     kind: SnapshotContainerKind,
     snapshots: Vec<PendingSnapshot>,
     patcher: Option<FilePatcher>,
+    /// Snapshot files accepted inline snapshots were moved to.
+    moved_to_files: Vec<PathBuf>,
 }
 
 impl SnapshotContainer {
@@ -114,7 +240,9 @@ impl SnapshotContainer {
                     old,
                     new,
                     op: Operation::Skip,
+                    hunks: None,
                     line: None,
+                    as_variant: false,
                 });
                 None
             }
@@ -127,14 +255,20 @@ impl SnapshotContainer {
                     pending_vec.sort_by_key(|pending| pending.line);
                     for (id, pending) in pending_vec.into_iter().enumerate() {
                         if let Some(new) = pending.new {
+                            patcher.add_snapshot_macro(
+                                pending.line as usize,
+                                pending.old.as_ref().map(|x| x.contents()),
+                                pending.doctest,
+                            );
                             snapshots.push(PendingSnapshot {
                                 id,
                                 old: pending.old,
                                 new,
                                 op: Operation::Skip,
+                                hunks: None,
                                 line: Some(pending.line),
+                                as_variant: false,
                             });
-                            patcher.add_snapshot_macro(pending.line as usize);
                             have_new = true;
                         }
                     }
@@ -161,6 +295,7 @@ impl SnapshotContainer {
             kind,
             snapshots,
             patcher,
+            moved_to_files: vec![],
         })
     }
 
@@ -179,29 +314,92 @@ impl SnapshotContainer {
         self.snapshots.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Returns how long ago the pending snapshots were written.
+    pub fn age(&self) -> Option<Duration> {
+        let modified = fs::metadata(&self.snapshot_path).ok()?.modified().ok()?;
+        SystemTime::now().duration_since(modified).ok()
+    }
+
     pub fn iter_snapshots(&mut self) -> impl Iterator<Item = &'_ mut PendingSnapshot> {
         self.snapshots.iter_mut()
     }
 
-    pub fn commit(&mut self) -> Result<(), Box<dyn Error>> {
+    /// Applies the decisions.
+    ///
+    /// Rejected snapshots are moved to the trash if one is given.  Accepted
+    /// inline snapshots over the inline limits are moved to snapshot files
+    /// and source files are formatted as configured.  The edition is passed
+    /// on to rustfmt.  Blobs of accepted snapshots are uploaded to the blob
+    /// backend if one is given.
+    pub fn commit(
+        &mut self,
+        trash: Option<&Trash>,
+        inline_config: &InlineConfig,
+        edition: Option<&str>,
+        blob_backend: Option<&DirBlobBackend>,
+    ) -> Result<(), Box<dyn Error>> {
         if let Some(ref mut patcher) = self.patcher {
             let mut new_pending = vec![];
+            let mut rejected = vec![];
             let mut did_accept = false;
             let mut did_skip = false;
 
             for (idx, snapshot) in self.snapshots.iter().enumerate() {
+                if let Some((accepted, pending)) = snapshot.split_by_hunks() {
+                    patcher.set_new_content(idx, &accepted, inline_config);
+                    did_accept = true;
+                    if let Some(pending) = pending {
+                        let old = snapshot.old.as_ref().unwrap_or(&snapshot.new);
+                        let mut pending = PendingInlineSnapshot::new(
+                            Some(snapshot.new.with_contents(pending)),
+                            Some(old.with_contents(accepted)),
+                            patcher.get_new_line(idx) as u32,
+                        );
+                        pending.doctest = patcher.is_doctest(idx);
+                        new_pending.push(pending);
+                        did_skip = true;
+                    }
+                    continue;
+                }
                 match snapshot.op {
                     Operation::Accept => {
-                        patcher.set_new_content(idx, snapshot.new.contents());
+                        let snapshot_file = moved_snapshot_file(&self.target_path, &snapshot.new);
+                        match snapshot_file {
+                            Some(snapshot_file)
+                                if inline_config.is_exceeded_by(snapshot.new.contents_str())
+                                    && patcher.can_remove_snapshot(idx) =>
+                            {
+                                patcher.remove_snapshot(idx);
+                                snapshot.new.save(&snapshot_file)?;
+                                self.moved_to_files.push(snapshot_file);
+                            }
+                            _ => {
+                                patcher.set_new_content(idx, snapshot.new.contents(), inline_config)
+                            }
+                        }
                         did_accept = true;
                     }
-                    Operation::Reject => {}
+                    Operation::Reject => {
+                        let mut pending = PendingInlineSnapshot::new(
+                            Some(snapshot.new.clone()),
+                            snapshot.old.clone(),
+                            patcher.get_new_line(idx) as u32,
+                        );
+                        pending.doctest = patcher.is_doctest(idx);
+                        rejected.push(pending);
+                    }
                     Operation::Skip => {
-                        new_pending.push(PendingInlineSnapshot::new(
+                        let mut pending = PendingInlineSnapshot::new(
                             Some(snapshot.new.clone()),
                             snapshot.old.clone(),
                             patcher.get_new_line(idx) as u32,
-                        ));
+                        );
+                        pending.doctest = patcher.is_doctest(idx);
+                        new_pending.push(pending);
                         did_skip = true;
                     }
                 }
@@ -209,6 +407,16 @@ impl SnapshotContainer {
 
             if did_accept {
                 patcher.save()?;
+                match inline_config.rustfmt {
+                    RustfmtMode::Off => {}
+                    RustfmtMode::File => format_file(&self.target_path, edition, None)?,
+                    RustfmtMode::Lines => {
+                        format_file(&self.target_path, edition, Some(patcher.changed_lines()))?
+                    }
+                }
+            }
+            if let Some(trash) = trash {
+                trash.add_inline(&self.snapshot_path, &rejected)?;
             }
             if did_skip {
                 PendingInlineSnapshot::save_batch(&self.snapshot_path, &new_pending)?;
@@ -218,19 +426,286 @@ impl SnapshotContainer {
         } else {
             // should only be one or this is weird
             for snapshot in self.snapshots.iter() {
+                if let Some((accepted, pending)) = snapshot.split_by_hunks() {
+                    let accepted = snapshot.new.with_contents(accepted);
+                    accepted.save(&self.target_path)?;
+                    upload_blob(&accepted, &self.target_path, blob_backend)?;
+                    match pending {
+                        Some(pending) => snapshot
+                            .new
+                            .with_contents(pending)
+                            .save(&self.snapshot_path)?,
+                        None => fs::remove_file(&self.snapshot_path)?,
+                    }
+                    self.remove_unreferenced_blobs(snapshot)?;
+                    continue;
+                }
                 match snapshot.op {
                     Operation::Accept => {
-                        fs::rename(&self.snapshot_path, &self.target_path)?;
+                        let target_path = self.accepted_path(snapshot);
+                        fs::rename(&self.snapshot_path, &target_path)?;
+                        // an expiry date might have been added in the review
+                        if snapshot.new.metadata().expires().is_some() {
+                            snapshot.new.save(&target_path)?;
+                        }
+                        upload_blob(&snapshot.new, &target_path, blob_backend)?;
+                        if let Some(binary_file) = snapshot.new.binary_file(&self.snapshot_path) {
+                            let target_binary_file =
+                                snapshot.new.binary_file(&target_path).unwrap();
+                            fs::rename(&binary_file, &target_binary_file)?;
+                        }
+                        // the old snapshot might have been stored with a
+                        // different extension.
+                        if let Some(old_binary_file) = snapshot
+                            .old
+                            .as_ref()
+                            .and_then(|old| old.binary_file(&target_path))
+                        {
+                            if snapshot.new.binary_file(&target_path).as_ref()
+                                != Some(&old_binary_file)
+                            {
+                                fs::remove_file(&old_binary_file).ok();
+                            }
+                        }
                     }
                     Operation::Reject => {
-                        fs::remove_file(&self.snapshot_path)?;
+                        let binary_file = snapshot.new.binary_file(&self.snapshot_path);
+                        for path in Some(&self.snapshot_path).into_iter().chain(&binary_file) {
+                            match trash {
+                                Some(trash) => trash.move_file(path)?,
+                                None => fs::remove_file(path)?,
+                            }
+                        }
                     }
                     Operation::Skip => {}
                 }
+                if snapshot.op != Operation::Skip {
+                    // visual diffs written for image snapshots are stale now
+                    if let Some(mut binary_file) = snapshot.new.binary_file(&self.snapshot_path) {
+                        binary_file.set_extension("diff.png");
+                        fs::remove_file(&binary_file).ok();
+                    }
+                    self.remove_unreferenced_blobs(snapshot)?;
+                }
             }
         }
         Ok(())
     }
+
+    /// Returns the file that holds the pending snapshots.
+    pub fn pending_file(&self) -> &Path {
+        &self.snapshot_path
+    }
+
+    /// Returns the files that make up the pending snapshots.
+    ///
+    /// This is the pending snapshot file and the binary contents and blobs
+    /// it refers to.
+    pub fn pending_files(&self) -> Vec<PathBuf> {
+        let mut rv = vec![self.snapshot_path.clone()];
+        if self.patcher.is_none() {
+            for snapshot in self.snapshots.iter() {
+                rv.extend(snapshot.new.binary_file(&self.snapshot_path));
+                rv.extend(snapshot.new.blob_file(&self.snapshot_path));
+            }
+        }
+        rv.retain(|x| x.is_file());
+        rv
+    }
+
+    /// Returns the snapshot files accepted inline snapshots were moved to.
+    pub fn moved_to_files(&self) -> &[PathBuf] {
+        &self.moved_to_files
+    }
+
+    /// Checks if any snapshot of the container is accepted.
+    pub fn has_accepted(&self) -> bool {
+        self.snapshots.iter().any(|x| x.op == Operation::Accept)
+    }
+
+    /// Returns the snapshot files that accepting snapshots wrote or removed.
+    ///
+    /// Inline snapshots are not included as they are stored in the source,
+    /// unless they were moved to snapshot files.
+    pub fn accepted_files(&self) -> Vec<PathBuf> {
+        let mut rv = vec![];
+        if self.patcher.is_some() {
+            rv.extend(self.moved_to_files.iter().cloned());
+            return rv;
+        }
+        for snapshot in self.snapshots.iter() {
+            if snapshot.op != Operation::Accept {
+                continue;
+            }
+            let target_path = self.accepted_path(snapshot);
+            for stored in snapshot.old.iter().chain(Some(&snapshot.new)) {
+                rv.extend(stored.binary_file(&target_path));
+                rv.extend(stored.blob_file(&target_path));
+            }
+            rv.push(target_path);
+        }
+        rv
+    }
+
+    /// Returns the file an accepted snapshot is written to.
+    fn accepted_path(&self, snapshot: &PendingSnapshot) -> PathBuf {
+        match snapshot.new.metadata().variant() {
+            Some(variant) if snapshot.as_variant => {
+                snapshot_variant_file(&self.target_path, variant)
+            }
+            _ => self.target_path.clone(),
+        }
+    }
+
+    /// Remembers the files that committing the decisions can modify.
+    pub fn backup_files(&self, backup: &mut FileBackup) -> Result<(), Box<dyn Error>> {
+        backup.add(&self.snapshot_path)?;
+        backup.add(&self.target_path)?;
+        for snapshot in self.snapshots.iter() {
+            if snapshot.as_variant {
+                backup.add(&self.accepted_path(snapshot))?;
+            }
+            if self.patcher.is_some() {
+                if let Some(snapshot_file) = moved_snapshot_file(&self.target_path, &snapshot.new) {
+                    backup.add(&snapshot_file)?;
+                }
+            }
+            for stored in snapshot.old.iter().chain(Some(&snapshot.new)) {
+                for path in &[&self.snapshot_path, &self.target_path] {
+                    if let Some(binary_file) = stored.binary_file(path) {
+                        backup.add(&binary_file.with_extension("diff.png"))?;
+                        backup.add(&binary_file)?;
+                    }
+                    if let Some(blob_file) = stored.blob_file(path) {
+                        backup.add(&blob_file)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the blobs of a decided snapshot that no snapshot file in the
+    /// same folder refers to anymore.
+    fn remove_unreferenced_blobs(&self, snapshot: &PendingSnapshot) -> Result<(), Box<dyn Error>> {
+        let folder = match self.target_path.parent() {
+            Some(folder) => folder,
+            None => return Ok(()),
+        };
+        for stored in snapshot.old.iter().chain(Some(&snapshot.new)) {
+            let (hash, blob_file) = match (
+                stored.metadata().blob(),
+                stored.blob_file(&self.target_path),
+            ) {
+                (Some(hash), Some(blob_file)) => (hash, blob_file),
+                _ => continue,
+            };
+            let mut is_referenced = false;
+            for entry in fs::read_dir(folder)? {
+                let path = entry?.path();
+                let is_snapshot_file = path
+                    .file_name()
+                    .and_then(|x| x.to_str())
+                    .map_or(false, |name| {
+                        name.ends_with(".snap") || name.ends_with(".snap.new")
+                    });
+                if is_snapshot_file
+                    && metadata_from_file(&path).map_or(false, |x| x.blob() == Some(hash))
+                {
+                    is_referenced = true;
+                    break;
+                }
+            }
+            if !is_referenced {
+                fs::remove_file(&blob_file).ok();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The contents of files before review decisions are applied.
+///
+/// This allows rolling back if applying the decisions fails halfway.
+/// Uploaded blobs are not rolled back.
+#[derive(Default, Debug)]
+pub struct FileBackup {
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl FileBackup {
+    /// Remembers the current contents of a file or that it does not exist.
+    pub fn add(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if self.files.iter().any(|x| x.0 == path) {
+            return Ok(());
+        }
+        let contents = match fs::read(path) {
+            Ok(contents) => Some(contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        self.files.push((path.to_path_buf(), contents));
+        Ok(())
+    }
+
+    /// Restores all remembered files.
+    pub fn restore(&self) -> Result<(), Box<dyn Error>> {
+        let mut rv = Ok(());
+        for (path, contents) in &self.files {
+            let result = match *contents {
+                Some(ref contents) => fs::write(path, contents),
+                None if path.exists() => fs::remove_file(path),
+                None => Ok(()),
+            };
+            if let Err(err) = result {
+                rv = rv.and(Err(err));
+            }
+        }
+        Ok(rv?)
+    }
+}
+
+/// Returns the snapshot file an inline snapshot of a source file moves to.
+///
+/// Once the snapshot is removed from the assertion it is named the same, so
+/// the file goes into the default `snapshots` folder next to the source.
+fn moved_snapshot_file(source_file: &Path, snapshot: &Snapshot) -> Option<PathBuf> {
+    let name = snapshot.snapshot_name()?;
+    Some(source_file.parent()?.join("snapshots").join(format!(
+        "{}__{}.snap",
+        snapshot.module_name(),
+        name.replace('/', "__").replace('\\', "__")
+    )))
+}
+
+/// Creates the backend accepted blobs are uploaded to.
+///
+/// `INSTA_BLOB_DIR` takes precedence over the `blobs` section of the
+/// config, whose folder is relative to the workspace root.
+pub fn blob_backend(workspace_root: &Path, config: &BlobsConfig) -> Option<DirBlobBackend> {
+    DirBlobBackend::from_env().or_else(|| {
+        config
+            .dir
+            .as_ref()
+            .map(|dir| DirBlobBackend::new(workspace_root.join(dir)))
+    })
+}
+
+/// Uploads the blob of an accepted snapshot if a blob backend is configured.
+fn upload_blob(
+    snapshot: &Snapshot,
+    snapshot_file: &Path,
+    blob_backend: Option<&DirBlobBackend>,
+) -> Result<(), Box<dyn Error>> {
+    let (hash, blob_file, blob_backend) = match (
+        snapshot.metadata().blob(),
+        snapshot.blob_file(snapshot_file),
+        blob_backend,
+    ) {
+        (Some(hash), Some(blob_file), Some(blob_backend)) => (hash, blob_file, blob_backend),
+        _ => return Ok(()),
+    };
+    blob_backend.store(hash, &fs::read(&blob_file)?)
 }
 
 fn is_hidden(entry: &DirEntry) -> bool {
@@ -246,6 +721,30 @@ pub fn find_snapshots<'a>(
     extensions: &'a [&'a str],
     no_ignore: bool,
 ) -> impl Iterator<Item = Result<SnapshotContainer, Box<dyn Error>>> + 'a {
+    walk_pending_files(root, extensions, no_ignore)
+        .filter_map(|e| load_pending_file(e.path().to_path_buf(), e.path()))
+}
+
+/// Finds the pending snapshots that were written to the target directory.
+///
+/// `pending_dir` mirrors the layout of the workspace so the snapshot and
+/// source files they belong to are found below `workspace_root`.
+pub fn find_target_snapshots<'a>(
+    pending_dir: PathBuf,
+    workspace_root: PathBuf,
+    extensions: &'a [&'a str],
+) -> impl Iterator<Item = Result<SnapshotContainer, Box<dyn Error>>> + 'a {
+    walk_pending_files(pending_dir.clone(), extensions, true).filter_map(move |e| {
+        let local_path = workspace_root.join(e.path().strip_prefix(&pending_dir).ok()?);
+        load_pending_file(e.into_path(), &local_path)
+    })
+}
+
+fn walk_pending_files(
+    root: PathBuf,
+    extensions: &[&str],
+    no_ignore: bool,
+) -> impl Iterator<Item = ignore::DirEntry> {
     let mut builder = WalkBuilder::new(root.clone());
     builder
         .hidden(false)
@@ -264,31 +763,104 @@ pub fn find_snapshots<'a>(
     }
 
     builder.overrides(override_builder.build().unwrap());
+    builder.build().filter_map(|e| e.ok())
+}
+
+/// Loads the pending file at `path`.
+///
+/// `local_path` is where the file is if it was written next to the
+/// snapshot or source file it belongs to.
+fn load_pending_file(
+    path: PathBuf,
+    local_path: &Path,
+) -> Option<Result<SnapshotContainer, Box<dyn Error>>> {
+    let fname = local_path.file_name()?.to_string_lossy();
+    if fname.ends_with(".new") {
+        let mut old_path = local_path.to_path_buf();
+        old_path.set_extension("");
+        Some(SnapshotContainer::load(
+            path,
+            old_path,
+            SnapshotContainerKind::External,
+        ))
+    } else if fname.starts_with('.') && fname.ends_with(".pending-snap") {
+        let mut target_path = local_path.to_path_buf();
+        target_path.set_file_name(&fname[1..fname.len() - 13]);
+        Some(SnapshotContainer::load(
+            path,
+            target_path,
+            SnapshotContainerKind::Inline,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Finds pending inline snapshots in the output folder of a build script.
+///
+/// These belong to files that are generated by the build script and
+/// included with `include!(concat!(env!("OUT_DIR"), ...))`.  If the
+/// build script copied such a file from the package the copy in the
+/// package is patched.
+fn find_generated_snapshots<'a>(
+    out_dir: PathBuf,
+    source_roots: Vec<PathBuf>,
+    target_dir: &'a Path,
+) -> impl Iterator<Item = Result<SnapshotContainer, Box<dyn Error>>> + 'a {
+    let mut builder = WalkBuilder::new(out_dir);
+    builder.hidden(false).standard_filters(false);
     builder.build().filter_map(|e| e.ok()).filter_map(move |e| {
         let fname = e.file_name().to_string_lossy();
-        if fname.ends_with(".new") {
-            let new_path = e.into_path();
-            let mut old_path = new_path.clone();
-            old_path.set_extension("");
-            Some(SnapshotContainer::load(
-                new_path,
-                old_path,
-                SnapshotContainerKind::External,
-            ))
-        } else if fname.starts_with('.') && fname.ends_with(".pending-snap") {
-            let mut target_path = e.path().to_path_buf();
-            target_path.set_file_name(&fname[1..fname.len() - 13]);
-            Some(SnapshotContainer::load(
-                e.path().to_path_buf(),
-                target_path,
-                SnapshotContainerKind::Inline,
-            ))
-        } else {
-            None
+        if !fname.starts_with('.') || !fname.ends_with(".pending-snap") {
+            return None;
         }
+        let mut generated_path = e.path().to_path_buf();
+        generated_path.set_file_name(&fname[1..fname.len() - 13]);
+        let target_path = find_generated_source(&generated_path, &source_roots, target_dir)
+            .unwrap_or(generated_path);
+        Some(SnapshotContainer::load(
+            e.into_path(),
+            target_path,
+            SnapshotContainerKind::Inline,
+        ))
     })
 }
 
+/// Finds the source file a generated file was copied from.
+fn find_generated_source(
+    generated_path: &Path,
+    source_roots: &[PathBuf],
+    target_dir: &Path,
+) -> Option<PathBuf> {
+    let contents = fs::read(generated_path).ok()?;
+    source_roots
+        .iter()
+        .flat_map(|root| WalkBuilder::new(root).build())
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_type().map_or(false, |x| x.is_file())
+                && e.path().extension().map_or(false, |x| x == "rs")
+                && !e.path().starts_with(target_dir)
+        })
+        .find(|e| {
+            e.metadata()
+                .map_or(false, |x| x.len() == contents.len() as u64)
+                && fs::read(e.path()).ok().as_ref() == Some(&contents)
+        })
+        .map(|e| e.into_path())
+}
+
+/// Returns the sub folders of a folder.
+fn sub_folders(path: &Path) -> Vec<PathBuf> {
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map_or(false, |x| x.is_dir()))
+        .map(|e| e.path())
+        .collect()
+}
+
 impl Package {
     pub fn manifest_path(&self) -> &Path {
         &self.manifest_path
@@ -302,12 +874,19 @@ impl Package {
         &self.version
     }
 
-    pub fn iter_snapshot_containers<'a>(
-        &self,
-        extensions: &'a [&'a str],
-        no_ignore: bool,
-    ) -> impl Iterator<Item = Result<SnapshotContainer, Box<dyn Error>>> + 'a {
+    pub fn edition(&self) -> Option<&str> {
+        self.edition.as_deref()
+    }
+
+    /// Returns the folders that can hold snapshots of this package.
+    ///
+    /// With a central snapshot root for the workspace that holds the
+    /// snapshots of all packages the folder of this package is included.
+    pub fn snapshot_roots(&self, central_root: Option<&Path>) -> Vec<PathBuf> {
         let mut roots = Vec::new();
+        if let Some(central_root) = central_root {
+            roots.push(central_root.join(&self.name));
+        }
 
         // the manifest path's parent is always a snapshot container.  For
         // a rationale see GH-70.  But generally a user would expect to be
@@ -340,14 +919,64 @@ impl Package {
         roots.sort_by_key(|x| x.as_os_str().len());
         let mut reduced_roots = vec![];
         for root in roots {
-            if !reduced_roots.iter().any(|x| root.starts_with(&x)) {
+            if !reduced_roots.iter().any(|x| root.starts_with(x)) {
                 reduced_roots.push(root);
             }
         }
 
         reduced_roots
+    }
+
+    /// Returns the output folders of the build script of this package.
+    pub fn build_out_dirs(&self, target_dir: &Path) -> Vec<PathBuf> {
+        // build folders are in target/<profile>/build or in
+        // target/<triple>/<profile>/build when cross compiling.
+        let mut build_dirs = vec![];
+        for profile_dir in sub_folders(target_dir) {
+            let build_dir = profile_dir.join("build");
+            if build_dir.is_dir() {
+                build_dirs.push(build_dir);
+            } else {
+                build_dirs.extend(
+                    sub_folders(&profile_dir)
+                        .into_iter()
+                        .map(|x| x.join("build")),
+                );
+            }
+        }
+
+        // the build folders of a package are named <name>-<hash>
+        let prefix = format!("{}-", self.name);
+        build_dirs
+            .iter()
+            .flat_map(|build_dir| sub_folders(build_dir))
+            .filter(|dir| {
+                dir.file_name().and_then(|x| x.to_str()).map_or(false, |x| {
+                    x.starts_with(&prefix) && !x[prefix.len()..].contains('-')
+                })
+            })
+            .map(|dir| dir.join("out"))
+            .filter(|dir| dir.is_dir())
+            .collect()
+    }
+
+    pub fn iter_snapshot_containers<'a>(
+        &self,
+        extensions: &'a [&'a str],
+        no_ignore: bool,
+        target_dir: Option<&'a Path>,
+        central_root: Option<&Path>,
+    ) -> impl Iterator<Item = Result<SnapshotContainer, Box<dyn Error>>> + 'a {
+        let roots = self.snapshot_roots(central_root);
+        let out_dirs = target_dir.map_or_else(Vec::new, |x| self.build_out_dirs(x));
+        let generated = out_dirs.into_iter().flat_map({
+            let roots = roots.clone();
+            move |out_dir| find_generated_snapshots(out_dir, roots.clone(), target_dir.unwrap())
+        });
+        roots
             .into_iter()
             .flat_map(move |root| find_snapshots(root, extensions, no_ignore))
+            .chain(generated)
     }
 }
 
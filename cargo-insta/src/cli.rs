@@ -1,24 +1,61 @@
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
 use std::ffi::OsStr;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
 use std::{env, fs};
 
 use console::{set_colors_enabled, style, Key, Term};
+use globset::GlobMatcher;
 use ignore::{Walk, WalkBuilder};
-use insta::{print_snapshot_diff, Snapshot};
+use insta::{
+    pending_dir, print_snapshot, print_snapshot_diff, print_snapshot_diff_limited, ChecksumMode,
+    DiffAlgorithm, MetaData, NotifyMode, RustfmtMode, Settings, Snapshot, SnapshotContents,
+    ToolConfig,
+};
+use regex::RegexBuilder;
 use serde::Serialize;
+use serde_json::{json, Value};
+use similar::ChangeTag;
 use structopt::clap::AppSettings;
 use structopt::StructOpt;
 use uuid::Uuid;
 
+use crate::archive::{check_pending_only, read_archive, write_archive};
 use crate::cargo::{
-    find_packages, find_snapshots, get_cargo, get_package_metadata, Operation, Package,
-    SnapshotContainer,
+    find_packages, find_snapshots, find_target_snapshots, get_cargo, get_package_metadata,
+    Operation, Package, ReviewAction, SnapshotContainer,
+};
+use crate::commit::{commit_decisions, print_warnings};
+use crate::daemon::{
+    Connection, Incoming, PendingSnapshotInfo, RpcError, SnapshotDiff, SnapshotIds, INVALID_PARAMS,
+    METHOD_NOT_FOUND, PARSE_ERROR, REQUEST_FAILED,
+};
+use crate::decisions::{snapshot_ids, DecisionsFile};
+use crate::git::stage_files;
+use crate::grep::grep_snapshots;
+use crate::hunks::HunkDiff;
+use crate::inline::{AssertionKind, SnapshotAssertion};
+use crate::lint::{find_duplicate_names, find_nondeterminism, LintLevel};
+use crate::list::{list_assertions, load_snapshot_info};
+use crate::manifest::{merge_manifests, parse_test_list, Shard, SnapshotManifest};
+use crate::migrate::{find_migrations, NewSnapshot, StrandedSnapshot};
+use crate::normalize::{find_snapshot_files, normalize_snapshot_file, normalize_source_file};
+use crate::notify::notify_pending;
+use crate::report::{
+    load_results, print_github_annotations, print_json_events, write_junit_report,
+};
+use crate::session::{Decision, ReviewSession};
+use crate::stats::{format_size, PackageStats};
+use crate::trash::{list_trash, restore_trash};
+use crate::utils::{
+    compile_glob, err_msg, format_duration, parse_duration, parse_expiry_date, QuietExit,
 };
-use crate::utils::{err_msg, QuietExit};
+use crate::watch::SourceTree;
+use crate::web::{render_report, ReviewItem, WebReview};
 
 /// A helper utility to work with insta snapshots.
 #[derive(StructOpt, Debug)]
@@ -54,9 +91,51 @@ pub enum Command {
     /// Run tests and then reviews
     #[structopt(name = "test")]
     Test(TestCommand),
+    /// Run tests and move the snapshots of assertions that moved to another
+    /// module or snapshot path instead of leaving them behind.
+    #[structopt(name = "migrate")]
+    Migrate(TestCommand),
     /// Print a summary of all pending snapshots.
     #[structopt(name = "pending-snapshots")]
     PendingSnapshots(PendingSnapshotsCommand),
+    /// Print statistics about the snapshots in the workspace.
+    #[structopt(name = "stats")]
+    Stats(StatsCommand),
+    /// Restore snapshots that were rejected with `--trash`.
+    #[structopt(name = "restore")]
+    Restore(RestoreCommand),
+    /// Print a snapshot file with its metadata.
+    #[structopt(name = "show")]
+    Show(ShowCommand),
+    /// Print the diff between two snapshot files.
+    #[structopt(name = "diff")]
+    Diff(DiffCommand),
+    /// Search the contents of all snapshots for a pattern.
+    #[structopt(name = "grep")]
+    Grep(GrepCommand),
+    /// List the snapshot assertions in the source files.
+    #[structopt(name = "list")]
+    List(ListCommand),
+    /// Check snapshot assertions and snapshots for problems.
+    #[structopt(name = "lint")]
+    Lint(LintCommand),
+    /// Rewrite snapshot files and inline snapshots the way insta writes
+    /// them.
+    #[structopt(name = "normalize")]
+    Normalize(NormalizeCommand),
+    /// Export pending snapshots into an archive.
+    #[structopt(name = "export")]
+    Export(ExportCommand),
+    /// Apply the decisions of a decisions file.
+    #[structopt(name = "apply-decisions")]
+    ApplyDecisions(ApplyDecisionsCommand),
+    /// Combine the snapshot manifests of partial test runs to find
+    /// unreferenced snapshots.
+    #[structopt(name = "merge-manifests")]
+    MergeManifests(MergeManifestsCommand),
+    /// Serve editor integrations with JSON-RPC over stdin and stdout.
+    #[structopt(name = "daemon")]
+    Daemon(DaemonCommand),
 }
 
 #[derive(StructOpt, Debug, Clone)]
@@ -87,12 +166,52 @@ pub struct ProcessCommand {
     /// Limits the operation to one or more snapshots.
     #[structopt(long = "snapshot")]
     pub snapshot_filter: Option<Vec<String>>,
+    /// Only process snapshots whose name or path matches this glob (e.g.
+    /// `parser::*`).
+    #[structopt(long, value_name = "GLOB", parse(try_from_str = compile_glob))]
+    pub filter: Option<GlobMatcher>,
     /// Do not print to stdout.
     #[structopt(short = "q", long)]
     pub quiet: bool,
+    /// Review in the browser instead of the terminal.
+    #[structopt(long)]
+    pub web: bool,
+    /// The port to serve the web review on (defaults to a free port).
+    #[structopt(long, value_name = "PORT", requires = "web")]
+    pub port: Option<u16>,
+    /// Move rejected snapshots to `target/insta-trash` instead of deleting them.
+    #[structopt(long)]
+    pub trash: bool,
+    /// Stage accepted snapshot files in git.
+    #[structopt(long)]
+    pub stage: bool,
+    /// Show a summary of all decisions and ask before applying them.
+    #[structopt(long, conflicts_with = "web")]
+    pub confirm: bool,
+    /// Only process pending snapshots older than this (e.g. `2w` or `12h`).
+    #[structopt(long, value_name = "DURATION", parse(try_from_str = parse_duration))]
+    pub older_than: Option<Duration>,
+    /// Extract pending snapshots from an archive created with `cargo insta
+    /// export` before processing.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    pub from_archive: Option<PathBuf>,
+    /// The number of unchanged lines shown around changes in diffs.
+    #[structopt(long, value_name = "LINES")]
+    pub diff_context: Option<usize>,
+    /// The diff algorithm: myers, patience or lcs.
+    #[structopt(long, value_name = "ALGORITHM", possible_values = &["myers", "patience", "lcs"])]
+    pub diff_algorithm: Option<DiffAlgorithm>,
+    /// Run rustfmt on source files after writing inline snapshots: off,
+    /// file or lines (needs a nightly rustfmt).
+    #[structopt(long, value_name = "MODE", possible_values = &["off", "file", "lines"])]
+    pub rustfmt: Option<RustfmtMode>,
+    /// Accept snapshots stored in files only provisionally until a date
+    /// (`YYYY-MM-DD`) or for a duration (e.g. `4w`).
+    #[structopt(long, value_name = "DATE", parse(try_from_str = parse_expiry_date))]
+    pub expires: Option<String>,
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(StructOpt, Debug, Clone)]
 #[structopt(rename_all = "kebab-case")]
 pub struct TestCommand {
     #[structopt(flatten)]
@@ -121,6 +240,22 @@ pub struct TestCommand {
     /// Do not activate the `default` feature
     #[structopt(long)]
     pub no_default_features: bool,
+    /// Only run one part of the tests, e.g. `2/8` for the second of eight
+    /// parts.  The snapshots the part references are written to a manifest
+    /// for `cargo insta merge-manifests`.
+    #[structopt(long, value_name = "INDEX/COUNT")]
+    pub shard: Option<Shard>,
+    /// Write the snapshots this run references to a manifest for `cargo
+    /// insta merge-manifests`, for runs that only cover some of the tests.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    pub manifest: Option<PathBuf>,
+    /// Only run the tests of this integration test target.
+    #[structopt(long = "test", value_name = "NAME", number_of_values = 1)]
+    pub test_targets: Vec<String>,
+    /// Run the tests once for every feature combination in the
+    /// test.feature_matrix section of insta.yaml.
+    #[structopt(long)]
+    pub feature_matrix: bool,
     /// Follow up with review.
     #[structopt(long)]
     pub review: bool,
@@ -130,6 +265,10 @@ pub struct TestCommand {
     /// Accept all new (previously unseen).
     #[structopt(long)]
     pub accept_unseen: bool,
+    /// Only write snapshots for assertions that have none yet, mismatches
+    /// with existing snapshots fail without creating new snapshots.
+    #[structopt(long, conflicts_with_all = &["accept", "accept-unseen"])]
+    pub new_only: bool,
     /// Do not reject pending snapshots before run.
     #[structopt(long)]
     pub keep_pending: bool,
@@ -137,8 +276,44 @@ pub struct TestCommand {
     #[structopt(long)]
     pub force_update_snapshots: bool,
     /// Delete unreferenced snapshots after the test run.
-    #[structopt(long)]
+    #[structopt(long, conflicts_with = "unreferenced")]
     pub delete_unreferenced_snapshots: bool,
+    /// What to do with unreferenced snapshots after the test run: ignore,
+    /// delete or auto.  With auto snapshots of renamed or moved tests are
+    /// moved to their new place and the others are deleted.
+    #[structopt(long, value_name = "MODE", possible_values = &["ignore", "delete", "auto"])]
+    pub unreferenced: Option<String>,
+    /// Stage accepted snapshot files in git.
+    #[structopt(long)]
+    pub stage: bool,
+    /// Re-run tests when sources change and review new snapshots.
+    #[structopt(long)]
+    pub watch: bool,
+    /// Write a JUnit XML report of all snapshot assertions to this path.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    pub junit: Option<PathBuf>,
+    /// How to report snapshot assertions: human, github or json.  Defaults to
+    /// github when running in GitHub Actions.  With json only the events
+    /// are written to stdout, the output of the tests goes to stderr.
+    #[structopt(long, value_name = "FMT", possible_values = &["human", "github", "json"])]
+    pub message_format: Option<String>,
+    /// The number of unchanged lines shown around changes in diffs.
+    #[structopt(long, value_name = "LINES")]
+    pub diff_context: Option<usize>,
+    /// The diff algorithm: myers, patience or lcs.
+    #[structopt(long, value_name = "ALGORITHM", possible_values = &["myers", "patience", "lcs"])]
+    pub diff_algorithm: Option<DiffAlgorithm>,
+    /// Run rustfmt on source files after writing inline snapshots: off,
+    /// file or lines (needs a nightly rustfmt).
+    #[structopt(long, value_name = "MODE", possible_values = &["off", "file", "lines"])]
+    pub rustfmt: Option<RustfmtMode>,
+    /// Ring the terminal bell or show a desktop notification when the tests
+    /// leave pending snapshots behind: off, bell or desktop.
+    #[structopt(long, value_name = "MODE", possible_values = &["off", "bell", "desktop"])]
+    pub notify: Option<NotifyMode>,
+    /// Move the snapshots of moved assertions to their new place.
+    #[structopt(skip)]
+    pub migrate: bool,
 }
 
 #[derive(StructOpt, Debug)]
@@ -149,6 +324,200 @@ pub struct PendingSnapshotsCommand {
     /// Changes the output from human readable to JSON.
     #[structopt(long)]
     pub as_json: bool,
+    /// The output format: human, json or html.  The html format renders a
+    /// standalone report with the diffs of all pending snapshots.
+    #[structopt(long, value_name = "FMT", possible_values = &["human", "json", "html"], conflicts_with = "as-json")]
+    pub format: Option<String>,
+    /// Write the output to this file instead of stdout.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct StatsCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// The number of largest snapshots to list.
+    #[structopt(long, value_name = "N", default_value = "10")]
+    pub largest: usize,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ShowCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// Changes the output from human readable to JSON.
+    #[structopt(long)]
+    pub json: bool,
+    /// The snapshot file to show.
+    #[structopt(value_name = "PATH", parse(from_os_str))]
+    pub path: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct DiffCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// The old snapshot file.
+    #[structopt(value_name = "OLD", parse(from_os_str))]
+    pub old: PathBuf,
+    /// The new snapshot file.  Defaults to the `.snap.new` file next to
+    /// the old one, or to the `.snap` file if a `.snap.new` file is given.
+    #[structopt(value_name = "NEW", parse(from_os_str))]
+    pub new: Option<PathBuf>,
+    /// The number of unchanged lines shown around changes in diffs.
+    #[structopt(long, value_name = "LINES")]
+    pub diff_context: Option<usize>,
+    /// The diff algorithm: myers, patience or lcs.
+    #[structopt(long, value_name = "ALGORITHM", possible_values = &["myers", "patience", "lcs"])]
+    pub diff_algorithm: Option<DiffAlgorithm>,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct GrepCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// Match case insensitively.
+    #[structopt(short = "i", long)]
+    pub ignore_case: bool,
+    /// Treat the pattern as literal string instead of a regular expression.
+    #[structopt(short = "F", long)]
+    pub fixed_strings: bool,
+    /// The pattern to search for.
+    #[structopt(value_name = "PATTERN")]
+    pub pattern: String,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ListCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// Changes the output from human readable to JSON.
+    #[structopt(long)]
+    pub json: bool,
+    /// Only list assertions whose snapshots have this custom metadata.
+    #[structopt(long, value_name = "KEY[=VALUE]")]
+    pub info: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct LintCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct NormalizeCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// Only list the files that would change and fail if there are any.
+    #[structopt(long)]
+    pub check: bool,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ExportCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// Write all pending snapshots to this archive (`.tar` or `.tar.gz`).
+    #[structopt(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        required_unless = "decisions"
+    )]
+    pub pending: Option<PathBuf>,
+    /// Write a decisions file (`.yaml` or `.json`) listing all pending
+    /// snapshots to be filled in and applied with `cargo insta
+    /// apply-decisions`.
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    pub decisions: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ApplyDecisionsCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// Move rejected snapshots to `target/insta-trash` instead of deleting them.
+    #[structopt(long)]
+    pub trash: bool,
+    /// Stage accepted snapshot files in git.
+    #[structopt(long)]
+    pub stage: bool,
+    /// Do not print to stdout.
+    #[structopt(short = "q", long)]
+    pub quiet: bool,
+    /// Run rustfmt on source files after writing inline snapshots: off,
+    /// file or lines (needs a nightly rustfmt).
+    #[structopt(long, value_name = "MODE", possible_values = &["off", "file", "lines"])]
+    pub rustfmt: Option<RustfmtMode>,
+    /// The decisions file created with `cargo insta export --decisions`.
+    #[structopt(value_name = "PATH", parse(from_os_str))]
+    pub path: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct MergeManifestsCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// Delete the snapshots that no manifest references instead of only
+    /// listing them.
+    #[structopt(long)]
+    pub delete: bool,
+    /// Stage deleted snapshot files in git.
+    #[structopt(long)]
+    pub stage: bool,
+    /// The manifests written by the partial test runs.
+    #[structopt(value_name = "MANIFEST", parse(from_os_str), required = true)]
+    pub manifests: Vec<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct DaemonCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// Check for changed pending snapshots this often (e.g. `2s`) to notify
+    /// subscribers.  Without it subscriptions are refused.
+    #[structopt(long, value_name = "DURATION", parse(try_from_str = parse_duration))]
+    pub poll_interval: Option<Duration>,
+}
+
+/// How far the review of a file of the current package is.
+struct FileProgress<'a> {
+    file: &'a str,
+    reviewed: usize,
+    total: usize,
+    is_current: bool,
+}
+
+/// The number of files listed around the current one during review.
+const FILE_LIST_LEN: usize = 10;
+
+/// The number of terminal lines kept free for everything but the diff.
+const DIFF_PAGE_RESERVED_LINES: usize = 30;
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct RestoreCommand {
+    #[structopt(flatten)]
+    pub target_args: TargetArgs,
+    /// List the rejections in the trash instead of restoring.
+    #[structopt(long)]
+    pub list: bool,
+    /// The rejection to restore.  Defaults to the most recent one.
+    #[structopt(value_name = "ID")]
+    pub id: Option<String>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -162,50 +531,313 @@ fn query_snapshot(
     i: usize,
     n: usize,
     snapshot_file: Option<&Path>,
-) -> Result<Operation, Box<dyn Error>> {
-    term.clear_screen()?;
-    println!(
-        "{}{}{}",
-        style("Reviewing [").bold(),
-        style(format!("{}/{}", i, n)).yellow().bold(),
-        style("]:").bold(),
-    );
+    hunk_diff: Option<HunkDiff>,
+    new_variant: Option<&str>,
+    files: &[FileProgress],
+) -> Result<ReviewAction, Box<dyn Error>> {
+    // hunk level review only makes sense if there is more than one hunk
+    let hunk_diff = hunk_diff.filter(|x| x.len() > 1);
+    // large diffs are shown a page at a time
+    let page = (term.size().0 as usize)
+        .saturating_sub(DIFF_PAGE_RESERVED_LINES)
+        .max(20);
+    let mut max_lines = page;
+    let mut expanded = false;
+    'render: loop {
+        term.clear_screen()?;
+        println!(
+            "{}{}{}",
+            style("Reviewing [").bold(),
+            style(format!("{}/{}", i, n)).yellow().bold(),
+            style("]:").bold(),
+        );
+
+        if let Some(pkg) = pkg {
+            println!("Package: {} ({})", style(pkg.name()).dim(), pkg.version());
+        } else {
+            println!();
+        }
+        if files.len() > 1 {
+            let current = files.iter().position(|x| x.is_current).unwrap_or(0);
+            let start = current
+                .saturating_sub(FILE_LIST_LEN / 2)
+                .min(files.len().saturating_sub(FILE_LIST_LEN));
+            for file in files.iter().skip(start).take(FILE_LIST_LEN) {
+                let progress = format!("{}/{}", file.reviewed, file.total);
+                if file.is_current {
+                    println!("  {} {} {}", style(">").cyan().bold(), file.file, progress);
+                } else {
+                    println!("    {}", style(format!("{} {}", file.file, progress)).dim());
+                }
+            }
+        }
+
+        let mut settings = Settings::clone_current();
+        if expanded {
+            settings.set_diff_context(std::usize::MAX);
+        }
+        let hidden = settings.bind(|| {
+            print_snapshot_diff_limited(
+                workspace_root,
+                new,
+                old,
+                snapshot_file,
+                line,
+                Some(max_lines),
+            )
+        });
 
-    if let Some(pkg) = pkg {
-        println!("Package: {} ({})", style(pkg.name()).dim(), pkg.version());
-    } else {
         println!();
-    }
+        println!(
+            "  {} accept   {}",
+            style("a").green().bold(),
+            style("keep the new snapshot").dim()
+        );
+        println!(
+            "  {} reject   {}",
+            style("r").red().bold(),
+            style("keep the old snapshot").dim()
+        );
+        println!(
+            "  {} skip     {}",
+            style("s").yellow().bold(),
+            style("keep both for now").dim()
+        );
+        if let Some(variant) = new_variant {
+            println!(
+                "  {} variant  {}",
+                style("v").green().bold(),
+                style(format!(
+                    "keep both, the new snapshot as variant {}",
+                    variant
+                ))
+                .dim()
+            );
+        }
+        if let Some(ref hunk_diff) = hunk_diff {
+            println!(
+                "  {} hunks    {}",
+                style("h").magenta().bold(),
+                style(format!("review the {} hunks one by one", hunk_diff.len())).dim()
+            );
+        }
+        if i > 1 {
+            println!(
+                "  {} undo     {}",
+                style("u").cyan().bold(),
+                style("undo the last decision").dim()
+            );
+        }
+        println!(
+            "  {} file     {}",
+            style("A").green().bold(),
+            style("accept the remaining snapshots of this file").dim()
+        );
+        println!(
+            "  {} package  {}",
+            style("S").yellow().bold(),
+            style("skip the remaining snapshots of this package").dim()
+        );
+        println!(
+            "  {} search   {}",
+            style("/").blue().bold(),
+            style("only review snapshots matching a pattern").dim()
+        );
+        if !expanded {
+            println!(
+                "  {} expand   {}",
+                style("e").cyan().bold(),
+                style("show the unchanged lines").dim()
+            );
+        }
+        if hidden > 0 {
+            println!(
+                "  {} more     {}",
+                style("m").cyan().bold(),
+                style(format!("show more of the diff ({} lines left)", hidden)).dim()
+            );
+        }
 
-    print_snapshot_diff(workspace_root, new, old, snapshot_file, line);
+        loop {
+            match term.read_key()? {
+                Key::Char('a') | Key::Enter => {
+                    break 'render Ok(ReviewAction::Decide(Operation::Accept))
+                }
+                Key::Char('r') | Key::Escape => {
+                    break 'render Ok(ReviewAction::Decide(Operation::Reject))
+                }
+                Key::Char('s') | Key::Char(' ') => {
+                    break 'render Ok(ReviewAction::Decide(Operation::Skip))
+                }
+                Key::Char('v') if new_variant.is_some() => {
+                    break 'render Ok(ReviewAction::AcceptVariant)
+                }
+                Key::Char('h') if hunk_diff.is_some() => {
+                    let hunks = query_hunks(term, hunk_diff.as_ref().unwrap(), i, n)?;
+                    break 'render Ok(ReviewAction::DecideHunks(hunks));
+                }
+                Key::Char('A') => break 'render Ok(ReviewAction::DecideFile(Operation::Accept)),
+                Key::Char('S') => break 'render Ok(ReviewAction::SkipPackage),
+                Key::Char('u') | Key::Backspace if i > 1 => break 'render Ok(ReviewAction::Undo),
+                Key::Char('/') => {
+                    if let Some(filter) = query_filter(term)? {
+                        break 'render Ok(ReviewAction::Search(Box::new(filter)));
+                    }
+                }
+                Key::Char('e') if !expanded => {
+                    expanded = true;
+                    continue 'render;
+                }
+                Key::Char('m') if hidden > 0 => {
+                    max_lines += page;
+                    continue 'render;
+                }
+                _ => {}
+            }
+        }
+    }
+}
 
-    println!();
+/// Asks if an interrupted review should be resumed.
+fn query_resume(term: &Term, decisions: usize) -> Result<bool, Box<dyn Error>> {
     println!(
-        "  {} accept   {}",
-        style("a").green().bold(),
-        style("keep the new snapshot").dim()
+        "{}: an interrupted review with {} decision{} was found",
+        style("info").bold(),
+        style(decisions).yellow(),
+        if decisions != 1 { "s" } else { "" }
     );
     println!(
-        "  {} reject   {}",
-        style("r").red().bold(),
-        style("keep the old snapshot").dim()
+        "  {} resume   {}",
+        style("y").green().bold(),
+        style("continue where the review stopped").dim()
     );
     println!(
-        "  {} skip     {}",
-        style("s").yellow().bold(),
-        style("keep both for now").dim()
+        "  {} restart  {}",
+        style("n").red().bold(),
+        style("review all snapshots again").dim()
     );
-
     loop {
         match term.read_key()? {
-            Key::Char('a') | Key::Enter => break Ok(Operation::Accept),
-            Key::Char('r') | Key::Escape => break Ok(Operation::Reject),
-            Key::Char('s') | Key::Char(' ') => break Ok(Operation::Skip),
+            Key::Char('y') | Key::Enter => return Ok(true),
+            Key::Char('n') | Key::Escape => return Ok(false),
             _ => {}
         }
     }
 }
 
+/// Prompts for a pattern to filter the remaining snapshots by.
+///
+/// Returns `None` if no pattern was entered.
+fn query_filter(term: &Term) -> Result<Option<GlobMatcher>, Box<dyn Error>> {
+    loop {
+        term.write_str(&format!("{} ", style("search:").blue().bold()))?;
+        let pattern = term.read_line()?;
+        if pattern.trim().is_empty() {
+            return Ok(None);
+        }
+        match compile_glob(pattern.trim()) {
+            Ok(filter) => return Ok(Some(filter)),
+            Err(err) => println!("{}: {}", style("error").red().bold(), err),
+        }
+    }
+}
+
+fn query_hunks(
+    term: &Term,
+    hunk_diff: &HunkDiff,
+    i: usize,
+    n: usize,
+) -> Result<Vec<Operation>, Box<dyn Error>> {
+    let mut hunks = vec![];
+    while hunks.len() < hunk_diff.len() {
+        let idx = hunks.len();
+        term.clear_screen()?;
+        println!(
+            "{}{}{}{}{}",
+            style("Reviewing [").bold(),
+            style(format!("{}/{}", i, n)).yellow().bold(),
+            style("], hunk [").bold(),
+            style(format!("{}/{}", idx + 1, hunk_diff.len()))
+                .yellow()
+                .bold(),
+            style("]:").bold(),
+        );
+        println!();
+        for (tag, line) in hunk_diff.hunk_lines(idx) {
+            match tag {
+                ChangeTag::Delete => println!("{}", style(format!("-{}", line)).red()),
+                ChangeTag::Insert => println!("{}", style(format!("+{}", line)).green()),
+                ChangeTag::Equal => println!("{}", style(format!(" {}", line)).dim()),
+            }
+        }
+
+        println!();
+        println!(
+            "  {} accept   {}",
+            style("a").green().bold(),
+            style("take this change into the snapshot").dim()
+        );
+        println!(
+            "  {} reject   {}",
+            style("r").red().bold(),
+            style("discard this change").dim()
+        );
+        println!(
+            "  {} skip     {}",
+            style("s").yellow().bold(),
+            style("leave this change pending").dim()
+        );
+        if idx > 0 {
+            println!(
+                "  {} undo     {}",
+                style("u").cyan().bold(),
+                style("go back to the previous hunk").dim()
+            );
+        }
+
+        loop {
+            match term.read_key()? {
+                Key::Char('a') | Key::Enter => hunks.push(Operation::Accept),
+                Key::Char('r') | Key::Escape => hunks.push(Operation::Reject),
+                Key::Char('s') | Key::Char(' ') => hunks.push(Operation::Skip),
+                Key::Char('u') | Key::Backspace if idx > 0 => {
+                    hunks.pop();
+                }
+                _ => continue,
+            }
+            break;
+        }
+    }
+    Ok(hunks)
+}
+
+/// Applies the diff options to the diffs rendered by insta and the review.
+///
+/// Without an explicit algorithm the one from the workspace config is used.
+fn set_diff_options(
+    loc: &LocationInfo,
+    diff_context: Option<usize>,
+    diff_algorithm: Option<DiffAlgorithm>,
+) -> Result<(), Box<dyn Error>> {
+    let mut settings = Settings::clone_current();
+    if let Some(diff_context) = diff_context {
+        settings.set_diff_context(diff_context);
+    }
+    let diff_algorithm = match diff_algorithm.or_else(|| settings.diff_algorithm()) {
+        Some(diff_algorithm) => Some(diff_algorithm),
+        None => {
+            ToolConfig::from_workspace(&loc.workspace_root)?
+                .diff
+                .algorithm
+        }
+    };
+    if let Some(diff_algorithm) = diff_algorithm {
+        settings.set_diff_algorithm(diff_algorithm);
+    }
+    settings.bind_to_thread();
+    Ok(())
+}
+
 fn handle_color(color: &str) -> Result<(), Box<dyn Error>> {
     match color {
         "always" => set_colors_enabled(true),
@@ -232,11 +864,38 @@ enum SnapshotKey<'a> {
     },
 }
 
+#[derive(Serialize, Debug)]
+struct SnapshotInfo<'a> {
+    path: &'a Path,
+    module_name: &'a str,
+    snapshot_name: Option<&'a str>,
+    #[serde(flatten)]
+    metadata: &'a MetaData,
+    /// The contents unless the snapshot is binary.
+    contents: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    binary_file: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug)]
+struct AssertionInfo<'a> {
+    path: &'a Path,
+    #[serde(flatten)]
+    assertion: &'a SnapshotAssertion,
+    /// The custom metadata of the snapshot file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    info: Option<&'a BTreeMap<String, String>>,
+}
+
 struct LocationInfo<'a> {
     workspace_root: PathBuf,
     packages: Option<Vec<Package>>,
+    /// The target directory to find snapshots of generated files in.
+    target_dir: Option<PathBuf>,
     exts: Vec<&'a str>,
     no_ignore: bool,
+    /// The folder holding the snapshots of all packages if configured.
+    central_root: Option<PathBuf>,
 }
 
 fn handle_target_args(target_args: &TargetArgs) -> Result<LocationInfo<'_>, Box<dyn Error>> {
@@ -253,9 +912,7 @@ fn handle_target_args(target_args: &TargetArgs) -> Result<LocationInfo<'_>, Box<
         target_args.manifest_path.as_ref(),
     ) {
         (Some(_), Some(_)) => {
-            return Err(err_msg(format!(
-                "both manifest-path and workspace-root provided."
-            )))
+            return Err(err_msg("both manifest-path and workspace-root provided."))
         }
         (None, Some(manifest)) => (None, Some(Cow::Borrowed(manifest))),
         (Some(root), manifest_path) => {
@@ -270,34 +927,68 @@ fn handle_target_args(target_args: &TargetArgs) -> Result<LocationInfo<'_>, Box<
         (None, None) => (None, None),
     };
 
-    if let Some(workspace_root) = workspace_root {
-        Ok(LocationInfo {
+    let mut loc = if let Some(workspace_root) = workspace_root {
+        LocationInfo {
             workspace_root: workspace_root.to_owned(),
             packages: None,
+            target_dir: None,
             exts,
             no_ignore: target_args.no_ignore,
-        })
+            central_root: None,
+        }
     } else {
         let metadata = get_package_metadata(manifest_path.as_ref().map(|x| x.as_path()))?;
         let packages = find_packages(&metadata, target_args.all)?;
-        Ok(LocationInfo {
+        LocationInfo {
             workspace_root: metadata.workspace_root().to_path_buf(),
             packages: Some(packages),
+            target_dir: Some(metadata.target_directory().to_path_buf()),
             exts,
             no_ignore: target_args.no_ignore,
-        })
+            central_root: None,
+        }
+    };
+    apply_config_env(&loc.workspace_root)?;
+    loc.central_root = ToolConfig::from_workspace(&loc.workspace_root)?
+        .snapshots
+        .root
+        .map(|root| loc.workspace_root.join(root));
+    Ok(loc)
+}
+
+/// Exposes settings from the workspace config to insta via the environment.
+///
+/// This covers both snapshots loaded by cargo-insta itself and the tests
+/// it runs.  Variables that are already set take precedence.
+fn apply_config_env(workspace_root: &Path) -> Result<(), Box<dyn Error>> {
+    let config = ToolConfig::from_workspace(workspace_root)?;
+    if let Some(ref dir) = config.blobs.dir {
+        if env::var_os("INSTA_BLOB_DIR").is_none() {
+            env::set_var("INSTA_BLOB_DIR", workspace_root.join(dir));
+        }
     }
+    Ok(())
 }
 
+#[allow(clippy::type_complexity)]
 fn load_snapshot_containers<'a>(
     loc: &'a LocationInfo,
 ) -> Result<Vec<(SnapshotContainer, Option<&'a Package>)>, Box<dyn Error>> {
+    let pending_dir = pending_dir(
+        &loc.target_dir
+            .clone()
+            .unwrap_or_else(|| loc.workspace_root.join("target")),
+    );
     let mut snapshot_containers = vec![];
     match loc.packages {
         Some(ref packages) => {
             for package in packages.iter() {
-                for snapshot_container in package.iter_snapshot_containers(&loc.exts, loc.no_ignore)
-                {
+                for snapshot_container in package.iter_snapshot_containers(
+                    &loc.exts,
+                    loc.no_ignore,
+                    loc.target_dir.as_deref(),
+                    loc.central_root.as_deref(),
+                ) {
                     snapshot_containers.push((snapshot_container?, Some(package)));
                 }
             }
@@ -310,16 +1001,63 @@ fn load_snapshot_containers<'a>(
             }
         }
     }
+    snapshot_containers
+        .retain(|(container, _)| !container.pending_file().starts_with(&pending_dir));
+
+    // pending snapshots can also be kept in the target directory
+    // (`pending.location: target`), they belong to the package that
+    // contains the file they are for.
+    for snapshot_container in
+        find_target_snapshots(pending_dir, loc.workspace_root.clone(), &loc.exts)
+    {
+        let snapshot_container = snapshot_container?;
+        let package = match loc.packages {
+            Some(ref packages) => {
+                match packages
+                    .iter()
+                    .filter(|x| {
+                        x.manifest_path().parent().map_or(false, |root| {
+                            snapshot_container.target_file().starts_with(root)
+                        })
+                    })
+                    .max_by_key(|x| x.manifest_path().components().count())
+                {
+                    Some(package) => Some(package),
+                    None => continue,
+                }
+            }
+            None => None,
+        };
+        snapshot_containers.push((snapshot_container, package));
+    }
     Ok(snapshot_containers)
 }
 
 fn process_snapshots(cmd: ProcessCommand, op: Option<Operation>) -> Result<(), Box<dyn Error>> {
     let term = Term::stdout();
-
     let loc = handle_target_args(&cmd.target_args)?;
+    set_diff_options(&loc, cmd.diff_context, cmd.diff_algorithm)?;
+    if let Some(ref archive) = cmd.from_archive {
+        let files = read_archive(archive)?;
+        check_pending_only(&files)?;
+        for file in &files {
+            let path = loc.workspace_root.join(&file.path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(&path, &file.contents)?;
+        }
+        if !cmd.quiet {
+            println!(
+                "{}: extracted {} file{} from {}",
+                style("info").bold(),
+                files.len(),
+                if files.len() != 1 { "s" } else { "" },
+                archive.display()
+            );
+        }
+    }
     let mut snapshot_containers = load_snapshot_containers(&loc)?;
 
-    let snapshot_count = snapshot_containers.iter().map(|x| x.0.len()).sum();
+    let snapshot_count = snapshot_containers.iter().map(|x| x.0.len()).sum::<usize>();
 
     if snapshot_count == 0 {
         if !cmd.quiet {
@@ -328,15 +1066,54 @@ fn process_snapshots(cmd: ProcessCommand, op: Option<Operation>) -> Result<(), B
         return Ok(());
     }
 
-    let mut accepted = vec![];
+    let mut config = ToolConfig::from_workspace(&loc.workspace_root)?;
+    if let Some(rustfmt) = cmd.rustfmt {
+        config.inline.rustfmt = rustfmt;
+    }
+    let max_age = match config.pending.max_age {
+        Some(ref max_age) => Some(parse_duration(max_age)?),
+        None => None,
+    };
+
     let mut rejected = vec![];
     let mut skipped = vec![];
-    let mut num = 0;
 
-    for (snapshot_container, package) in snapshot_containers.iter_mut() {
+    // collect the snapshots to review first so that decisions can be undone.
+    // nothing is written until the review is finished.  Snapshots are
+    // grouped by package and file which are tracked in `groups`.
+    let mut queue = vec![];
+    let mut groups: Vec<(String, String)> = vec![];
+    for (container_idx, (snapshot_container, package)) in snapshot_containers.iter_mut().enumerate()
+    {
         let target_file = snapshot_container.target_file().to_path_buf();
-        let snapshot_file = snapshot_container.snapshot_file().map(|x| x.to_path_buf());
-        for snapshot_ref in snapshot_container.iter_snapshots() {
+        let rel_target_file = target_file
+            .strip_prefix(&loc.workspace_root)
+            .unwrap_or(&target_file);
+        let age = snapshot_container.age().unwrap_or_default();
+        // stale snapshots from abandoned branches are discarded right away
+        // by reviews and rejects but never accepted
+        let is_stale = max_age.map_or(false, |max_age| age > max_age);
+        let is_recent = cmd.older_than.map_or(false, |older_than| age <= older_than);
+        for (snapshot_idx, snapshot_ref) in snapshot_container.iter_snapshots().enumerate() {
+            if is_stale {
+                if op == Some(Operation::Accept) {
+                    skipped.push(format!("{} (stale)", snapshot_ref.summary()));
+                } else {
+                    snapshot_ref.op = Operation::Reject;
+                    rejected.push(format!("{} (stale)", snapshot_ref.summary()));
+                }
+                continue;
+            }
+            if is_recent {
+                skipped.push(snapshot_ref.summary());
+                continue;
+            }
+            if let Some(ref filter) = cmd.filter {
+                if !snapshot_ref.matches_filter(filter, rel_target_file) {
+                    skipped.push(snapshot_ref.summary());
+                    continue;
+                }
+            }
             // if a filter is provided, check if the snapshot reference is included
             if let Some(ref filter) = cmd.snapshot_filter {
                 let key = if let Some(line) = snapshot_ref.line {
@@ -349,74 +1126,397 @@ fn process_snapshots(cmd: ProcessCommand, op: Option<Operation>) -> Result<(), B
                     continue;
                 }
             }
-
-            num += 1;
-            let op = match op {
-                Some(op) => op,
-                None => query_snapshot(
-                    &loc.workspace_root,
-                    &term,
-                    &snapshot_ref.new,
-                    snapshot_ref.old.as_ref(),
-                    *package,
-                    snapshot_ref.line,
-                    num,
-                    snapshot_count,
-                    snapshot_file.as_ref().map(|x| x.as_path()),
-                )?,
-            };
-            match op {
-                Operation::Accept => {
-                    snapshot_ref.op = Operation::Accept;
-                    accepted.push(snapshot_ref.summary());
-                }
-                Operation::Reject => {
-                    snapshot_ref.op = Operation::Reject;
-                    rejected.push(snapshot_ref.summary());
-                }
-                Operation::Skip => {
-                    skipped.push(snapshot_ref.summary());
+            let group = (
+                package.map_or("", |x| x.name()).to_string(),
+                snapshot_ref
+                    .new
+                    .metadata()
+                    .source()
+                    .map_or_else(|| rel_target_file.display().to_string(), |x| x.into()),
+            );
+            let group_idx = match groups.iter().position(|x| *x == group) {
+                Some(idx) => idx,
+                None => {
+                    groups.push(group);
+                    groups.len() - 1
                 }
-            }
+            };
+            queue.push((container_idx, snapshot_idx, group_idx));
         }
-        snapshot_container.commit()?;
     }
+    queue.sort_by_key(|&(_, _, group_idx)| group_idx);
 
+    // decisions of an interrupted review are restored and the review
+    // continues after them.
+    let mut pos = 0;
+    let mut session = ReviewSession::default();
     if op.is_none() {
-        term.clear_screen()?;
-    }
-
-    if !cmd.quiet {
-        println!("{}", style("insta review finished").bold());
-        if !accepted.is_empty() {
-            println!("{}:", style("accepted").green());
-            for item in accepted {
-                println!("  {}", item);
-            }
-        }
-        if !rejected.is_empty() {
-            println!("{}:", style("rejected").red());
-            for item in rejected {
-                println!("  {}", item);
-            }
-        }
-        if !skipped.is_empty() {
-            println!("{}:", style("skipped").yellow());
-            for item in skipped {
-                println!("  {}", item);
+        if let Some(previous) = ReviewSession::load(&loc.workspace_root) {
+            if query_resume(&term, previous.len())? {
+                let (decided, undecided): (Vec<_>, Vec<_>) =
+                    queue
+                        .iter()
+                        .partition(|&&(container_idx, snapshot_idx, _)| {
+                            let snapshot_container = &mut snapshot_containers[container_idx].0;
+                            let target_file = snapshot_container.target_file().to_path_buf();
+                            let snapshot_ref = snapshot_container
+                                .iter_snapshots()
+                                .nth(snapshot_idx)
+                                .unwrap();
+                            match previous.decision(snapshot_ref, &target_file) {
+                                Some(decision) => {
+                                    decision.apply(snapshot_ref);
+                                    true
+                                }
+                                None => false,
+                            }
+                        });
+                pos = decided.len();
+                queue = decided.into_iter().chain(undecided).collect();
+                session = previous;
             }
         }
     }
 
-    Ok(())
-}
+    let web = if cmd.web && op.is_none() {
+        let summaries = queue
+            .iter()
+            .map(|&(container_idx, snapshot_idx, _)| {
+                snapshot_containers[container_idx]
+                    .0
+                    .iter_snapshots()
+                    .nth(snapshot_idx)
+                    .unwrap()
+                    .summary()
+            })
+            .collect();
+        let web = WebReview::start(cmd.port.unwrap_or(0), summaries)?;
+        println!(
+            "{}: review snapshots at {}",
+            style("info").bold(),
+            style(web.url()?).cyan().underlined()
+        );
+        Some(web)
+    } else {
+        None
+    };
 
-fn make_deletion_walker(loc: &LocationInfo) -> Walk {
-    let roots: HashSet<_> = match loc.packages {
-        Some(ref packages) => packages
+    // the positions reviewing continued from after each action, so that
+    // undoing an action that decided several snapshots undoes all of them.
+    // snapshots after the current position are never decided.
+    let mut undo_stack = vec![];
+    while pos < queue.len() {
+        let (container_idx, snapshot_idx, group_idx) = queue[pos];
+        let previous_pos = pos;
+        let files: Vec<_> = groups
             .iter()
-            .filter_map(|x| x.manifest_path().parent().unwrap().canonicalize().ok())
-            .collect(),
+            .enumerate()
+            .filter(|(_, group)| group.0 == groups[group_idx].0)
+            .filter_map(|(idx, group)| {
+                let total = queue.iter().filter(|x| x.2 == idx).count();
+                if total == 0 {
+                    return None;
+                }
+                Some(FileProgress {
+                    file: &group.1,
+                    reviewed: queue[..pos].iter().filter(|x| x.2 == idx).count(),
+                    total,
+                    is_current: idx == group_idx,
+                })
+            })
+            .collect();
+        let (ref mut snapshot_container, package) = snapshot_containers[container_idx];
+        let snapshot_file = snapshot_container.snapshot_file().map(|x| x.to_path_buf());
+        let target_file = snapshot_container.target_file().to_path_buf();
+        let snapshot_ref = snapshot_container
+            .iter_snapshots()
+            .nth(snapshot_idx)
+            .unwrap();
+        let action = match (op, web.as_ref()) {
+            (Some(op), _) => ReviewAction::Decide(op),
+            (None, Some(web)) => web.query_snapshot(
+                &loc.workspace_root,
+                &ReviewItem {
+                    new: &snapshot_ref.new,
+                    old: snapshot_ref.old.as_ref(),
+                    pkg: package,
+                    line: snapshot_ref.line,
+                    snapshot_file: snapshot_file.as_deref(),
+                },
+                pos + 1,
+            )?,
+            (None, None) => query_snapshot(
+                &loc.workspace_root,
+                &term,
+                &snapshot_ref.new,
+                snapshot_ref.old.as_ref(),
+                package,
+                snapshot_ref.line,
+                pos + 1,
+                queue.len(),
+                snapshot_file.as_deref(),
+                snapshot_ref.hunk_diff(),
+                snapshot_ref.new_variant(&target_file),
+                &files,
+            )?,
+        };
+        match action {
+            ReviewAction::Decide(op) => {
+                snapshot_ref.op = op;
+                snapshot_ref.as_variant = false;
+                pos += 1;
+            }
+            ReviewAction::DecideHunks(hunks) => {
+                snapshot_ref.set_hunks(hunks);
+                snapshot_ref.as_variant = false;
+                pos += 1;
+            }
+            ReviewAction::AcceptVariant => {
+                snapshot_ref.op = Operation::Accept;
+                snapshot_ref.hunks = None;
+                snapshot_ref.as_variant = true;
+                pos += 1;
+            }
+            ReviewAction::Search(filter) => {
+                let (matching, other): (Vec<_>, Vec<_>) =
+                    queue[pos..]
+                        .iter()
+                        .partition(|&&(container_idx, snapshot_idx, _)| {
+                            let snapshot_container = &mut snapshot_containers[container_idx].0;
+                            let target_file = snapshot_container.target_file().to_path_buf();
+                            let rel_target_file = target_file
+                                .strip_prefix(&loc.workspace_root)
+                                .unwrap_or(&target_file);
+                            snapshot_container
+                                .iter_snapshots()
+                                .nth(snapshot_idx)
+                                .unwrap()
+                                .matches_filter(&filter, rel_target_file)
+                        });
+                // if nothing matches the current snapshot is shown again
+                if !matching.is_empty() {
+                    for (container_idx, snapshot_idx, _) in other {
+                        let snapshot_ref = snapshot_containers[container_idx]
+                            .0
+                            .iter_snapshots()
+                            .nth(snapshot_idx)
+                            .unwrap();
+                        skipped.push(snapshot_ref.summary());
+                    }
+                    queue.truncate(pos);
+                    queue.extend(matching);
+                }
+            }
+            ReviewAction::DecideFile(op) => {
+                while pos < queue.len() && queue[pos].2 == group_idx {
+                    let (container_idx, snapshot_idx, _) = queue[pos];
+                    let snapshot_ref = snapshot_containers[container_idx]
+                        .0
+                        .iter_snapshots()
+                        .nth(snapshot_idx)
+                        .unwrap();
+                    snapshot_ref.op = op;
+                    snapshot_ref.hunks = None;
+                    snapshot_ref.as_variant = false;
+                    pos += 1;
+                }
+            }
+            ReviewAction::SkipPackage => {
+                while pos < queue.len() && groups[queue[pos].2].0 == groups[group_idx].0 {
+                    pos += 1;
+                }
+            }
+            ReviewAction::Undo => {
+                // decisions restored from an interrupted review are undone
+                // one by one
+                let undo_pos = undo_stack.pop().unwrap_or_else(|| pos.saturating_sub(1));
+                for &(container_idx, snapshot_idx, _) in &queue[undo_pos..pos] {
+                    let snapshot_ref = snapshot_containers[container_idx]
+                        .0
+                        .iter_snapshots()
+                        .nth(snapshot_idx)
+                        .unwrap();
+                    snapshot_ref.op = Operation::Skip;
+                    snapshot_ref.hunks = None;
+                    snapshot_ref.as_variant = false;
+                }
+                pos = undo_pos;
+            }
+        }
+        if pos > previous_pos {
+            undo_stack.push(previous_pos);
+        }
+
+        if op.is_none() {
+            let decisions = queue[..pos]
+                .iter()
+                .map(|&(container_idx, snapshot_idx, _)| {
+                    let snapshot_container = &mut snapshot_containers[container_idx].0;
+                    let target_file = snapshot_container.target_file().to_path_buf();
+                    let snapshot_ref = snapshot_container
+                        .iter_snapshots()
+                        .nth(snapshot_idx)
+                        .unwrap();
+                    Decision::new(snapshot_ref, &target_file)
+                })
+                .collect();
+            session.save(&loc.workspace_root, decisions)?;
+        }
+    }
+
+    let mut accepted = vec![];
+    for &(container_idx, snapshot_idx, _) in &queue {
+        let snapshot_ref = snapshot_containers[container_idx]
+            .0
+            .iter_snapshots()
+            .nth(snapshot_idx)
+            .unwrap();
+        match snapshot_ref.op {
+            Operation::Accept if snapshot_ref.hunks.is_some() => {
+                accepted.push(format!("{} (partially)", snapshot_ref.summary()))
+            }
+            Operation::Accept if snapshot_ref.as_variant => accepted.push(format!(
+                "{} (as variant {})",
+                snapshot_ref.summary(),
+                snapshot_ref.new.metadata().variant().unwrap_or("")
+            )),
+            Operation::Accept => accepted.push(snapshot_ref.summary()),
+            Operation::Reject => rejected.push(snapshot_ref.summary()),
+            Operation::Skip => skipped.push(snapshot_ref.summary()),
+        }
+    }
+    if cmd.confirm && op.is_none() && !query_apply(&term, &accepted, &rejected, &skipped)? {
+        // the session is kept so that the review can be picked up again
+        term.clear_screen()?;
+        println!(
+            "{}: no changes were applied",
+            style("review aborted").bold()
+        );
+        return Ok(());
+    }
+
+    if let Some(ref expires) = cmd.expires {
+        for (snapshot_container, _) in snapshot_containers.iter_mut() {
+            if snapshot_container.snapshot_file().is_none() {
+                continue;
+            }
+            for snapshot_ref in snapshot_container.iter_snapshots() {
+                if snapshot_ref.op == Operation::Accept {
+                    snapshot_ref.new = snapshot_ref.new.with_expires(Some(expires.clone()));
+                }
+            }
+        }
+    }
+
+    let committed = commit_decisions(
+        &loc.workspace_root,
+        &config,
+        &mut snapshot_containers,
+        cmd.trash,
+        cmd.stage || config.review.stage,
+        false,
+    )?;
+    if op.is_none() {
+        ReviewSession::discard(&loc.workspace_root);
+    }
+
+    if let Some(ref web) = web {
+        web.finish(&accepted, &rejected, &skipped)?;
+    } else if op.is_none() {
+        term.clear_screen()?;
+    }
+    print_warnings(&committed.warnings);
+
+    if !cmd.quiet {
+        println!("{}", style("insta review finished").bold());
+        print_decisions(&accepted, &rejected, &skipped);
+        if committed.trash.is_some() && !rejected.is_empty() {
+            println!(
+                "{}: rejected snapshots were moved to the trash, use `cargo insta restore` \
+                 to bring them back",
+                style("info").bold()
+            );
+        }
+        for (snapshot_container, _) in snapshot_containers.iter() {
+            for snapshot_file in snapshot_container.moved_to_files() {
+                println!(
+                    "{}: inline snapshot in {} was too large and moved to {}",
+                    style("info").bold(),
+                    snapshot_container
+                        .target_file()
+                        .strip_prefix(&loc.workspace_root)
+                        .unwrap_or_else(|_| snapshot_container.target_file())
+                        .display(),
+                    snapshot_file
+                        .strip_prefix(&loc.workspace_root)
+                        .unwrap_or(snapshot_file)
+                        .display()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the snapshots that were accepted, rejected and skipped.
+fn print_decisions(accepted: &[String], rejected: &[String], skipped: &[String]) {
+    if !accepted.is_empty() {
+        println!("{}:", style("accepted").green());
+        for item in accepted {
+            println!("  {}", item);
+        }
+    }
+    if !rejected.is_empty() {
+        println!("{}:", style("rejected").red());
+        for item in rejected {
+            println!("  {}", item);
+        }
+    }
+    if !skipped.is_empty() {
+        println!("{}:", style("skipped").yellow());
+        for item in skipped {
+            println!("  {}", item);
+        }
+    }
+}
+
+/// Shows all decisions and asks if they should be applied.
+fn query_apply(
+    term: &Term,
+    accepted: &[String],
+    rejected: &[String],
+    skipped: &[String],
+) -> Result<bool, Box<dyn Error>> {
+    term.clear_screen()?;
+    println!("{}", style("Review summary:").bold());
+    print_decisions(accepted, rejected, skipped);
+    println!();
+    println!(
+        "  {} apply    {}",
+        style("y").green().bold(),
+        style("write the decisions to disk").dim()
+    );
+    println!(
+        "  {} abort    {}",
+        style("n").red().bold(),
+        style("leave all snapshots pending").dim()
+    );
+    loop {
+        match term.read_key()? {
+            Key::Char('y') | Key::Enter => return Ok(true),
+            Key::Char('n') | Key::Escape => return Ok(false),
+            _ => {}
+        }
+    }
+}
+
+fn make_deletion_walker(loc: &LocationInfo) -> Walk {
+    let roots: HashSet<_> = match loc.packages {
+        Some(ref packages) => packages
+            .iter()
+            .filter_map(|x| x.manifest_path().parent().unwrap().canonicalize().ok())
+            .collect(),
         None => {
             let mut hs = HashSet::new();
             hs.insert(loc.workspace_root.clone());
@@ -438,7 +1538,7 @@ fn make_deletion_walker(loc: &LocationInfo) -> Walk {
 
             // We always want to skip target even if it was not excluded by
             // ignore files.
-            if entry.path().file_name() == Some(&OsStr::new("target"))
+            if entry.path().file_name() == Some(OsStr::new("target"))
                 && roots.contains(canonicalized.parent().unwrap())
             {
                 return false;
@@ -461,47 +1561,194 @@ fn make_deletion_walker(loc: &LocationInfo) -> Walk {
         .build()
 }
 
-fn test_run(mut cmd: TestCommand, color: &str) -> Result<(), Box<dyn Error>> {
-    let mut proc = process::Command::new(get_cargo());
-    proc.arg("test");
+/// Finds the snapshot files that are not among the referenced files.
+fn find_unreferenced_snapshots(loc: &LocationInfo, referenced: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let mut rv = vec![];
+    for entry in make_deletion_walker(loc) {
+        let rel_path = match entry {
+            Ok(ref entry) => entry.path(),
+            _ => continue,
+        };
+        if !rel_path.is_file()
+            || !rel_path
+                .file_name()
+                .map_or(false, |x| x.to_str().unwrap_or("").ends_with(".snap"))
+        {
+            continue;
+        }
 
-    // when unreferenced snapshots should be deleted we need to instruct
-    // insta to dump referenced snapshots somewhere.
-    let snapshot_ref_file = if cmd.delete_unreferenced_snapshots {
-        let snapshot_ref_file = env::temp_dir().join(Uuid::new_v4().to_string());
-        proc.env("INSTA_SNAPSHOT_REFERENCES_FILE", &snapshot_ref_file);
-        Some(snapshot_ref_file)
-    } else {
-        None
-    };
+        if let Ok(path) = fs::canonicalize(rel_path) {
+            if !referenced.contains(&path) {
+                rv.push(path);
+            }
+        }
+    }
+    rv
+}
 
-    // if INSTA_UPDATE is set as environment variable we're using it to
-    // override some arguments.  The logic is is quite weird because we
-    // don't support all of the same values and we also want to override
-    // it through the command line switches.
-    match env::var("INSTA_UPDATE").ok().as_deref() {
-        Some("auto") | Some("new") => {}
-        Some("always") => {
-            if !cmd.accept && !cmd.accept_unseen && !cmd.review {
-                cmd.review = false;
-                cmd.accept = true;
+/// Reads the snapshot files insta recorded as referenced during a test run.
+fn read_references(path: &Path) -> HashSet<PathBuf> {
+    let mut rv = HashSet::new();
+    for line in fs::read_to_string(path).unwrap_or_default().lines() {
+        if let Ok(path) = fs::canonicalize(line) {
+            rv.insert(path);
+        }
+    }
+    rv
+}
+
+/// Asks if an unreferenced snapshot should be moved to a new assertion.
+fn query_migrate(term: &Term, from: &Path, to: &Path) -> Result<bool, Box<dyn Error>> {
+    println!(
+        "{} looks like it was renamed to {}",
+        style(from.display()).cyan(),
+        style(to.display()).cyan()
+    );
+    println!(
+        "  {} move     {}",
+        style("y").green().bold(),
+        style("move the snapshot file").dim()
+    );
+    println!(
+        "  {} keep     {}",
+        style("n").red().bold(),
+        style("review the new snapshot").dim()
+    );
+    loop {
+        match term.read_key()? {
+            Key::Char('y') | Key::Enter => return Ok(true),
+            Key::Char('n') | Key::Escape => return Ok(false),
+            _ => {}
+        }
+    }
+}
+
+/// Moves unreferenced snapshots to the assertions they belong to.
+///
+/// If `ask` is set every move has to be confirmed.
+fn migrate_snapshots(
+    loc: &LocationInfo,
+    referenced: &HashSet<PathBuf>,
+    stage: bool,
+    ask: bool,
+) -> Result<(), Box<dyn Error>> {
+    let stranded: Vec<_> = find_unreferenced_snapshots(loc, referenced)
+        .into_iter()
+        .filter_map(|path| {
+            let snapshot = Snapshot::from_file(&path).ok()?;
+            Some(StrandedSnapshot { path, snapshot })
+        })
+        .collect();
+    let mut new = vec![];
+    for (snapshot_container, _) in load_snapshot_containers(loc)?.iter_mut() {
+        let path = match snapshot_container.snapshot_file() {
+            Some(path) => path.to_path_buf(),
+            None => continue,
+        };
+        let pending_files = snapshot_container.pending_files();
+        for snapshot_ref in snapshot_container.iter_snapshots() {
+            if snapshot_ref.old.is_none() {
+                new.push(NewSnapshot {
+                    path: path.clone(),
+                    snapshot: snapshot_ref.new.clone(),
+                    pending_files: pending_files.clone(),
+                });
             }
         }
-        Some("unseen") => {
-            if !cmd.accept {
-                cmd.accept_unseen = true;
-                cmd.review = true;
-                cmd.accept = false;
+    }
+
+    let mut migrations = find_migrations(&stranded, &new);
+    if ask {
+        let term = Term::stdout();
+        let mut confirmed = vec![];
+        for migration in migrations {
+            let from = migration.from.path.strip_prefix(&loc.workspace_root);
+            let to = migration.to.path.strip_prefix(&loc.workspace_root);
+            if query_migrate(
+                &term,
+                from.unwrap_or(&migration.from.path),
+                to.unwrap_or(&migration.to.path),
+            )? {
+                confirmed.push(migration);
             }
         }
-        // silently ignored always
-        None | Some("") | Some("no") => {}
-        _ => {
-            return Err(err_msg("invalid value for INSTA_UPDATE"));
+        migrations = confirmed;
+    }
+    if migrations.is_empty() {
+        if !ask {
+            eprintln!("{}: no snapshots to migrate", style("info").bold());
         }
+        return Ok(());
+    }
+    eprintln!("{}: migrated snapshots:", style("info").bold());
+    let mut files = vec![];
+    for migration in &migrations {
+        let changed = migration.apply()?;
+        eprintln!(
+            "  {} -> {}{}",
+            migration
+                .from
+                .path
+                .strip_prefix(&loc.workspace_root)
+                .unwrap_or(&migration.from.path)
+                .display(),
+            migration
+                .to
+                .path
+                .strip_prefix(&loc.workspace_root)
+                .unwrap_or(&migration.to.path)
+                .display(),
+            if changed {
+                style(" (changed, pending review)").yellow().to_string()
+            } else {
+                "".into()
+            }
+        );
+        files.extend(migration.changed_files());
+    }
+    if stage
+        || ToolConfig::from_workspace(&loc.workspace_root)?
+            .review
+            .stage
+    {
+        stage_files(&loc.workspace_root, &files)?;
+    }
+    Ok(())
+}
+
+/// Deletes the snapshot files that are not among the referenced files.
+fn delete_unreferenced_snapshots(
+    loc: &LocationInfo,
+    referenced: &HashSet<PathBuf>,
+    stage: bool,
+) -> Result<(), Box<dyn Error>> {
+    let deleted = find_unreferenced_snapshots(loc, referenced);
+    if deleted.is_empty() {
+        eprintln!("{}: no unreferenced snapshots found", style("info").bold());
+        return Ok(());
+    }
+    eprintln!("{}: deleted unreferenced snapshots:", style("info").bold());
+    for path in &deleted {
+        eprintln!("  {}", path.display());
+        fs::remove_file(path).ok();
+    }
+    if stage
+        || ToolConfig::from_workspace(&loc.workspace_root)?
+            .review
+            .stage
+    {
+        stage_files(&loc.workspace_root, &deleted)?;
     }
+    Ok(())
+}
+
+/// Builds the `cargo test` command for a test run with the given features.
+fn make_test_command(cmd: &TestCommand, features: Option<&str>, color: &str) -> process::Command {
+    let mut proc = process::Command::new(get_cargo());
+    proc.arg("test");
 
-    if cmd.target_args.all {
+    // cargo ignores the package when testing the entire workspace
+    if cmd.target_args.all && cmd.package.is_none() {
         proc.arg("--all");
     }
     if let Some(ref pkg) = cmd.package {
@@ -512,6 +1759,10 @@ fn test_run(mut cmd: TestCommand, color: &str) -> Result<(), Box<dyn Error>> {
         proc.arg("--manifest-path");
         proc.arg(manifest_path);
     }
+    for target in &cmd.test_targets {
+        proc.arg("--test");
+        proc.arg(target);
+    }
     if !cmd.fail_fast {
         proc.arg("--no-fail-fast");
     }
@@ -520,18 +1771,30 @@ fn test_run(mut cmd: TestCommand, color: &str) -> Result<(), Box<dyn Error>> {
     }
     proc.env(
         "INSTA_UPDATE",
-        if cmd.accept_unseen { "unseen" } else { "new" },
+        if cmd.new_only {
+            "new-only"
+        } else if cmd.accept_unseen {
+            "unseen"
+        } else {
+            "new"
+        },
     );
     if cmd.force_update_snapshots {
         proc.env("INSTA_FORCE_UPDATE_SNAPSHOTS", "1");
     }
+    if let Some(diff_context) = cmd.diff_context {
+        proc.env("INSTA_DIFF_CONTEXT", diff_context.to_string());
+    }
+    if let Some(algorithm) = cmd.diff_algorithm {
+        proc.env("INSTA_DIFF_ALGORITHM", algorithm.to_string());
+    }
     if cmd.release {
         proc.arg("--release");
     }
     if let Some(n) = cmd.jobs {
         proc.arg(format!("--jobs={}", n));
     }
-    if let Some(ref features) = cmd.features {
+    if let Some(features) = features {
         proc.arg("--features");
         proc.arg(features);
     }
@@ -543,23 +1806,295 @@ fn test_run(mut cmd: TestCommand, color: &str) -> Result<(), Box<dyn Error>> {
     }
     proc.arg("--color");
     proc.arg(color);
-    proc.arg("--");
-    proc.arg("-q");
+    proc
+}
+
+/// Lists the names of the tests a test run would run.
+fn list_tests(
+    cmd: &TestCommand,
+    features: Option<&str>,
+    color: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let output = make_test_command(cmd, features, color)
+        .arg("--")
+        .arg("--list")
+        .arg("--format")
+        .arg("terse")
+        .stderr(process::Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(QuietExit(1).into());
+    }
+    Ok(parse_test_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Returns the feature combinations of a test run.
+///
+/// Without `--feature-matrix` there is a single run with the features
+/// from the command line.
+fn feature_combinations(cmd: &TestCommand) -> Result<Vec<Option<String>>, Box<dyn Error>> {
+    if !cmd.feature_matrix {
+        return Ok(vec![cmd.features.clone()]);
+    }
+    if cmd.features.is_some() || cmd.all_features {
+        return Err(err_msg(
+            "--feature-matrix cannot be combined with --features or --all-features",
+        ));
+    }
+    let loc = handle_target_args(&cmd.target_args)?;
+    let matrix = ToolConfig::from_workspace(&loc.workspace_root)?
+        .test
+        .feature_matrix;
+    if matrix.is_empty() {
+        return Err(err_msg(
+            "no feature combinations configured in the test.feature_matrix section of insta.yaml",
+        ));
+    }
+    Ok(matrix
+        .into_iter()
+        .map(|features| Some(features).filter(|x| !x.trim().is_empty()))
+        .collect())
+}
+
+/// Describes a feature combination for messages.
+fn describe_features(features: Option<&str>) -> String {
+    match features {
+        Some(features) => format!("features `{}`", features),
+        None => "default features".into(),
+    }
+}
+
+/// Tracks which feature combination produced which pending snapshots.
+#[derive(Default)]
+struct PendingTracker {
+    seen: HashMap<(PathBuf, Option<u32>), (String, SnapshotContents)>,
+}
+
+impl PendingTracker {
+    /// Records the pending snapshots after the run of a feature combination.
+    ///
+    /// Returns the number of snapshots that became pending in this run.
+    fn record(
+        &mut self,
+        loc: &LocationInfo,
+        features: Option<&str>,
+    ) -> Result<usize, Box<dyn Error>> {
+        let combination = describe_features(features);
+        let mut count = 0;
+        for (mut snapshot_container, _) in load_snapshot_containers(loc)? {
+            let target_file = snapshot_container.target_file().to_path_buf();
+            for snapshot_ref in snapshot_container.iter_snapshots() {
+                let key = (target_file.clone(), snapshot_ref.line);
+                let contents = snapshot_ref.new.contents();
+                match self.seen.get(&key) {
+                    Some((_, seen)) if seen == contents => continue,
+                    Some((other, _)) => eprintln!(
+                        "{} {} differs between {} and {}, only the latter is kept for review \
+                         (use `insta::cfg_suffix!` to store one snapshot per combination)",
+                        style("warning:").bold().yellow(),
+                        snapshot_ref.summary(),
+                        other,
+                        combination
+                    ),
+                    None => {}
+                }
+                self.seen
+                    .insert(key, (combination.clone(), contents.clone()));
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+fn test_run(mut cmd: TestCommand, color: &str) -> Result<(), Box<dyn Error>> {
+    // when unreferenced snapshots should be deleted we need to instruct
+    // insta to dump referenced snapshots somewhere.  With a feature matrix
+    // all runs share the file so that only snapshots that no combination
+    // references are deleted.
+    let unreferenced = match cmd.unreferenced.as_deref() {
+        Some(mode) => mode,
+        None if cmd.delete_unreferenced_snapshots => "delete",
+        None => "ignore",
+    };
+    let snapshot_ref_file = if unreferenced != "ignore"
+        || cmd.migrate
+        || cmd.review
+        || cmd.shard.is_some()
+        || cmd.manifest.is_some()
+    {
+        Some(env::temp_dir().join(Uuid::new_v4().to_string()))
+    } else {
+        None
+    };
+
+    // reports need the results of all snapshot assertions which insta
+    // writes into this file.
+    let message_format = match cmd.message_format.as_deref() {
+        Some(format) => format,
+        None if env::var("GITHUB_ACTIONS").ok().as_deref() == Some("true") => "github",
+        None => "human",
+    };
+    let assertion_results_file = if cmd.junit.is_some() || message_format != "human" {
+        Some(env::temp_dir().join(Uuid::new_v4().to_string()))
+    } else {
+        None
+    };
+
+    // if INSTA_UPDATE is set as environment variable we're using it to
+    // override some arguments.  The logic is is quite weird because we
+    // don't support all of the same values and we also want to override
+    // it through the command line switches.
+    match env::var("INSTA_UPDATE").ok().as_deref() {
+        Some("auto") | Some("new") | Some("ask") => {}
+        Some("new-only") => {
+            if !cmd.accept && !cmd.accept_unseen {
+                cmd.new_only = true;
+            }
+        }
+        Some("always") => {
+            if !cmd.accept && !cmd.accept_unseen && !cmd.review {
+                cmd.review = false;
+                cmd.accept = true;
+            }
+        }
+        Some("unseen") => {
+            if !cmd.accept {
+                cmd.accept_unseen = true;
+                cmd.review = true;
+                cmd.accept = false;
+            }
+        }
+        // silently ignored always
+        None | Some("") | Some("no") => {}
+        _ => {
+            return Err(err_msg("invalid value for INSTA_UPDATE"));
+        }
+    }
+
+    // with json the events are the only output on stdout so that it can be
+    // read line by line.
+    let json_events = message_format == "json";
+    if json_events && cmd.review {
+        return Err(err_msg(
+            "snapshots cannot be reviewed with --message-format json, \
+             use `cargo insta review` afterwards",
+        ));
+    }
+
+    let combinations = feature_combinations(&cmd)?;
+    let partial_run = cmd.shard.is_some() || cmd.manifest.is_some();
+    if partial_run && unreferenced != "ignore" {
+        return Err(err_msg(
+            "unreferenced snapshots cannot be handled with --shard or --manifest, \
+             use `cargo insta merge-manifests --delete` once all runs finished",
+        ));
+    }
+    if partial_run && cmd.migrate {
+        return Err(err_msg(
+            "snapshots cannot be migrated with --shard or --manifest as other \
+             runs reference other snapshots",
+        ));
+    }
 
     if !cmd.keep_pending {
         process_snapshots(
             ProcessCommand {
                 target_args: cmd.target_args.clone(),
                 snapshot_filter: None,
+                filter: None,
                 quiet: true,
+                web: false,
+                port: None,
+                confirm: false,
+                trash: false,
+                stage: cmd.stage,
+                older_than: None,
+                from_archive: None,
+                diff_context: cmd.diff_context,
+                diff_algorithm: cmd.diff_algorithm,
+                rustfmt: cmd.rustfmt,
+                expires: None,
             },
             Some(Operation::Reject),
         )?;
     }
 
-    let status = proc.status()?;
+    let mut success = true;
+    let mut tracker = PendingTracker::default();
+    for features in &combinations {
+        let mut proc = make_test_command(&cmd, features.as_deref(), color);
+        proc.arg("--");
+        proc.arg("-q");
+        if let Some(shard) = cmd.shard {
+            let tests = list_tests(&cmd, features.as_deref(), color)?;
+            let selected = shard.select(&tests);
+            eprintln!(
+                "{}: running {} of {} tests in shard {}",
+                style("info").bold(),
+                selected.len(),
+                tests.len(),
+                shard
+            );
+            if selected.is_empty() {
+                continue;
+            }
+            proc.arg("--exact");
+            proc.args(selected);
+        }
+        if let Some(ref path) = snapshot_ref_file {
+            proc.env("INSTA_SNAPSHOT_REFERENCES_FILE", path);
+        }
+        if let Some(ref path) = assertion_results_file {
+            proc.env("INSTA_ASSERTION_RESULTS_FILE", path);
+        }
+        if cmd.feature_matrix {
+            eprintln!(
+                "{}: running tests with {}",
+                style("info").bold(),
+                describe_features(features.as_deref())
+            );
+        }
+        let status = if json_events {
+            status_with_stdout_on_stderr(&mut proc)?
+        } else {
+            proc.status()?
+        };
+        if !status.success() {
+            success = false;
+        }
+        if cmd.feature_matrix {
+            let loc = handle_target_args(&cmd.target_args)?;
+            let count = tracker.record(&loc, features.as_deref())?;
+            eprintln!(
+                "{}: {} new pending snapshot{} with {}",
+                style("info").bold(),
+                style(count).yellow(),
+                if count != 1 { "s" } else { "" },
+                describe_features(features.as_deref())
+            );
+        }
+    }
+
+    if let Some(ref path) = assertion_results_file {
+        let results = load_results(path)?;
+        fs::remove_file(path).ok();
+        if let Some(ref junit) = cmd.junit {
+            write_junit_report(junit, &results)?;
+        }
+        match message_format {
+            "github" => print_github_annotations(&results),
+            "json" => print_json_events(&results)?,
+            _ => {}
+        }
+    }
+
+    // accepted snapshots do not need a review
+    if !success || !cmd.accept {
+        notify_pending_snapshots(&cmd)?;
+    }
 
-    if !status.success() {
+    if !success {
         if cmd.review {
             eprintln!(
                 "{} non snapshot tests failed, skipping review",
@@ -574,55 +2109,65 @@ fn test_run(mut cmd: TestCommand, color: &str) -> Result<(), Box<dyn Error>> {
         return Err(QuietExit(1).into());
     }
 
-    // delete unreferenced snapshots if we were instructed to do so
     if let Some(ref path) = snapshot_ref_file {
-        let mut files = HashSet::new();
-        for line in fs::read_to_string(path).unwrap().lines() {
-            if let Ok(path) = fs::canonicalize(line) {
-                files.insert(path);
+        let loc = handle_target_args(&cmd.target_args)?;
+        if cmd.shard.is_some() || cmd.manifest.is_some() {
+            // other runs reference other snapshots, they are only deleted
+            // once the manifests of all runs are merged.
+            let manifest_path = match (cmd.manifest.as_ref(), cmd.shard) {
+                (Some(path), _) => path.clone(),
+                (None, Some(shard)) => shard.manifest_path(&loc.workspace_root),
+                (None, None) => unreachable!(),
+            };
+            let packages = match (cmd.package.as_ref(), loc.packages.as_ref()) {
+                (Some(package), _) => vec![package.clone()],
+                (None, Some(packages)) => packages.iter().map(|x| x.name().to_string()).collect(),
+                (None, None) => vec![],
+            };
+            let mut manifest =
+                SnapshotManifest::from_references(&loc.workspace_root, path, cmd.shard)?;
+            manifest.packages.extend(packages);
+            manifest.save(&manifest_path)?;
+            eprintln!(
+                "{}: wrote snapshot manifest to {}",
+                style("info").bold(),
+                manifest_path.display()
+            );
+        } else {
+            let referenced = read_references(path);
+            if cmd.migrate || unreferenced == "auto" {
+                migrate_snapshots(&loc, &referenced, cmd.stage, false)?;
+            } else if cmd.review {
+                // renames are only offered, the review decides on the rest
+                migrate_snapshots(&loc, &referenced, cmd.stage, true)?;
+            }
+            if unreferenced != "ignore" {
+                // moved snapshots only exist now
+                let referenced = read_references(path);
+                delete_unreferenced_snapshots(&loc, &referenced, cmd.stage)?;
             }
         }
-
-        if let Ok(loc) = handle_target_args(&cmd.target_args) {
-            let mut deleted_any = false;
-            for entry in make_deletion_walker(&loc) {
-                let rel_path = match entry {
-                    Ok(ref entry) => entry.path(),
-                    _ => continue,
-                };
-                if !rel_path.is_file()
-                    || !rel_path
-                        .file_name()
-                        .map_or(false, |x| x.to_str().unwrap_or("").ends_with(".snap"))
-                {
-                    continue;
-                }
-
-                if let Ok(path) = fs::canonicalize(rel_path) {
-                    if !files.contains(&path) {
-                        if !deleted_any {
-                            eprintln!("{}: deleted unreferenced snapshots:", style("info").bold());
-                            deleted_any = true;
-                        }
-                        eprintln!("  {}", rel_path.display());
-                        fs::remove_file(path).ok();
-                    }
-                }
-            }
-            if !deleted_any {
-                eprintln!("{}: no unreferenced snapshots found", style("info").bold());
-            }
-        }
-
-        fs::remove_file(&path).ok();
-    }
+        fs::remove_file(path).ok();
+    }
 
     if cmd.review || cmd.accept {
         process_snapshots(
             ProcessCommand {
                 target_args: cmd.target_args.clone(),
                 snapshot_filter: None,
-                quiet: false,
+                filter: None,
+                quiet: json_events,
+                web: false,
+                port: None,
+                confirm: false,
+                trash: false,
+                stage: cmd.stage,
+                older_than: None,
+                from_archive: None,
+                diff_context: cmd.diff_context,
+                diff_algorithm: cmd.diff_algorithm,
+                rustfmt: cmd.rustfmt,
+                expires: None,
             },
             if cmd.accept {
                 Some(Operation::Accept)
@@ -643,22 +2188,150 @@ fn test_run(mut cmd: TestCommand, color: &str) -> Result<(), Box<dyn Error>> {
             );
             eprintln!("use `cargo insta review` to review snapshots");
         } else {
-            println!("{}: no snapshots to review", style("info").bold());
+            eprintln!("{}: no snapshots to review", style("info").bold());
         }
     }
 
     Ok(())
 }
 
+/// Runs a command and passes what it writes to stdout on to stderr.
+fn status_with_stdout_on_stderr(
+    proc: &mut process::Command,
+) -> Result<process::ExitStatus, Box<dyn Error>> {
+    let mut child = proc.stdout(process::Stdio::piped()).spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    io::copy(&mut stdout, &mut io::stderr())?;
+    Ok(child.wait()?)
+}
+
+/// Lets the user know about pending snapshots if asked to.
+fn notify_pending_snapshots(cmd: &TestCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let mode = match cmd.notify {
+        Some(mode) => mode,
+        None => ToolConfig::from_workspace(&loc.workspace_root)?.test.notify,
+    };
+    if mode == NotifyMode::Off {
+        return Ok(());
+    }
+    let snapshot_containers = load_snapshot_containers(&loc)?;
+    let snapshot_count = snapshot_containers.iter().map(|x| x.0.len()).sum::<usize>();
+    if snapshot_count > 0 {
+        notify_pending(mode, snapshot_count);
+    }
+    Ok(())
+}
+
+/// Finds the single package all changed files belong to.
+fn find_affected_package<'a>(loc: &'a LocationInfo, changed: &[PathBuf]) -> Option<&'a Package> {
+    let packages = loc.packages.as_ref()?;
+    let mut rv: Option<&Package> = None;
+    for path in changed {
+        let package = packages
+            .iter()
+            .filter(|pkg| path.starts_with(pkg.manifest_path().parent().unwrap()))
+            .max_by_key(|pkg| pkg.manifest_path().components().count())?;
+        match rv {
+            Some(other) if other.name() != package.name() => return None,
+            _ => rv = Some(package),
+        }
+    }
+    rv
+}
+
+fn test_watch(mut cmd: TestCommand, color: &str) -> Result<(), Box<dyn Error>> {
+    if !cmd.accept {
+        cmd.review = true;
+    }
+    let loc = handle_target_args(&cmd.target_args)?;
+    let mut run_cmd = cmd.clone();
+
+    loop {
+        match test_run(run_cmd, color) {
+            Ok(()) => {}
+            // failing tests are reported by cargo, keep watching
+            Err(err) if err.downcast_ref::<QuietExit>().is_some() => {}
+            Err(err) => return Err(err),
+        }
+
+        // scan after the run so that snapshots accepted into sources during
+        // review do not trigger another run right away.
+        let tree = SourceTree::scan(&loc.workspace_root);
+        eprintln!(
+            "{}: watching for changes (press ctrl-c to stop)",
+            style("info").bold()
+        );
+        let changed = tree.wait_for_changes(&loc.workspace_root);
+
+        run_cmd = cmd.clone();
+        if run_cmd.package.is_none() {
+            run_cmd.package = find_affected_package(&loc, &changed).map(|x| x.name().to_string());
+        }
+        eprintln!(
+            "{}: {} file{} changed, re-running tests{}",
+            style("info").bold(),
+            changed.len(),
+            if changed.len() != 1 { "s" } else { "" },
+            match run_cmd.package {
+                Some(ref pkg) => format!(" for {}", style(pkg).cyan()),
+                None => "".into(),
+            }
+        );
+    }
+}
+
 fn pending_snapshots_cmd(cmd: PendingSnapshotsCommand) -> Result<(), Box<dyn Error>> {
     let loc = handle_target_args(&cmd.target_args)?;
     let mut snapshot_containers = load_snapshot_containers(&loc)?;
+    let format = match cmd.format.as_deref() {
+        Some(format) => format,
+        None if cmd.as_json => "json",
+        None => "human",
+    };
 
+    if format == "html" {
+        let mut items = vec![];
+        for (snapshot_container, package) in snapshot_containers.iter_mut() {
+            let snapshot_file = snapshot_container.snapshot_file().map(|x| x.to_path_buf());
+            for snapshot_ref in snapshot_container.iter_snapshots() {
+                items.push((
+                    snapshot_ref.summary(),
+                    snapshot_ref,
+                    snapshot_file.clone(),
+                    *package,
+                ));
+            }
+        }
+        let items: Vec<_> = items
+            .iter()
+            .map(|(summary, snapshot_ref, snapshot_file, package)| {
+                (
+                    summary.clone(),
+                    ReviewItem {
+                        new: &snapshot_ref.new,
+                        old: snapshot_ref.old.as_ref(),
+                        pkg: *package,
+                        line: snapshot_ref.line,
+                        snapshot_file: snapshot_file.as_deref(),
+                    },
+                )
+            })
+            .collect();
+        let report = render_report(&loc.workspace_root, &items);
+        match cmd.out {
+            Some(ref path) => fs::write(path, report)?,
+            None => print!("{}", report),
+        }
+        return Ok(());
+    }
+
+    let mut out = String::new();
     for (snapshot_container, _package) in snapshot_containers.iter_mut() {
         let target_file = snapshot_container.target_file().to_path_buf();
         let is_inline = snapshot_container.snapshot_file().is_none();
         for snapshot_ref in snapshot_container.iter_snapshots() {
-            if cmd.as_json {
+            if format == "json" {
                 let info = if is_inline {
                     SnapshotKey::InlineSnapshot {
                         path: &target_file,
@@ -671,20 +2344,751 @@ fn pending_snapshots_cmd(cmd: PendingSnapshotsCommand) -> Result<(), Box<dyn Err
                 } else {
                     SnapshotKey::NamedSnapshot { path: &target_file }
                 };
-                println!("{}", serde_json::to_string(&info).unwrap());
+                out.push_str(&serde_json::to_string(&info).unwrap());
+            } else if is_inline {
+                out.push_str(&format!(
+                    "{}:{}",
+                    target_file.display(),
+                    snapshot_ref.line.unwrap()
+                ));
             } else {
-                if is_inline {
-                    println!("{}:{}", target_file.display(), snapshot_ref.line.unwrap());
-                } else {
-                    println!("{}", target_file.display());
+                out.push_str(&target_file.display().to_string());
+            }
+            out.push('\n');
+        }
+    }
+
+    match cmd.out {
+        Some(ref path) => fs::write(path, out)?,
+        None => print!("{}", out),
+    }
+    Ok(())
+}
+
+fn stats_cmd(cmd: StatsCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let stats = match loc.packages {
+        Some(ref packages) => packages
+            .iter()
+            .map(|package| {
+                PackageStats::collect(
+                    package.name(),
+                    &package.snapshot_roots(loc.central_root.as_deref()),
+                    &loc.exts,
+                    loc.no_ignore,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![PackageStats::collect(
+            "<workspace>",
+            std::slice::from_ref(&loc.workspace_root),
+            &loc.exts,
+            loc.no_ignore,
+        )?],
+    };
+
+    let name_width = stats
+        .iter()
+        .map(|x| x.name.len())
+        .chain(Some("Package".len()))
+        .max()
+        .unwrap();
+    println!(
+        "{:<width$}  {:>6}  {:>6}  {:>7}  {:>10}",
+        style("Package").bold(),
+        style("File").bold(),
+        style("Inline").bold(),
+        style("Pending").bold(),
+        style("Size").bold(),
+        width = name_width
+    );
+    let mut total = PackageStats::default();
+    for package in &stats {
+        println!(
+            "{:<width$}  {:>6}  {:>6}  {:>7}  {:>10}",
+            package.name,
+            package.file_snapshots,
+            package.inline_snapshots,
+            package.pending_files,
+            format_size(package.size),
+            width = name_width
+        );
+        total.file_snapshots += package.file_snapshots;
+        total.inline_snapshots += package.inline_snapshots;
+        total.pending_files += package.pending_files;
+        total.size += package.size;
+    }
+    if stats.len() > 1 {
+        println!(
+            "{:<width$}  {:>6}  {:>6}  {:>7}  {:>10}",
+            style("Total").bold(),
+            total.file_snapshots,
+            total.inline_snapshots,
+            total.pending_files,
+            format_size(total.size),
+            width = name_width
+        );
+    }
+
+    let mut largest: Vec<_> = stats.iter().flat_map(|x| x.snapshot_sizes.iter()).collect();
+    largest.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    largest.truncate(cmd.largest);
+    if !largest.is_empty() {
+        println!();
+        println!("{}", style("Largest snapshots:").bold());
+        for (path, size) in largest {
+            println!(
+                "  {:>10}  {}",
+                format_size(*size),
+                path.strip_prefix(&loc.workspace_root)
+                    .unwrap_or(path)
+                    .display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn restore_cmd(cmd: RestoreCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let entries = list_trash(&loc.workspace_root)?;
+
+    if cmd.list {
+        if entries.is_empty() {
+            println!("{}: the trash is empty", style("info").bold());
+        }
+        for entry in &entries {
+            println!(
+                "{}  {} ago, {} file{}",
+                style(&entry.id).cyan(),
+                format_duration(entry.age),
+                entry.files.len(),
+                if entry.files.len() != 1 { "s" } else { "" }
+            );
+        }
+        return Ok(());
+    }
+
+    let entry = match cmd.id {
+        Some(ref id) => entries
+            .iter()
+            .find(|x| &x.id == id)
+            .ok_or_else(|| err_msg(format!("no rejection with id {} in the trash", id)))?,
+        None => entries
+            .last()
+            .ok_or_else(|| err_msg("no rejected snapshots in the trash"))?,
+    };
+    let (restored, skipped) = restore_trash(&loc.workspace_root, entry)?;
+    if !restored.is_empty() {
+        println!("{}:", style("restored").green());
+        for path in &restored {
+            println!("  {}", path.display());
+        }
+    }
+    if !skipped.is_empty() {
+        println!(
+            "{} (newer pending snapshots exist):",
+            style("not restored").yellow()
+        );
+        for path in &skipped {
+            println!("  {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn show_cmd(cmd: ShowCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let snapshot = Snapshot::from_file(&cmd.path)
+        .map_err(|err| err_msg(format!("could not load {}: {}", cmd.path.display(), err)))?;
+
+    if cmd.json {
+        let info = SnapshotInfo {
+            path: &cmd.path,
+            module_name: snapshot.module_name(),
+            snapshot_name: snapshot.snapshot_name(),
+            metadata: snapshot.metadata(),
+            contents: snapshot.contents().as_str(),
+            binary_file: snapshot.binary_file(&cmd.path),
+        };
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        print_snapshot(&loc.workspace_root, &snapshot, Some(&cmd.path));
+    }
+
+    Ok(())
+}
+
+fn diff_cmd(cmd: DiffCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    set_diff_options(&loc, cmd.diff_context, cmd.diff_algorithm)?;
+    let (old_path, new_path) = match cmd.new {
+        Some(new) => (cmd.old, new),
+        None if cmd.old.extension() == Some(OsStr::new("new")) => {
+            (cmd.old.with_extension(""), cmd.old)
+        }
+        None => {
+            let mut new = cmd.old.clone().into_os_string();
+            new.push(".new");
+            (cmd.old, PathBuf::from(new))
+        }
+    };
+    let load = |path: &Path| {
+        Snapshot::from_file(path)
+            .map_err(|err| err_msg(format!("could not load {}: {}", path.display(), err)))
+    };
+    let old = load(&old_path)?;
+    let new = load(&new_path)?;
+    print_snapshot_diff(&loc.workspace_root, &new, Some(&old), Some(&old_path), None);
+    Ok(())
+}
+
+fn grep_cmd(cmd: GrepCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let pattern = if cmd.fixed_strings {
+        regex::escape(&cmd.pattern)
+    } else {
+        cmd.pattern.clone()
+    };
+    let pattern = RegexBuilder::new(&pattern)
+        .case_insensitive(cmd.ignore_case)
+        .build()
+        .map_err(|err| err_msg(format!("invalid pattern: {}", err)))?;
+    let roots = match loc.packages {
+        Some(ref packages) => packages
+            .iter()
+            .flat_map(|x| x.snapshot_roots(loc.central_root.as_deref()))
+            .collect(),
+        None => vec![loc.workspace_root.clone()],
+    };
+
+    let matches = grep_snapshots(&roots, &loc.exts, loc.no_ignore, &pattern)?;
+    if matches.is_empty() {
+        return Err(QuietExit(1).into());
+    }
+    for (idx, found) in matches.iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+        let path = found
+            .path
+            .strip_prefix(&loc.workspace_root)
+            .unwrap_or(&found.path);
+        println!(
+            "{}{}{}",
+            style(path.display()).cyan().underlined(),
+            match found.snapshot.snapshot_name() {
+                Some(name) => format!(" {}", style(name.replace("__", "::")).yellow()),
+                None => "".into(),
+            },
+            match found.snapshot.metadata().source() {
+                Some(source) => format!(" ({})", source),
+                None => "".into(),
+            }
+        );
+        for (line_no, line) in &found.lines {
+            let mut last = 0;
+            let mut highlighted = String::new();
+            for m in pattern.find_iter(line) {
+                highlighted.push_str(&line[last..m.start()]);
+                highlighted.push_str(&style(m.as_str()).red().bold().to_string());
+                last = m.end();
+            }
+            highlighted.push_str(&line[last..]);
+            println!("{:>5}: {}", style(line_no).dim(), highlighted);
+        }
+    }
+
+    Ok(())
+}
+
+fn list_cmd(cmd: ListCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let roots = match loc.packages {
+        Some(ref packages) => packages
+            .iter()
+            .flat_map(|x| x.snapshot_roots(loc.central_root.as_deref()))
+            .collect(),
+        None => vec![loc.workspace_root.clone()],
+    };
+
+    let sources = list_assertions(&roots, loc.no_ignore)?;
+    let snapshot_info = load_snapshot_info(&roots, &loc.exts, loc.no_ignore, &loc.workspace_root)?;
+    let info_filter = cmd.info.as_ref().map(|x| {
+        let mut iter = x.splitn(2, '=');
+        (iter.next().unwrap_or(""), iter.next())
+    });
+    let mut rows = vec![];
+    for source in &sources {
+        let path = source
+            .path
+            .strip_prefix(&loc.workspace_root)
+            .unwrap_or(&source.path);
+        for assertion in &source.assertions {
+            let info = snapshot_info
+                .iter()
+                .find(|x| x.matches(&source.path, assertion))
+                .map(|x| &x.info);
+            if let Some((key, value)) = info_filter {
+                let matches = info
+                    .and_then(|x| x.get(key))
+                    .map_or(false, |x| value.map_or(true, |value| x == value));
+                if !matches {
+                    continue;
                 }
             }
+            if cmd.json {
+                let info = AssertionInfo {
+                    path,
+                    assertion,
+                    info,
+                };
+                println!("{}", serde_json::to_string(&info)?);
+            } else {
+                rows.push((
+                    format!("{}:{}", path.display(), assertion.line),
+                    assertion,
+                    info,
+                ));
+            }
+        }
+    }
+
+    let location_width = rows.iter().map(|x| x.0.len()).max().unwrap_or(0);
+    let macro_width = rows.iter().map(|x| x.1.macro_name.len()).max().unwrap_or(0);
+    for (location, assertion, info) in rows {
+        println!(
+            "{}  {:<6}  {:<macro_width$}  {}{}",
+            style(format!("{:<width$}", location, width = location_width)).cyan(),
+            match assertion.kind {
+                AssertionKind::Inline => "inline",
+                AssertionKind::File => "file",
+            },
+            assertion.macro_name,
+            match assertion.name {
+                Some(ref name) => style(name.as_str()).yellow(),
+                None => style("<unknown>").dim(),
+            },
+            info.map_or_else(String::new, |info| {
+                let info = info
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<_>>();
+                format!("  {}", style(info.join(" ")).dim())
+            }),
+            macro_width = macro_width
+        );
+    }
+
+    Ok(())
+}
+
+fn lint_cmd(cmd: LintCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let roots = match loc.packages {
+        Some(ref packages) => packages
+            .iter()
+            .flat_map(|x| x.snapshot_roots(loc.central_root.as_deref()))
+            .collect(),
+        None => vec![loc.workspace_root.clone()],
+    };
+
+    let sources = list_assertions(&roots, loc.no_ignore)?;
+    let mut issues = find_duplicate_names(&sources);
+    issues.extend(find_nondeterminism(&roots, &loc.exts, loc.no_ignore)?);
+    if issues.is_empty() {
+        println!("{}: no problems found", style("info").bold());
+        return Ok(());
+    }
+    for (idx, issue) in issues.iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+        let label = match issue.level {
+            LintLevel::Error => style("error:").red().bold(),
+            LintLevel::Warning => style("warning:").bold().yellow(),
+        };
+        println!("{} {}", label, issue.message);
+        for (path, line) in &issue.locations {
+            println!(
+                "  {} {}:{}",
+                style("-->").dim(),
+                path.strip_prefix(&loc.workspace_root)
+                    .unwrap_or(path)
+                    .display(),
+                line
+            );
+        }
+    }
+    Err(QuietExit(1).into())
+}
+
+fn normalize_cmd(cmd: NormalizeCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let config = ToolConfig::from_workspace(&loc.workspace_root)?;
+    let checksums = config.snapshots.checksums != ChecksumMode::Off;
+    let roots = match loc.packages {
+        Some(ref packages) => packages
+            .iter()
+            .flat_map(|x| x.snapshot_roots(loc.central_root.as_deref()))
+            .collect(),
+        None => vec![loc.workspace_root.clone()],
+    };
+
+    let mut changed_files = vec![];
+    for path in find_snapshot_files(&roots, &loc.exts, loc.no_ignore) {
+        if normalize_snapshot_file(&path, checksums, cmd.check)? {
+            changed_files.push(path);
+        }
+    }
+    for source in list_assertions(&roots, loc.no_ignore)? {
+        let mut lines: Vec<_> = source
+            .assertions
+            .iter()
+            .filter(|x| x.kind == AssertionKind::Inline)
+            .map(|x| x.line)
+            .collect();
+        lines.dedup();
+        if !lines.is_empty()
+            && normalize_source_file(&source.path, &lines, &config.inline, cmd.check)?
+        {
+            changed_files.push(source.path);
+        }
+    }
+
+    for path in &changed_files {
+        println!(
+            "{} {}",
+            if cmd.check {
+                style("not normalized:").yellow()
+            } else {
+                style("normalized:").green()
+            },
+            path.strip_prefix(&loc.workspace_root)
+                .unwrap_or(path)
+                .display()
+        );
+    }
+    if cmd.check && !changed_files.is_empty() {
+        return Err(QuietExit(1).into());
+    }
+    if changed_files.is_empty() {
+        println!("{}: all snapshots are normalized", style("info").bold());
+    }
+    Ok(())
+}
+
+fn export_cmd(cmd: ExportCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let mut snapshot_containers = load_snapshot_containers(&loc)?;
+    let snapshot_count = snapshot_containers.iter().map(|x| x.0.len()).sum::<usize>();
+
+    if let Some(ref archive) = cmd.pending {
+        let mut files: Vec<_> = snapshot_containers
+            .iter()
+            .flat_map(|(snapshot_container, _)| snapshot_container.pending_files())
+            .collect();
+        files.sort();
+        files.dedup();
+        write_archive(archive, &loc.workspace_root, &files)?;
+        println!(
+            "{}: exported {} pending snapshot{} to {}",
+            style("done").bold(),
+            snapshot_count,
+            if snapshot_count != 1 { "s" } else { "" },
+            archive.display()
+        );
+    }
+
+    if let Some(ref path) = cmd.decisions {
+        let mut decisions = DecisionsFile::default();
+        for (snapshot_container, _) in snapshot_containers.iter_mut() {
+            let target_file = snapshot_container.target_file().to_path_buf();
+            let snapshots: Vec<_> = snapshot_container.iter_snapshots().map(|x| &*x).collect();
+            let ids = snapshot_ids(&loc.workspace_root, &target_file, &snapshots);
+            for (id, snapshot) in ids.into_iter().zip(snapshots) {
+                decisions.add(id, snapshot);
+            }
+        }
+        decisions.save(path)?;
+        println!(
+            "{}: exported decisions for {} pending snapshot{} to {}",
+            style("done").bold(),
+            snapshot_count,
+            if snapshot_count != 1 { "s" } else { "" },
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn apply_decisions_cmd(cmd: ApplyDecisionsCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let mut config = ToolConfig::from_workspace(&loc.workspace_root)?;
+    if let Some(rustfmt) = cmd.rustfmt {
+        config.inline.rustfmt = rustfmt;
+    }
+    let decisions = DecisionsFile::load(&cmd.path)?;
+    let entries = decisions.by_id();
+    let mut snapshot_containers = load_snapshot_containers(&loc)?;
+
+    let mut found = HashSet::new();
+    let mut changed = vec![];
+    let mut accepted = vec![];
+    let mut rejected = vec![];
+    let mut skipped = vec![];
+    for (snapshot_container, _) in snapshot_containers.iter_mut() {
+        let target_file = snapshot_container.target_file().to_path_buf();
+        let ids = {
+            let snapshots: Vec<_> = snapshot_container.iter_snapshots().map(|x| &*x).collect();
+            snapshot_ids(&loc.workspace_root, &target_file, &snapshots)
+        };
+        for (id, snapshot_ref) in ids.iter().zip(snapshot_container.iter_snapshots()) {
+            let entry = match entries.get(id.as_str()) {
+                Some(entry) => entry,
+                None => {
+                    skipped.push(snapshot_ref.summary());
+                    continue;
+                }
+            };
+            found.insert(id.clone());
+            let op = match entry.decision {
+                Some(op) => op,
+                None => {
+                    skipped.push(snapshot_ref.summary());
+                    continue;
+                }
+            };
+            if !entry.matches(snapshot_ref) {
+                changed.push(id.clone());
+                continue;
+            }
+            snapshot_ref.op = op;
+            snapshot_ref.hunks = None;
+            match op {
+                Operation::Accept => accepted.push(snapshot_ref.summary()),
+                Operation::Reject => rejected.push(snapshot_ref.summary()),
+                Operation::Skip => skipped.push(snapshot_ref.summary()),
+            }
+        }
+    }
+    changed.extend(
+        decisions
+            .snapshots
+            .iter()
+            .filter(|x| x.decision.is_some() && !found.contains(&x.id))
+            .map(|x| format!("{} (no longer pending)", x.id)),
+    );
+    if !changed.is_empty() {
+        return Err(err_msg(format!(
+            "snapshots changed since the decisions were exported, no decisions \
+             were applied:\n  {}",
+            changed.join("\n  ")
+        )));
+    }
+
+    let committed = commit_decisions(
+        &loc.workspace_root,
+        &config,
+        &mut snapshot_containers,
+        cmd.trash,
+        cmd.stage || config.review.stage,
+        false,
+    )?;
+    print_warnings(&committed.warnings);
+    if !cmd.quiet {
+        println!("{}", style("decisions applied").bold());
+        print_decisions(&accepted, &rejected, &skipped);
+        if committed.trash.is_some() && !rejected.is_empty() {
+            println!(
+                "{}: rejected snapshots were moved to the trash, use `cargo insta restore` \
+                 to bring them back",
+                style("info").bold()
+            );
         }
     }
+    Ok(())
+}
 
+fn merge_manifests_cmd(cmd: MergeManifestsCommand) -> Result<(), Box<dyn Error>> {
+    let mut loc = handle_target_args(&cmd.target_args)?;
+    let manifests = cmd
+        .manifests
+        .iter()
+        .map(|path| SnapshotManifest::load(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // snapshots of packages that no run tested are left alone
+    if manifests.iter().all(|x| !x.packages.is_empty()) {
+        if let Some(ref mut packages) = loc.packages {
+            packages.retain(|package| {
+                manifests
+                    .iter()
+                    .any(|x| x.packages.contains(package.name()))
+            });
+        }
+    }
+    let referenced = merge_manifests(&loc.workspace_root, &manifests)?;
+    if cmd.delete {
+        return delete_unreferenced_snapshots(&loc, &referenced, cmd.stage);
+    }
+    let unreferenced = find_unreferenced_snapshots(&loc, &referenced);
+    if unreferenced.is_empty() {
+        eprintln!("{}: no unreferenced snapshots found", style("info").bold());
+    } else {
+        eprintln!("{}: unreferenced snapshots:", style("info").bold());
+        for path in &unreferenced {
+            eprintln!("  {}", path.display());
+        }
+    }
     Ok(())
 }
 
+/// Returns the pending snapshots with their ids.
+fn list_pending_snapshots(loc: &LocationInfo) -> Result<Vec<PendingSnapshotInfo>, Box<dyn Error>> {
+    let mut rv = vec![];
+    for (snapshot_container, _) in load_snapshot_containers(loc)?.iter_mut() {
+        let target_file = snapshot_container.target_file().to_path_buf();
+        let snapshots: Vec<_> = snapshot_container.iter_snapshots().map(|x| &*x).collect();
+        let ids = snapshot_ids(&loc.workspace_root, &target_file, &snapshots);
+        for (id, snapshot) in ids.into_iter().zip(snapshots) {
+            rv.push(PendingSnapshotInfo::new(id, snapshot));
+        }
+    }
+    Ok(rv)
+}
+
+fn daemon_diff(loc: &LocationInfo, params: Value) -> Result<Value, RpcError> {
+    let params = SnapshotIds::parse(params)?;
+    let mut diffs = vec![];
+    for (snapshot_container, _) in load_snapshot_containers(loc)?.iter_mut() {
+        let target_file = snapshot_container.target_file().to_path_buf();
+        let snapshots: Vec<_> = snapshot_container.iter_snapshots().map(|x| &*x).collect();
+        let ids = snapshot_ids(&loc.workspace_root, &target_file, &snapshots);
+        for (id, snapshot) in ids.into_iter().zip(snapshots) {
+            if params.ids.contains(&id) {
+                diffs.push(SnapshotDiff::new(id, snapshot));
+            }
+        }
+    }
+    if let Some(id) = params
+        .ids
+        .iter()
+        .find(|id| !diffs.iter().any(|x| &x.id == *id))
+    {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("no pending snapshot '{}'", id),
+        ));
+    }
+    Ok(json!({ "diffs": diffs }))
+}
+
+fn daemon_decide(loc: &LocationInfo, params: Value, op: Operation) -> Result<Value, RpcError> {
+    let params = SnapshotIds::parse(params)?;
+    let config = ToolConfig::from_workspace(&loc.workspace_root)?;
+    let mut snapshot_containers = load_snapshot_containers(loc)?;
+    let mut found = HashSet::new();
+    for (snapshot_container, _) in snapshot_containers.iter_mut() {
+        let target_file = snapshot_container.target_file().to_path_buf();
+        let ids = {
+            let snapshots: Vec<_> = snapshot_container.iter_snapshots().map(|x| &*x).collect();
+            snapshot_ids(&loc.workspace_root, &target_file, &snapshots)
+        };
+        for (id, snapshot_ref) in ids.into_iter().zip(snapshot_container.iter_snapshots()) {
+            if params.ids.contains(&id) {
+                snapshot_ref.op = op;
+                found.insert(id);
+            }
+        }
+    }
+    if let Some(id) = params.ids.iter().find(|id| !found.contains(*id)) {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("no pending snapshot '{}'", id),
+        ));
+    }
+    // the protocol is spoken over stdout, so the hooks must not write to it
+    let committed = commit_decisions(
+        &loc.workspace_root,
+        &config,
+        &mut snapshot_containers,
+        false,
+        config.review.stage,
+        true,
+    )?;
+    Ok(json!({ "ids": params.ids, "warnings": committed.warnings }))
+}
+
+fn daemon_cmd(cmd: DaemonCommand) -> Result<(), Box<dyn Error>> {
+    let loc = handle_target_args(&cmd.target_args)?;
+    let connection = Connection::stdio();
+    // the snapshots subscribers last heard about
+    let mut subscribed: Option<Vec<PendingSnapshotInfo>> = None;
+    // only poll while somebody is subscribed, every poll walks the workspace
+    loop {
+        let poll_interval = subscribed.as_ref().and(cmd.poll_interval);
+        let request = match connection.next(poll_interval) {
+            Incoming::Request(request) => request,
+            Incoming::Invalid(err) => {
+                connection.respond(&Value::Null, Err(RpcError::new(PARSE_ERROR, err)))?;
+                continue;
+            }
+            Incoming::Idle => {
+                if let Some(ref mut last) = subscribed {
+                    // snapshots can be halfway written while tests run
+                    if let Ok(snapshots) = list_pending_snapshots(&loc) {
+                        if snapshots != *last {
+                            connection.notify("changed", json!({ "snapshots": snapshots }))?;
+                            *last = snapshots;
+                        }
+                    }
+                }
+                continue;
+            }
+            Incoming::Closed => return Ok(()),
+        };
+
+        let result = match request.method.as_str() {
+            "subscribe" if cmd.poll_interval.is_none() => Err(RpcError::new(
+                REQUEST_FAILED,
+                "subscriptions need the daemon to be started with --poll-interval",
+            )),
+            "list" | "subscribe" => list_pending_snapshots(&loc)
+                .map(|snapshots| {
+                    let rv = json!({ "snapshots": snapshots });
+                    if request.method == "subscribe" {
+                        subscribed = Some(snapshots);
+                    }
+                    rv
+                })
+                .map_err(RpcError::from),
+            "unsubscribe" => {
+                subscribed = None;
+                Ok(Value::Null)
+            }
+            "diff" => daemon_diff(&loc, request.params),
+            "accept" => daemon_decide(&loc, request.params, Operation::Accept),
+            "reject" => daemon_decide(&loc, request.params, Operation::Reject),
+            "shutdown" => {
+                if let Some(ref id) = request.id {
+                    connection.respond(id, Ok(Value::Null))?;
+                }
+                return Ok(());
+            }
+            method => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method '{}'", method),
+            )),
+        };
+        if let Some(ref id) = request.id {
+            connection.respond(id, result)?;
+        }
+    }
+}
+
 pub fn run() -> Result<(), Box<dyn Error>> {
     // chop off cargo
     let mut args: Vec<_> = env::args_os().collect();
@@ -694,13 +3098,35 @@ pub fn run() -> Result<(), Box<dyn Error>> {
 
     let opts = Opts::from_iter(args);
 
-    let color = opts.color.as_ref().map(|x| x.as_str()).unwrap_or("auto");
+    let color = opts.color.as_deref().unwrap_or("auto");
     handle_color(color)?;
     match opts.command {
         Command::Review(cmd) => process_snapshots(cmd, None),
         Command::Accept(cmd) => process_snapshots(cmd, Some(Operation::Accept)),
         Command::Reject(cmd) => process_snapshots(cmd, Some(Operation::Reject)),
-        Command::Test(cmd) => test_run(cmd, color),
+        Command::Test(cmd) => {
+            if cmd.watch {
+                test_watch(cmd, color)
+            } else {
+                test_run(cmd, color)
+            }
+        }
+        Command::Migrate(mut cmd) => {
+            cmd.migrate = true;
+            test_run(cmd, color)
+        }
         Command::PendingSnapshots(cmd) => pending_snapshots_cmd(cmd),
+        Command::Stats(cmd) => stats_cmd(cmd),
+        Command::Restore(cmd) => restore_cmd(cmd),
+        Command::Show(cmd) => show_cmd(cmd),
+        Command::Diff(cmd) => diff_cmd(cmd),
+        Command::Grep(cmd) => grep_cmd(cmd),
+        Command::List(cmd) => list_cmd(cmd),
+        Command::Lint(cmd) => lint_cmd(cmd),
+        Command::Normalize(cmd) => normalize_cmd(cmd),
+        Command::Export(cmd) => export_cmd(cmd),
+        Command::ApplyDecisions(cmd) => apply_decisions_cmd(cmd),
+        Command::MergeManifests(cmd) => merge_manifests_cmd(cmd),
+        Command::Daemon(cmd) => daemon_cmd(cmd),
     }
 }
@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::path::Path;
+
+use console::style;
+use insta::{find_secrets, SecretsCheck, ToolConfig};
+
+use crate::cargo::{blob_backend, FileBackup, Operation, Package, SnapshotContainer};
+use crate::git::stage_files;
+use crate::hooks::run_hooks;
+use crate::trash::Trash;
+use crate::utils::err_msg;
+
+/// Checks accepted snapshots for credentials and returns the warnings.
+///
+/// With [`SecretsCheck::Deny`] nothing is accepted if a snapshot contains
+/// credentials.
+fn check_accepted_for_secrets(
+    check: SecretsCheck,
+    snapshot_containers: &mut [(SnapshotContainer, Option<&Package>)],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if check == SecretsCheck::Off {
+        return Ok(vec![]);
+    }
+    let mut found = vec![];
+    for (snapshot_container, _) in snapshot_containers.iter_mut() {
+        for snapshot_ref in snapshot_container.iter_snapshots() {
+            if snapshot_ref.op != Operation::Accept {
+                continue;
+            }
+            let secrets = find_secrets(snapshot_ref.new.contents().as_str().unwrap_or(""));
+            if !secrets.is_empty() {
+                found.push(format!(
+                    "{}: {}",
+                    snapshot_ref.summary(),
+                    secrets
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+    }
+    if check == SecretsCheck::Deny && !found.is_empty() {
+        return Err(err_msg(format!(
+            "snapshots likely contain credentials, no snapshots were accepted:\n  {}",
+            found.join("\n  ")
+        )));
+    }
+    Ok(found
+        .into_iter()
+        .map(|snapshot| format!("snapshot likely contains credentials: {}", snapshot))
+        .collect())
+}
+
+/// Prints the warnings of applied decisions.
+pub fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        println!("{} {}", style("warning:").bold().yellow(), warning);
+    }
+}
+
+/// The outcome of applied decisions.
+pub struct Committed {
+    /// The trash rejected snapshots were moved to.
+    pub trash: Option<Trash>,
+    /// Warnings about accepted snapshots for the user.
+    pub warnings: Vec<String>,
+}
+
+/// Applies the decisions made on the snapshots.
+///
+/// The accept hooks are run around the changes and either all decisions
+/// are applied or none of them.  Nothing is printed, the warnings are
+/// returned instead.  With `capture_output` the hooks do not write to the
+/// terminal either.
+pub fn commit_decisions(
+    workspace_root: &Path,
+    config: &ToolConfig,
+    snapshot_containers: &mut [(SnapshotContainer, Option<&Package>)],
+    use_trash: bool,
+    stage: bool,
+    capture_output: bool,
+) -> Result<Committed, Box<dyn Error>> {
+    let warnings = check_accepted_for_secrets(config.secrets.check, snapshot_containers)?;
+
+    let hooks = &config.review.hooks;
+    let accepted_containers: Vec<_> = snapshot_containers
+        .iter()
+        .map(|x| &x.0)
+        .filter(|x| x.has_accepted())
+        .collect();
+    let pending_files: Vec<_> = accepted_containers
+        .iter()
+        .map(|x| x.pending_file().to_path_buf())
+        .collect();
+    let written_files: Vec<_> = accepted_containers
+        .iter()
+        .map(|x| x.target_file().to_path_buf())
+        .collect();
+    if !accepted_containers.is_empty() {
+        run_hooks(
+            "pre-accept",
+            &hooks.pre_accept,
+            workspace_root,
+            &pending_files,
+            capture_output,
+        )
+        .map_err(|err| err_msg(format!("{}, no snapshots were accepted", err)))?;
+    }
+
+    // either all decisions are applied or none of them
+    let trash = if use_trash {
+        Some(Trash::new(workspace_root))
+    } else {
+        None
+    };
+    let blob_backend = blob_backend(workspace_root, &config.blobs);
+    let mut backup = FileBackup::default();
+    for (snapshot_container, _) in snapshot_containers.iter() {
+        snapshot_container.backup_files(&mut backup)?;
+    }
+    let result = snapshot_containers
+        .iter_mut()
+        .try_for_each(|(snapshot_container, package)| {
+            snapshot_container.commit(
+                trash.as_ref(),
+                &config.inline,
+                package.and_then(|x| x.edition()),
+                blob_backend.as_ref(),
+            )
+        })
+        .map_err(|err| format!("could not apply decisions: {}", err))
+        .and_then(|()| {
+            if written_files.is_empty() {
+                return Ok(());
+            }
+            run_hooks(
+                "post-accept",
+                &hooks.post_accept,
+                workspace_root,
+                &written_files,
+                capture_output,
+            )
+            .map_err(|err| err.to_string())
+        });
+    if let Err(err) = result {
+        backup.restore()?;
+        if let Some(ref trash) = trash {
+            trash.discard();
+        }
+        return Err(err_msg(format!("{}, all changes were rolled back", err)));
+    }
+    if stage {
+        let files: Vec<_> = snapshot_containers
+            .iter()
+            .flat_map(|(snapshot_container, _)| snapshot_container.accepted_files())
+            .collect();
+        stage_files(workspace_root, &files)?;
+    }
+
+    Ok(Committed { trash, warnings })
+}
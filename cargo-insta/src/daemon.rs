@@ -0,0 +1,222 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cargo::PendingSnapshot;
+use crate::decisions::{contents_hash, unified_diff};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The request was valid but could not be carried out.
+pub const REQUEST_FAILED: i64 = -32000;
+
+/// A JSON-RPC request.  Requests without an id are notifications and get
+/// no response.
+#[derive(Deserialize, Debug)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new<S: Into<String>>(code: i64, message: S) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Box<dyn Error>> for RpcError {
+    fn from(err: Box<dyn Error>) -> RpcError {
+        RpcError::new(REQUEST_FAILED, err.to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct Response<'a> {
+    jsonrpc: &'static str,
+    id: &'a Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize)]
+struct Notification<'a> {
+    jsonrpc: &'static str,
+    method: &'a str,
+    params: Value,
+}
+
+/// The parameters of requests that refer to snapshots.
+#[derive(Deserialize, Debug)]
+pub struct SnapshotIds {
+    pub ids: Vec<String>,
+}
+
+impl SnapshotIds {
+    pub fn parse(params: Value) -> Result<SnapshotIds, RpcError> {
+        serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+    }
+}
+
+/// A pending snapshot as reported to clients.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PendingSnapshotInfo {
+    /// Identifies the snapshot like in decisions files.
+    pub id: String,
+    /// Changes whenever the old or new contents change.
+    pub hash: String,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Set if there is no snapshot yet.
+    pub is_new: bool,
+}
+
+impl PendingSnapshotInfo {
+    pub fn new(id: String, snapshot: &PendingSnapshot) -> PendingSnapshotInfo {
+        PendingSnapshotInfo {
+            id,
+            hash: contents_hash(snapshot),
+            summary: snapshot.summary(),
+            line: snapshot.line,
+            name: snapshot.new.snapshot_name().map(|x| x.to_string()),
+            is_new: snapshot.old.is_none(),
+        }
+    }
+}
+
+/// The old and new contents of a pending snapshot with their diff.
+#[derive(Serialize, Debug)]
+pub struct SnapshotDiff {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    pub new: String,
+    pub diff: Vec<String>,
+}
+
+impl SnapshotDiff {
+    pub fn new(id: String, snapshot: &PendingSnapshot) -> SnapshotDiff {
+        SnapshotDiff {
+            id,
+            old: snapshot
+                .old
+                .as_ref()
+                .map(|x| x.contents().to_diffable_string().into_owned()),
+            new: snapshot.new.contents().to_diffable_string().into_owned(),
+            diff: unified_diff(snapshot)
+                .lines()
+                .map(|x| x.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// What the daemon got from its client.
+pub enum Incoming {
+    Request(Request),
+    /// A line that is not a valid request.
+    Invalid(String),
+    /// Nothing arrived within the poll interval.
+    Idle,
+    /// The client closed stdin.
+    Closed,
+}
+
+/// A client connected over stdin and stdout.
+///
+/// Messages are JSON objects, one per line.
+pub struct Connection {
+    requests: Receiver<String>,
+}
+
+impl Connection {
+    pub fn stdio() -> Connection {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Connection { requests: rx }
+    }
+
+    /// Waits for the next request, at most for the poll interval if any.
+    pub fn next(&self, poll_interval: Option<Duration>) -> Incoming {
+        let line = match poll_interval {
+            Some(poll_interval) => match self.requests.recv_timeout(poll_interval) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Incoming::Idle,
+                Err(RecvTimeoutError::Disconnected) => return Incoming::Closed,
+            },
+            None => match self.requests.recv() {
+                Ok(line) => line,
+                Err(_) => return Incoming::Closed,
+            },
+        };
+        match serde_json::from_str(&line) {
+            Ok(request) => Incoming::Request(request),
+            Err(err) => Incoming::Invalid(err.to_string()),
+        }
+    }
+
+    pub fn respond(&self, id: &Value, result: Result<Value, RpcError>) -> io::Result<()> {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        send(&Response {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        })
+    }
+
+    pub fn notify(&self, method: &str, params: Value) -> io::Result<()> {
+        send(&Notification {
+            jsonrpc: "2.0",
+            method,
+            params,
+        })
+    }
+}
+
+fn send<T: Serialize>(message: &T) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer(&mut stdout, message)?;
+    stdout.write_all(b"\n")?;
+    stdout.flush()
+}
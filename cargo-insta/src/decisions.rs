@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use similar::TextDiff;
+
+use crate::cargo::{Operation, PendingSnapshot};
+use crate::utils::err_msg;
+
+/// A pending snapshot in a decisions file.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DecisionEntry {
+    /// Identifies the snapshot by its file relative to the workspace root
+    /// and for inline snapshots the line.
+    pub id: String,
+    /// A hash of the old and new contents at the time of the export.
+    pub hash: String,
+    pub snapshot: String,
+    /// The decision to apply: accept, reject or skip.
+    #[serde(default)]
+    pub decision: Option<Operation>,
+    /// The diff for reviewers as list of lines.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diff: Vec<String>,
+}
+
+impl DecisionEntry {
+    /// Checks if the decision was made for the current contents of the
+    /// snapshot.
+    pub fn matches(&self, snapshot: &PendingSnapshot) -> bool {
+        self.hash == contents_hash(snapshot)
+    }
+}
+
+/// Decisions on pending snapshots that are made outside of a review.
+///
+/// The file is exported with all decisions unset.  Once they are filled in
+/// the decisions are applied to the snapshots, but only if they did not
+/// change since the export.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DecisionsFile {
+    pub snapshots: Vec<DecisionEntry>,
+}
+
+fn is_json(path: &Path) -> Result<bool, Box<dyn Error>> {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("json") => Ok(true),
+        Some("yaml") | Some("yml") => Ok(false),
+        _ => Err(err_msg(format!(
+            "unsupported decisions file {}: use a .yaml or .json file",
+            path.display()
+        ))),
+    }
+}
+
+impl DecisionsFile {
+    pub fn load(path: &Path) -> Result<DecisionsFile, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(if is_json(path)? {
+            serde_json::from_str(&contents)?
+        } else {
+            serde_yaml::from_str(&contents)?
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let contents = if is_json(path)? {
+            serde_json::to_string_pretty(self)?
+        } else {
+            serde_yaml::to_string(self)?
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Adds an undecided entry for a pending snapshot.
+    pub fn add(&mut self, id: String, snapshot: &PendingSnapshot) {
+        self.snapshots.push(DecisionEntry {
+            id,
+            hash: contents_hash(snapshot),
+            snapshot: snapshot.summary(),
+            decision: None,
+            diff: unified_diff(snapshot)
+                .lines()
+                .map(|x| x.to_string())
+                .collect(),
+        });
+    }
+
+    /// Returns the entries by their id.
+    pub fn by_id(&self) -> HashMap<&str, &DecisionEntry> {
+        self.snapshots.iter().map(|x| (x.id.as_str(), x)).collect()
+    }
+}
+
+/// Returns the ids of the snapshots of a file.
+///
+/// Inline snapshots in the same line are told apart by a counter.
+pub fn snapshot_ids(
+    workspace_root: &Path,
+    target_file: &Path,
+    snapshots: &[&PendingSnapshot],
+) -> Vec<String> {
+    let path = target_file
+        .strip_prefix(workspace_root)
+        .unwrap_or(target_file)
+        .to_string_lossy()
+        .replace('\\', "/");
+    let mut seen = HashMap::new();
+    snapshots
+        .iter()
+        .map(|snapshot| match snapshot.line {
+            Some(line) => {
+                let count = seen.entry(line).or_insert(0);
+                *count += 1;
+                if *count > 1 {
+                    format!("{}:{}#{}", path, line, count)
+                } else {
+                    format!("{}:{}", path, line)
+                }
+            }
+            None => path.clone(),
+        })
+        .collect()
+}
+
+/// Hashes the old and new contents of a pending snapshot.
+///
+/// Unlike the keys of review sessions this hash is stable across machines
+/// as decisions files are passed around.
+pub fn contents_hash(snapshot: &PendingSnapshot) -> String {
+    let mut hasher = Sha1::new();
+    match snapshot.old {
+        Some(ref old) => {
+            hasher.input(b"old:");
+            hasher.input(old.contents().as_bytes());
+        }
+        None => hasher.input(b"none:"),
+    }
+    hasher.input(b"\0new:");
+    hasher.input(snapshot.new.contents().as_bytes());
+    format!("{:x}", hasher.result())
+}
+
+pub fn unified_diff(snapshot: &PendingSnapshot) -> String {
+    let old = snapshot
+        .old
+        .as_ref()
+        .map(|x| x.contents().to_diffable_string())
+        .unwrap_or_default();
+    let new = snapshot.new.contents().to_diffable_string();
+    let old = if old.is_empty() {
+        old.into_owned()
+    } else {
+        format!("{}\n", old)
+    };
+    let new = format!("{}\n", new);
+    let diff = TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .context_radius(3)
+        .header("old snapshot", "new results")
+        .to_string();
+    diff.trim_end().to_string()
+}
+
+#[cfg(test)]
+fn pending_snapshot(old: Option<&str>, new: &str) -> PendingSnapshot {
+    use insta::{MetaData, Snapshot, SnapshotContents};
+    let snapshot = |contents: &str| {
+        Snapshot::from_components(
+            "module".into(),
+            Some("name".into()),
+            MetaData::default(),
+            SnapshotContents::from(contents),
+        )
+    };
+    PendingSnapshot {
+        id: 0,
+        old: old.map(snapshot),
+        new: snapshot(new),
+        op: Operation::Skip,
+        hunks: None,
+        line: None,
+        as_variant: false,
+    }
+}
+
+#[test]
+fn test_decisions_hash() {
+    let snapshot = pending_snapshot(Some("old"), "new");
+    let mut decisions = DecisionsFile::default();
+    decisions.add("tests/snapshots/module__name.snap".into(), &snapshot);
+
+    for name in &["decisions.yaml", "decisions.json"] {
+        let path = std::env::temp_dir().join(format!("insta-{}-{}", std::process::id(), name));
+        decisions.save(&path).unwrap();
+        let loaded = DecisionsFile::load(&path);
+        fs::remove_file(&path).ok();
+        let entry = &loaded.unwrap().snapshots[0];
+        assert_eq!(entry.decision, None);
+        assert!(entry.matches(&snapshot));
+        assert!(!entry.matches(&pending_snapshot(Some("old"), "newer")));
+        assert!(!entry.matches(&pending_snapshot(Some("older"), "new")));
+    }
+
+    // a new snapshot is not the same as one replacing an empty snapshot
+    assert_ne!(
+        contents_hash(&pending_snapshot(None, "new")),
+        contents_hash(&pending_snapshot(Some(""), "new"))
+    );
+    assert!(DecisionsFile::load(Path::new("decisions.txt")).is_err());
+}
@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::utils::err_msg;
+
+fn run_git(workspace_root: &Path, args: &[&str], paths: &[&PathBuf]) -> Result<(), Box<dyn Error>> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let output = process::Command::new("git")
+        .args(args)
+        .arg("--")
+        .args(paths)
+        .current_dir(workspace_root)
+        .output()
+        .map_err(|err| err_msg(format!("could not run git: {}", err)))?;
+    if !output.status.success() {
+        return Err(err_msg(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Stages files in git.
+///
+/// Files that do not exist anymore are staged as removed.
+pub fn stage_files(workspace_root: &Path, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    let (existing, removed): (Vec<_>, Vec<_>) = files.iter().partition(|x| x.exists());
+    run_git(workspace_root, &["add"], &existing)?;
+    run_git(
+        workspace_root,
+        &["rm", "--cached", "--quiet", "--ignore-unmatch"],
+        &removed,
+    )
+}
@@ -0,0 +1,72 @@
+use std::error::Error;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+use insta::Snapshot;
+use regex::Regex;
+
+/// A snapshot file with contents matching a search.
+#[derive(Debug)]
+pub struct SnapshotMatch {
+    pub path: PathBuf,
+    pub snapshot: Snapshot,
+    /// The matching lines with their line number.
+    pub lines: Vec<(usize, String)>,
+}
+
+/// Searches the contents of all snapshot files below the given roots.
+///
+/// Only the contents are searched, the metadata in the header of the
+/// snapshot files is ignored.  Binary snapshots are skipped.
+pub fn grep_snapshots(
+    roots: &[PathBuf],
+    extensions: &[&str],
+    no_ignore: bool,
+    pattern: &Regex,
+) -> Result<Vec<SnapshotMatch>, Box<dyn Error>> {
+    let mut rv = vec![];
+    for root in roots {
+        let walker = WalkBuilder::new(root)
+            .hidden(false)
+            .standard_filters(!no_ignore)
+            .filter_entry(|entry| entry.file_name() != OsStr::new("target"))
+            .build();
+        for entry in walker.filter_map(|x| x.ok()) {
+            let fname = entry.file_name().to_string_lossy();
+            let is_snapshot = entry.file_type().map_or(false, |x| x.is_file())
+                && extensions
+                    .iter()
+                    .any(|ext| fname.ends_with(&format!(".{}", ext)));
+            if is_snapshot {
+                if let Some(found) = grep_snapshot(entry.path(), pattern)? {
+                    rv.push(found);
+                }
+            }
+        }
+    }
+    rv.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(rv)
+}
+
+fn grep_snapshot(path: &Path, pattern: &Regex) -> Result<Option<SnapshotMatch>, Box<dyn Error>> {
+    let snapshot = Snapshot::from_file(path)?;
+    let lines: Vec<_> = match snapshot.contents().as_str() {
+        Some(contents) => contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| pattern.is_match(line))
+            .map(|(idx, line)| (idx + 1, line.to_string()))
+            .collect(),
+        None => return Ok(None),
+    };
+    Ok(if lines.is_empty() {
+        None
+    } else {
+        Some(SnapshotMatch {
+            path: path.to_path_buf(),
+            snapshot,
+            lines,
+        })
+    })
+}
@@ -0,0 +1,64 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::utils::err_msg;
+
+/// Runs hook commands from the config one after another.
+///
+/// Fails with the first command that does not succeed.  With
+/// `capture_output` the commands get no stdin and their output is only
+/// reported if they fail, so that they cannot interfere with a protocol
+/// spoken over stdin and stdout.
+pub fn run_hooks(
+    kind: &str,
+    commands: &[String],
+    workspace_root: &Path,
+    files: &[PathBuf],
+    capture_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let files = files
+        .iter()
+        .map(|x| x.display().to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    for command in commands {
+        let mut proc = if cfg!(windows) {
+            let mut proc = process::Command::new("cmd");
+            proc.arg("/C");
+            proc
+        } else {
+            let mut proc = process::Command::new("sh");
+            proc.arg("-c");
+            proc
+        };
+        proc.arg(command)
+            .current_dir(workspace_root)
+            .env("INSTA_SNAPSHOT_FILES", &files);
+        let (status, stderr) = if capture_output {
+            proc.stdin(process::Stdio::null())
+                .output()
+                .map(|output| (output.status, output.stderr))
+        } else {
+            proc.status().map(|status| (status, vec![]))
+        }
+        .map_err(|err| {
+            err_msg(format!(
+                "could not run {} hook `{}`: {}",
+                kind, command, err
+            ))
+        })?;
+        if !status.success() {
+            let stderr = String::from_utf8_lossy(&stderr);
+            return Err(err_msg(format!(
+                "{} hook `{}` failed ({}){}{}",
+                kind,
+                command,
+                status,
+                if stderr.trim().is_empty() { "" } else { ": " },
+                stderr.trim()
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use similar::{capture_diff_slices, ChangeTag, DiffOp, DiffTag};
+
+use crate::utils::diff_algorithm;
+
+/// The number of unchanged lines shown around a hunk.
+const CONTEXT_LINES: usize = 3;
+
+/// A line based diff between two snapshots that can be applied partially.
+///
+/// Every change (a run of removed and/or inserted lines) forms a hunk that
+/// can be taken from the new snapshot or left at the old version.
+pub struct HunkDiff {
+    old: Vec<String>,
+    new: Vec<String>,
+    ops: Vec<DiffOp>,
+}
+
+impl HunkDiff {
+    pub fn new(old: &str, new: &str) -> HunkDiff {
+        let old: Vec<String> = old.lines().map(|x| x.to_string()).collect();
+        let new: Vec<String> = new.lines().map(|x| x.to_string()).collect();
+        let ops = capture_diff_slices(diff_algorithm(), &old, &new);
+        HunkDiff { old, new, ops }
+    }
+
+    fn changes(&self) -> impl Iterator<Item = &DiffOp> {
+        self.ops.iter().filter(|op| op.tag() != DiffTag::Equal)
+    }
+
+    /// Returns the number of hunks.
+    pub fn len(&self) -> usize {
+        self.changes().count()
+    }
+
+    /// Returns the lines of a hunk including some surrounding context.
+    pub fn hunk_lines(&self, idx: usize) -> Vec<(ChangeTag, &str)> {
+        let op = self.changes().nth(idx).expect("hunk out of range");
+        let old_range = op.old_range();
+        let new_range = op.new_range();
+        let context_start = old_range.start.saturating_sub(CONTEXT_LINES);
+        let context_end = (old_range.end + CONTEXT_LINES).min(self.old.len());
+
+        let mut rv = vec![];
+        for line in &self.old[context_start..old_range.start] {
+            rv.push((ChangeTag::Equal, line.as_str()));
+        }
+        for line in &self.old[old_range.clone()] {
+            rv.push((ChangeTag::Delete, line.as_str()));
+        }
+        for line in &self.new[new_range] {
+            rv.push((ChangeTag::Insert, line.as_str()));
+        }
+        for line in &self.old[old_range.end..context_end] {
+            rv.push((ChangeTag::Equal, line.as_str()));
+        }
+        rv
+    }
+
+    /// Builds the contents with only the selected hunks applied.
+    pub fn apply(&self, selected: &[bool]) -> String {
+        let mut lines = vec![];
+        let mut hunk = 0;
+        for op in &self.ops {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            if tag == DiffTag::Equal {
+                lines.extend(&self.old[old_range]);
+                continue;
+            }
+            if selected[hunk] {
+                lines.extend(&self.new[new_range]);
+            } else {
+                lines.extend(&self.old[old_range]);
+            }
+            hunk += 1;
+        }
+        lines
+            .into_iter()
+            .map(|x| x.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[test]
+fn test_apply_hunks() {
+    let diff = HunkDiff::new("a\nb\nc\nd\ne\nf\ng\nh\n", "a\nB\nc\nd\ne\nf\ng\nh\ni\n");
+    assert_eq!(diff.len(), 2);
+    assert_eq!(diff.apply(&[false, false]), "a\nb\nc\nd\ne\nf\ng\nh");
+    assert_eq!(diff.apply(&[true, false]), "a\nB\nc\nd\ne\nf\ng\nh");
+    assert_eq!(diff.apply(&[false, true]), "a\nb\nc\nd\ne\nf\ng\nh\ni");
+    assert_eq!(diff.apply(&[true, true]), "a\nB\nc\nd\ne\nf\ng\nh\ni");
+    assert_eq!(
+        diff.hunk_lines(0),
+        vec![
+            (ChangeTag::Equal, "a"),
+            (ChangeTag::Delete, "b"),
+            (ChangeTag::Insert, "B"),
+            (ChangeTag::Equal, "c"),
+            (ChangeTag::Equal, "d"),
+            (ChangeTag::Equal, "e"),
+        ]
+    );
+    assert_eq!(
+        diff.hunk_lines(1),
+        vec![
+            (ChangeTag::Equal, "f"),
+            (ChangeTag::Equal, "g"),
+            (ChangeTag::Equal, "h"),
+            (ChangeTag::Insert, "i"),
+        ]
+    );
+}
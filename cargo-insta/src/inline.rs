@@ -1,19 +1,27 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use insta::SnapshotContents;
-use proc_macro2::TokenTree;
-use syn;
+use insta::{InlineConfig, SnapshotContents};
+use proc_macro2::{TokenStream, TokenTree};
+use serde::Serialize;
 use syn::spanned::Spanned;
 
+use crate::utils::err_msg;
+
 #[derive(Debug)]
 pub struct InlineSnapshot {
     start: (usize, usize),
     end: (usize, usize),
     indentation: usize,
+    /// Where the arguments before the snapshot end if the assertion still
+    /// works once the snapshot is removed.
+    value_end: Option<(usize, usize)>,
+    /// The doc comment prefix of lines if the snapshot is in a doctest.
+    doc_prefix: Option<String>,
 }
 
 pub struct FilePatcher {
@@ -21,6 +29,8 @@ pub struct FilePatcher {
     lines: Vec<String>,
     source: syn::File,
     inline_snapshots: Vec<InlineSnapshot>,
+    /// The line ranges that were changed (1-based and inclusive).
+    changed_lines: Vec<(usize, usize)>,
 }
 
 impl fmt::Debug for FilePatcher {
@@ -43,6 +53,7 @@ impl FilePatcher {
             source,
             lines,
             inline_snapshots: vec![],
+            changed_lines: vec![],
         })
     }
 
@@ -54,57 +65,162 @@ impl FilePatcher {
         Ok(())
     }
 
-    pub fn add_snapshot_macro(&mut self, line: usize) {
-        match self.find_snapshot_macro(line) {
+    /// Adds the inline snapshot of the assertion in a line.
+    ///
+    /// The old contents are used to find the snapshot in macros that wrap
+    /// the assertion.
+    pub fn add_snapshot_macro(
+        &mut self,
+        line: usize,
+        old: Option<&SnapshotContents>,
+        doctest: bool,
+    ) {
+        if !self.try_add_snapshot_macro(line, old, doctest) {
+            panic!("Could not find snapshot in line {}", line);
+        }
+    }
+
+    /// Like [`add_snapshot_macro`](Self::add_snapshot_macro) but returns
+    /// `false` if there is no snapshot in the line.
+    pub fn try_add_snapshot_macro(
+        &mut self,
+        line: usize,
+        old: Option<&SnapshotContents>,
+        doctest: bool,
+    ) -> bool {
+        let snapshot = if doctest {
+            self.find_doctest_snapshot(line, old)
+        } else {
+            Self::find_snapshot_macro(&self.source, line, old)
+        };
+        match snapshot {
             Some(snapshot) => {
                 assert!(self
                     .inline_snapshots
                     .last()
                     .map_or(true, |x| x.end.0 <= line));
-                self.inline_snapshots.push(snapshot)
+                self.inline_snapshots.push(snapshot);
+                true
             }
-            None => panic!("Could not find snapshot in line {}", line),
+            None => false,
         }
     }
 
+    /// Returns the line ranges that were changed (1-based and inclusive).
+    pub fn changed_lines(&self) -> &[(usize, usize)] {
+        &self.changed_lines
+    }
+
     pub fn get_new_line(&self, id: usize) -> usize {
         self.inline_snapshots[id].start.0 + 1
     }
 
-    pub fn set_new_content(&mut self, id: usize, snapshot: &SnapshotContents) {
-        let inline = &mut self.inline_snapshots[id];
+    /// Checks if a snapshot is in a doctest.
+    pub fn is_doctest(&self, id: usize) -> bool {
+        self.inline_snapshots[id].doc_prefix.is_some()
+    }
+
+    /// Writes the new contents of a snapshot formatted as configured.
+    pub fn set_new_content(
+        &mut self,
+        id: usize,
+        snapshot: &SnapshotContents,
+        config: &InlineConfig,
+    ) {
+        let inline = &self.inline_snapshots[id];
+        let mut contents = snapshot.to_inline_with_config(inline.indentation, config);
+        if let Some(ref prefix) = inline.doc_prefix {
+            contents = contents
+                .lines()
+                .enumerate()
+                .map(|(idx, line)| match (idx, line.is_empty()) {
+                    (0, _) => line.to_string(),
+                    (_, true) => prefix.trim_end().to_string(),
+                    (_, false) => format!("{}{}", prefix, line),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        let (start, end) = (inline.start, inline.end);
+        self.replace(id, start, end, &contents);
+    }
+
+    /// Rewrites a snapshot the way insta writes new snapshots.
+    ///
+    /// The contents stay the same, only the string literal changes, for
+    /// instance to use as few hashes as possible for raw strings.  Returns
+    /// if the source changed.
+    pub fn normalize_snapshot(&mut self, id: usize, config: &InlineConfig) -> bool {
+        let inline = &self.inline_snapshots[id];
+        let literal = match self.snapshot_literal(inline) {
+            Some(literal) => literal,
+            None => return false,
+        };
+        let contents = match syn::parse_str::<syn::LitStr>(&literal) {
+            Ok(lit) => SnapshotContents::from_inline(&lit.value()),
+            Err(_) => return false,
+        };
+        if contents.to_inline_with_config(inline.indentation, config) == literal {
+            return false;
+        }
+        self.set_new_content(id, &contents, config);
+        true
+    }
+
+    /// Checks if the snapshot can be removed to turn the assertion into one
+    /// that writes a snapshot file.
+    pub fn can_remove_snapshot(&self, id: usize) -> bool {
+        self.inline_snapshots[id].value_end.is_some()
+    }
+
+    /// Removes the snapshot including the `@` from the assertion.
+    pub fn remove_snapshot(&mut self, id: usize) {
+        let inline = &self.inline_snapshots[id];
+        let start = inline.value_end.expect("snapshot cannot be removed");
+        let end = inline.end;
+        self.replace(id, start, end, "");
+    }
 
+    /// Replaces the source between two positions.
+    ///
+    /// The locations of the snapshot and the ones after it are updated.
+    fn replace(&mut self, id: usize, start: (usize, usize), end: (usize, usize), contents: &str) {
         // find prefix and suffix on the first and last lines
-        let prefix: String = self.lines[inline.start.0]
-            .chars()
-            .take(inline.start.1)
-            .collect();
-        let suffix: String = self.lines[inline.end.0]
-            .chars()
-            .skip(inline.end.1)
-            .collect();
+        let prefix: String = self.lines[start.0].chars().take(start.1).collect();
+        let suffix: String = self.lines[end.0].chars().skip(end.1).collect();
 
         // replace lines
-        let snapshot_line_contents =
-            vec![prefix, snapshot.to_inline(inline.indentation), suffix].join("");
+        let line_contents = [prefix.as_str(), contents, suffix.as_str()].join("");
 
         self.lines.splice(
-            inline.start.0..=inline.end.0,
-            snapshot_line_contents.lines().map(|l| l.to_string()),
+            start.0..=end.0,
+            line_contents.lines().map(|l| l.to_string()),
         );
 
+        let new_lines_count = line_contents.lines().count();
+        self.changed_lines
+            .push((start.0 + 1, start.0 + new_lines_count.max(1)));
+
         // update other snapshot locations
-        let old_lines_count = inline.end.0 - inline.start.0 + 1;
-        let line_count_diff =
-            (snapshot_line_contents.lines().count() as isize) - (old_lines_count as isize);
+        let old_lines_count = end.0 - start.0 + 1;
+        let line_count_diff = (new_lines_count as isize) - (old_lines_count as isize);
+        let shift =
+            |pos: &mut (usize, usize)| pos.0 = ((pos.0 as isize) + line_count_diff) as usize;
         for inl in &mut self.inline_snapshots[id..] {
-            inl.start.0 = ((inl.start.0 as isize) + line_count_diff) as usize;
-            inl.end.0 = ((inl.end.0 as isize) + line_count_diff) as usize;
+            shift(&mut inl.start);
+            shift(&mut inl.end);
+            if let Some(ref mut value_end) = inl.value_end {
+                shift(value_end);
+            }
         }
     }
 
-    fn find_snapshot_macro(&self, line: usize) -> Option<InlineSnapshot> {
-        struct Visitor(usize, Option<InlineSnapshot>);
+    fn find_snapshot_macro(
+        source: &syn::File,
+        line: usize,
+        old: Option<&SnapshotContents>,
+    ) -> Option<InlineSnapshot> {
+        struct Visitor<'a>(usize, Option<InlineSnapshot>, Option<&'a SnapshotContents>);
 
         fn scan_for_path_start(tokens: &[TokenTree], pos: usize) -> usize {
             let mut rev_tokens = tokens[..=pos].iter().rev();
@@ -127,7 +243,35 @@ impl FilePatcher {
             start.span().start().column
         }
 
-        impl Visitor {
+        fn literal_span(lit: &proc_macro2::Literal) -> ((usize, usize), (usize, usize)) {
+            let span = lit.span();
+            (
+                (span.start().line - 1, span.start().column),
+                (span.end().line - 1, span.end().column),
+            )
+        }
+
+        /// Collects the literals of a token stream and if they follow an `@`.
+        fn collect_literals(tokens: &[TokenTree], rv: &mut Vec<(bool, proc_macro2::Literal)>) {
+            for (idx, token) in tokens.iter().enumerate() {
+                match token {
+                    TokenTree::Literal(lit) => {
+                        let after_at = match idx.checked_sub(1).map(|x| &tokens[x]) {
+                            Some(TokenTree::Punct(punct)) => punct.as_char() == '@',
+                            _ => false,
+                        };
+                        rv.push((after_at, lit.clone()));
+                    }
+                    TokenTree::Group(group) => {
+                        let tokens: Vec<_> = group.stream().into_iter().collect();
+                        collect_literals(&tokens, rv);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        impl<'a> Visitor<'a> {
             pub fn scan_nested_macros(&mut self, tokens: &[TokenTree]) {
                 for idx in 0..tokens.len() {
                     if let Some(TokenTree::Ident(_)) = tokens.get(idx) {
@@ -157,6 +301,9 @@ impl FilePatcher {
                 tokens: &[TokenTree],
                 indentation: usize,
             ) -> bool {
+                if tokens.len() < 2 {
+                    return false;
+                }
                 match &tokens[tokens.len() - 2] {
                     TokenTree::Punct(ref punct) if punct.as_char() == '@' => {}
                     _ => {
@@ -165,13 +312,7 @@ impl FilePatcher {
                 }
 
                 let (start, end) = match &tokens[tokens.len() - 1] {
-                    TokenTree::Literal(lit) => {
-                        let span = lit.span();
-                        (
-                            (span.start().line - 1, span.start().column),
-                            (span.end().line - 1, span.end().column),
-                        )
-                    }
+                    TokenTree::Literal(lit) => literal_span(lit),
                     _ => return false,
                 };
 
@@ -179,12 +320,46 @@ impl FilePatcher {
                     start,
                     end,
                     indentation,
+                    value_end: find_value_end(&tokens[..tokens.len() - 2]),
+                    doc_prefix: None,
                 });
                 true
             }
+
+            /// Finds the snapshot in a macro that wraps an assertion.
+            ///
+            /// The line points to the invocation of the wrapper which can
+            /// take the snapshot anywhere in its arguments.  A literal after
+            /// an `@` is picked first, otherwise the literal with the old
+            /// contents of the snapshot.  Literals before the line are
+            /// ignored.
+            pub fn find_wrapped_snapshot(&mut self, tokens: &[TokenTree], indentation: usize) {
+                let mut literals = vec![];
+                collect_literals(tokens, &mut literals);
+                literals.retain(|(_, lit)| lit.span().start().line >= self.0);
+                let old = self.2;
+                let found = literals.iter().find(|(after_at, _)| *after_at).or_else(|| {
+                    let old = old?;
+                    literals.iter().find(|(_, lit)| {
+                        syn::parse_str::<syn::LitStr>(&lit.to_string())
+                            .map_or(false, |x| SnapshotContents::from_inline(&x.value()) == *old)
+                    })
+                });
+                if let Some((_, lit)) = found {
+                    let (start, end) = literal_span(lit);
+                    self.1 = Some(InlineSnapshot {
+                        start,
+                        end,
+                        indentation,
+                        // the wrapper might not support file snapshots
+                        value_end: None,
+                        doc_prefix: None,
+                    });
+                }
+            }
         }
 
-        impl<'ast> syn::visit::Visit<'ast> for Visitor {
+        impl<'a, 'ast> syn::visit::Visit<'ast> for Visitor<'a> {
             fn visit_attribute(&mut self, i: &'ast syn::Attribute) {
                 let start = i.span().start().line;
                 let end = i
@@ -228,11 +403,510 @@ impl FilePatcher {
                     // token stream.
                     self.scan_nested_macros(&tokens);
                 }
+
+                // the assertion might be wrapped in a user defined macro
+                if self.1.is_none() {
+                    self.find_wrapped_snapshot(&tokens, indentation);
+                }
+            }
+        }
+
+        /// Finds the end of the value if the assertion still works without
+        /// the snapshot.
+        ///
+        /// That's the case if only the value is left or the value followed
+        /// by redactions.
+        fn find_value_end(tokens: &[TokenTree]) -> Option<(usize, usize)> {
+            let (last, args) = tokens.split_last()?;
+            match last {
+                TokenTree::Punct(ref punct) if punct.as_char() == ',' => {}
+                _ => return None,
+            }
+            let commas: Vec<_> = args
+                .iter()
+                .enumerate()
+                .filter(|(_, token)| match token {
+                    TokenTree::Punct(ref punct) => punct.as_char() == ',',
+                    _ => false,
+                })
+                .map(|(idx, _)| idx)
+                .collect();
+            let is_value = match *commas.as_slice() {
+                [] => !args.is_empty(),
+                [idx] => {
+                    idx > 0
+                        && idx + 2 == args.len()
+                        && match args[idx + 1] {
+                            TokenTree::Group(ref group) => {
+                                group.delimiter() == proc_macro2::Delimiter::Brace
+                            }
+                            _ => false,
+                        }
+                }
+                _ => false,
+            };
+            if !is_value {
+                return None;
             }
+            let end = args.last()?.span().end();
+            Some((end.line - 1, end.column))
         }
 
-        let mut visitor = Visitor(line, None);
-        syn::visit::visit_file(&mut visitor, &self.source);
+        let mut visitor = Visitor(line, None, old);
+        syn::visit::visit_file(&mut visitor, source);
         visitor.1
     }
+
+    /// Finds a snapshot in a doctest.
+    ///
+    /// Rustdoc reports lines one line late for some doctests so the line
+    /// before is tried as well.  Of the two the one with the old contents
+    /// wins.
+    fn find_doctest_snapshot(
+        &self,
+        line: usize,
+        old: Option<&SnapshotContents>,
+    ) -> Option<InlineSnapshot> {
+        let mut candidates = vec![];
+        for &line in &[line, line.saturating_sub(1)] {
+            let source = match self.doctest_source(line) {
+                Some(source) => source,
+                None => continue,
+            };
+            if let Some(mut snapshot) = Self::find_snapshot_macro(&source, line, old) {
+                let prefix_len = doc_comment_prefix(&self.lines[snapshot.start.0])?;
+                snapshot.indentation = snapshot.indentation.saturating_sub(prefix_len);
+                snapshot.value_end = None;
+                snapshot.doc_prefix = Some(self.lines[snapshot.start.0][..prefix_len].to_string());
+                candidates.push(snapshot);
+            }
+        }
+        let idx = old
+            .and_then(|old| {
+                candidates
+                    .iter()
+                    .position(|x| self.doctest_snapshot_value(x).as_ref() == Some(old))
+            })
+            .unwrap_or(0);
+        if idx < candidates.len() {
+            Some(candidates.swap_remove(idx))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value of a snapshot in a doctest.
+    fn doctest_snapshot_value(&self, snapshot: &InlineSnapshot) -> Option<SnapshotContents> {
+        let literal = self.snapshot_literal(snapshot)?;
+        let lit = syn::parse_str::<syn::LitStr>(&literal).ok()?;
+        Some(SnapshotContents::from_inline(&lit.value()))
+    }
+
+    /// Returns the source of the string literal of a snapshot.
+    ///
+    /// In doctests the doc comment prefixes are left out.
+    fn snapshot_literal(&self, snapshot: &InlineSnapshot) -> Option<String> {
+        let mut literal = String::new();
+        for idx in snapshot.start.0..=snapshot.end.0 {
+            let line = self.lines.get(idx)?;
+            let end = if idx == snapshot.end.0 {
+                snapshot.end.1
+            } else {
+                line.chars().count()
+            };
+            let start = if idx == snapshot.start.0 {
+                snapshot.start.1
+            } else {
+                literal.push('\n');
+                if snapshot.doc_prefix.is_some() {
+                    doc_comment_prefix(line)?
+                } else {
+                    0
+                }
+            };
+            literal.extend(line.chars().skip(start).take(end.checked_sub(start)?));
+        }
+        Some(literal)
+    }
+
+    /// Builds a source file from the doctest that contains a line.
+    ///
+    /// The code fences become a function around the doctest and everything
+    /// else is left empty so that lines and columns match the source file.
+    fn doctest_source(&self, line: usize) -> Option<syn::File> {
+        let idx = line.checked_sub(1)?;
+        doc_comment_prefix(self.lines.get(idx)?)?;
+        let mut block_start = idx;
+        while block_start > 0 && doc_comment_prefix(&self.lines[block_start - 1]).is_some() {
+            block_start -= 1;
+        }
+
+        // find the fences around the line
+        let mut open = None;
+        let mut close = None;
+        for (fence_idx, fence_line) in self.lines.iter().enumerate().skip(block_start) {
+            let prefix_len = match doc_comment_prefix(fence_line) {
+                Some(prefix_len) => prefix_len,
+                None => break,
+            };
+            if !fence_line[prefix_len..].trim_start().starts_with("```") {
+                continue;
+            }
+            match open {
+                Some(_) if fence_idx > idx => {
+                    close = Some(fence_idx);
+                    break;
+                }
+                Some(_) => open = None,
+                None if fence_idx < idx => open = Some(fence_idx),
+                None => return None,
+            }
+        }
+        let open = open?;
+        let close = close.unwrap_or_else(|| {
+            (idx..self.lines.len())
+                .find(|&x| doc_comment_prefix(&self.lines[x]).is_none())
+                .unwrap_or(self.lines.len())
+        });
+
+        let mut source = String::new();
+        for (line_idx, line) in self.lines.iter().enumerate() {
+            if line_idx == open {
+                source.push_str("fn __insta_doctest() {");
+            } else if line_idx == close {
+                source.push('}');
+            } else if line_idx > open && line_idx < close {
+                let prefix_len = doc_comment_prefix(line).unwrap_or(0);
+                let code = &line[prefix_len..];
+                source.extend(std::iter::repeat(' ').take(prefix_len));
+                // rustdoc hides lines starting with `# ` but runs them
+                let trimmed = code.trim_start();
+                if trimmed == "#" || trimmed.starts_with("# ") {
+                    source.push_str(&code.replacen('#', " ", 1));
+                } else {
+                    source.push_str(code);
+                }
+            }
+            source.push('\n');
+        }
+        syn::parse_file(&source).ok()
+    }
+}
+
+/// Returns the length of the doc comment prefix of a line.
+///
+/// The prefix includes the indentation and a space after the `///`.
+fn doc_comment_prefix(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start();
+    let is_doc =
+        (trimmed.starts_with("///") && !trimmed.starts_with("////")) || trimmed.starts_with("//!");
+    if !is_doc {
+        return None;
+    }
+    let space = if trimmed[3..].starts_with(' ') { 1 } else { 0 };
+    Some(line.len() - trimmed.len() + 3 + space)
+}
+
+/// Counts the inline snapshot assertions in a source file.
+///
+/// Every macro invocation that ends in `@` followed by a literal counts,
+/// including ones nested in other macros.
+pub fn count_inline_snapshots(contents: &str) -> Result<usize, Box<dyn Error>> {
+    fn count(tokens: &[TokenTree]) -> usize {
+        let mut rv = 0;
+        for (idx, token) in tokens.iter().enumerate() {
+            if let TokenTree::Group(group) = token {
+                let inner: Vec<_> = group.stream().into_iter().collect();
+                let is_macro_call =
+                    idx >= 2 && matches_punct(&tokens[idx - 1], '!') && is_ident(&tokens[idx - 2]);
+                if is_macro_call
+                    && inner.len() >= 2
+                    && matches_punct(&inner[inner.len() - 2], '@')
+                    && is_literal(&inner[inner.len() - 1])
+                {
+                    rv += 1;
+                }
+                rv += count(&inner);
+            }
+        }
+        rv
+    }
+
+    fn matches_punct(token: &TokenTree, c: char) -> bool {
+        match token {
+            TokenTree::Punct(punct) => punct.as_char() == c,
+            _ => false,
+        }
+    }
+
+    fn is_ident(token: &TokenTree) -> bool {
+        match token {
+            TokenTree::Ident(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_literal(token: &TokenTree) -> bool {
+        match token {
+            TokenTree::Literal(_) => true,
+            _ => false,
+        }
+    }
+
+    let tokens: TokenStream = contents
+        .parse()
+        .map_err(|_| err_msg("failed to tokenize source file"))?;
+    Ok(count(&tokens.into_iter().collect::<Vec<_>>()))
+}
+
+/// Where a snapshot assertion keeps its snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssertionKind {
+    Inline,
+    File,
+}
+
+/// A snapshot assertion found in a source file.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotAssertion {
+    pub line: usize,
+    /// The name of the assertion macro.
+    #[serde(rename = "macro")]
+    pub macro_name: String,
+    pub kind: AssertionKind,
+    /// The path of the module within the file.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub module: String,
+    /// The snapshot name if it can be inferred.
+    ///
+    /// Names are generated from the test function like the runtime does
+    /// it, explicit names that are not string literals are unknown.
+    pub name: Option<String>,
+}
+
+/// Finds all snapshot assertions in a source file.
+///
+/// Assertions in other macros are found too, the bodies of `macro_rules!`
+/// definitions are skipped.
+pub fn find_snapshot_assertions(contents: &str) -> Result<Vec<SnapshotAssertion>, Box<dyn Error>> {
+    #[derive(Default)]
+    struct Visitor {
+        modules: Vec<String>,
+        function: Option<String>,
+        counters: HashMap<String, usize>,
+        assertions: Vec<SnapshotAssertion>,
+    }
+
+    fn is_snapshot_macro(name: &str) -> bool {
+        name.starts_with("assert_") && name.ends_with("_snapshot")
+    }
+
+    /// Splits macro arguments at the commas on the top level.
+    fn split_args(tokens: &[TokenTree]) -> Vec<&[TokenTree]> {
+        tokens
+            .split(|token| match token {
+                TokenTree::Punct(punct) => punct.as_char() == ',',
+                _ => false,
+            })
+            .filter(|arg| !arg.is_empty())
+            .collect()
+    }
+
+    fn literal_value(arg: &[TokenTree]) -> Option<String> {
+        match arg {
+            [TokenTree::Literal(lit)] => syn::parse_str::<syn::LitStr>(&lit.to_string())
+                .ok()
+                .map(|x| x.value()),
+            _ => None,
+        }
+    }
+
+    impl Visitor {
+        fn add(&mut self, macro_name: String, line: usize, tokens: &[TokenTree]) {
+            let mut args = split_args(tokens);
+            let inline = match args.last().and_then(|x| x.first()) {
+                Some(TokenTree::Punct(punct)) => punct.as_char() == '@',
+                _ => false,
+            };
+            if inline {
+                args.pop();
+            }
+            // redactions are not arguments that count
+            args.retain(|arg| match arg {
+                [TokenTree::Group(group)] => group.delimiter() != proc_macro2::Delimiter::Brace,
+                _ => true,
+            });
+
+            let explicit_name = if inline {
+                None
+            } else if macro_name == "assert_binary_snapshot" {
+                // the name is in the form `name.extension`
+                args.first()
+                    .and_then(|arg| literal_value(arg))
+                    .map(|x| match x.rfind('.') {
+                        Some(idx) => x[..idx].to_string(),
+                        None => x,
+                    })
+                    .filter(|x| !x.is_empty())
+                    .map(Some)
+            } else if args.len() >= 2 {
+                let is_auto_name = match args[0].last() {
+                    Some(TokenTree::Ident(ident)) => ident == "AutoName",
+                    _ => false,
+                };
+                if is_auto_name {
+                    None
+                } else {
+                    Some(literal_value(args[0]))
+                }
+            } else {
+                None
+            };
+
+            let name = match explicit_name {
+                Some(name) => name,
+                None => self.generate_name(),
+            };
+            self.assertions.push(SnapshotAssertion {
+                line,
+                macro_name,
+                kind: if inline {
+                    AssertionKind::Inline
+                } else {
+                    AssertionKind::File
+                },
+                module: self.modules.join("::"),
+                name,
+            });
+        }
+
+        /// Generates a name from the test function like the runtime.
+        fn generate_name(&mut self) -> Option<String> {
+            let function = self.function.as_ref()?;
+            let name = if function.starts_with("test_") {
+                &function[5..]
+            } else {
+                function
+            };
+            let key = format!("{}::{}", self.modules.join("::"), name);
+            let counter = self.counters.entry(key).or_insert(0);
+            *counter += 1;
+            Some(if *counter == 1 {
+                name.to_string()
+            } else {
+                format!("{}-{}", name, counter)
+            })
+        }
+
+        fn scan_tokens(&mut self, tokens: &[TokenTree]) {
+            for (idx, token) in tokens.iter().enumerate() {
+                if let TokenTree::Group(group) = token {
+                    let inner: Vec<_> = group.stream().into_iter().collect();
+                    let macro_name = match (
+                        idx.checked_sub(2).map(|x| &tokens[x]),
+                        &tokens[idx.saturating_sub(1)],
+                    ) {
+                        (Some(TokenTree::Ident(ident)), TokenTree::Punct(punct))
+                            if punct.as_char() == '!' =>
+                        {
+                            Some(ident)
+                        }
+                        _ => None,
+                    };
+                    match macro_name {
+                        Some(ident) if is_snapshot_macro(&ident.to_string()) => {
+                            self.add(ident.to_string(), ident.span().start().line, &inner)
+                        }
+                        _ => self.scan_tokens(&inner),
+                    }
+                }
+            }
+        }
+    }
+
+    impl<'ast> syn::visit::Visit<'ast> for Visitor {
+        fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+            self.modules.push(i.ident.to_string());
+            syn::visit::visit_item_mod(self, i);
+            self.modules.pop();
+        }
+
+        fn visit_item_fn(&mut self, i: &'ast syn::ItemFn) {
+            let old = self.function.replace(i.sig.ident.to_string());
+            syn::visit::visit_item_fn(self, i);
+            self.function = old;
+        }
+
+        fn visit_impl_item_method(&mut self, i: &'ast syn::ImplItemMethod) {
+            let old = self.function.replace(i.sig.ident.to_string());
+            syn::visit::visit_impl_item_method(self, i);
+            self.function = old;
+        }
+
+        fn visit_item_macro(&mut self, i: &'ast syn::ItemMacro) {
+            if !i.mac.path.is_ident("macro_rules") {
+                syn::visit::visit_item_macro(self, i);
+            }
+        }
+
+        fn visit_macro(&mut self, i: &'ast syn::Macro) {
+            let tokens: Vec<_> = i.tokens.clone().into_iter().collect();
+            match i.path.segments.last() {
+                Some(segment) if is_snapshot_macro(&segment.ident.to_string()) => {
+                    self.add(segment.ident.to_string(), i.span().start().line, &tokens)
+                }
+                _ => self.scan_tokens(&tokens),
+            }
+        }
+    }
+
+    let source = syn::parse_file(contents)?;
+    let mut visitor = Visitor::default();
+    syn::visit::visit_file(&mut visitor, &source);
+    Ok(visitor.assertions)
+}
+
+#[test]
+fn test_patch_raw_strings() {
+    let path = std::env::temp_dir().join(format!("insta-inline-{}.rs", std::process::id()));
+    fs::write(
+        &path,
+        r####"#[test]
+fn test_value() {
+    insta::assert_snapshot!(value, @"old");
+    insta::assert_snapshot!(other, @r###"plain"###);
+}
+"####,
+    )
+    .unwrap();
+    let mut patcher = FilePatcher::open(&path).unwrap();
+    patcher.add_snapshot_macro(3, None, false);
+    patcher.add_snapshot_macro(4, None, false);
+    // contents with `"#` need more hashes than that, the snapshot after it
+    // moves down
+    patcher.set_new_content(
+        0,
+        &SnapshotContents::from("say \"#hi\"\nsecond line"),
+        &InlineConfig::default(),
+    );
+    assert!(patcher.normalize_snapshot(1, &InlineConfig::default()));
+    assert!(!patcher.normalize_snapshot(1, &InlineConfig::default()));
+    patcher.save().unwrap();
+    let patched = fs::read_to_string(&path);
+    fs::remove_file(&path).ok();
+
+    assert_eq!(
+        patched.unwrap(),
+        r####"#[test]
+fn test_value() {
+    insta::assert_snapshot!(value, @r##"
+    say "#hi"
+    second line
+    "##);
+    insta::assert_snapshot!(other, @"plain");
+}
+"####
+    );
+    assert_eq!(patcher.changed_lines(), &[(3, 6), (7, 7)]);
 }
@@ -0,0 +1,111 @@
+//! <div align="center">
+//!  <img src="https://github.com/mitsuhiko/insta/blob/master/assets/logo.png?raw=true" width="250" height="250">
+//!  <p><strong>cargo-insta: review tool for insta, a snapshot testing library for Rust</strong></p>
+//!</div>
+//!
+//! This crate provides a cargo command for insta snapshot reviews.
+//!
+//! ```text
+//! $ cargo install cargo-insta
+//! $ cargo insta --help
+//! ```
+//!
+//! The core operations are also available as a library so that editor
+//! integrations and bots can accept or reject snapshots without running
+//! the command and parsing its output:
+//!
+//! ```no_run
+//! use cargo_insta::{apply_decisions, find_pending_snapshots, Operation};
+//! # use std::path::Path;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let workspace_root = Path::new(".");
+//! let mut containers = find_pending_snapshots(workspace_root)?;
+//! for container in &mut containers {
+//!     for snapshot in container.iter_snapshots() {
+//!         snapshot.op = Operation::Accept;
+//!     }
+//! }
+//! apply_decisions(workspace_root, containers)?;
+//! # Ok(()) }
+//! ```
+//!
+//! For more information see [the insta crate documentation](https://docs.rs/insta).
+mod archive;
+mod cargo;
+mod cli;
+mod commit;
+mod daemon;
+mod decisions;
+mod git;
+mod grep;
+mod hooks;
+mod hunks;
+mod inline;
+mod lint;
+mod list;
+mod manifest;
+mod migrate;
+mod normalize;
+mod notify;
+mod report;
+mod rustfmt;
+mod session;
+mod stats;
+mod trash;
+mod utils;
+mod watch;
+mod web;
+
+use std::error::Error;
+use std::path::Path;
+
+use insta::ToolConfig;
+
+pub use crate::cargo::{Operation, PendingSnapshot, SnapshotContainer};
+
+#[doc(hidden)]
+pub use crate::{cli::run, utils::QuietExit};
+
+/// Finds the pending snapshots below the workspace root.
+///
+/// This includes pending snapshots in `target/insta/pending`.
+///
+/// The pending snapshots of a file or of the inline snapshots of a source
+/// file are grouped in one container.  All of them start out skipped.
+pub fn find_pending_snapshots(
+    workspace_root: &Path,
+) -> Result<Vec<SnapshotContainer>, Box<dyn Error>> {
+    let pending_dir = insta::pending_dir(&workspace_root.join("target"));
+    cargo::find_snapshots(workspace_root.to_path_buf(), &["snap"], false)
+        .chain(cargo::find_target_snapshots(
+            pending_dir,
+            workspace_root.to_path_buf(),
+            &["snap"],
+        ))
+        .collect()
+}
+
+/// Applies the operations set on the pending snapshots.
+///
+/// This behaves like a review: the `insta.yaml` of the workspace is
+/// honored, so the accept hooks are run, credentials are checked for and
+/// accepted snapshots are staged if configured.  Either all decisions are
+/// applied or none of them.
+pub fn apply_decisions(
+    workspace_root: &Path,
+    snapshot_containers: Vec<SnapshotContainer>,
+) -> Result<(), Box<dyn Error>> {
+    let config = ToolConfig::from_workspace(workspace_root)?;
+    let mut snapshot_containers: Vec<_> =
+        snapshot_containers.into_iter().map(|x| (x, None)).collect();
+    let committed = commit::commit_decisions(
+        workspace_root,
+        &config,
+        &mut snapshot_containers,
+        false,
+        config.review.stage,
+        false,
+    )?;
+    commit::print_warnings(&committed.warnings);
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+use insta::Snapshot;
+use regex::Regex;
+
+use crate::inline::AssertionKind;
+use crate::list::SourceAssertions;
+
+/// How serious a lint issue is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    /// Something that is wrong for sure.
+    Error,
+    /// Something that is likely wrong.
+    Warning,
+}
+
+/// A problem found by linting the snapshot assertions or snapshots.
+#[derive(Debug)]
+pub struct LintIssue {
+    pub level: LintLevel,
+    pub message: String,
+    /// The source locations involved.
+    pub locations: Vec<(PathBuf, usize)>,
+}
+
+/// Finds assertions that write to the same snapshot file.
+///
+/// Snapshot files are named after the module and the snapshot name, so
+/// assertions of the same file and module with the same name clash.  The
+/// last one to run would win.
+pub fn find_duplicate_names(sources: &[SourceAssertions]) -> Vec<LintIssue> {
+    let mut rv = vec![];
+    for source in sources {
+        let mut by_name = BTreeMap::new();
+        for assertion in &source.assertions {
+            if let (AssertionKind::File, Some(name)) = (assertion.kind, assertion.name.as_ref()) {
+                by_name
+                    .entry((assertion.module.as_str(), name.as_str()))
+                    .or_insert_with(Vec::new)
+                    .push(assertion.line);
+            }
+        }
+        for ((module, name), lines) in by_name {
+            if lines.len() < 2 {
+                continue;
+            }
+            rv.push(LintIssue {
+                level: LintLevel::Error,
+                message: format!(
+                    "snapshot name '{}' is used by {} assertions{}",
+                    name,
+                    lines.len(),
+                    if module.is_empty() {
+                        "".to_string()
+                    } else {
+                        format!(" in module {}", module)
+                    }
+                ),
+                locations: lines
+                    .into_iter()
+                    .map(|line| (source.path.clone(), line))
+                    .collect(),
+            });
+        }
+    }
+    rv
+}
+
+/// Returns patterns of likely nondeterministic contents with a description
+/// and the standard filter that replaces them.
+fn nondeterminism_patterns() -> Vec<(Regex, &'static str, &'static str)> {
+    vec![
+        (
+            Regex::new(r"\b\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}").unwrap(),
+            "a timestamp",
+            "Timestamp",
+        ),
+        (
+            Regex::new(
+                r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b",
+            )
+            .unwrap(),
+            "a UUID",
+            "Uuid",
+        ),
+        (
+            Regex::new(r"(?:/tmp/|/var/folders/|\\AppData\\+Local\\+Temp\b)").unwrap(),
+            "a temporary path",
+            "TempDir",
+        ),
+        (
+            Regex::new(r"(?:/home/|/Users/|\b[A-Za-z]:\\+Users\\+)[^/\\\s]+").unwrap(),
+            "a home directory",
+            "HomeDir",
+        ),
+        (
+            Regex::new(r"\b0x[0-9a-fA-F]{8,}\b").unwrap(),
+            "a memory address",
+            "HexAddress",
+        ),
+        (
+            Regex::new(r"(?:\blocalhost|\b127\.0\.0\.1|\b0\.0\.0\.0|\[::1\]):\d{4,5}\b").unwrap(),
+            "a port number",
+            "Port",
+        ),
+    ]
+}
+
+/// Finds likely nondeterministic contents in the snapshot files below the
+/// roots.
+///
+/// Every kind of finding is reported once per snapshot file with all the
+/// lines it is in and the standard filter that would replace it.
+pub fn find_nondeterminism(
+    roots: &[PathBuf],
+    extensions: &[&str],
+    no_ignore: bool,
+) -> Result<Vec<LintIssue>, Box<dyn Error>> {
+    let mut paths = vec![];
+    for root in roots {
+        let walker = WalkBuilder::new(root)
+            .hidden(false)
+            .standard_filters(!no_ignore)
+            .filter_entry(|entry| entry.file_name() != OsStr::new("target"))
+            .build();
+        for entry in walker.filter_map(|x| x.ok()) {
+            let fname = entry.file_name().to_string_lossy();
+            let is_snapshot = entry.file_type().map_or(false, |x| x.is_file())
+                && extensions
+                    .iter()
+                    .any(|ext| fname.ends_with(&format!(".{}", ext)));
+            if is_snapshot {
+                paths.push(entry.into_path());
+            }
+        }
+    }
+    paths.sort();
+
+    let patterns = nondeterminism_patterns();
+    let mut rv = vec![];
+    for path in paths {
+        let snapshot = Snapshot::from_file(&path)?;
+        let contents = match snapshot.contents().as_str() {
+            Some(contents) => contents,
+            None => continue,
+        };
+        let offset = header_lines(&path);
+        for (regex, description, filter) in patterns.iter() {
+            let mut example = None;
+            let locations: Vec<_> = contents
+                .lines()
+                .enumerate()
+                .filter_map(|(idx, line)| {
+                    let m = regex.find(line)?;
+                    example.get_or_insert_with(|| m.as_str().to_string());
+                    Some((path.clone(), offset + idx + 1))
+                })
+                .collect();
+            if let Some(example) = example {
+                rv.push(LintIssue {
+                    level: LintLevel::Warning,
+                    message: format!(
+                        "snapshot contains {} ({}), consider StandardFilter::{}",
+                        description, example, filter
+                    ),
+                    locations,
+                });
+            }
+        }
+    }
+    Ok(rv)
+}
+
+/// Returns the number of lines of the metadata header of a snapshot file.
+///
+/// Compressed snapshot files have no lines to point to in which case the
+/// line numbers are those of the contents.
+fn header_lines(path: &Path) -> usize {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return 0,
+    };
+    if !contents.starts_with("---") {
+        return 0;
+    }
+    contents
+        .lines()
+        .enumerate()
+        .skip(1)
+        .find(|(_, line)| line.trim_end() == "---")
+        .map_or(0, |(idx, _)| idx + 1)
+}
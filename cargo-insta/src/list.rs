@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+use insta::MetaData;
+
+use crate::inline::{find_snapshot_assertions, AssertionKind, SnapshotAssertion};
+
+/// The snapshot assertions of a source file.
+#[derive(Debug)]
+pub struct SourceAssertions {
+    pub path: PathBuf,
+    pub assertions: Vec<SnapshotAssertion>,
+}
+
+/// Finds the snapshot assertions in all Rust source files below the roots.
+///
+/// Files that do not parse are skipped.
+pub fn list_assertions(
+    roots: &[PathBuf],
+    no_ignore: bool,
+) -> Result<Vec<SourceAssertions>, Box<dyn Error>> {
+    let mut rv = vec![];
+    for root in roots {
+        let walker = WalkBuilder::new(root)
+            .hidden(false)
+            .standard_filters(!no_ignore)
+            .filter_entry(|entry| entry.file_name() != OsStr::new("target"))
+            .build();
+        for entry in walker.filter_map(|x| x.ok()) {
+            let is_source = entry.file_type().map_or(false, |x| x.is_file())
+                && entry.path().extension() == Some(OsStr::new("rs"));
+            if !is_source {
+                continue;
+            }
+            let contents = fs::read_to_string(entry.path())?;
+            match find_snapshot_assertions(&contents) {
+                Ok(assertions) if !assertions.is_empty() => rv.push(SourceAssertions {
+                    path: entry.path().to_path_buf(),
+                    assertions,
+                }),
+                _ => {}
+            }
+        }
+    }
+    rv.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(rv)
+}
+
+/// The custom metadata of a snapshot file.
+#[derive(Debug)]
+pub struct SnapshotInfo {
+    /// The source file of the assertion that wrote the snapshot.
+    pub source: PathBuf,
+    /// The file name of the snapshot without extension.
+    pub stem: String,
+    pub info: BTreeMap<String, String>,
+}
+
+impl SnapshotInfo {
+    /// Checks if the snapshot was written by an assertion.
+    pub fn matches(&self, path: &Path, assertion: &SnapshotAssertion) -> bool {
+        let name = match (assertion.kind, &assertion.name) {
+            (AssertionKind::File, Some(name)) => name,
+            _ => return false,
+        };
+        let name = if assertion.module.is_empty() {
+            name.clone()
+        } else {
+            format!("{}__{}", assertion.module.replace("::", "__"), name)
+        };
+        self.source == path && (self.stem == name || self.stem.ends_with(&format!("__{}", name)))
+    }
+}
+
+/// Reads the custom metadata of the snapshot files below the roots.
+///
+/// Only the headers of the snapshot files are read.  Snapshot files
+/// without custom metadata are left out.
+pub fn load_snapshot_info(
+    roots: &[PathBuf],
+    extensions: &[&str],
+    no_ignore: bool,
+    workspace_root: &Path,
+) -> Result<Vec<SnapshotInfo>, Box<dyn Error>> {
+    let mut rv = vec![];
+    for root in roots {
+        let walker = WalkBuilder::new(root)
+            .hidden(false)
+            .standard_filters(!no_ignore)
+            .filter_entry(|entry| entry.file_name() != OsStr::new("target"))
+            .build();
+        for entry in walker.filter_map(|x| x.ok()) {
+            if !entry.file_type().map_or(false, |x| x.is_file()) {
+                continue;
+            }
+            let fname = entry.file_name().to_string_lossy();
+            let stem = match extensions
+                .iter()
+                .map(|ext| format!(".{}", ext))
+                .find(|ext| fname.ends_with(ext.as_str()))
+            {
+                Some(ext) => &fname[..fname.len() - ext.len()],
+                None => continue,
+            };
+            let metadata = match read_metadata(entry.path())? {
+                Some(metadata) if !metadata.info().is_empty() => metadata,
+                _ => continue,
+            };
+            if let Some(source) = metadata.source() {
+                rv.push(SnapshotInfo {
+                    source: workspace_root.join(source),
+                    stem: stem.to_string(),
+                    info: metadata.info().clone(),
+                });
+            }
+        }
+    }
+    Ok(rv)
+}
+
+/// Reads the metadata in the header of a snapshot file.
+fn read_metadata(path: &Path) -> Result<Option<MetaData>, Box<dyn Error>> {
+    let mut header = String::new();
+    for (idx, line) in BufReader::new(fs::File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim_end() == "---" {
+            if idx > 0 {
+                return Ok(serde_yaml::from_str(&header).ok());
+            }
+        } else if idx == 0 {
+            break;
+        } else {
+            header.push_str(&line);
+            header.push('\n');
+        }
+    }
+    Ok(None)
+}
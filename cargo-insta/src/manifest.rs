@@ -0,0 +1,195 @@
+use std::collections::{BTreeSet, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::err_msg;
+
+/// One of several parts the tests are split into, for instance `2/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Shard {
+    /// The number of the shard, starting at 1.
+    pub index: usize,
+    pub count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Shard, String> {
+        let mut parts = value.splitn(2, '/');
+        let index = parts.next().and_then(|x| x.trim().parse().ok());
+        let count = parts.next().and_then(|x| x.trim().parse().ok());
+        match (index, count) {
+            (Some(index), Some(count)) if index >= 1 && index <= count => {
+                Ok(Shard { index, count })
+            }
+            _ => Err(format!(
+                "invalid shard '{}': use INDEX/COUNT like 2/8",
+                value
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Shard {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Shard, String> {
+        value.parse()
+    }
+}
+
+impl From<Shard> for String {
+    fn from(shard: Shard) -> String {
+        shard.to_string()
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl Shard {
+    /// Picks the tests of this shard.
+    ///
+    /// The tests are sorted by name and dealt out in turn, so every test ends
+    /// up in exactly one shard no matter in which order they are listed.
+    pub fn select<'a>(&self, tests: &'a [String]) -> Vec<&'a str> {
+        let tests: BTreeSet<&str> = tests.iter().map(|x| x.as_str()).collect();
+        tests
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| idx % self.count == self.index - 1)
+            .map(|(_, test)| test)
+            .collect()
+    }
+
+    /// The default path of the manifest of this shard.
+    pub fn manifest_path(&self, workspace_root: &Path) -> PathBuf {
+        workspace_root.join("target").join(format!(
+            "insta-manifest-shard-{}-of-{}.json",
+            self.index, self.count
+        ))
+    }
+}
+
+/// Parses the output of `cargo test -- --list --format terse`.
+pub fn parse_test_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| line.ends_with(": test"))
+        .map(|line| line[..line.len() - 6].to_string())
+        .collect()
+}
+
+/// The snapshot files a partial test run referenced.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SnapshotManifest {
+    /// The shard of the tests the run covered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+    /// The packages the run tested.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub packages: BTreeSet<String>,
+    /// The snapshot files relative to the workspace root.
+    pub snapshots: BTreeSet<String>,
+}
+
+impl SnapshotManifest {
+    /// Creates a manifest from the references insta recorded during a run.
+    pub fn from_references(
+        workspace_root: &Path,
+        references_file: &Path,
+        shard: Option<Shard>,
+    ) -> Result<SnapshotManifest, Box<dyn Error>> {
+        let workspace_root = workspace_root.canonicalize()?;
+        let mut snapshots = BTreeSet::new();
+        // without any snapshot assertions insta does not create the file
+        let references = fs::read_to_string(references_file).unwrap_or_default();
+        for line in references.lines() {
+            if let Ok(path) = fs::canonicalize(line) {
+                let path = path.strip_prefix(&workspace_root).unwrap_or(&path);
+                let components: Vec<_> = path
+                    .components()
+                    .map(|x| x.as_os_str().to_string_lossy())
+                    .collect();
+                snapshots.insert(components.join("/"));
+            }
+        }
+        Ok(SnapshotManifest {
+            shard,
+            packages: BTreeSet::new(),
+            snapshots,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<SnapshotManifest, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read manifest {}: {}", path.display(), err))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| format!("invalid manifest {}: {}", path.display(), err).into())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Merges the manifests of partial runs into the referenced snapshot files.
+///
+/// If some of the manifests are from sharded runs all shards have to be
+/// there, otherwise snapshots of the missing shards would look
+/// unreferenced.
+pub fn merge_manifests(
+    workspace_root: &Path,
+    manifests: &[SnapshotManifest],
+) -> Result<HashSet<PathBuf>, Box<dyn Error>> {
+    let shards: BTreeSet<_> = manifests.iter().filter_map(|x| x.shard).collect();
+    if let Some(first) = shards.iter().next() {
+        if let Some(other) = shards.iter().find(|x| x.count != first.count) {
+            return Err(err_msg(format!(
+                "manifests of shards {} and {} are from different runs",
+                first, other
+            )));
+        }
+        let missing: Vec<_> = (1..=first.count)
+            .filter(|&index| !shards.iter().any(|x| x.index == index))
+            .map(|index| {
+                Shard {
+                    index,
+                    count: first.count,
+                }
+                .to_string()
+            })
+            .collect();
+        if !missing.is_empty() {
+            return Err(err_msg(format!(
+                "manifests of shards {} are missing",
+                missing.join(", ")
+            )));
+        }
+    }
+
+    let mut rv = HashSet::new();
+    for manifest in manifests {
+        for snapshot in &manifest.snapshots {
+            if let Ok(path) = workspace_root.join(snapshot).canonicalize() {
+                rv.insert(path);
+            }
+        }
+    }
+    Ok(rv)
+}
@@ -0,0 +1,138 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use insta::Snapshot;
+
+/// A snapshot file that no assertion referenced in a test run.
+pub struct StrandedSnapshot {
+    pub path: PathBuf,
+    pub snapshot: Snapshot,
+}
+
+/// A pending snapshot of an assertion that has no snapshot yet.
+pub struct NewSnapshot {
+    /// Where the assertion expects its snapshot.
+    pub path: PathBuf,
+    pub snapshot: Snapshot,
+    pub pending_files: Vec<PathBuf>,
+}
+
+/// A stranded snapshot that belongs to an assertion that moved.
+pub struct Migration<'a> {
+    pub from: &'a StrandedSnapshot,
+    pub to: &'a NewSnapshot,
+}
+
+/// The name of a snapshot file without the extension.
+fn snapshot_name(path: &Path) -> &str {
+    let name = path.file_name().and_then(|x| x.to_str()).unwrap_or("");
+    if name.ends_with(".snap") {
+        &name[..name.len() - 5]
+    } else {
+        name
+    }
+}
+
+/// The name of a snapshot without the module path of the test.
+fn test_name(name: &str) -> &str {
+    name.rsplit("__").next().unwrap_or(name)
+}
+
+fn is_same_name(stranded: &StrandedSnapshot, new: &NewSnapshot) -> bool {
+    snapshot_name(&stranded.path) == snapshot_name(&new.path)
+}
+
+fn is_same_assertion(stranded: &StrandedSnapshot, new: &NewSnapshot) -> bool {
+    let expression = new.snapshot.metadata().expression();
+    expression.is_some()
+        && expression == stranded.snapshot.metadata().expression()
+        && test_name(snapshot_name(&stranded.path)) == test_name(snapshot_name(&new.path))
+}
+
+fn is_same_contents(stranded: &StrandedSnapshot, new: &NewSnapshot) -> bool {
+    stranded.snapshot.contents() == new.snapshot.contents()
+}
+
+/// Pairs stranded snapshots with the new snapshots of moved assertions.
+///
+/// A stranded snapshot belongs to a new one if both have the same name,
+/// which is the case when only the snapshot path changed, or if the test of
+/// the same name moved to another module and still asserts the same
+/// expression.  Otherwise a stranded snapshot with the same contents is
+/// taken as the snapshot of a renamed test.  Pairs that are not clear are
+/// left alone.
+pub fn find_migrations<'a>(
+    stranded: &'a [StrandedSnapshot],
+    new: &'a [NewSnapshot],
+) -> Vec<Migration<'a>> {
+    let chosen: Vec<_> = new
+        .iter()
+        .map(|new| {
+            let matchers: [fn(&StrandedSnapshot, &NewSnapshot) -> bool; 3] =
+                [is_same_name, is_same_assertion, is_same_contents];
+            let candidates = matchers
+                .iter()
+                .map(|matches| {
+                    stranded
+                        .iter()
+                        .filter(|x| matches(x, new))
+                        .collect::<Vec<_>>()
+                })
+                .find(|candidates| !candidates.is_empty())?;
+            if candidates.len() == 1 {
+                Some(candidates[0])
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    new.iter()
+        .zip(chosen.iter())
+        .filter_map(|(new, from)| {
+            let from = (*from)?;
+            if chosen
+                .iter()
+                .filter(|x| x.map_or(false, |x| std::ptr::eq(x, from)))
+                .count()
+                > 1
+            {
+                return None;
+            }
+            Some(Migration { from, to: new })
+        })
+        .collect()
+}
+
+impl<'a> Migration<'a> {
+    /// Moves the stranded snapshot to its new place.
+    ///
+    /// If the contents did not change the pending snapshot is accepted right
+    /// away, otherwise it stays pending for review against the moved
+    /// snapshot.  Returns if the contents changed.
+    pub fn apply(&self) -> Result<bool, Box<dyn Error>> {
+        let changed = self.from.snapshot.contents() != self.to.snapshot.contents();
+        if changed {
+            self.from.snapshot.save(&self.to.path)?;
+        } else {
+            self.to.snapshot.save(&self.to.path)?;
+            for path in &self.to.pending_files {
+                fs::remove_file(path)?;
+            }
+        }
+        fs::remove_file(&self.from.path)?;
+        if let Some(binary_file) = self.from.snapshot.binary_file(&self.from.path) {
+            fs::remove_file(binary_file).ok();
+        }
+        Ok(changed)
+    }
+
+    /// Returns the snapshot files the migration wrote or removed.
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        let mut rv = vec![self.from.path.clone(), self.to.path.clone()];
+        rv.extend(self.from.snapshot.binary_file(&self.from.path));
+        rv.extend(self.to.snapshot.binary_file(&self.to.path));
+        rv
+    }
+}
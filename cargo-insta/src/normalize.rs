@@ -0,0 +1,94 @@
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+use insta::{InlineConfig, Snapshot};
+
+use crate::inline::FilePatcher;
+
+/// Finds all snapshot files below the given roots.
+pub fn find_snapshot_files(
+    roots: &[PathBuf],
+    extensions: &[&str],
+    no_ignore: bool,
+) -> Vec<PathBuf> {
+    let mut rv = vec![];
+    for root in roots {
+        let walker = WalkBuilder::new(root)
+            .hidden(false)
+            .standard_filters(!no_ignore)
+            .filter_entry(|entry| entry.file_name() != OsStr::new("target"))
+            .build();
+        for entry in walker.filter_map(|x| x.ok()) {
+            let fname = entry.file_name().to_string_lossy();
+            if entry.file_type().map_or(false, |x| x.is_file())
+                && extensions
+                    .iter()
+                    .any(|ext| fname.ends_with(&format!(".{}", ext)))
+            {
+                rv.push(entry.into_path());
+            }
+        }
+    }
+    rv.sort();
+    rv.dedup();
+    rv
+}
+
+/// Rewrites a snapshot file the way insta writes it.
+///
+/// This upgrades files from older versions of insta to the current
+/// metadata layout, with the keys in the order insta writes them and
+/// custom metadata sorted, and turns Windows line endings into Unix ones.
+/// With `checksums` the file gets a checksum, otherwise an existing one is
+/// dropped.  Returns if the file changed.  With `check` the file is left
+/// alone and only the return value tells if it would change.
+pub fn normalize_snapshot_file(
+    path: &Path,
+    checksums: bool,
+    check: bool,
+) -> Result<bool, Box<dyn Error>> {
+    let original = fs::read(path)?;
+    let snapshot = Snapshot::from_file(path)?;
+    let snapshot = if checksums {
+        snapshot.with_checksum()
+    } else {
+        snapshot.without_checksum()
+    };
+    snapshot.save(path)?;
+    let changed = fs::read(path)? != original;
+    if changed && check {
+        fs::write(path, &original)?;
+    }
+    Ok(changed)
+}
+
+/// Rewrites the inline snapshots in the given lines of a source file the
+/// way insta writes them.
+///
+/// Returns if the file changed.  With `check` the file is left alone and
+/// only the return value tells if it would change.
+pub fn normalize_source_file(
+    path: &Path,
+    lines: &[usize],
+    config: &InlineConfig,
+    check: bool,
+) -> Result<bool, Box<dyn Error>> {
+    let mut patcher = FilePatcher::open(path)?;
+    let mut ids = vec![];
+    for &line in lines {
+        if patcher.try_add_snapshot_macro(line, None, false) {
+            ids.push(ids.len());
+        }
+    }
+    let mut changed = false;
+    for id in ids {
+        changed |= patcher.normalize_snapshot(id, config);
+    }
+    if changed && !check {
+        patcher.save()?;
+    }
+    Ok(changed)
+}
@@ -0,0 +1,50 @@
+use std::io::{self, Write};
+use std::process;
+
+use insta::NotifyMode;
+
+/// Lets the user know that pending snapshots need a review.
+pub fn notify_pending(mode: NotifyMode, count: usize) {
+    let message = format!(
+        "{} snapshot{} to review",
+        count,
+        if count != 1 { "s" } else { "" }
+    );
+    match mode {
+        NotifyMode::Off => {}
+        NotifyMode::Bell => ring_bell(),
+        NotifyMode::Desktop => {
+            if !show_desktop_notification(&message) {
+                ring_bell();
+            }
+        }
+    }
+}
+
+fn ring_bell() {
+    let mut stderr = io::stderr();
+    stderr.write_all(b"\x07").ok();
+    stderr.flush().ok();
+}
+
+/// Shows a desktop notification, returns `false` if that is not supported.
+fn show_desktop_notification(message: &str) -> bool {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut cmd = process::Command::new("osascript");
+        cmd.arg("-e").arg(format!(
+            "display notification {:?} with title \"cargo insta\"",
+            message
+        ));
+        cmd
+    } else if cfg!(windows) {
+        return false;
+    } else {
+        let mut cmd = process::Command::new("notify-send");
+        cmd.arg("cargo insta").arg(message);
+        cmd
+    };
+    cmd.stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .status()
+        .map_or(false, |status| status.success())
+}
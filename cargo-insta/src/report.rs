@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use insta::{AssertionResult, AssertionStatus};
+use serde::Serialize;
+use similar::TextDiff;
+
+/// The maximum number of diff lines included in reports.
+const MAX_DIFF_LINES: usize = 50;
+
+/// Loads the assertion results written by a test run.
+pub fn load_results(path: &Path) -> Result<Vec<AssertionResult>, Box<dyn Error>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        // no snapshot assertions were run
+        Err(_) => return Ok(vec![]),
+    };
+    let mut rv = vec![];
+    for line in contents.lines() {
+        rv.push(serde_json::from_str(line)?);
+    }
+    Ok(rv)
+}
+
+/// Returns a human readable name for the asserted snapshot.
+fn display_name(result: &AssertionResult) -> String {
+    match result.snapshot_name {
+        Some(ref name) => name.clone(),
+        None => format!("<inline>:{}", result.line),
+    }
+}
+
+/// Returns a message describing why an assertion failed.
+fn failure_message(result: &AssertionResult) -> String {
+    match result.status {
+        AssertionStatus::New => format!(
+            "new snapshot '{}' in line {}",
+            display_name(result),
+            result.line
+        ),
+        _ => format!(
+            "snapshot assertion for '{}' failed in line {}",
+            display_name(result),
+            result.line
+        ),
+    }
+}
+
+/// Renders a unified diff of a failed assertion.
+fn unified_diff(result: &AssertionResult) -> String {
+    // snapshots are stored without trailing newline which would otherwise
+    // show up as change markers in the diff.
+    let with_newline = |value: Option<&str>| match value {
+        Some(value) if !value.is_empty() => format!("{}\n", value),
+        _ => String::new(),
+    };
+    let old = with_newline(result.old.as_deref());
+    let new = with_newline(result.new.as_deref());
+    let diff = TextDiff::from_lines(&old, &new);
+    let diff = diff
+        .unified_diff()
+        .context_radius(3)
+        .header("old snapshot", "new results")
+        .to_string();
+    diff.trim_end().to_string()
+}
+
+/// Renders a unified diff of a failed assertion cut off after a few lines.
+pub fn truncated_diff(result: &AssertionResult) -> String {
+    let diff = unified_diff(result);
+    let lines: Vec<_> = diff.lines().collect();
+    let mut rv = lines
+        .iter()
+        .take(MAX_DIFF_LINES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if lines.len() > MAX_DIFF_LINES {
+        write!(rv, "\n... ({} more lines)", lines.len() - MAX_DIFF_LINES).unwrap();
+    }
+    rv
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut rv = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => rv.push_str("&amp;"),
+            '<' => rv.push_str("&lt;"),
+            '>' => rv.push_str("&gt;"),
+            '"' => rv.push_str("&quot;"),
+            '\'' => rv.push_str("&apos;"),
+            // control characters are not allowed in XML 1.0
+            c if c.is_control() && c != '\n' && c != '\t' && c != '\r' => {}
+            c => rv.push(c),
+        }
+    }
+    rv
+}
+
+/// Writes the results as JUnit XML report.
+///
+/// Every source file becomes a test suite and every snapshot assertion a
+/// test case within it.
+pub fn write_junit_report(path: &Path, results: &[AssertionResult]) -> Result<(), Box<dyn Error>> {
+    let mut suites = BTreeMap::new();
+    for result in results {
+        suites
+            .entry(result.source_file.as_str())
+            .or_insert_with(Vec::new)
+            .push(result);
+    }
+
+    let failures = results
+        .iter()
+        .filter(|x| x.status != AssertionStatus::Pass)
+        .count();
+    let mut rv = String::new();
+    writeln!(rv, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        rv,
+        r#"<testsuites name="insta" tests="{}" failures="{}">"#,
+        results.len(),
+        failures
+    )
+    .unwrap();
+    for (source_file, results) in suites {
+        writeln!(
+            rv,
+            r#"  <testsuite name="{}" tests="{}" failures="{}">"#,
+            escape_xml(source_file),
+            results.len(),
+            results
+                .iter()
+                .filter(|x| x.status != AssertionStatus::Pass)
+                .count()
+        )
+        .unwrap();
+        for result in results {
+            write!(
+                rv,
+                r#"    <testcase name="{}" classname="{}""#,
+                escape_xml(&display_name(result)),
+                escape_xml(source_file),
+            )
+            .unwrap();
+            if let Some(ref snapshot_file) = result.snapshot_file {
+                write!(
+                    rv,
+                    r#" file="{}""#,
+                    escape_xml(&snapshot_file.display().to_string())
+                )
+                .unwrap();
+            }
+            if result.status == AssertionStatus::Pass {
+                writeln!(rv, "/>").unwrap();
+                continue;
+            }
+            writeln!(rv, ">").unwrap();
+            writeln!(
+                rv,
+                r#"      <failure type="snapshot" message="{}">{}</failure>"#,
+                escape_xml(&failure_message(result)),
+                escape_xml(&truncated_diff(result)),
+            )
+            .unwrap();
+            writeln!(rv, "    </testcase>").unwrap();
+        }
+        writeln!(rv, "  </testsuite>").unwrap();
+    }
+    writeln!(rv, "</testsuites>").unwrap();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, rv)?;
+    Ok(())
+}
+
+/// Escapes a value for use in a GitHub Actions workflow command.
+fn escape_github(value: &str, is_property: bool) -> String {
+    let mut rv = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' => rv.push_str("%25"),
+            '\r' => rv.push_str("%0D"),
+            '\n' => rv.push_str("%0A"),
+            ':' if is_property => rv.push_str("%3A"),
+            ',' if is_property => rv.push_str("%2C"),
+            c => rv.push(c),
+        }
+    }
+    rv
+}
+
+/// Prints GitHub Actions annotations for all failed assertions.
+///
+/// The annotations point at the line of the assertion and carry a short
+/// diff so that failures show up inline in pull requests.
+pub fn print_github_annotations(results: &[AssertionResult]) {
+    for result in results {
+        if result.status == AssertionStatus::Pass {
+            continue;
+        }
+        println!(
+            "::error file={},line={},title={}::{}",
+            escape_github(&result.source_file, true),
+            result.line,
+            escape_github(&failure_message(result), true),
+            escape_github(&truncated_diff(result), false),
+        );
+    }
+}
+
+/// The location of a snapshot assertion in the source.
+#[derive(Serialize)]
+struct SourceSpan<'a> {
+    file: &'a str,
+    line: u32,
+}
+
+/// The events emitted with `--message-format=json`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Event<'a> {
+    Assertion {
+        status: AssertionStatus,
+        snapshot_name: Option<&'a str>,
+        snapshot_file: Option<&'a Path>,
+        expression: Option<&'a str>,
+        span: SourceSpan<'a>,
+        diff: Option<String>,
+    },
+    Summary {
+        passed: usize,
+        failed: usize,
+        new: usize,
+    },
+}
+
+/// Prints one JSON event per line for every assertion followed by a summary.
+pub fn print_json_events(results: &[AssertionResult]) -> Result<(), Box<dyn Error>> {
+    let count = |status: AssertionStatus| results.iter().filter(|x| x.status == status).count();
+    for result in results {
+        let event = Event::Assertion {
+            status: result.status,
+            snapshot_name: result.snapshot_name.as_deref(),
+            snapshot_file: result.snapshot_file.as_deref(),
+            expression: result.expression.as_deref(),
+            span: SourceSpan {
+                file: &result.source_file,
+                line: result.line,
+            },
+            diff: if result.status == AssertionStatus::Pass {
+                None
+            } else {
+                Some(unified_diff(result))
+            },
+        };
+        println!("{}", serde_json::to_string(&event)?);
+    }
+    let summary = Event::Summary {
+        passed: count(AssertionStatus::Pass),
+        failed: count(AssertionStatus::Fail),
+        new: count(AssertionStatus::New),
+    };
+    println!("{}", serde_json::to_string(&summary)?);
+    Ok(())
+}
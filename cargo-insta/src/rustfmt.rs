@@ -0,0 +1,54 @@
+use std::env;
+use std::error::Error;
+use std::path::Path;
+use std::process;
+
+use serde_json::json;
+
+use crate::utils::err_msg;
+
+/// Formats a source file with rustfmt.
+///
+/// If line ranges (1-based and inclusive) are given only those are
+/// formatted which needs a nightly rustfmt.  Like `cargo fmt` the
+/// `RUSTFMT` environment variable can point to another rustfmt.
+pub fn format_file(
+    path: &Path,
+    edition: Option<&str>,
+    lines: Option<&[(usize, usize)]>,
+) -> Result<(), Box<dyn Error>> {
+    let rustfmt = env::var_os("RUSTFMT").unwrap_or_else(|| "rustfmt".into());
+    let mut proc = process::Command::new(rustfmt);
+    if let Some(edition) = edition {
+        proc.arg("--edition").arg(edition);
+    }
+    if let Some(lines) = lines {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let file_lines: Vec<_> = lines
+            .iter()
+            .map(|&(start, end)| json!({"file": path, "range": [start, end]}))
+            .collect();
+        proc.arg("--unstable-features")
+            .arg("--file-lines")
+            .arg(serde_json::to_string(&file_lines)?);
+    }
+    let output = proc
+        .arg(path)
+        .output()
+        .map_err(|err| err_msg(format!("could not run rustfmt: {}", err)))?;
+    if !output.status.success() {
+        return Err(err_msg(format!(
+            "rustfmt failed on {}: {}{}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim(),
+            if lines.is_some() {
+                " (formatting lines needs a nightly rustfmt)"
+            } else {
+                ""
+            }
+        )));
+    }
+    Ok(())
+}
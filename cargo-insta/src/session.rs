@@ -0,0 +1,112 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cargo::{Operation, PendingSnapshot};
+
+/// A decision made on a snapshot during a review.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Decision {
+    key: String,
+    op: Operation,
+    hunks: Option<Vec<Operation>>,
+    #[serde(default)]
+    as_variant: bool,
+}
+
+impl Decision {
+    pub fn new(snapshot: &PendingSnapshot, target_file: &Path) -> Decision {
+        Decision {
+            key: snapshot_key(snapshot, target_file),
+            op: snapshot.op,
+            hunks: snapshot.hunks.clone(),
+            as_variant: snapshot.as_variant,
+        }
+    }
+
+    /// Applies the decision to the snapshot it was made for.
+    pub fn apply(&self, snapshot: &mut PendingSnapshot) {
+        snapshot.op = self.op;
+        snapshot.hunks = self.hunks.clone();
+        snapshot.as_variant = self.as_variant;
+    }
+}
+
+/// Identifies a pending snapshot across review sessions.
+///
+/// The contents are part of the key so that decisions are not carried
+/// over to snapshots that changed since.
+fn snapshot_key(snapshot: &PendingSnapshot, target_file: &Path) -> String {
+    let mut hasher = DefaultHasher::new();
+    snapshot.new.contents_str().hash(&mut hasher);
+    snapshot
+        .old
+        .as_ref()
+        .map(|x| x.contents_str())
+        .hash(&mut hasher);
+    format!(
+        "{}:{}:{:x}",
+        target_file.display(),
+        snapshot.line.unwrap_or(0),
+        hasher.finish()
+    )
+}
+
+/// The progress of an interactive review.
+///
+/// The decisions are written to a file in the target folder as they are
+/// made so that an interrupted review can be resumed.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ReviewSession {
+    decisions: Vec<Decision>,
+}
+
+impl ReviewSession {
+    fn path(workspace_root: &Path) -> PathBuf {
+        workspace_root
+            .join("target")
+            .join("insta-review-session.json")
+    }
+
+    /// Loads the session of an interrupted review if there is one.
+    pub fn load(workspace_root: &Path) -> Option<ReviewSession> {
+        let contents = fs::read(ReviewSession::path(workspace_root)).ok()?;
+        serde_json::from_slice::<ReviewSession>(&contents)
+            .ok()
+            .filter(|session| !session.decisions.is_empty())
+    }
+
+    pub fn len(&self) -> usize {
+        self.decisions.len()
+    }
+
+    /// Returns the decision made on a snapshot.
+    pub fn decision(&self, snapshot: &PendingSnapshot, target_file: &Path) -> Option<&Decision> {
+        let key = snapshot_key(snapshot, target_file);
+        self.decisions.iter().find(|x| x.key == key)
+    }
+
+    /// Replaces the decisions and writes the session to disk.
+    pub fn save(
+        &mut self,
+        workspace_root: &Path,
+        decisions: Vec<Decision>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.decisions = decisions;
+        let path = ReviewSession::path(workspace_root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Removes the session once the review is finished.
+    pub fn discard(workspace_root: &Path) {
+        fs::remove_file(ReviewSession::path(workspace_root)).ok();
+    }
+}
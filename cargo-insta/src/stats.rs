@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+use crate::inline::count_inline_snapshots;
+
+/// Snapshot statistics of a single package.
+#[derive(Default, Debug)]
+pub struct PackageStats {
+    pub name: String,
+    pub file_snapshots: usize,
+    pub inline_snapshots: usize,
+    pub pending_files: usize,
+    pub size: u64,
+    /// The size of every snapshot file including binary sidecar files.
+    pub snapshot_sizes: BTreeMap<PathBuf, u64>,
+}
+
+impl PackageStats {
+    /// Scans the given roots for snapshots.
+    ///
+    /// Snapshot files are matched by extension, inline snapshots are
+    /// counted in all Rust source files and pending files are `.new` files
+    /// and `.pending-snap` files from inline snapshots.
+    pub fn collect(
+        name: &str,
+        roots: &[PathBuf],
+        extensions: &[&str],
+        no_ignore: bool,
+    ) -> Result<PackageStats, Box<dyn Error>> {
+        let mut rv = PackageStats {
+            name: name.to_string(),
+            ..PackageStats::default()
+        };
+        let mut sidecars = vec![];
+
+        for root in roots {
+            let walker = WalkBuilder::new(root)
+                .hidden(false)
+                .standard_filters(!no_ignore)
+                .filter_entry(|entry| entry.file_name() != OsStr::new("target"))
+                .build();
+            for entry in walker.filter_map(|x| x.ok()) {
+                if !entry.file_type().map_or(false, |x| x.is_file()) {
+                    continue;
+                }
+                let path = entry.path();
+                let fname = entry.file_name().to_string_lossy();
+                let size = || entry.metadata().map(|x| x.len()).unwrap_or(0);
+                if fname.contains(".new.") {
+                    // binary contents and image diffs of pending snapshots
+                    continue;
+                } else if fname.ends_with(".rs") {
+                    let contents = fs::read_to_string(path)?;
+                    rv.inline_snapshots += count_inline_snapshots(&contents).unwrap_or(0);
+                } else if fname.ends_with(".new") || fname.ends_with(".pending-snap") {
+                    rv.pending_files += 1;
+                } else if extensions
+                    .iter()
+                    .any(|ext| fname.ends_with(&format!(".{}", ext)))
+                {
+                    rv.file_snapshots += 1;
+                    rv.size += size();
+                    rv.snapshot_sizes.insert(path.to_path_buf(), size());
+                } else if let Some(snapshot_file) = sidecar_owner(path, extensions) {
+                    sidecars.push((snapshot_file, size()));
+                }
+            }
+        }
+
+        // binary contents are stored next to the snapshot file
+        for (snapshot_file, size) in sidecars {
+            if let Some(snapshot_size) = rv.snapshot_sizes.get_mut(&snapshot_file) {
+                *snapshot_size += size;
+                rv.size += size;
+            }
+        }
+
+        Ok(rv)
+    }
+}
+
+/// Returns the snapshot file a binary sidecar file belongs to.
+fn sidecar_owner(path: &Path, extensions: &[&str]) -> Option<PathBuf> {
+    let fname = path.file_name()?.to_str()?;
+    for ext in extensions {
+        if let Some(idx) = fname.rfind(&format!(".{}.", ext)) {
+            return Some(path.with_file_name(&fname[..idx + ext.len() + 1]));
+        }
+    }
+    None
+}
+
+/// Formats a number of bytes for humans.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
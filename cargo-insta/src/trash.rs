@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use insta::PendingInlineSnapshot;
+
+use crate::utils::err_msg;
+
+/// The folder within the target folder that holds rejected snapshots.
+const TRASH_DIR: &str = "insta-trash";
+
+fn trash_root(workspace_root: &Path) -> PathBuf {
+    workspace_root.join("target").join(TRASH_DIR)
+}
+
+/// Rejected snapshots of a single run that can be restored later.
+///
+/// Every run gets a folder named after the time of the run in which the
+/// files are stored at their path relative to the workspace root.
+#[derive(Debug)]
+pub struct Trash {
+    workspace_root: PathBuf,
+    path: PathBuf,
+}
+
+impl Trash {
+    /// Creates the trash for the current run.
+    pub fn new(workspace_root: &Path) -> Trash {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        Trash {
+            workspace_root: workspace_root.to_path_buf(),
+            path: trash_root(workspace_root).join(now.as_secs().to_string()),
+        }
+    }
+
+    /// Returns where a file of the workspace is stored in the trash.
+    fn trash_path(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        let rel_path = path.strip_prefix(&self.workspace_root).map_err(|_| {
+            err_msg(format!(
+                "cannot move {} to the trash as it is outside of the workspace",
+                path.display()
+            ))
+        })?;
+        let rv = self.path.join(rel_path);
+        fs::create_dir_all(rv.parent().unwrap())?;
+        Ok(rv)
+    }
+
+    /// Moves a file into the trash.
+    pub fn move_file(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::rename(path, self.trash_path(path)?)?;
+        Ok(())
+    }
+
+    /// Stores rejected inline snapshots of a pending snapshot file.
+    pub fn add_inline(
+        &self,
+        pending_file: &Path,
+        snapshots: &[PendingInlineSnapshot],
+    ) -> Result<(), Box<dyn Error>> {
+        if !snapshots.is_empty() {
+            PendingInlineSnapshot::save_batch(self.trash_path(pending_file)?, snapshots)?;
+        }
+        Ok(())
+    }
+
+    /// Removes everything moved into the trash by this run.
+    pub fn discard(&self) {
+        fs::remove_dir_all(&self.path).ok();
+    }
+}
+
+/// The trash of a previous run.
+#[derive(Debug)]
+pub struct TrashEntry {
+    pub id: String,
+    pub age: Duration,
+    pub files: Vec<PathBuf>,
+    path: PathBuf,
+}
+
+fn collect_files(path: &Path, rv: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, rv)?;
+        } else {
+            rv.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Lists the trash of previous runs, oldest first.
+pub fn list_trash(workspace_root: &Path) -> Result<Vec<TrashEntry>, Box<dyn Error>> {
+    let root = trash_root(workspace_root);
+    if !root.is_dir() {
+        return Ok(vec![]);
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let mut rv = vec![];
+    for entry in fs::read_dir(&root)? {
+        let path = entry?.path();
+        let secs: u64 = match path.file_name().and_then(|x| x.to_str()) {
+            Some(name) => match name.parse() {
+                Ok(secs) => secs,
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        let mut files = vec![];
+        collect_files(&path, &mut files)?;
+        files.sort();
+        rv.push(TrashEntry {
+            id: secs.to_string(),
+            age: now
+                .checked_sub(Duration::from_secs(secs))
+                .unwrap_or_default(),
+            files,
+            path,
+        });
+    }
+    rv.sort_by_key(|x| x.age);
+    rv.reverse();
+    Ok(rv)
+}
+
+/// Moves the files of a trash back into the workspace.
+///
+/// Rejected inline snapshots are added to the pending snapshots of the
+/// file.  Other files are not restored if a newer pending snapshot took
+/// their place.  Returns the restored and the skipped files.
+pub fn restore_trash(
+    workspace_root: &Path,
+    entry: &TrashEntry,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Box<dyn Error>> {
+    let mut restored = vec![];
+    let mut skipped = vec![];
+    for file in &entry.files {
+        let target = workspace_root.join(file.strip_prefix(&entry.path)?);
+        let is_inline = target
+            .file_name()
+            .and_then(|x| x.to_str())
+            .map_or(false, |x| x.ends_with(".pending-snap"));
+        if is_inline {
+            let mut batch = vec![];
+            if target.is_file() {
+                batch.extend(PendingInlineSnapshot::load_batch(&target)?);
+            }
+            batch.extend(PendingInlineSnapshot::load_batch(file)?);
+            // the runtime only picks up the snapshots of the last run
+            let batch: Vec<_> = batch
+                .into_iter()
+                .map(|x| {
+                    let mut pending = PendingInlineSnapshot::new(x.new, x.old, x.line);
+                    pending.doctest = x.doctest;
+                    pending
+                })
+                .collect();
+            PendingInlineSnapshot::save_batch(&target, &batch)?;
+            fs::remove_file(file)?;
+        } else if target.exists() {
+            skipped.push(target);
+            continue;
+        } else {
+            fs::create_dir_all(target.parent().unwrap())?;
+            fs::rename(file, &target)?;
+        }
+        restored.push(target);
+    }
+    if skipped.is_empty() {
+        fs::remove_dir_all(&entry.path)?;
+    }
+    Ok((restored, skipped))
+}
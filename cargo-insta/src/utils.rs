@@ -1,5 +1,10 @@
 use std::error::Error;
 use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use globset::{GlobBuilder, GlobMatcher};
+use insta::{format_date, is_iso_date, DiffAlgorithm, Settings};
+use similar::Algorithm;
 
 /// Close without message but exit code.
 #[derive(Debug)]
@@ -27,3 +32,88 @@ impl fmt::Display for ErrMsg {
 pub fn err_msg<S: Into<String>>(s: S) -> Box<dyn Error> {
     Box::new(ErrMsg(s.into()))
 }
+
+/// Parses a duration like `30d`, `2w` or `1h30m`.
+///
+/// Supported units are `s`, `m`, `h`, `d` and `w`.
+pub fn parse_duration(value: &str) -> Result<Duration, Box<dyn Error>> {
+    let invalid = || err_msg(format!("invalid duration '{}'", value));
+    let mut secs = 0u64;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let number: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let mut chars = rest[digits..].chars();
+        let unit = match chars.next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 60 * 60,
+            Some('d') => 60 * 60 * 24,
+            Some('w') => 60 * 60 * 24 * 7,
+            _ => return Err(invalid()),
+        };
+        secs = number
+            .checked_mul(unit)
+            .and_then(|x| secs.checked_add(x))
+            .ok_or_else(invalid)?;
+        rest = chars.as_str();
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Formats a duration in the largest unit that fits (`3d`, `5m`).
+///
+/// This is the rough inverse of [`parse_duration`].
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    for &(unit, unit_secs) in &[
+        ("w", 60 * 60 * 24 * 7),
+        ("d", 60 * 60 * 24),
+        ("h", 60 * 60),
+        ("m", 60),
+    ] {
+        if secs >= unit_secs {
+            return format!("{}{}", secs / unit_secs, unit);
+        }
+    }
+    format!("{}s", secs)
+}
+
+/// Parses an expiry date which is either an ISO date (`2021-06-30`) or a
+/// duration from now (`2w`).
+pub fn parse_expiry_date(value: &str) -> Result<String, Box<dyn Error>> {
+    if is_iso_date(value) {
+        return Ok(value.to_string());
+    }
+    parse_duration(value)
+        .map(|duration| format_date(SystemTime::now() + duration))
+        .map_err(|_| err_msg(format!("invalid expiry date '{}'", value)))
+}
+
+/// Compiles a glob pattern as given on the command line.
+///
+/// Unlike for paths `*` also matches `/` and `::` so that `parser::*`
+/// covers nested modules.
+pub fn compile_glob(pattern: &str) -> Result<GlobMatcher, Box<dyn Error>> {
+    GlobBuilder::new(pattern)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|err| err_msg(format!("invalid pattern '{}': {}", pattern, err.kind())))
+}
+
+/// Returns the diff algorithm of the current settings.
+pub fn diff_algorithm() -> Algorithm {
+    match Settings::clone_current()
+        .diff_algorithm()
+        .unwrap_or_default()
+    {
+        DiffAlgorithm::Myers => Algorithm::Myers,
+        DiffAlgorithm::Patience => Algorithm::Patience,
+        DiffAlgorithm::Lcs => Algorithm::Lcs,
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use ignore::WalkBuilder;
+
+/// How often the workspace is scanned for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A snapshot of the modification times of all source files.
+#[derive(PartialEq)]
+pub struct SourceTree(BTreeMap<PathBuf, SystemTime>);
+
+impl SourceTree {
+    /// Scans all files below the root that are not ignored.
+    ///
+    /// Snapshot files are left out as they are written by the test run
+    /// itself and must not trigger another run.
+    pub fn scan(root: &Path) -> SourceTree {
+        let mut files = BTreeMap::new();
+        let walker = WalkBuilder::new(root)
+            .filter_entry(|entry| entry.file_name() != OsStr::new("target"))
+            .build();
+        for entry in walker.filter_map(|x| x.ok()) {
+            if !entry.file_type().map_or(false, |x| x.is_file()) || is_snapshot_file(entry.path()) {
+                continue;
+            }
+            if let Some(modified) = entry.metadata().ok().and_then(|x| x.modified().ok()) {
+                files.insert(entry.path().to_path_buf(), modified);
+            }
+        }
+        SourceTree(files)
+    }
+
+    /// Blocks until a file was added, removed or modified and returns the
+    /// changed paths.
+    pub fn wait_for_changes(&self, root: &Path) -> Vec<PathBuf> {
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let new = SourceTree::scan(root);
+            if new != *self {
+                return self.changed_files(&new);
+            }
+        }
+    }
+
+    fn changed_files(&self, new: &SourceTree) -> Vec<PathBuf> {
+        let mut rv: Vec<PathBuf> = new
+            .0
+            .iter()
+            .filter(|(path, modified)| self.0.get(*path) != Some(modified))
+            .map(|(path, _)| path.clone())
+            .collect();
+        rv.extend(
+            self.0
+                .keys()
+                .filter(|path| !new.0.contains_key(*path))
+                .cloned(),
+        );
+        rv
+    }
+}
+
+fn is_snapshot_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|x| x.to_str()).unwrap_or("");
+    name.contains(".snap") || name.ends_with(".pending-snap")
+}
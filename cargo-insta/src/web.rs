@@ -0,0 +1,516 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use insta::Snapshot;
+use similar::{ChangeTag, TextDiff};
+use uuid::Uuid;
+
+use crate::cargo::{Operation, Package, ReviewAction};
+use crate::utils::diff_algorithm;
+
+/// How long the finished page is served after the last decision.
+const FINISH_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    body: String,
+}
+
+/// Information about the snapshot currently under review.
+pub struct ReviewItem<'a> {
+    pub new: &'a Snapshot,
+    pub old: Option<&'a Snapshot>,
+    pub pkg: Option<&'a Package>,
+    pub line: Option<u32>,
+    pub snapshot_file: Option<&'a Path>,
+}
+
+/// Serves the review UI on a local HTTP port.
+///
+/// The server is driven by the review loop: every call to
+/// [`WebReview::query_snapshot`] serves the page for one snapshot until a
+/// decision is posted back, so decisions are applied exactly like the ones
+/// made in the terminal.
+///
+/// Any page open in the browser can send requests to localhost, so all
+/// pages are served below a random token that is only part of the URL
+/// printed to the user, and requests for other hosts are refused so that
+/// DNS rebinding does not work either.
+pub struct WebReview {
+    listener: TcpListener,
+    queue: Vec<String>,
+    token: String,
+}
+
+impl WebReview {
+    /// Binds the server to localhost.  A port of `0` picks a free port.
+    pub fn start(port: u16, queue: Vec<String>) -> Result<WebReview, Box<dyn Error>> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let token = Uuid::new_v4().to_simple().to_string();
+        Ok(WebReview {
+            listener,
+            queue,
+            token,
+        })
+    }
+
+    /// Returns the URL the review UI is served on.
+    pub fn url(&self) -> Result<String, Box<dyn Error>> {
+        Ok(format!(
+            "http://{}/{}/",
+            self.listener.local_addr()?,
+            self.token
+        ))
+    }
+
+    /// Returns the path of a request below the session token.
+    ///
+    /// Requests for other hosts or without the token are not routed.
+    fn route<'a>(&self, req: &'a Request) -> Option<&'a str> {
+        let port = self.listener.local_addr().ok()?.port();
+        let host_allowed = req.host.as_ref().map_or(false, |host| {
+            *host == format!("127.0.0.1:{}", port) || *host == format!("localhost:{}", port)
+        });
+        let prefix = format!("/{}/", self.token);
+        if host_allowed && req.path.starts_with(&prefix) {
+            Some(&req.path[prefix.len() - 1..])
+        } else {
+            None
+        }
+    }
+
+    /// Serves the snapshot at index `i` (1 based) until a decision is made.
+    pub fn query_snapshot(
+        &self,
+        workspace_root: &Path,
+        item: &ReviewItem<'_>,
+        i: usize,
+    ) -> Result<ReviewAction, Box<dyn Error>> {
+        let page = render_snapshot_page(workspace_root, item, i, &self.queue, &self.token);
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            let mut stream = match prepare_stream(stream) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let req = match read_request(&mut stream) {
+                Ok(req) => req,
+                Err(_) => continue,
+            };
+            let path = match self.route(&req) {
+                Some(path) => path,
+                None => {
+                    respond(&mut stream, "403 Forbidden", "forbidden").ok();
+                    continue;
+                }
+            };
+            match (req.method.as_str(), path) {
+                ("GET", "/") => respond(&mut stream, "200 OK", &page).ok(),
+                ("POST", "/decide") => {
+                    let index =
+                        form_value(&req.body, "index").and_then(|x| x.parse::<usize>().ok());
+                    let action = match form_value(&req.body, "op").as_deref() {
+                        Some("accept") => Some(ReviewAction::Decide(Operation::Accept)),
+                        Some("reject") => Some(ReviewAction::Decide(Operation::Reject)),
+                        Some("skip") => Some(ReviewAction::Decide(Operation::Skip)),
+                        Some("undo") if i > 1 => Some(ReviewAction::Undo),
+                        _ => None,
+                    };
+                    redirect(&mut stream, &self.token).ok();
+                    // decisions for other snapshots come from stale pages
+                    // and are ignored.
+                    if let (Some(action), Some(index)) = (action, index) {
+                        if index == i {
+                            return Ok(action);
+                        }
+                    }
+                    None
+                }
+                _ => respond(&mut stream, "404 Not Found", "not found").ok(),
+            };
+        }
+    }
+
+    /// Serves a final page with the outcome of the review.
+    pub fn finish(
+        &self,
+        accepted: &[String],
+        rejected: &[String],
+        skipped: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut body = String::from("<h1>insta review finished</h1>");
+        for &(title, items) in &[
+            ("accepted", accepted),
+            ("rejected", rejected),
+            ("skipped", skipped),
+        ] {
+            if items.is_empty() {
+                continue;
+            }
+            write!(body, "<h2 class=\"{}\">{}</h2><ul>", title, title).unwrap();
+            for item in items {
+                write!(body, "<li>{}</li>", escape(item)).unwrap();
+            }
+            body.push_str("</ul>");
+        }
+        body.push_str("<p>You can close this page now.</p>");
+        let page = render_page("insta review", &body);
+
+        self.listener.set_nonblocking(true)?;
+        let started = Instant::now();
+        while started.elapsed() < FINISH_GRACE_PERIOD {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Ok(mut stream) = prepare_stream(stream) {
+                        if let Ok(req) = read_request(&mut stream) {
+                            if self.route(&req).is_some() {
+                                respond(&mut stream, "200 OK", &page).ok();
+                            } else {
+                                respond(&mut stream, "403 Forbidden", "forbidden").ok();
+                            }
+                        }
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn prepare_stream(stream: TcpStream) -> io::Result<TcpStream> {
+    stream.set_nonblocking(false)?;
+    // browsers like to open connections speculatively without ever sending
+    // a request.  Don't let these block the review.
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    Ok(stream)
+}
+
+fn read_request(stream: &mut TcpStream) -> io::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+
+    let mut content_length = 0;
+    let mut host = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let mut header = header.splitn(2, ':');
+        let name = header.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = header.next().unwrap_or("").trim().parse().unwrap_or(0);
+        } else if name.eq_ignore_ascii_case("host") {
+            host = Some(header.next().unwrap_or("").trim().to_ascii_lowercase());
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request {
+        method,
+        path,
+        host,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn redirect(stream: &mut TcpStream, token: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 303 See Other\r\nLocation: /{}/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        token
+    )?;
+    stream.flush()
+}
+
+/// Extracts a value from an url encoded form body.
+///
+/// Only the values posted by the review page are supported, so no percent
+/// decoding is performed.
+fn form_value(body: &str, key: &str) -> Option<String> {
+    body.split('&').find_map(|pair| {
+        let mut pair = pair.splitn(2, '=');
+        if pair.next() == Some(key) {
+            pair.next().map(|x| x.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+fn escape(text: &str) -> String {
+    let mut rv = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => rv.push_str("&amp;"),
+            '<' => rv.push_str("&lt;"),
+            '>' => rv.push_str("&gt;"),
+            '"' => rv.push_str("&quot;"),
+            c => rv.push(c),
+        }
+    }
+    rv
+}
+
+fn render_page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>
+body {{ font-family: sans-serif; margin: 0; display: flex; }}
+nav {{ width: 280px; padding: 1em; background: #f4f4f4; min-height: 100vh; font-size: 13px; }}
+nav li.current {{ font-weight: bold; }}
+nav li.done {{ color: #999; }}
+main {{ flex: 1; padding: 1em 2em; overflow-x: auto; }}
+dl {{ display: grid; grid-template-columns: max-content auto; gap: 0.2em 1em; }}
+dt {{ color: #666; }}
+dd {{ margin: 0; font-family: monospace; }}
+table.diff {{ border-collapse: collapse; font-family: monospace; font-size: 13px; width: 100%; }}
+table.diff td {{ padding: 0 0.5em; white-space: pre; vertical-align: top; }}
+table.diff td.idx {{ color: #999; text-align: right; width: 3em; }}
+tr.insert {{ background: #e6ffec; }}
+tr.delete {{ background: #ffebe9; }}
+tr.insert mark {{ background: #abf2bc; }}
+tr.delete mark {{ background: #ff8182; }}
+tr.gap td {{ color: #999; text-align: center; }}
+form {{ margin: 1em 0; }}
+button {{ font-size: 1em; padding: 0.4em 1.2em; margin-right: 0.5em; }}
+h2.accepted {{ color: #1a7f37; }}
+h2.rejected {{ color: #cf222e; }}
+h2.skipped {{ color: #9a6700; }}
+details {{ border-bottom: 1px solid #ddd; padding: 0.5em 0; }}
+summary {{ cursor: pointer; font-family: monospace; }}
+</style>
+</head>
+<body>
+{}
+</body>
+</html>
+"#,
+        escape(title),
+        body
+    )
+}
+
+/// Renders the metadata of a snapshot as definition list.
+fn render_metadata(workspace_root: &Path, item: &ReviewItem<'_>) -> String {
+    let mut rv = String::from("<dl>");
+    if let Some(pkg) = item.pkg {
+        write!(
+            rv,
+            "<dt>Package</dt><dd>{} ({})</dd>",
+            escape(pkg.name()),
+            escape(pkg.version())
+        )
+        .unwrap();
+    }
+    if let Some(snapshot_file) = item.snapshot_file {
+        let snapshot_file = snapshot_file
+            .strip_prefix(workspace_root)
+            .unwrap_or(snapshot_file);
+        write!(
+            rv,
+            "<dt>Snapshot file</dt><dd>{}</dd>",
+            escape(&snapshot_file.display().to_string())
+        )
+        .unwrap();
+    }
+    write!(
+        rv,
+        "<dt>Snapshot</dt><dd>{}</dd>",
+        escape(item.new.snapshot_name().unwrap_or("<inline>"))
+    )
+    .unwrap();
+    if let Some(source) = item.new.metadata().get_relative_source(workspace_root) {
+        let mut source = source.display().to_string();
+        if let Some(line) = item.line {
+            write!(source, ":{}", line).unwrap();
+        }
+        write!(rv, "<dt>Source</dt><dd>{}</dd>", escape(&source)).unwrap();
+    }
+    if let Some(description) = item.new.metadata().description() {
+        write!(rv, "<dt>Description</dt><dd>{}</dd>", escape(description)).unwrap();
+    }
+    if let Some(expires) = item.new.metadata().expires() {
+        write!(rv, "<dt>Expires</dt><dd>{}</dd>", escape(expires)).unwrap();
+    }
+    for (key, value) in item.new.metadata().info() {
+        write!(rv, "<dt>{}</dt><dd>{}</dd>", escape(key), escape(value)).unwrap();
+    }
+    if let Some(expression) = item.new.metadata().expression() {
+        write!(rv, "<dt>Expression</dt><dd>{}</dd>", escape(expression)).unwrap();
+    }
+    rv.push_str("</dl>");
+    rv
+}
+
+fn render_item_diff(item: &ReviewItem<'_>) -> String {
+    let old_contents = item
+        .old
+        .map(|x| x.contents().to_diffable_string())
+        .unwrap_or_default();
+    let new_contents = item.new.contents().to_diffable_string();
+    render_diff(&old_contents, &new_contents)
+}
+
+/// Renders a standalone page with the diffs of all given snapshots.
+///
+/// Every snapshot is shown as collapsible section with its title so that
+/// the report can be shared with people without a checkout.
+pub fn render_report(workspace_root: &Path, items: &[(String, ReviewItem<'_>)]) -> String {
+    let mut body = String::new();
+    write!(body, "<main><h1>Pending snapshots ({})</h1>", items.len()).unwrap();
+    if items.is_empty() {
+        body.push_str("<p>There are no pending snapshots.</p>");
+    }
+    for (title, item) in items {
+        write!(
+            body,
+            "<details open><summary>{}{}</summary>",
+            escape(title),
+            if item.old.is_none() { " (new)" } else { "" }
+        )
+        .unwrap();
+        body.push_str(&render_metadata(workspace_root, item));
+        body.push_str(&render_item_diff(item));
+        body.push_str("</details>");
+    }
+    body.push_str("</main>");
+    render_page("insta pending snapshots", &body)
+}
+
+fn render_snapshot_page(
+    workspace_root: &Path,
+    item: &ReviewItem<'_>,
+    i: usize,
+    queue: &[String],
+    token: &str,
+) -> String {
+    let mut body = String::new();
+
+    body.push_str("<nav><h3>Pending snapshots</h3><ol>");
+    for (idx, summary) in queue.iter().enumerate() {
+        let class = if idx + 1 == i {
+            "current"
+        } else if idx + 1 < i {
+            "done"
+        } else {
+            ""
+        };
+        write!(body, "<li class=\"{}\">{}</li>", class, escape(summary)).unwrap();
+    }
+    body.push_str("</ol></nav><main>");
+
+    write!(body, "<h1>Reviewing [{}/{}]</h1>", i, queue.len()).unwrap();
+    body.push_str(&render_metadata(workspace_root, item));
+
+    write!(
+        body,
+        r#"<form method="post" action="/{}/decide">
+<input type="hidden" name="index" value="{}">
+<button name="op" value="accept" accesskey="a">accept</button>
+<button name="op" value="reject" accesskey="r">reject</button>
+<button name="op" value="skip" accesskey="s">skip</button>
+{}</form>"#,
+        token,
+        i,
+        if i > 1 {
+            r#"<button name="op" value="undo" accesskey="u">undo</button>"#
+        } else {
+            ""
+        }
+    )
+    .unwrap();
+
+    body.push_str(&render_item_diff(item));
+    body.push_str(
+        r#"</main>
+<script>
+document.addEventListener("keydown", function(event) {
+  var ops = {a: "accept", r: "reject", s: "skip", u: "undo"};
+  var button = document.querySelector("button[value=" + ops[event.key] + "]");
+  if (button && !event.ctrlKey && !event.metaKey && !event.altKey) {
+    button.click();
+  }
+});
+</script>"#,
+    );
+    render_page("insta review", &body)
+}
+
+/// Renders a line diff as HTML table with intra-line changes marked.
+fn render_diff(old: &str, new: &str) -> String {
+    let diff = TextDiff::configure()
+        .algorithm(diff_algorithm())
+        .timeout(Duration::from_millis(500))
+        .diff_lines(old, new);
+
+    let mut rv = String::from("<table class=\"diff\">");
+    for (idx, group) in diff.grouped_ops(4).iter().enumerate() {
+        if idx > 0 {
+            rv.push_str("<tr class=\"gap\"><td colspan=\"4\">⋯</td></tr>");
+        }
+        for op in group {
+            for change in diff.iter_inline_changes(op) {
+                let (class, sign) = match change.tag() {
+                    ChangeTag::Insert => ("insert", "+"),
+                    ChangeTag::Delete => ("delete", "-"),
+                    ChangeTag::Equal => ("equal", " "),
+                };
+                write!(
+                    rv,
+                    "<tr class=\"{}\"><td class=\"idx\">{}</td><td class=\"idx\">{}</td><td>{}</td><td>",
+                    class,
+                    change.old_index().map_or(String::new(), |x| x.to_string()),
+                    change.new_index().map_or(String::new(), |x| x.to_string()),
+                    sign
+                )
+                .unwrap();
+                for &(emphasized, value) in change.values() {
+                    let value = escape(value.trim_end_matches('\n'));
+                    if emphasized {
+                        write!(rv, "<mark>{}</mark>", value).unwrap();
+                    } else {
+                        rv.push_str(&value);
+                    }
+                }
+                rv.push_str("</td></tr>");
+            }
+        }
+    }
+    rv.push_str("</table>");
+    if old == new {
+        rv.push_str("<p>snapshots are matching</p>");
+    }
+    rv
+}
@@ -0,0 +1,103 @@
+//! Procedural macros for insta.
+//!
+//! This crate is an implementation detail of insta.  Use the macros through
+//! the `macros` feature of insta instead.
+extern crate proc_macro;
+
+mod selector;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Error, Expr, ItemFn, Lit, Token};
+
+/// Marks a test function that makes snapshot assertions.
+///
+/// See `insta::test` for the documentation.
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    let test_attr = if attr.is_empty() {
+        None
+    } else {
+        Some(TokenStream2::from(attr))
+    };
+    match expand_test(test_attr, func) {
+        Ok(rv) => rv.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_test(test_attr: Option<TokenStream2>, func: ItemFn) -> Result<TokenStream2, Error> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = func;
+    let name = sig.ident.to_string();
+
+    let body = if sig.asyncness.is_some() {
+        if test_attr.is_none() {
+            return Err(Error::new_spanned(
+                sig.fn_token,
+                "async tests need the test attribute of the runtime, \
+                 for instance #[insta::test(tokio::test)]",
+            ));
+        }
+        quote! {
+            ::insta::_macro_support::run_test_future(#name, async move #block).await
+        }
+    } else {
+        quote! {
+            ::insta::_macro_support::run_test(#name, move || #block)
+        }
+    };
+    let test_attr = match test_attr {
+        Some(attr) => attr,
+        None => quote!(test),
+    };
+
+    Ok(quote! {
+        #[#test_attr]
+        #(#attrs)*
+        #vis #sig {
+            #body
+        }
+    })
+}
+
+/// Validates the selectors of redactions that are string literals.
+///
+/// Expands to nothing or to a compiler error pointing at the first invalid
+/// selector.  Other expressions are left to be checked at runtime.
+#[doc(hidden)]
+#[proc_macro]
+pub fn validate_selectors(input: TokenStream) -> TokenStream {
+    let parser = Punctuated::<Expr, Token![,]>::parse_terminated;
+    let selectors = parse_macro_input!(input with parser);
+    for selector in selectors.iter() {
+        if let Err(err) = validate_selector(selector) {
+            return err.to_compile_error().into();
+        }
+    }
+    TokenStream::new()
+}
+
+fn validate_selector(expr: &Expr) -> Result<(), Error> {
+    match *expr {
+        Expr::Group(ref group) => validate_selector(&group.expr),
+        Expr::Paren(ref paren) => validate_selector(&paren.expr),
+        Expr::Lit(ref lit) => match lit.lit {
+            Lit::Str(ref lit) => selector::validate(&lit.value()).map_err(|(col, msg)| {
+                Error::new(
+                    lit.span(),
+                    format!("invalid selector (column {}): {}", col, msg),
+                )
+            }),
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
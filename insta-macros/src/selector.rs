@@ -0,0 +1,206 @@
+use syn::ext::IdentExt;
+use syn::parse::Parser as _;
+use syn::Ident;
+
+/// Checks a selector the way `Selector::parse` of insta does.
+///
+/// This follows the selector grammar of insta by hand.  On failure the
+/// column and a description of the error are returned.
+pub(crate) fn validate(selector: &str) -> Result<(), (usize, String)> {
+    if selector.starts_with('/') {
+        return validate_pointer(selector);
+    }
+    let mut parser = Parser {
+        input: selector,
+        pos: 0,
+    };
+    loop {
+        parser.parse_selector()?;
+        parser.skip_whitespace();
+        if parser.rest().is_empty() {
+            return Ok(());
+        }
+        parser.expect(",")?;
+        parser.skip_whitespace();
+        if parser.rest().is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, msg: &str) -> (usize, String) {
+        (self.input[..self.pos].chars().count() + 1, msg.into())
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), (usize, String)> {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", token)))
+        }
+    }
+
+    /// Parses segments or `.` for the identity.
+    fn parse_selector(&mut self) -> Result<(), (usize, String)> {
+        self.skip_whitespace();
+        let mut segments = 0;
+        let mut have_deep_wildcard = false;
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            match self.parse_segment()? {
+                Some(is_deep_wildcard) => {
+                    if is_deep_wildcard && have_deep_wildcard {
+                        self.pos = start;
+                        return Err(self.error("deep wildcard used twice"));
+                    }
+                    have_deep_wildcard |= is_deep_wildcard;
+                    segments += 1;
+                }
+                None => break,
+            }
+        }
+        if segments == 0 && self.expect(".").is_err() {
+            return Err(self.error("expected selector"));
+        }
+        Ok(())
+    }
+
+    /// Parses a segment and returns if it is a deep wildcard.
+    fn parse_segment(&mut self) -> Result<Option<bool>, (usize, String)> {
+        let start = self.pos;
+        if self.expect(".").is_ok() {
+            self.skip_whitespace();
+            if self.expect("**").is_ok() {
+                return Ok(Some(true));
+            } else if self.expect("*").is_ok() {
+                return Ok(Some(false));
+            }
+            // keys do not allow whitespace after the dot
+            self.pos = start + 1;
+            let rest = self.rest();
+            let len = rest
+                .find(|c: char| c.is_whitespace() || !is_ident(&format!("a{}", c)))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            if !word.is_empty() && !word.starts_with('_') && is_ident(word) {
+                self.pos += len;
+                return Ok(Some(false));
+            }
+            self.pos = start;
+            return Ok(None);
+        }
+        if self.expect("[").is_err() {
+            return Ok(None);
+        }
+        self.skip_whitespace();
+        if self.expect("]").is_ok() {
+            return Ok(Some(false));
+        }
+        if self.expect("\"").is_ok() {
+            match self.rest().find('"') {
+                Some(end) => self.pos += end + 1,
+                None => return Err(self.error("unterminated string")),
+            }
+        } else if self.expect(":").is_ok() {
+            self.skip_whitespace();
+            self.parse_int()?;
+        } else {
+            let int_start = self.pos;
+            let is_negative = self.parse_int()?;
+            self.skip_whitespace();
+            // the open end of a range needs `:]` without whitespace
+            if self.expect(":]").is_ok() {
+                return Ok(Some(false));
+            } else if self.expect(":").is_ok() {
+                self.skip_whitespace();
+                self.parse_int()?;
+            } else if is_negative {
+                self.pos = int_start;
+                return Err(self.error("negative index"));
+            }
+        }
+        self.skip_whitespace();
+        self.expect("]")?;
+        Ok(Some(false))
+    }
+
+    /// Parses an integer and returns if it is negative.
+    ///
+    /// Unlike the grammar this does not allow whitespace or non ASCII
+    /// digits within numbers as insta fails to convert them.
+    fn parse_int(&mut self) -> Result<bool, (usize, String)> {
+        let rest = self.rest();
+        let sign = if rest.starts_with('-') { 1 } else { 0 };
+        let digits = rest[sign..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len() - sign);
+        if digits == 0 {
+            self.pos += sign;
+            return Err(self.error("expected number"));
+        }
+        self.pos += sign + digits;
+        Ok(sign > 0)
+    }
+}
+
+/// Checks if a word is an identifier by the rules of Rust, which are the
+/// same as those of the selector grammar except for leading underscores.
+fn is_ident(word: &str) -> bool {
+    Ident::parse_any.parse_str(word).is_ok()
+}
+
+fn validate_pointer(selector: &str) -> Result<(), (usize, String)> {
+    let mut chars = selector.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if c == '~' {
+            match chars.next() {
+                Some((_, '0')) | Some((_, '1')) => {}
+                _ => return Err((idx + 1, "invalid escape in JSON pointer".into())),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_validate() {
+    assert_eq!(validate(".foo[\"bar\"][1:].**.baz, .x"), Ok(()));
+    assert_eq!(validate("/items/0/a~1b"), Ok(()));
+    assert_eq!(validate("/a~2").unwrap_err().0, 3);
+    assert_eq!(validate(".**.a.**").unwrap_err().0, 6);
+    assert!(validate(".foo[").is_err());
+    assert!(validate("foo").is_err());
+
+    for selector in &[
+        ".",
+        ". **.a",
+        ".a .b[ ]",
+        "[1 : 2][:3][-1:][ 1 ]",
+        ".ä.naïve[\"a b\"],",
+        ".n\u{301}",
+    ] {
+        assert_eq!(validate(selector), Ok(()), "{}", selector);
+    }
+    for selector in &[
+        "", "._a", ". a", "[1: ]", "[- 1]", "[1 2]", "[-1]", "[\"a]", "[x]", ".a,,", ".a.", ".r#a",
+    ] {
+        assert!(validate(selector).is_err(), "{}", selector);
+    }
+}
@@ -0,0 +1,260 @@
+use std::borrow::Cow;
+
+use crate::settings::AnsiEscapes;
+
+const COLORS: &[&str] = &[
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// A piece of terminal output.
+enum Token<'a> {
+    Text(&'a str),
+    /// The parameters of a select graphic rendition sequence.
+    Sgr(&'a str),
+    /// Any other escape sequence.
+    Other,
+}
+
+/// Splits text into the text and the escape sequences in it.
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let bytes = text.as_bytes();
+    let mut rv = vec![];
+    let mut text_start = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] != 0x1b {
+            idx += 1;
+            continue;
+        }
+        if text_start < idx {
+            rv.push(Token::Text(&text[text_start..idx]));
+        }
+        let start = idx;
+        idx += 1;
+        match bytes.get(idx) {
+            // control sequence, parameters up to a final byte
+            Some(b'[') => {
+                idx += 1;
+                while idx < bytes.len() && !(0x40..=0x7e).contains(&bytes[idx]) {
+                    idx += 1;
+                }
+                if bytes.get(idx) == Some(&b'm') {
+                    rv.push(Token::Sgr(&text[start + 2..idx]));
+                } else {
+                    rv.push(Token::Other);
+                }
+                idx += 1;
+            }
+            // operating system command (titles, hyperlinks) up to a bell
+            // or a string terminator
+            Some(b']') => {
+                while idx < bytes.len() {
+                    if bytes[idx] == 0x07 {
+                        idx += 1;
+                        break;
+                    } else if bytes[idx] == 0x1b && bytes.get(idx + 1) == Some(&b'\\') {
+                        idx += 2;
+                        break;
+                    }
+                    idx += 1;
+                }
+                rv.push(Token::Other);
+            }
+            Some(_) => {
+                idx += 1;
+                rv.push(Token::Other);
+            }
+            None => rv.push(Token::Other),
+        }
+        idx = idx.min(bytes.len());
+        text_start = idx;
+    }
+    if text_start < bytes.len() {
+        rv.push(Token::Text(&text[text_start..]));
+    }
+    rv
+}
+
+/// The graphic rendition in effect.
+#[derive(Default)]
+struct Style {
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    blink: bool,
+    inverse: bool,
+    hidden: bool,
+    strikethrough: bool,
+    fg: Option<String>,
+    bg: Option<String>,
+}
+
+impl Style {
+    /// Applies the parameters of a select graphic rendition sequence.
+    fn apply(&mut self, params: &str) {
+        let params: Vec<u32> = params
+            .split(|c| c == ';' || c == ':')
+            .map(|x| x.parse().unwrap_or(0))
+            .collect();
+        let mut iter = params.into_iter();
+        while let Some(param) = iter.next() {
+            match param {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 | 21 => self.underline = true,
+                5 | 6 => self.blink = true,
+                7 => self.inverse = true,
+                8 => self.hidden = true,
+                9 => self.strikethrough = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                25 => self.blink = false,
+                27 => self.inverse = false,
+                28 => self.hidden = false,
+                29 => self.strikethrough = false,
+                30..=37 => self.fg = Some(COLORS[param as usize - 30].to_string()),
+                90..=97 => self.fg = Some(format!("bright-{}", COLORS[param as usize - 90])),
+                40..=47 => self.bg = Some(format!("bg-{}", COLORS[param as usize - 40])),
+                100..=107 => self.bg = Some(format!("bg-bright-{}", COLORS[param as usize - 100])),
+                38 => self.fg = extended_color(&mut iter),
+                48 => self.bg = extended_color(&mut iter).map(|x| format!("bg-{}", x)),
+                39 => self.fg = None,
+                49 => self.bg = None,
+                _ => {}
+            }
+        }
+    }
+
+    /// The tags of the style in the order they are opened.
+    fn tags(&self) -> Vec<String> {
+        let mut rv: Vec<String> = [
+            (self.bold, "bold"),
+            (self.dim, "dim"),
+            (self.italic, "italic"),
+            (self.underline, "underline"),
+            (self.blink, "blink"),
+            (self.inverse, "inverse"),
+            (self.hidden, "hidden"),
+            (self.strikethrough, "strikethrough"),
+        ]
+        .iter()
+        .filter(|x| x.0)
+        .map(|x| x.1.to_string())
+        .collect();
+        rv.extend(self.fg.clone());
+        rv.extend(self.bg.clone());
+        rv
+    }
+}
+
+/// Reads a 256 color (`5;n`) or a true color (`2;r;g;b`) argument.
+fn extended_color<I: Iterator<Item = u32>>(iter: &mut I) -> Option<String> {
+    match iter.next() {
+        Some(5) => iter.next().map(|x| format!("color-{}", x)),
+        Some(2) => {
+            let r = iter.next().unwrap_or(0);
+            let g = iter.next().unwrap_or(0);
+            let b = iter.next().unwrap_or(0);
+            Some(format!("rgb-{:02x}{:02x}{:02x}", r, g, b))
+        }
+        _ => None,
+    }
+}
+
+/// Removes all escape sequences from terminal output.
+pub fn strip_ansi(text: &str) -> String {
+    tokenize(text)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Renders the colors and styles of terminal output as tags.
+///
+/// Colors become `<red>..</red>`, bright colors `<bright-red>`, background
+/// colors `<bg-red>`, 256 colors `<color-208>` and true colors
+/// `<rgb-ff8800>`.  Styles become tags like `<bold>` or `<underline>`.
+/// Tags are only opened around text and closed as late as possible so that
+/// redundant escape sequences do not show up.  All other escape sequences
+/// are removed.
+pub fn render_ansi(text: &str) -> String {
+    let mut rv = String::new();
+    let mut open: Vec<String> = vec![];
+    let mut style = Style::default();
+    for token in tokenize(text) {
+        match token {
+            Token::Text(text) => {
+                let wanted = style.tags();
+                let keep = open
+                    .iter()
+                    .position(|x| !wanted.contains(x))
+                    .unwrap_or(open.len());
+                while open.len() > keep {
+                    rv.push_str(&format!("</{}>", open.pop().unwrap()));
+                }
+                for tag in wanted {
+                    if !open.contains(&tag) {
+                        rv.push_str(&format!("<{}>", tag));
+                        open.push(tag);
+                    }
+                }
+                rv.push_str(text);
+            }
+            Token::Sgr(params) => style.apply(params),
+            Token::Other => {}
+        }
+    }
+    while let Some(tag) = open.pop() {
+        rv.push_str(&format!("</{}>", tag));
+    }
+    rv
+}
+
+/// Handles the escape sequences in a snapshot as configured.
+pub fn process_ansi(text: &str, mode: AnsiEscapes) -> Cow<'_, str> {
+    if mode == AnsiEscapes::Keep || !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(match mode {
+        AnsiEscapes::Strip => strip_ansi(text),
+        _ => render_ansi(text),
+    })
+}
+
+#[test]
+fn test_strip_ansi() {
+    assert_eq!(
+        strip_ansi(
+            "\x1b[1;31merror\x1b[0m: \x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\\x1b[2K"
+        ),
+        "error: link"
+    );
+    assert_eq!(strip_ansi("no escapes"), "no escapes");
+}
+
+#[test]
+fn test_render_ansi() {
+    assert_eq!(
+        render_ansi("\x1b[31mred\x1b[0m plain \x1b[1mbold \x1b[32mgreen\x1b[39m\x1b[22m"),
+        "<red>red</red> plain <bold>bold <green>green</green></bold>"
+    );
+    assert_eq!(
+        render_ansi("\x1b[38;5;208;48;2;0;0;255mx\x1b[m\x1b[31m\x1b[0m"),
+        "<color-208><bg-rgb-0000ff>x</bg-rgb-0000ff></color-208>"
+    );
+    // changing the color closes only what is needed
+    assert_eq!(
+        render_ansi("\x1b[4;31ma\x1b[94mb\x1b[24mc\x1b[0m"),
+        "<underline><red>a</red><bright-blue>b</bright-blue></underline><bright-blue>c</bright-blue>"
+    );
+}
@@ -0,0 +1,154 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use sha1::{Digest, Sha1};
+
+use crate::settings::Settings;
+use crate::utils::create_temp_file;
+
+/// Returns the hash under which contents are stored in the blob directory.
+pub(crate) fn blob_hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(contents))
+}
+
+/// A remote storage for snapshot blobs.
+///
+/// Blobs that are missing from the local `blobs` folder are fetched from
+/// the backend on demand and `cargo insta accept` uploads the blobs of
+/// accepted snapshots.  This allows keeping only the hashes in the
+/// repository.  A backend can be set with
+/// [`Settings::set_blob_backend`](crate::Settings::set_blob_backend),
+/// otherwise a [`DirBlobBackend`] is used if the `INSTA_BLOB_DIR`
+/// environment variable is set.
+///
+/// Storages like HTTP servers or S3 buckets are supported by implementing
+/// this trait with the client of the storage.
+pub trait BlobBackend: Send + Sync {
+    /// Fetches the contents of a blob.
+    fn fetch(&self, hash: &str) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    /// Stores the contents of a blob.
+    fn store(&self, hash: &str, contents: &[u8]) -> Result<(), Box<dyn Error>>;
+}
+
+/// Stores blobs in a folder outside of the repository.
+///
+/// Every blob is a file named after its hash.  The folder is meant to be
+/// shared, for instance on a network drive or a mounted bucket.
+#[derive(Debug, Clone)]
+pub struct DirBlobBackend {
+    dir: PathBuf,
+}
+
+impl DirBlobBackend {
+    /// Creates a backend storing blobs in the given folder.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> DirBlobBackend {
+        DirBlobBackend { dir: dir.into() }
+    }
+
+    /// Creates a backend for the folder in `INSTA_BLOB_DIR`.
+    ///
+    /// Returns `None` if the variable is not set.
+    pub fn from_env() -> Option<DirBlobBackend> {
+        env::var_os("INSTA_BLOB_DIR")
+            .filter(|x| !x.is_empty())
+            .map(DirBlobBackend::new)
+    }
+
+    /// Returns the folder the blobs are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn blob_path(&self, hash: &str) -> Result<PathBuf, Box<dyn Error>> {
+        // hashes come from snapshot files and must not point elsewhere
+        if hash.is_empty() || !hash.bytes().all(|x| x.is_ascii_hexdigit()) {
+            return Err(format!("invalid blob hash '{}'", hash).into());
+        }
+        Ok(self.dir.join(hash))
+    }
+}
+
+impl BlobBackend for DirBlobBackend {
+    fn fetch(&self, hash: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let path = self.blob_path(hash)?;
+        fs::read(&path).map_err(|err| format!("failed to fetch blob {}: {}", hash, err).into())
+    }
+
+    fn store(&self, hash: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        let path = self.blob_path(hash)?;
+        if path.is_file() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        // written next to the blob and renamed so that nobody fetches a
+        // partially written blob
+        let (temp_path, mut file) = create_temp_file(&self.dir, ".insta-blob", "")?;
+        let rv = file
+            .write_all(contents)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&temp_path, &path));
+        if rv.is_err() {
+            fs::remove_file(&temp_path).ok();
+        }
+        rv.map_err(|err| format!("failed to store blob {}: {}", hash, err).into())
+    }
+}
+
+/// Returns the blob backend of the current settings or the environment.
+pub(crate) fn get_blob_backend() -> Result<Option<Arc<dyn BlobBackend>>, Box<dyn Error>> {
+    if let Some(backend) = Settings::with(|settings| settings.blob_backend()) {
+        return Ok(Some(backend));
+    }
+    Ok(DirBlobBackend::from_env().map(|x| Arc::new(x) as Arc<dyn BlobBackend>))
+}
+
+/// Fetches a blob that is missing locally and caches it in the blob folder.
+pub(crate) fn fetch_missing_blob(blob_file: &Path, hash: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let backend = get_blob_backend()?.ok_or_else(|| {
+        format!(
+            "blob {} is missing and no blob backend is configured",
+            blob_file.display()
+        )
+    })?;
+    let contents = backend.fetch(hash)?;
+    if blob_hash(&contents) != hash {
+        return Err(format!("blob {} fetched with wrong contents", hash).into());
+    }
+    if let Some(folder) = blob_file.parent() {
+        fs::create_dir_all(folder)?;
+    }
+    fs::write(blob_file, &contents)?;
+    Ok(contents)
+}
+
+#[test]
+fn test_blob_hash() {
+    assert_eq!(
+        blob_hash(b"hello world"),
+        "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed"
+    );
+}
+
+#[test]
+fn test_dir_blob_backend() {
+    let dir = env::temp_dir().join(format!("insta-blob-backend-{}", std::process::id()));
+    let backend = DirBlobBackend::new(&dir);
+    let hash = blob_hash(b"hello world");
+    assert!(backend.fetch(&hash).is_err());
+    backend.store(&hash, b"hello world").unwrap();
+    backend.store(&hash, b"hello world").unwrap();
+    let fetched = backend.fetch(&hash);
+    let files = fs::read_dir(&dir).unwrap().count();
+    let bad_hashes = ["../secret", "", "ab/cd"]
+        .iter()
+        .all(|hash| backend.fetch(hash).is_err() && backend.store(hash, b"").is_err());
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(fetched.unwrap(), b"hello world");
+    assert_eq!(files, 1);
+    assert!(bad_hashes);
+}
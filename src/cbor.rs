@@ -0,0 +1,224 @@
+use std::convert::TryFrom;
+
+use crate::content::Content;
+
+/// Key of the tag number when a tagged value is represented as content.
+const TAG_KEY: &str = "$tag";
+
+/// Key of the value when a tagged value is represented as content.
+const TAG_VALUE_KEY: &str = "$value";
+
+/// Key of simple values without a meaning when represented as content.
+const SIMPLE_KEY: &str = "$simple";
+
+/// Decodes a CBOR data item into the content model.
+///
+/// Integers become `u64` or `i64` (`i128` for large negative ones) no
+/// matter how they were encoded and strings of indefinite length are
+/// joined.  Tagged values become maps with the tag under `$tag` and the
+/// value under `$value`, `undefined` is represented as unit.
+pub fn parse_cbor(bytes: &[u8]) -> Result<Content, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let rv = reader.read_item()?;
+    if reader.pos != bytes.len() {
+        return Err(format!(
+            "{} trailing bytes after data item",
+            bytes.len() - reader.pos
+        ));
+    }
+    Ok(rv)
+}
+
+/// The marker of the end of items of indefinite length.
+const BREAK: u8 = 0xff;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| format!("unexpected end of data at offset {}", self.pos))?;
+        let rv = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(rv)
+    }
+
+    fn read_uint(&mut self, len: usize) -> Result<u64, String> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |rv, &byte| (rv << 8) | u64::from(byte)))
+    }
+
+    /// Reads the argument of an initial byte, `None` for indefinite length.
+    fn read_argument(&mut self, info: u8) -> Result<Option<u64>, String> {
+        match info {
+            0..=23 => Ok(Some(u64::from(info))),
+            24..=27 => self.read_uint(1 << (info - 24)).map(Some),
+            31 => Ok(None),
+            _ => Err(format!(
+                "invalid additional information {} at offset {}",
+                info,
+                self.pos - 1
+            )),
+        }
+    }
+
+    fn is_break(&self) -> bool {
+        self.bytes.get(self.pos) == Some(&BREAK)
+    }
+
+    fn read_item(&mut self) -> Result<Content, String> {
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+        if major == 7 {
+            return self.read_simple(info);
+        }
+        let argument = self.read_argument(info)?;
+        Ok(match (major, argument) {
+            (0, Some(value)) => Content::U64(value),
+            (1, Some(value)) => match i64::try_from(value) {
+                Ok(value) => Content::I64(-1 - value),
+                Err(_) => Content::I128(-1 - i128::from(value)),
+            },
+            (2, _) | (3, _) => {
+                let bytes = match argument {
+                    Some(len) => self.take(len as usize)?.to_vec(),
+                    None => self.read_chunks(major)?,
+                };
+                if major == 2 {
+                    Content::Bytes(bytes)
+                } else {
+                    Content::from(String::from_utf8(bytes).map_err(|err| err.to_string())?)
+                }
+            }
+            (4, _) => {
+                let mut items = vec![];
+                while argument.map_or(!self.is_break(), |len| (items.len() as u64) < len) {
+                    items.push(self.read_item()?);
+                }
+                if argument.is_none() {
+                    self.pos += 1;
+                }
+                Content::Seq(items)
+            }
+            (5, _) => {
+                let mut entries = vec![];
+                while argument.map_or(!self.is_break(), |len| (entries.len() as u64) < len) {
+                    let key = self.read_item()?;
+                    let value = self.read_item()?;
+                    entries.push((key, value));
+                }
+                if argument.is_none() {
+                    self.pos += 1;
+                }
+                Content::Map(entries)
+            }
+            (6, Some(tag)) => Content::Map(vec![
+                (Content::from(TAG_KEY), Content::U64(tag)),
+                (Content::from(TAG_VALUE_KEY), self.read_item()?),
+            ]),
+            _ => {
+                return Err(format!(
+                    "invalid indefinite length at offset {}",
+                    self.pos - 1
+                ))
+            }
+        })
+    }
+
+    /// Reads the chunks of a byte or text string of indefinite length.
+    fn read_chunks(&mut self, major: u8) -> Result<Vec<u8>, String> {
+        let mut rv = vec![];
+        while !self.is_break() {
+            let initial = self.take(1)?[0];
+            let len = match self.read_argument(initial & 0x1f)? {
+                Some(len) if initial >> 5 == major => len,
+                _ => {
+                    return Err(format!(
+                        "invalid chunk of indefinite length string at offset {}",
+                        self.pos - 1
+                    ))
+                }
+            };
+            rv.extend_from_slice(self.take(len as usize)?);
+        }
+        self.pos += 1;
+        Ok(rv)
+    }
+
+    fn read_simple(&mut self, info: u8) -> Result<Content, String> {
+        Ok(match info {
+            20 => Content::Bool(false),
+            21 => Content::Bool(true),
+            22 => Content::None,
+            23 => Content::Unit,
+            24 => {
+                let value = self.take(1)?[0];
+                Content::Map(vec![(Content::from(SIMPLE_KEY), Content::U8(value))])
+            }
+            25 => Content::F32(f16_to_f32(self.read_uint(2)? as u16)),
+            26 => Content::F32(f32::from_bits(self.read_uint(4)? as u32)),
+            27 => Content::F64(f64::from_bits(self.read_uint(8)?)),
+            0..=19 => Content::Map(vec![(Content::from(SIMPLE_KEY), Content::U8(info))]),
+            _ => {
+                return Err(format!(
+                    "unexpected simple value {} at offset {}",
+                    info,
+                    self.pos - 1
+                ))
+            }
+        })
+    }
+}
+
+/// Converts a half precision float.
+fn f16_to_f32(half: u16) -> f32 {
+    let exponent = i32::from((half >> 10) & 0x1f);
+    let mantissa = f32::from(half & 0x3ff);
+    let value = match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => std::f32::INFINITY,
+        31 => std::f32::NAN,
+        _ => (mantissa + 1024.0) * 2f32.powi(exponent - 25),
+    };
+    if half & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+#[test]
+fn test_parse_cbor() {
+    // {"id": 7, "tags": ["a", -3], "ok": true, "none": null}
+    let bytes = b"\xa4\x62id\x07\x64tags\x82\x61a\x22\x62ok\xf5\x64none\xf6";
+    let content = parse_cbor(bytes).unwrap();
+    assert_eq!(
+        serde_json::to_string(&content).unwrap(),
+        r#"{"id":7,"tags":["a",-3],"ok":true,"none":null}"#
+    );
+
+    // indefinite length array and string
+    assert_eq!(
+        serde_json::to_string(&parse_cbor(b"\x9f\x01\x7f\x61a\x61b\xff\xff").unwrap()).unwrap(),
+        r#"[1,"ab"]"#
+    );
+    assert_eq!(
+        serde_json::to_string(&parse_cbor(b"\xc1\x1a\x51\x4b\x67\xb0").unwrap()).unwrap(),
+        r#"{"$tag":1,"$value":1363896240}"#
+    );
+    assert_eq!(
+        serde_json::to_string(&parse_cbor(b"\xf9\x3e\x00").unwrap()).unwrap(),
+        "1.5"
+    );
+    assert!(parse_cbor(b"\x82\x01").is_err());
+    assert!(parse_cbor(b"\x01\x02").is_err());
+}
@@ -0,0 +1,45 @@
+use std::borrow::BorrowMut;
+use std::process::{Command, Output};
+
+use crate::settings::Settings;
+
+/// Formats the output of a command as a snapshot.
+///
+/// The snapshot starts with whether the command succeeded and its exit code
+/// (`none` if it was killed by a signal) followed by the sections for
+/// stdout and stderr.  Output that is not valid UTF-8 is decoded lossily.
+fn format_output(output: &Output, settings: &Settings) -> String {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    #[cfg(feature = "filters")]
+    let (stdout, stderr) = (
+        settings.stdout_filters().apply_to(&stdout).into_owned(),
+        settings.stderr_filters().apply_to(&stderr).into_owned(),
+    );
+    #[cfg(not(feature = "filters"))]
+    let _ = settings;
+    format!(
+        "success: {}\nexit_code: {}\n----- stdout -----\n{}\n----- stderr -----\n{}",
+        output.status.success(),
+        output
+            .status
+            .code()
+            .map_or_else(|| "none".to_string(), |x| x.to_string()),
+        stdout,
+        stderr
+    )
+}
+
+/// Runs a command and formats its output as a snapshot.
+///
+/// The command line is recorded in the metadata of the snapshot by the
+/// returned settings.  This panics if the command cannot be started.
+pub fn run_cmd_snapshot<C: BorrowMut<Command>>(mut cmd: C) -> (String, Settings) {
+    let cmd = cmd.borrow_mut();
+    let output = cmd
+        .output()
+        .unwrap_or_else(|err| panic!("failed to run command {:?}: {}", cmd, err));
+    let mut settings = Settings::clone_current();
+    settings.add_info("command", format!("{:?}", cmd));
+    (format_output(&output, &settings), settings)
+}
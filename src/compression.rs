@@ -0,0 +1,107 @@
+use std::error::Error;
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec;
+
+/// The length of the lines the encoded contents are wrapped at.
+const LINE_LENGTH: usize = 76;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+/// Compresses data into the gzip format.
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // magic, deflate, no flags, no mtime, max compression, unknown os
+    let mut rv = vec![GZIP_MAGIC[0], GZIP_MAGIC[1], 8, 0, 0, 0, 0, 0, 2, 255];
+    rv.extend(compress_to_vec(data, 9));
+    rv.extend(&crc32fast::hash(data).to_le_bytes());
+    rv.extend(&(data.len() as u32).to_le_bytes());
+    rv
+}
+
+/// Decompresses gzip data.
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if data.len() < 18 || data[..2] != GZIP_MAGIC || data[2] != 8 {
+        return Err("invalid gzip data".into());
+    }
+    let flags = data[3];
+    let mut offset = 10;
+    if flags & FLAG_EXTRA != 0 {
+        let len = data
+            .get(offset..offset + 2)
+            .ok_or("truncated gzip header")?;
+        offset += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for &flag in &[FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let end = data
+                .get(offset..)
+                .and_then(|rest| rest.iter().position(|&x| x == 0))
+                .ok_or("truncated gzip header")?;
+            offset += end + 1;
+        }
+    }
+    if flags & FLAG_HCRC != 0 {
+        offset += 2;
+    }
+    let rest = data
+        .get(offset..)
+        .filter(|rest| rest.len() >= 8)
+        .ok_or("truncated gzip data")?;
+
+    let (body, trailer) = rest.split_at(rest.len() - 8);
+    let rv = decompress_to_vec(body).map_err(|err| format!("invalid gzip data: {:?}", err))?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    if crc != crc32fast::hash(&rv) {
+        return Err("gzip checksum mismatch".into());
+    }
+    Ok(rv)
+}
+
+/// Compresses snapshot contents with gzip and encodes them as base64.
+///
+/// The result is wrapped into lines so the snapshot file stays readable
+/// by line based tools.
+pub(crate) fn encode_gzip(contents: &str) -> String {
+    let encoded = base64::encode(gzip(contents.as_bytes()));
+    encoded
+        .as_bytes()
+        .chunks(LINE_LENGTH)
+        .map(|x| std::str::from_utf8(x).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reverses [`encode_gzip`].
+pub(crate) fn decode_gzip(encoded: &str) -> Result<String, Box<dyn Error>> {
+    let encoded: String = encoded.split_whitespace().collect();
+    let data = gunzip(&base64::decode(&encoded)?)?;
+    Ok(String::from_utf8(data)?)
+}
+
+#[test]
+fn test_gzip_roundtrip() {
+    let contents = "hello world\n".repeat(100);
+    let encoded = encode_gzip(&contents);
+    assert!(encoded.len() < contents.len());
+    assert!(encoded.lines().all(|x| x.len() <= LINE_LENGTH));
+    assert_eq!(decode_gzip(&encoded).unwrap(), contents);
+
+    // produced by `gzip -n`
+    let gzipped = base64::decode("H4sIAAAAAAAAA8tIzcnJVyjPL8pJAQCFEUoNCwAAAA==").unwrap();
+    assert_eq!(gunzip(&gzipped).unwrap(), b"hello world");
+
+    // header fields that claim to be longer than the data
+    let mut extra = gzipped[..10].to_vec();
+    extra[3] = FLAG_EXTRA | FLAG_NAME;
+    extra.extend(&[0xff, 0xff]);
+    extra.extend(&[0; 8]);
+    assert!(gunzip(&extra).is_err());
+    let mut name = gzipped[..10].to_vec();
+    name[3] = FLAG_NAME | FLAG_HCRC;
+    name.extend(b"name without end");
+    assert!(gunzip(&name).is_err());
+}
@@ -0,0 +1,476 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::settings::{DiffAlgorithm, SnapshotLayout};
+
+/// The name of the config file in the workspace root.
+const CONFIG_FILE: &str = "insta.yaml";
+
+/// Configures how pending snapshots are handled.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct PendingConfig {
+    /// Pending snapshots older than this (for instance `30d`) are discarded
+    /// when snapshots are reviewed.
+    pub max_age: Option<String>,
+    /// Where `.snap.new` and `.pending-snap` files are written.
+    pub location: PendingLocation,
+}
+
+/// Where pending snapshots are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PendingLocation {
+    /// Next to the snapshot file or the source file of inline snapshots.
+    Source,
+    /// Below `insta/pending` in the target directory, in the same layout
+    /// as the workspace.
+    Target,
+}
+
+impl Default for PendingLocation {
+    fn default() -> PendingLocation {
+        PendingLocation::Source
+    }
+}
+
+/// The folder below the target directory that pending snapshots go into
+/// with [`PendingLocation::Target`].
+pub fn pending_dir(target_dir: &Path) -> PathBuf {
+    target_dir.join("insta").join("pending")
+}
+
+/// Shell commands that run when snapshots are accepted.
+///
+/// The commands run in the workspace root.  The affected files are passed
+/// in the `INSTA_SNAPSHOT_FILES` environment variable, one per line.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Runs before accepted snapshots are written.
+    pub pre_accept: Vec<String>,
+    /// Runs after accepted snapshots were written.
+    pub post_accept: Vec<String>,
+}
+
+/// Configures what happens when snapshots are reviewed.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReviewConfig {
+    /// Stage accepted snapshot files in git.
+    pub stage: bool,
+    pub hooks: HooksConfig,
+}
+
+/// Configures where blobs are stored.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct BlobsConfig {
+    /// The folder blobs are fetched from and uploaded to, relative to the
+    /// workspace root.
+    pub dir: Option<PathBuf>,
+}
+
+/// Configures how snapshots are diffed.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct DiffConfig {
+    /// The diff algorithm: `myers`, `patience` or `lcs`.
+    pub algorithm: Option<DiffAlgorithm>,
+}
+
+/// Configures limits and names of snapshots.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SnapshotsConfig {
+    /// The maximum size of a snapshot in bytes.
+    pub max_size: Option<usize>,
+    /// How snapshot files are laid out in the snapshot folder.
+    pub layout: Option<SnapshotLayout>,
+    /// A template for the names of snapshot files without the extension,
+    /// for instance `{module}.{name}`.  The placeholders are `{crate}`,
+    /// `{module}` (the module path below the crate), `{test}`, `{name}`
+    /// and `{suffix}`.
+    pub filename: Option<String>,
+    /// Joins the modules in the `{module}` placeholder.
+    pub module_separator: String,
+    /// A folder relative to the workspace root to store the snapshots of
+    /// all packages in, with a subfolder per package.
+    pub root: Option<PathBuf>,
+    /// Whether snapshot files carry a checksum to detect edits by hand.
+    pub checksums: ChecksumMode,
+}
+
+/// What happens to snapshot files that were edited by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumMode {
+    /// Snapshot files are written without a checksum.
+    Off,
+    /// Assertions print a warning for edited snapshot files.
+    Warn,
+    /// Assertions fail for edited snapshot files.
+    Fail,
+}
+
+impl Default for ChecksumMode {
+    fn default() -> ChecksumMode {
+        ChecksumMode::Off
+    }
+}
+
+impl Default for SnapshotsConfig {
+    fn default() -> SnapshotsConfig {
+        SnapshotsConfig {
+            max_size: None,
+            layout: None,
+            filename: None,
+            module_separator: "__".into(),
+            root: None,
+            checksums: ChecksumMode::default(),
+        }
+    }
+}
+
+/// Sets the update mode of the tests matching a pattern.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateRule {
+    /// The test path pattern, for instance `generated::**`.  `*` matches
+    /// within a module, `**` across modules.
+    pub tests: String,
+    /// An update mode like in `INSTA_UPDATE`, for instance `always`.
+    pub mode: String,
+}
+
+/// Configures how snapshots of some tests are updated.
+///
+/// The rules take the place of the `auto` and `new` modes (the defaults of
+/// `cargo test` and `cargo insta test`), other modes set in `INSTA_UPDATE`
+/// still apply to all tests.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct UpdateConfig {
+    /// The first rule matching a test applies.
+    pub rules: Vec<UpdateRule>,
+}
+
+impl UpdateConfig {
+    /// Returns the update mode of a test.
+    pub fn mode_for(&self, test_path: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|rule| matches_test_pattern(rule.tests.as_bytes(), test_path.as_bytes()))
+            .map(|rule| rule.mode.as_str())
+    }
+}
+
+/// Matches a test path like `parser::tests::tokens` against a pattern.
+fn matches_test_pattern(pattern: &[u8], path: &[u8]) -> bool {
+    if pattern.starts_with(b"**") {
+        (0..=path.len()).any(|idx| matches_test_pattern(&pattern[2..], &path[idx..]))
+    } else if pattern.first() == Some(&b'*') {
+        for idx in 0..=path.len() {
+            if matches_test_pattern(&pattern[1..], &path[idx..]) {
+                return true;
+            }
+            if path.get(idx) == Some(&b':') {
+                break;
+            }
+        }
+        false
+    } else {
+        match (pattern.first(), path.first()) {
+            (Some(a), Some(b)) if a == b => matches_test_pattern(&pattern[1..], &path[1..]),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Configures `cargo insta test`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct TestConfig {
+    /// The feature combinations `--feature-matrix` runs the tests with.
+    /// Each entry is passed to `--features`, an empty one runs the tests
+    /// with the default features.
+    pub feature_matrix: Vec<String>,
+    /// How to let the user know that pending snapshots need a review once
+    /// the tests finished.
+    pub notify: NotifyMode,
+}
+
+/// How `cargo insta test` announces pending snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyMode {
+    /// Pending snapshots are only printed.
+    Off,
+    /// The terminal bell is rung.
+    Bell,
+    /// A desktop notification is shown.  Where that is not supported the
+    /// terminal bell is rung instead.
+    Desktop,
+}
+
+impl Default for NotifyMode {
+    fn default() -> NotifyMode {
+        NotifyMode::Off
+    }
+}
+
+impl FromStr for NotifyMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<NotifyMode, String> {
+        match value {
+            "off" => Ok(NotifyMode::Off),
+            "bell" => Ok(NotifyMode::Bell),
+            "desktop" => Ok(NotifyMode::Desktop),
+            _ => Err(format!(
+                "unknown notify mode '{}': use off, bell or desktop",
+                value
+            )),
+        }
+    }
+}
+
+/// How source files are formatted after inline snapshots were written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RustfmtMode {
+    /// Source files are left as they are.
+    Off,
+    /// The whole source file is formatted.
+    File,
+    /// Only the lines of the written snapshots are formatted.  This needs
+    /// a nightly rustfmt.
+    Lines,
+}
+
+impl Default for RustfmtMode {
+    fn default() -> RustfmtMode {
+        RustfmtMode::Off
+    }
+}
+
+impl FromStr for RustfmtMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<RustfmtMode, String> {
+        match value {
+            "off" => Ok(RustfmtMode::Off),
+            "file" => Ok(RustfmtMode::File),
+            "lines" => Ok(RustfmtMode::Lines),
+            _ => Err(format!(
+                "unknown rustfmt mode '{}': use off, file or lines",
+                value
+            )),
+        }
+    }
+}
+
+/// Configures how inline snapshots are written.
+///
+/// Accepting an inline snapshot over one of the limits removes the
+/// snapshot from the assertion and writes it to a snapshot file instead.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct InlineConfig {
+    /// The maximum number of lines of an inline snapshot.
+    pub max_lines: Option<usize>,
+    /// The maximum size of an inline snapshot in bytes.
+    pub max_bytes: Option<usize>,
+    /// Runs rustfmt on source files after inline snapshots were written.
+    pub rustfmt: RustfmtMode,
+    /// The number of hashes of raw strings.  By default as few as
+    /// possible are used.
+    pub raw_string_hashes: Option<usize>,
+    /// The length from which contents of more than one word are put on
+    /// lines of their own instead of the line of the assertion.  With `0`
+    /// that's always the case.
+    pub max_single_line: Option<usize>,
+}
+
+impl InlineConfig {
+    /// Checks if contents are too large for an inline snapshot.
+    pub fn is_exceeded_by(&self, contents: &str) -> bool {
+        self.max_lines
+            .map_or(false, |x| contents.lines().count() > x)
+            || self.max_bytes.map_or(false, |x| contents.len() > x)
+    }
+
+    /// Checks if single line contents go on a line of their own.
+    pub fn breaks_line(&self, contents: &str) -> bool {
+        self.max_single_line.map_or(false, |x| {
+            contents.chars().count() >= x.max(1) && contents.trim().contains(char::is_whitespace)
+        })
+    }
+}
+
+/// What happens to snapshots that contain likely credentials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretsCheck {
+    /// Snapshots are not checked.
+    Off,
+    /// A warning is printed.
+    Warn,
+    /// Such snapshots are neither written nor accepted.
+    Deny,
+}
+
+impl Default for SecretsCheck {
+    fn default() -> SecretsCheck {
+        SecretsCheck::Off
+    }
+}
+
+/// Configures the check for credentials in snapshots.
+///
+/// When enabled new snapshots are scanned for AWS access keys, JSON web
+/// tokens and private keys before they are written or accepted.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct SecretsConfig {
+    pub check: SecretsCheck,
+}
+
+/// A single filter of a filter set.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FilterConfig {
+    pub regex: String,
+    pub replacement: String,
+}
+
+/// A named set of filters and redactions.
+///
+/// Tests enable them with `Settings::enable_filter_set`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct FilterSetConfig {
+    /// Filters in the order they are applied.
+    pub filters: Vec<FilterConfig>,
+    /// Redactions from selector to replacement value.
+    pub redactions: BTreeMap<String, String>,
+}
+
+/// The workspace wide configuration of insta.
+///
+/// This is loaded from an `insta.yaml` file in the workspace root.  All
+/// keys are optional.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ToolConfig {
+    pub pending: PendingConfig,
+    pub review: ReviewConfig,
+    pub blobs: BlobsConfig,
+    pub diff: DiffConfig,
+    pub snapshots: SnapshotsConfig,
+    pub inline: InlineConfig,
+    pub secrets: SecretsConfig,
+    pub test: TestConfig,
+    pub update: UpdateConfig,
+    pub filter_sets: BTreeMap<String, FilterSetConfig>,
+}
+
+impl ToolConfig {
+    /// Loads the config of a workspace.
+    ///
+    /// If the workspace does not have a config file the defaults are used.
+    pub fn from_workspace(workspace_dir: &Path) -> Result<ToolConfig, Box<dyn Error>> {
+        let path = workspace_dir.join(CONFIG_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(ToolConfig::default())
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if contents.trim().is_empty() {
+            return Ok(ToolConfig::default());
+        }
+        serde_yaml::from_str(&contents)
+            .map_err(|err| format!("invalid config in {}: {}", path.display(), err).into())
+    }
+}
+
+#[test]
+fn test_load_config() {
+    let config: ToolConfig = serde_yaml::from_str("pending:\n  max_age: 30d\n").unwrap();
+    assert_eq!(config.pending.max_age.as_deref(), Some("30d"));
+    let config: ToolConfig = serde_yaml::from_str("{}").unwrap();
+    assert_eq!(config.pending.max_age, None);
+    assert_eq!(config.pending.location, PendingLocation::Source);
+    let config: ToolConfig = serde_yaml::from_str("pending:\n  location: target\n").unwrap();
+    assert_eq!(config.pending.location, PendingLocation::Target);
+
+    let config: ToolConfig = serde_yaml::from_str(
+        "filter_sets:\n  timestamps:\n    filters:\n      - regex: '\\d{4}'\n        replacement: '[YEAR]'\n",
+    )
+    .unwrap();
+    let set = &config.filter_sets["timestamps"];
+    assert_eq!(set.filters[0].regex, "\\d{4}");
+    assert_eq!(set.filters[0].replacement, "[YEAR]");
+    assert!(set.redactions.is_empty());
+
+    let config: ToolConfig = serde_yaml::from_str(
+        "review:\n  stage: true\n  hooks:\n    post_accept:\n      - prettier --check .\n",
+    )
+    .unwrap();
+    assert!(config.review.stage);
+    assert!(config.review.hooks.pre_accept.is_empty());
+    assert_eq!(config.review.hooks.post_accept, vec!["prettier --check ."]);
+
+    let config: ToolConfig = serde_yaml::from_str("diff:\n  algorithm: myers\n").unwrap();
+    assert_eq!(config.diff.algorithm, Some(DiffAlgorithm::Myers));
+
+    let config: ToolConfig = serde_yaml::from_str("snapshots:\n  max_size: 1024\n").unwrap();
+    assert_eq!(config.snapshots.max_size, Some(1024));
+
+    let config: ToolConfig = serde_yaml::from_str("inline:\n  max_lines: 2\n").unwrap();
+    assert!(!config.inline.is_exceeded_by("a\nb"));
+    assert!(config.inline.is_exceeded_by("a\nb\nc"));
+    assert!(!ToolConfig::default().inline.is_exceeded_by("a\nb\nc"));
+    assert_eq!(config.inline.rustfmt, RustfmtMode::Off);
+
+    let config: ToolConfig = serde_yaml::from_str("inline:\n  rustfmt: lines\n").unwrap();
+    assert_eq!(config.inline.rustfmt, RustfmtMode::Lines);
+    assert_eq!(config.inline.raw_string_hashes, None);
+
+    let config: ToolConfig = serde_yaml::from_str("inline:\n  raw_string_hashes: 1\n").unwrap();
+    assert_eq!(config.inline.raw_string_hashes, Some(1));
+
+    let config: ToolConfig = serde_yaml::from_str("inline:\n  max_single_line: 0\n").unwrap();
+    assert!(config.inline.breaks_line("a b"));
+    assert!(!config.inline.breaks_line("ab"));
+    assert!(!ToolConfig::default().inline.breaks_line("a b"));
+
+    let config: ToolConfig = serde_yaml::from_str("secrets:\n  check: deny\n").unwrap();
+    assert_eq!(config.secrets.check, SecretsCheck::Deny);
+    assert_eq!(ToolConfig::default().secrets.check, SecretsCheck::Off);
+
+    let config: ToolConfig =
+        serde_yaml::from_str("test:\n  feature_matrix:\n    - ''\n    - big-decimal serde\n")
+            .unwrap();
+    assert_eq!(config.test.feature_matrix, vec!["", "big-decimal serde"]);
+
+    let config: ToolConfig = serde_yaml::from_str(
+        "update:\n  rules:\n    - tests: 'generated::**'\n      mode: always\n    \
+         - tests: '*'\n      mode: 'no'\n",
+    )
+    .unwrap();
+    assert_eq!(
+        config.update.mode_for("generated::tests::tokens"),
+        Some("always")
+    );
+    assert_eq!(config.update.mode_for("tokens"), Some("no"));
+    assert_eq!(config.update.mode_for("parser::tokens"), None);
+    assert_eq!(ToolConfig::default().update.mode_for("tokens"), None);
+}
@@ -2,6 +2,8 @@
 use serde::ser::{self, Serialize, Serializer};
 use std::marker::PhantomData;
 
+use crate::floats::{is_integral, round_float};
+
 /// Represents variable typed content.
 ///
 /// This is used for the serialization system to represent values
@@ -97,6 +99,7 @@ pub enum Key<'a> {
 
 impl<'a> Eq for Key<'a> {}
 
+#[allow(clippy::derive_ord_xor_partial_ord)]
 impl<'a> Ord for Key<'a> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.partial_cmp(other).unwrap_or(std::cmp::Ordering::Less)
@@ -181,7 +184,7 @@ impl Content {
     /// Returns the value as bytes
     pub fn as_bytes(&self) -> Option<&[u8]> {
         match self.resolve_inner() {
-            Content::Bytes(ref b) => Some(&*b),
+            Content::Bytes(ref b) => Some(b),
             _ => None,
         }
     }
@@ -219,7 +222,7 @@ impl Content {
             Content::I128(val) => Key::I128(val),
             Content::F32(val) => Key::F64(val.into()),
             Content::F64(val) => Key::F64(val),
-            Content::String(ref val) => Key::Str(&val.as_str()),
+            Content::String(ref val) => Key::Str(val.as_str()),
             Content::Bytes(ref val) => Key::Bytes(&val[..]),
             _ => Key::Other,
         }
@@ -345,6 +348,27 @@ impl Content {
         })
     }
 
+    /// Rounds floats to `precision` decimal places.
+    ///
+    /// Floats holding integers become integers and negative zero becomes
+    /// zero.
+    pub(crate) fn normalize_numbers(&mut self, precision: u32) {
+        self.walk(&mut |content| {
+            let value = match *content {
+                Content::F32(value) => f64::from(value),
+                Content::F64(value) => value,
+                _ => return true,
+            };
+            let value = round_float(value, precision);
+            *content = if is_integral(value) {
+                Content::I64(value as i64)
+            } else {
+                Content::F64(value)
+            };
+            true
+        })
+    }
+
     /// Recursively walks the content structure mutably.
     ///
     /// The callback is invoked for every content in the tree.
@@ -463,7 +487,7 @@ impl Serialize for Content {
             Content::Map(ref entries) => {
                 use serde::ser::SerializeMap;
                 let mut map = serializer.serialize_map(Some(entries.len()))?;
-                for &(ref k, ref v) in entries {
+                for (k, v) in entries {
                     map.serialize_entry(k, v)?;
                 }
                 map.end()
@@ -581,9 +605,9 @@ where
         Ok(Content::None)
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Content, E>
+    fn serialize_some<T>(self, value: &T) -> Result<Content, E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         Ok(Content::Some(Box::new(value.serialize(self)?)))
     }
@@ -605,13 +629,9 @@ where
         Ok(Content::UnitVariant(name, variant_index, variant))
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
-        self,
-        name: &'static str,
-        value: &T,
-    ) -> Result<Content, E>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Content, E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         Ok(Content::NewtypeStruct(
             name,
@@ -619,7 +639,7 @@ where
         ))
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         name: &'static str,
         variant_index: u32,
@@ -627,7 +647,7 @@ where
         value: &T,
     ) -> Result<Content, E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         Ok(Content::NewtypeVariant(
             name,
@@ -724,9 +744,9 @@ where
     type Ok = Content;
     type Error = E;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), E>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let value = value.serialize(ContentSerializer::<E>::new())?;
         self.elements.push(value);
@@ -750,9 +770,9 @@ where
     type Ok = Content;
     type Error = E;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), E>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let value = value.serialize(ContentSerializer::<E>::new())?;
         self.elements.push(value);
@@ -777,9 +797,9 @@ where
     type Ok = Content;
     type Error = E;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), E>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let value = value.serialize(ContentSerializer::<E>::new())?;
         self.fields.push(value);
@@ -806,9 +826,9 @@ where
     type Ok = Content;
     type Error = E;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), E>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let value = value.serialize(ContentSerializer::<E>::new())?;
         self.fields.push(value);
@@ -838,18 +858,18 @@ where
     type Ok = Content;
     type Error = E;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), E>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let key = key.serialize(ContentSerializer::<E>::new())?;
         self.key = Some(key);
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), E>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let key = self
             .key
@@ -864,10 +884,10 @@ where
         Ok(Content::Map(self.entries))
     }
 
-    fn serialize_entry<K: ?Sized, V: ?Sized>(&mut self, key: &K, value: &V) -> Result<(), E>
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), E>
     where
-        K: Serialize,
-        V: Serialize,
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
     {
         let key = key.serialize(ContentSerializer::<E>::new())?;
         let value = value.serialize(ContentSerializer::<E>::new())?;
@@ -889,9 +909,9 @@ where
     type Ok = Content;
     type Error = E;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), E>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let value = value.serialize(ContentSerializer::<E>::new())?;
         self.fields.push((key, value));
@@ -918,9 +938,9 @@ where
     type Ok = Content;
     type Error = E;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), E>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), E>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let value = value.serialize(ContentSerializer::<E>::new())?;
         self.fields.push((key, value));
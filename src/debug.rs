@@ -0,0 +1,530 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::content::Content;
+use crate::redaction::{Redaction, Selector};
+use crate::settings::Settings;
+
+/// The name of the tuple struct sets are parsed into.
+const SET_MARKER: &str = "{set}";
+
+/// The name of the tuple struct words that are not understood are kept in.
+///
+/// This covers the output of hand written `Debug` implementations such as
+/// `1.5s` for a `Duration` or `127.0.0.1:8080` for a `SocketAddr`.
+const RAW_MARKER: &str = "{raw}";
+
+lazy_static! {
+    static ref NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+}
+
+/// Interns a type or field name so it can be stored in [`Content`].
+fn intern(name: &str) -> &'static str {
+    let mut names = NAMES.lock().unwrap_or_else(|x| x.into_inner());
+    if let Some(&rv) = names.get(name) {
+        return rv;
+    }
+    let rv: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.insert(rv);
+    rv
+}
+
+/// Parses the output of `{:#?}` back into content.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn error(&self, msg: &str) -> String {
+        format!("{} at offset {}", msg, self.pos)
+    }
+
+    /// Parses comma separated items up to the closing delimiter.
+    fn parse_items<T, F>(&mut self, close: char, mut parse_item: F) -> Result<Vec<T>, String>
+    where
+        F: FnMut(&mut Self) -> Result<T, String>,
+    {
+        let mut rv = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(rv);
+            }
+            rv.push(parse_item(self)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(c) if c == close => {}
+                _ => return Err(self.error(&format!("expected ',' or '{}'", close))),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Content, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => Ok(Content::String(self.parse_quoted('"')?)),
+            Some('\'') => {
+                let value = self.parse_quoted('\'')?;
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(Content::Char(c)),
+                    _ => Err(self.error("invalid char literal")),
+                }
+            }
+            Some('[') => {
+                let start = self.pos;
+                self.pos += 1;
+                let items = self.parse_items(']', Self::parse_value)?;
+                // ipv6 socket addresses look like `[::1]:8080`
+                if self.rest().starts_with(':') && !self.at_field_separator() {
+                    self.parse_word();
+                    return Ok(raw(&self.input[start..self.pos]));
+                }
+                Ok(Content::Seq(items))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let items = self.parse_items(')', Self::parse_value)?;
+                Ok(if items.is_empty() {
+                    Content::Unit
+                } else {
+                    Content::Tuple(items)
+                })
+            }
+            Some('{') => {
+                self.pos += 1;
+                self.parse_map_or_set()
+            }
+            Some(_) => self.parse_word_value(),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_map_or_set(&mut self) -> Result<Content, String> {
+        let mut map = vec![];
+        let mut set = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                break;
+            }
+            let value = self.parse_value()?;
+            self.skip_whitespace();
+            if self.peek() == Some(':') && set.is_empty() {
+                self.pos += 1;
+                map.push((value, self.parse_value()?));
+            } else if map.is_empty() {
+                set.push(value);
+            } else {
+                return Err(self.error("expected ':'"));
+            }
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {}
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+        Ok(if set.is_empty() {
+            Content::Map(map)
+        } else {
+            Content::TupleStruct(SET_MARKER, set)
+        })
+    }
+
+    /// Checks if a colon at the current position separates a field name or
+    /// map key from its value.  Colons within words like `::1` do not.
+    fn at_field_separator(&self) -> bool {
+        let mut chars = self.rest().chars();
+        chars.next() == Some(':') && chars.next().map_or(true, char::is_whitespace)
+    }
+
+    fn parse_word(&mut self) -> &'a str {
+        let start = self.pos;
+        loop {
+            let rest = self.rest();
+            let len = rest
+                .find(|c: char| c.is_whitespace() || ",:()[]{}\"'".contains(c))
+                .unwrap_or(rest.len());
+            self.pos += len;
+            if !self.rest().starts_with(':') || self.at_field_separator() {
+                break;
+            }
+            self.pos += 1;
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn parse_word_value(&mut self) -> Result<Content, String> {
+        let word = self.parse_word();
+        if word.is_empty() {
+            return Err(self.error("unexpected character"));
+        }
+
+        // structs and tuple structs have their fields on the same line
+        let rest = self.rest();
+        let after_spaces = rest.trim_start_matches(' ');
+        if after_spaces.starts_with('{') {
+            self.pos += rest.len() - after_spaces.len() + 1;
+            let fields = self.parse_items('}', |parser| {
+                parser.skip_whitespace();
+                let name = parser.parse_word();
+                if name.is_empty() {
+                    return Err(parser.error("expected field name"));
+                }
+                parser.expect(':')?;
+                Ok((intern(name), parser.parse_value()?))
+            })?;
+            return Ok(Content::Struct(intern(word), fields));
+        } else if rest.starts_with('(') {
+            self.pos += 1;
+            let mut items = self.parse_items(')', Self::parse_value)?;
+            return Ok(if word == "Some" && items.len() == 1 {
+                Content::Some(Box::new(items.remove(0)))
+            } else {
+                Content::TupleStruct(intern(word), items)
+            });
+        }
+
+        Ok(match word {
+            "true" => Content::Bool(true),
+            "false" => Content::Bool(false),
+            "None" => Content::None,
+            _ => {
+                if let Ok(value) = word.parse::<i64>() {
+                    Content::I64(value)
+                } else if let Ok(value) = word.parse::<u64>() {
+                    Content::U64(value)
+                } else if let Ok(value) = word.parse::<i128>() {
+                    Content::I128(value)
+                } else if let Ok(value) = word.parse::<u128>() {
+                    Content::U128(value)
+                } else if word.starts_with(|c: char| c == '-' || c.is_ascii_digit())
+                    || word == "inf"
+                    || word == "NaN"
+                {
+                    match word.parse() {
+                        Ok(value) => Content::F64(value),
+                        Err(_) => raw(word),
+                    }
+                } else if word.chars().all(|c| c == '_' || c.is_alphanumeric()) {
+                    Content::UnitStruct(intern(word))
+                } else {
+                    raw(word)
+                }
+            }
+        })
+    }
+
+    /// Parses a string or char literal as escaped by `Debug`.
+    fn parse_quoted(&mut self, quote: char) -> Result<String, String> {
+        self.pos += 1;
+        let mut rv = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.pos += idx + 1;
+                    return Ok(rv);
+                }
+                '\\' => match chars.next().map(|x| x.1) {
+                    Some('n') => rv.push('\n'),
+                    Some('r') => rv.push('\r'),
+                    Some('t') => rv.push('\t'),
+                    Some('0') => rv.push('\0'),
+                    Some('u') => {
+                        let mut hex = String::new();
+                        for (_, c) in chars.by_ref() {
+                            match c {
+                                '{' => {}
+                                '}' => break,
+                                c => hex.push(c),
+                            }
+                        }
+                        rv.push(
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(std::char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?,
+                        );
+                    }
+                    Some(c) => rv.push(c),
+                    None => break,
+                },
+                c => rv.push(c),
+            }
+        }
+        Err(self.error("unterminated literal"))
+    }
+}
+
+/// Keeps a word that is not understood verbatim.
+fn raw(word: &str) -> Content {
+    Content::TupleStruct(RAW_MARKER, vec![Content::String(word.into())])
+}
+
+/// Parses the output of `{:#?}` into content.
+///
+/// Structs, tuple structs, sequences, maps, sets, tuples, strings, chars,
+/// numbers and booleans are understood.  Anything else that forms a single
+/// word is kept as is.
+pub(crate) fn parse_debug(input: &str) -> Result<Content, String> {
+    let mut parser = Parser { input, pos: 0 };
+    let rv = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos != input.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(rv)
+}
+
+fn render_items<'a, I>(out: &mut String, open: &str, close: &str, items: I, indent: usize)
+where
+    I: ExactSizeIterator<Item = (Option<String>, &'a Content)>,
+{
+    out.push_str(open);
+    if items.len() == 0 {
+        out.push_str(close);
+        return;
+    }
+    out.push('\n');
+    for (prefix, value) in items {
+        out.push_str(&" ".repeat(indent + 4));
+        if let Some(prefix) = prefix {
+            out.push_str(&prefix);
+            out.push_str(": ");
+        }
+        render(out, value, indent + 4);
+        out.push_str(",\n");
+    }
+    out.push_str(&" ".repeat(indent));
+    out.push_str(close);
+}
+
+fn unnamed(items: &[Content]) -> impl ExactSizeIterator<Item = (Option<String>, &Content)> {
+    items.iter().map(|x| (None, x))
+}
+
+fn render(out: &mut String, content: &Content, indent: usize) {
+    macro_rules! debug {
+        ($value:expr) => {
+            write!(out, "{:?}", $value).unwrap()
+        };
+    }
+    match *content {
+        Content::Bool(value) => debug!(value),
+        Content::U8(value) => debug!(value),
+        Content::U16(value) => debug!(value),
+        Content::U32(value) => debug!(value),
+        Content::U64(value) => debug!(value),
+        Content::U128(value) => debug!(value),
+        Content::I8(value) => debug!(value),
+        Content::I16(value) => debug!(value),
+        Content::I32(value) => debug!(value),
+        Content::I64(value) => debug!(value),
+        Content::I128(value) => debug!(value),
+        Content::F32(value) => debug!(value),
+        Content::F64(value) => debug!(value),
+        Content::Char(value) => debug!(value),
+        Content::String(ref value) => debug!(value),
+        Content::Bytes(ref value) => {
+            let items: Vec<_> = value.iter().map(|&x| Content::U8(x)).collect();
+            render_items(out, "[", "]", unnamed(&items), indent);
+        }
+        Content::None => out.push_str("None"),
+        Content::Some(ref inner) => render_items(
+            out,
+            "Some(",
+            ")",
+            unnamed(std::slice::from_ref(&**inner)),
+            indent,
+        ),
+        Content::Unit => out.push_str("()"),
+        Content::UnitStruct(name) | Content::UnitVariant(_, _, name) => out.push_str(name),
+        Content::NewtypeStruct(name, ref inner)
+        | Content::NewtypeVariant(_, _, name, ref inner) => render_items(
+            out,
+            &format!("{}(", name),
+            ")",
+            unnamed(std::slice::from_ref(&**inner)),
+            indent,
+        ),
+        Content::Seq(ref items) => render_items(out, "[", "]", unnamed(items), indent),
+        Content::Tuple(ref items) => render_items(out, "(", ")", unnamed(items), indent),
+        Content::TupleStruct(SET_MARKER, ref items) => {
+            render_items(out, "{", "}", unnamed(items), indent)
+        }
+        Content::TupleStruct(RAW_MARKER, ref items) => match items.as_slice() {
+            [Content::String(ref word)] => out.push_str(word),
+            _ => render_items(out, "(", ")", unnamed(items), indent),
+        },
+        Content::TupleStruct(name, ref items) | Content::TupleVariant(_, _, name, ref items) => {
+            render_items(out, &format!("{}(", name), ")", unnamed(items), indent)
+        }
+        Content::Map(ref items) => {
+            let items = items.iter().map(|(key, value)| {
+                let mut rendered_key = String::new();
+                render(&mut rendered_key, key, indent + 4);
+                (Some(rendered_key), value)
+            });
+            render_items(out, "{", "}", items.collect::<Vec<_>>().into_iter(), indent);
+        }
+        Content::Struct(name, ref fields) | Content::StructVariant(_, _, name, ref fields) => {
+            if fields.is_empty() {
+                out.push_str(name);
+            } else {
+                let fields = fields
+                    .iter()
+                    .map(|&(key, ref value)| (Some(key.into()), value));
+                render_items(out, &format!("{} {{", name), "}", fields, indent);
+            }
+        }
+    }
+}
+
+/// Renders content in the format of `{:#?}`.
+pub(crate) fn render_debug(content: &Content) -> String {
+    let mut rv = String::new();
+    render(&mut rv, content, 0);
+    rv
+}
+
+/// Applies redactions to the output of `{:#?}`.
+///
+/// The redactions of the settings are applied as well.  Panics if the
+/// output cannot be parsed.
+pub fn redact_debug_output(output: &str, redactions: &[(Selector, Redaction)]) -> String {
+    let mut content = parse_debug(output)
+        .unwrap_or_else(|err| panic!("cannot apply redactions to debug output: {}", err));
+    for (selector, redaction) in redactions {
+        content = selector.redact(content, redaction);
+    }
+    content = Settings::with(|settings| {
+        for (selector, redaction) in settings.iter_redactions() {
+            content = selector.redact(content, redaction);
+        }
+        content
+    });
+    render_debug(&content)
+}
+
+#[test]
+fn test_debug_roundtrip() {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum Kind {
+        Unit,
+        Tuple(u8, &'static str),
+        Struct { flag: bool },
+    }
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Wrapper(f64);
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Value {
+        name: String,
+        escaped: &'static str,
+        chars: Vec<char>,
+        kinds: Vec<Kind>,
+        map: BTreeMap<&'static str, Option<i64>>,
+        set: BTreeSet<u32>,
+        tuple: (u8, Wrapper, ()),
+        empty: Vec<u8>,
+        big: u128,
+        float: f32,
+    }
+
+    let mut map = BTreeMap::new();
+    map.insert("a", Some(-1));
+    map.insert("b", None);
+    let value = Value {
+        name: "insta".into(),
+        escaped: "line\n\"quoted\" \\ \u{1b}",
+        chars: vec!['a', '\'', '\n'],
+        kinds: vec![Kind::Unit, Kind::Tuple(1, "x"), Kind::Struct { flag: true }],
+        map,
+        set: vec![1, 2].into_iter().collect(),
+        tuple: (1, Wrapper(1.5e-9), ()),
+        empty: vec![],
+        big: u128::max_value(),
+        float: 0.1,
+    };
+    let output = format!("{:#?}", value);
+    assert_eq!(render_debug(&parse_debug(&output).unwrap()), output);
+}
+
+#[test]
+fn test_debug_roundtrip_std_types() {
+    use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Value {
+        timeout: Duration,
+        short: Duration,
+        ip: IpAddr,
+        ipv6: Ipv6Addr,
+        addr: SocketAddr,
+        addr6: SocketAddr,
+        path: std::marker::PhantomData<u8>,
+    }
+
+    let value = Value {
+        timeout: Duration::from_millis(1500),
+        short: Duration::from_micros(3),
+        ip: "127.0.0.1".parse().unwrap(),
+        ipv6: "::1".parse().unwrap(),
+        addr: "127.0.0.1:8080".parse().unwrap(),
+        addr6: "[fe80::1]:443".parse().unwrap(),
+        path: std::marker::PhantomData,
+    };
+    let output = format!("{:#?}", value);
+    assert_eq!(render_debug(&parse_debug(&output).unwrap()), output);
+
+    let redacted = redact_debug_output(
+        &output,
+        &[(
+            crate::redaction::Selector::parse(".timeout").unwrap(),
+            Redaction::from("[duration]"),
+        )],
+    );
+    assert!(redacted.contains("timeout: \"[duration]\",\n"));
+    assert!(redacted.contains("ip: 127.0.0.1,\n"));
+    assert!(redacted.contains("addr6: [fe80::1]:443,\n"));
+}
@@ -0,0 +1,235 @@
+use std::fs;
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobMatcher};
+use walkdir::WalkDir;
+
+use crate::utils::fnv1a;
+
+/// Controls how a file in a directory snapshot is recorded.
+///
+/// This is used with [`assert_dir_snapshot!`](crate::assert_dir_snapshot) to
+/// map glob patterns to the files they should affect.  Strings convert into
+/// [`FileRedaction::Replace`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileRedaction {
+    /// Leaves the file out of the snapshot entirely.
+    Exclude,
+    /// Records the file but replaces its contents with the given value.
+    Replace(String),
+}
+
+impl<'a> From<&'a str> for FileRedaction {
+    fn from(value: &'a str) -> FileRedaction {
+        FileRedaction::Replace(value.to_string())
+    }
+}
+
+impl From<String> for FileRedaction {
+    fn from(value: String) -> FileRedaction {
+        FileRedaction::Replace(value)
+    }
+}
+
+fn section_header(path: &str) -> String {
+    format!("=== {} ===", path)
+}
+
+fn compile_glob(pattern: &str) -> GlobMatcher {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .unwrap_or_else(|err| panic!("invalid glob '{}': {}", pattern, err))
+        .compile_matcher()
+}
+
+/// Renders a directory into a text snapshot.
+///
+/// The snapshot starts with the sorted list of all files relative to `base`
+/// (always using `/` as separator) followed by one section per file with
+/// its contents.  Files that are not valid UTF-8 are recorded by size and
+/// hash.  The first matching rule decides if a file is excluded or if its
+/// contents are replaced.
+pub fn dir_snapshot(base: &Path, rules: Vec<(&str, FileRedaction)>) -> String {
+    let rules: Vec<_> = rules
+        .into_iter()
+        .map(|(pattern, redaction)| (compile_glob(pattern), redaction))
+        .collect();
+
+    let mut files = vec![];
+    for entry in WalkDir::new(base)
+        .follow_links(true)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+    {
+        let entry = entry.unwrap_or_else(|err| panic!("failed to walk directory: {}", err));
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative_path = entry.path().strip_prefix(base).unwrap_or(entry.path());
+        let name = relative_path
+            .components()
+            .map(|x| x.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let redaction = rules
+            .iter()
+            .find(|(glob, _)| glob.is_match(&name))
+            .map(|(_, redaction)| redaction);
+        let contents = match redaction {
+            Some(FileRedaction::Exclude) => continue,
+            Some(FileRedaction::Replace(value)) => value.clone(),
+            None => {
+                let bytes = fs::read(entry.path())
+                    .unwrap_or_else(|err| panic!("failed to read {}: {}", name, err));
+                match String::from_utf8(bytes) {
+                    Ok(text) if !text.contains('\0') => text,
+                    Ok(text) => binary_summary(text.as_bytes()),
+                    Err(err) => binary_summary(err.as_bytes()),
+                }
+            }
+        };
+        files.push((name, contents));
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut rv = String::new();
+    for (name, _) in &files {
+        rv.push_str(name);
+        rv.push('\n');
+    }
+    for (name, contents) in &files {
+        rv.push('\n');
+        rv.push_str(&section_header(name));
+        rv.push('\n');
+        if !contents.is_empty() {
+            rv.push_str(contents);
+            if !contents.ends_with('\n') {
+                rv.push('\n');
+            }
+        }
+    }
+    rv
+}
+
+fn binary_summary(bytes: &[u8]) -> String {
+    format!(
+        "[binary {} bytes, fnv1a {:016x}]",
+        bytes.len(),
+        fnv1a(bytes)
+    )
+}
+
+/// Splits a rendered directory snapshot into its file sections.
+///
+/// Returns `None` if the text is not a directory snapshot.
+fn parse_dir_snapshot(text: &str) -> Option<Vec<(&str, String)>> {
+    let mut lines = text.lines().peekable();
+    let mut files = vec![];
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        files.push(line);
+    }
+    if files.is_empty() {
+        return None;
+    }
+
+    let mut sections = vec![];
+    for (idx, file) in files.iter().enumerate() {
+        if lines.next()? != section_header(file) {
+            return None;
+        }
+        let next_header = files.get(idx + 1).map(|x| section_header(x));
+        let mut contents = vec![];
+        while let Some(line) = lines.peek() {
+            if next_header.as_deref() == Some(*line) {
+                break;
+            }
+            contents.push(lines.next().unwrap());
+        }
+        // the blank line separating sections is not part of the contents
+        if next_header.is_some() && contents.last() == Some(&"") {
+            contents.pop();
+        }
+        sections.push((*file, contents.join("\n")));
+    }
+    Some(sections)
+}
+
+/// The change to a single file between two directory snapshots.
+pub(crate) struct FileChange {
+    pub path: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Diffs two directory snapshots file by file.
+///
+/// Returns `None` if either of the two is not a directory snapshot, in
+/// which case the snapshots should be diffed as a whole.
+pub(crate) fn diff_dir_snapshots(old: &str, new: &str) -> Option<Vec<FileChange>> {
+    let old_sections = parse_dir_snapshot(old)?;
+    let new_sections = parse_dir_snapshot(new)?;
+    let mut paths: Vec<&str> = old_sections
+        .iter()
+        .chain(new_sections.iter())
+        .map(|(path, _)| *path)
+        .collect();
+    paths.sort_unstable();
+    paths.dedup();
+
+    let find = |sections: &[(&str, String)], path: &str| {
+        sections
+            .iter()
+            .find(|(name, _)| *name == path)
+            .map(|(_, contents)| contents.clone())
+    };
+    Some(
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let old = find(&old_sections, path);
+                let new = find(&new_sections, path);
+                if old == new {
+                    None
+                } else {
+                    Some(FileChange {
+                        path: path.to_string(),
+                        old,
+                        new,
+                    })
+                }
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn test_parse_dir_snapshot() {
+    let text = "a.txt\nb/c.txt\nempty\n\n=== a.txt ===\nhello\n\nworld\n\n=== b/c.txt ===\n=== c ===\n\n=== empty ===\n";
+    let sections = parse_dir_snapshot(text).unwrap();
+    assert_eq!(
+        sections,
+        vec![
+            ("a.txt", "hello\n\nworld".to_string()),
+            ("b/c.txt", "=== c ===".to_string()),
+            ("empty", "".to_string()),
+        ]
+    );
+    assert!(parse_dir_snapshot("just some text\n").is_none());
+}
+
+#[test]
+fn test_diff_dir_snapshots() {
+    let old = "a.txt\nb.txt\n\n=== a.txt ===\n1\n\n=== b.txt ===\n2\n";
+    let new = "a.txt\nc.txt\n\n=== a.txt ===\n1\n\n=== c.txt ===\n3\n";
+    let changes = diff_dir_snapshots(old, new).unwrap();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].path, "b.txt");
+    assert_eq!(changes[0].old.as_deref(), Some("2"));
+    assert_eq!(changes[0].new, None);
+    assert_eq!(changes[1].path, "c.txt");
+    assert_eq!(changes[1].old, None);
+    assert_eq!(changes[1].new.as_deref(), Some("3"));
+}
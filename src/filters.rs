@@ -0,0 +1,165 @@
+use std::borrow::Cow;
+use std::env;
+use std::path::MAIN_SEPARATOR;
+
+use regex::Regex;
+
+/// Represents stored filters.
+///
+/// Filters are regular expressions that are replaced in the snapshot
+/// contents before they are compared.  They apply to all snapshots.
+#[derive(Debug, Default, Clone)]
+pub struct Filters {
+    rules: Vec<(Regex, String)>,
+}
+
+impl<'a, I> From<I> for Filters
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    fn from(value: I) -> Filters {
+        let mut rv = Filters::default();
+        for (regex, replacement) in value {
+            rv.add(regex, replacement);
+        }
+        rv
+    }
+}
+
+impl Filters {
+    /// Applies all filters in order.
+    pub(crate) fn apply_to<'s>(&self, s: &'s str) -> Cow<'s, str> {
+        let mut rv = Cow::Borrowed(s);
+        for (regex, replacement) in &self.rules {
+            if let Cow::Owned(value) = regex.replace_all(&rv, replacement.as_str()) {
+                rv = Cow::Owned(value);
+            }
+        }
+        rv
+    }
+
+    /// Adds a filter.  Panics if the regular expression is invalid.
+    pub(crate) fn add<S: Into<String>>(&mut self, regex: &str, replacement: S) {
+        self.rules.push((
+            Regex::new(regex).expect("invalid regex for snapshot filter"),
+            replacement.into(),
+        ));
+    }
+
+    /// Removes all filters.
+    pub(crate) fn clear(&mut self) {
+        self.rules.clear();
+    }
+}
+
+/// Filters for values that commonly change between test runs.
+///
+/// These can be enabled with
+/// [`Settings::add_standard_filters`](crate::Settings::add_standard_filters)
+/// instead of writing the regular expressions by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandardFilter {
+    /// Replaces UUIDs with `[UUID]`.
+    Uuid,
+    /// Replaces ISO 8601 timestamps (`2021-05-01T12:30:00Z`) with
+    /// `[TIMESTAMP]`.
+    Timestamp,
+    /// Replaces durations as formatted by `Debug` for
+    /// `std::time::Duration` (`1.5s`, `250ms`, `12µs`) with `[DURATION]`.
+    Duration,
+    /// Replaces hexadecimal addresses (`0x7ffd5fbff8a0`) with `[ADDRESS]`.
+    HexAddress,
+    /// Replaces the temporary directory of the system with `[TEMP_DIR]`.
+    TempDir,
+    /// Replaces the home directory of the current user with `[HOME_DIR]`.
+    HomeDir,
+    /// Replaces the ports of local addresses (`localhost:54321`) with
+    /// `[PORT]`.
+    Port,
+    /// Replaces backslash path separators with forward slashes so Windows
+    /// paths match Unix paths.
+    PathSeparator,
+}
+
+impl StandardFilter {
+    /// Returns the regular expression and replacement of the filter.
+    pub(crate) fn rule(self) -> (String, &'static str) {
+        match self {
+            StandardFilter::Uuid => (
+                r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b"
+                    .into(),
+                "[UUID]",
+            ),
+            StandardFilter::Timestamp => (
+                r"\b\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?"
+                    .into(),
+                "[TIMESTAMP]",
+            ),
+            StandardFilter::Duration => (
+                r"\b\d+(?:\.\d+)?(?:ns|µs|us|ms|s|m|h)\b".into(),
+                "[DURATION]",
+            ),
+            StandardFilter::HexAddress => (r"\b0x[0-9a-fA-F]{4,}\b".into(), "[ADDRESS]"),
+            StandardFilter::TempDir => {
+                let temp_dir = env::temp_dir();
+                let temp_dir = temp_dir.to_string_lossy();
+                (
+                    regex::escape(temp_dir.trim_end_matches(MAIN_SEPARATOR)),
+                    "[TEMP_DIR]",
+                )
+            }
+            StandardFilter::HomeDir => {
+                match env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+                    Some(home_dir) => {
+                        let home_dir = home_dir.to_string_lossy();
+                        (
+                            regex::escape(home_dir.trim_end_matches(MAIN_SEPARATOR)),
+                            "[HOME_DIR]",
+                        )
+                    }
+                    // without a home directory there is nothing to match
+                    None => (r"[^\s\S]".into(), "[HOME_DIR]"),
+                }
+            }
+            StandardFilter::Port => (
+                r"(\blocalhost|\b127\.0\.0\.1|\b0\.0\.0\.0|\[::1\]):\d{1,5}\b".into(),
+                "$1:[PORT]",
+            ),
+            StandardFilter::PathSeparator => (r"\\\\?([\w.])".into(), "/$1"),
+        }
+    }
+}
+
+#[test]
+fn test_filters() {
+    let filters = Filters::from(vec![("[0-9]+", "N"), ("N-N", "range")]);
+    assert_eq!(filters.apply_to("1-2 and 33"), "range and N");
+    if let Cow::Owned(_) = filters.apply_to("none") {
+        panic!("unchanged contents were copied");
+    }
+}
+
+#[test]
+fn test_standard_filters() {
+    let mut filters = Filters::default();
+    for filter in &[
+        StandardFilter::Uuid,
+        StandardFilter::Timestamp,
+        StandardFilter::Duration,
+        StandardFilter::HexAddress,
+        StandardFilter::Port,
+        StandardFilter::PathSeparator,
+    ] {
+        let (regex, replacement) = filter.rule();
+        filters.add(&regex, replacement);
+    }
+    assert_eq!(
+        filters.apply_to(
+            "id=6f1c1b8e-0c3b-4a52-9d6f-58f44c2b1d7e at 2021-05-01T12:30:00.123+02:00 \
+             took 1.5s/250ms/12µs ptr=0x7ffd5fbff8a0 path=C:\\Users\\insta\\file.txt \
+             url=http://localhost:54321/ [::1]:8080"
+        ),
+        "id=[UUID] at [TIMESTAMP] took [DURATION]/[DURATION]/[DURATION] \
+         ptr=[ADDRESS] path=C:/Users/insta/file.txt url=http://localhost:[PORT]/ [::1]:[PORT]"
+    );
+}
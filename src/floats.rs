@@ -0,0 +1,164 @@
+/// Splits text into the text between numbers and the numbers.
+fn split_numbers(s: &str) -> (Vec<&str>, Vec<&str>) {
+    let bytes = s.as_bytes();
+    let mut texts = vec![];
+    let mut numbers = vec![];
+    let mut text_start = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let starts_number = bytes[idx].is_ascii_digit()
+            || (bytes[idx] == b'-' && bytes.get(idx + 1).map_or(false, u8::is_ascii_digit));
+        let in_word = idx > 0 && (bytes[idx - 1].is_ascii_alphanumeric() || bytes[idx - 1] == b'_');
+        if !starts_number || in_word {
+            idx += 1;
+            continue;
+        }
+
+        let start = idx;
+        if bytes[idx] == b'-' {
+            idx += 1;
+        }
+        let skip_digits = |mut idx: usize| {
+            while idx < bytes.len() && bytes[idx].is_ascii_digit() {
+                idx += 1;
+            }
+            idx
+        };
+        idx = skip_digits(idx);
+        if idx + 1 < bytes.len() && bytes[idx] == b'.' && bytes[idx + 1].is_ascii_digit() {
+            idx = skip_digits(idx + 1);
+        }
+        if idx < bytes.len() && (bytes[idx] == b'e' || bytes[idx] == b'E') {
+            let mut exp = idx + 1;
+            if exp < bytes.len() && (bytes[exp] == b'-' || bytes[exp] == b'+') {
+                exp += 1;
+            }
+            if exp < bytes.len() && bytes[exp].is_ascii_digit() {
+                idx = skip_digits(exp);
+            }
+        }
+        texts.push(&s[text_start..start]);
+        numbers.push(&s[start..idx]);
+        text_start = idx;
+    }
+    texts.push(&s[text_start..]);
+    (texts, numbers)
+}
+
+/// Compares two texts allowing numbers to differ within a tolerance.
+///
+/// Two numbers are considered equal if they differ by no more than
+/// `absolute` or by no more than `relative` times the larger magnitude.
+/// Everything besides the numbers has to match exactly.
+pub(crate) fn matches_with_tolerance(old: &str, new: &str, absolute: f64, relative: f64) -> bool {
+    let (old_texts, old_numbers) = split_numbers(old);
+    let (new_texts, new_numbers) = split_numbers(new);
+    old_texts == new_texts
+        && old_numbers
+            .iter()
+            .zip(new_numbers.iter())
+            .all(|(old, new)| {
+                if old == new {
+                    return true;
+                }
+                match (old.parse::<f64>(), new.parse::<f64>()) {
+                    (Ok(old), Ok(new)) => numbers_match(old, new, absolute, relative),
+                    _ => false,
+                }
+            })
+}
+
+/// Checks if two numbers differ by no more than the tolerances.
+pub(crate) fn numbers_match(old: f64, new: f64, absolute: f64, relative: f64) -> bool {
+    let diff = (old - new).abs();
+    diff <= absolute || diff <= relative * old.abs().max(new.abs())
+}
+
+/// Rounds a float to `precision` decimal places.
+///
+/// Negative zero becomes zero.
+pub(crate) fn round_float(value: f64, precision: u32) -> f64 {
+    let factor = 10f64.powi(precision as i32);
+    let rv = (value * factor).round() / factor;
+    if !rv.is_finite() {
+        value
+    } else if rv == 0.0 {
+        0.0
+    } else {
+        rv
+    }
+}
+
+/// Checks if a float holds an integer that converts to `i64` exactly.
+pub(crate) fn is_integral(value: f64) -> bool {
+    value.fract() == 0.0 && value.abs() < 9_007_199_254_740_992.0
+}
+
+/// Rewrites the floats in a text rounded to `precision` decimal places.
+///
+/// Floats holding integers are written as integers and negative zero as
+/// zero.  Integers in the text are kept as they are.
+pub(crate) fn normalize_numbers(text: &str, precision: u32) -> String {
+    let (texts, numbers) = split_numbers(text);
+    let mut rv = String::with_capacity(text.len());
+    for (idx, text) in texts.iter().enumerate() {
+        rv.push_str(text);
+        let number = match numbers.get(idx) {
+            Some(number) => *number,
+            None => break,
+        };
+        let is_float = number.contains(|c| c == '.' || c == 'e' || c == 'E') || number == "-0";
+        match number.parse::<f64>() {
+            Ok(value) if is_float => {
+                let value = round_float(value, precision);
+                if is_integral(value) {
+                    rv.push_str(&(value as i64).to_string());
+                } else {
+                    rv.push_str(&value.to_string());
+                }
+            }
+            _ => rv.push_str(number),
+        }
+    }
+    rv
+}
+
+#[test]
+fn test_split_numbers() {
+    assert_eq!(
+        split_numbers("x1: [-1.5, 2e-3, 3.]"),
+        (vec!["x1: [", ", ", ", ", ".]"], vec!["-1.5", "2e-3", "3"])
+    );
+}
+
+#[test]
+fn test_matches_with_tolerance() {
+    assert!(matches_with_tolerance(
+        "value: 0.30000000000000004",
+        "value: 0.3",
+        1e-9,
+        0.0
+    ));
+    assert!(!matches_with_tolerance(
+        "value: 0.31",
+        "value: 0.3",
+        1e-9,
+        0.0
+    ));
+    assert!(matches_with_tolerance("1000001.0", "1000000.0", 0.0, 1e-6));
+    assert!(!matches_with_tolerance("a: 1.0", "b: 1.0", 1.0, 1.0));
+    assert!(!matches_with_tolerance("1.0, 2.0", "1.0", 1.0, 1.0));
+}
+
+#[test]
+fn test_normalize_numbers() {
+    assert_eq!(
+        normalize_numbers("a: 0.30000000000000004, b: -0.0, c: 2.0, d: 1e3", 6),
+        "a: 0.3, b: 0, c: 2, d: 1000"
+    );
+    assert_eq!(
+        normalize_numbers("x1: 1.23456, y: 007", 2),
+        "x1: 1.23, y: 007"
+    );
+    assert_eq!(normalize_numbers("-0.0001", 2), "0");
+}
@@ -0,0 +1,380 @@
+use crate::settings::Settings;
+
+/// Elements that have no contents and no end tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose contents are kept as they are.
+const RAW_ELEMENTS: &[&str] = &["pre", "script", "style", "textarea"];
+
+/// Elements that are closed by a following sibling of the same name.
+const SELF_CLOSING_SIBLINGS: &[&str] = &["dd", "dt", "li", "option", "p", "td", "th", "tr"];
+
+type Attributes = Vec<(String, Option<String>)>;
+
+/// An open element with its name, attributes and children.
+type OpenElement = (String, Attributes, Vec<Node>);
+
+#[derive(Debug)]
+enum Node {
+    Doctype(String),
+    Element {
+        name: String,
+        attributes: Attributes,
+        children: Vec<Node>,
+    },
+    Text(String),
+    /// The contents of a raw element.
+    Raw(String),
+}
+
+struct Parser<'a> {
+    html: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.html[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Reads up to one of the chars or the end.
+    fn take_until(&mut self, stop: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let end = rest.find(stop).unwrap_or(rest.len());
+        self.pos += end;
+        &rest[..end]
+    }
+
+    /// Skips past a string or to the end.
+    fn skip_past(&mut self, needle: &str) {
+        match self.rest().find(needle) {
+            Some(idx) => self.pos += idx + needle.len(),
+            None => self.pos = self.html.len(),
+        }
+    }
+
+    fn parse_attributes(&mut self) -> (Attributes, bool) {
+        let mut attributes = vec![];
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.is_empty() {
+                return (attributes, false);
+            } else if rest.starts_with("/>") {
+                self.pos += 2;
+                return (attributes, true);
+            } else if rest.starts_with('>') {
+                self.pos += 1;
+                return (attributes, false);
+            }
+            let name = self.take_until(|c| c.is_whitespace() || c == '=' || c == '>' || c == '/');
+            if name.is_empty() {
+                // a stray slash
+                self.pos += 1;
+                continue;
+            }
+            self.skip_whitespace();
+            let value = if self.rest().starts_with('=') {
+                self.pos += 1;
+                self.skip_whitespace();
+                let quote = self.rest().chars().next();
+                Some(match quote {
+                    Some(quote) if quote == '"' || quote == '\'' => {
+                        self.pos += 1;
+                        let value = self.take_until(|c| c == quote);
+                        self.pos = (self.pos + 1).min(self.html.len());
+                        value
+                    }
+                    _ => self.take_until(|c| c.is_whitespace() || c == '>'),
+                })
+            } else {
+                None
+            };
+            attributes.push((
+                name.to_ascii_lowercase(),
+                value.map(|x| x.split_whitespace().collect::<Vec<_>>().join(" ")),
+            ));
+        }
+    }
+
+    fn parse(&mut self) -> Vec<Node> {
+        // the open elements with their children
+        let mut stack: Vec<OpenElement> = vec![(String::new(), vec![], vec![])];
+        while self.pos < self.html.len() {
+            let rest = self.rest();
+            if rest.starts_with("<!--") {
+                self.skip_past("-->");
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                self.pos += 2;
+                let decl = self.take_until(|c| c == '>');
+                self.pos = (self.pos + 1).min(self.html.len());
+                if decl.len() >= 7 && decl[..7].eq_ignore_ascii_case("doctype") {
+                    let decl = decl
+                        .split_whitespace()
+                        .skip(1)
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    stack.last_mut().unwrap().2.push(Node::Doctype(decl));
+                }
+            } else if rest.starts_with("</") {
+                self.pos += 2;
+                let name = self.take_until(|c| c == '>').trim().to_ascii_lowercase();
+                self.pos = (self.pos + 1).min(self.html.len());
+                // close everything up to the matching element, end tags
+                // without one are dropped.
+                if let Some(idx) = stack.iter().rposition(|x| x.0 == name) {
+                    while stack.len() > idx {
+                        close_element(&mut stack);
+                    }
+                }
+            } else if rest.starts_with('<')
+                && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())
+            {
+                self.pos += 1;
+                let name = self
+                    .take_until(|c| c.is_whitespace() || c == '>' || c == '/')
+                    .to_ascii_lowercase();
+                let (attributes, self_closing) = self.parse_attributes();
+                if SELF_CLOSING_SIBLINGS.contains(&name.as_str())
+                    && stack.last().map_or(false, |x| x.0 == name)
+                {
+                    close_element(&mut stack);
+                }
+                if VOID_ELEMENTS.contains(&name.as_str()) || self_closing {
+                    stack.last_mut().unwrap().2.push(Node::Element {
+                        name,
+                        attributes,
+                        children: vec![],
+                    });
+                } else if RAW_ELEMENTS.contains(&name.as_str()) {
+                    let end = format!("</{}", name);
+                    let lower = self.rest().to_ascii_lowercase();
+                    let len = lower.find(&end).unwrap_or(lower.len());
+                    let raw = &self.rest()[..len];
+                    self.pos += len;
+                    self.skip_past(">");
+                    let children = if raw.trim().is_empty() {
+                        vec![]
+                    } else {
+                        vec![Node::Raw(raw.to_string())]
+                    };
+                    stack.last_mut().unwrap().2.push(Node::Element {
+                        name,
+                        attributes,
+                        children,
+                    });
+                } else {
+                    stack.push((name, attributes, vec![]));
+                }
+            } else {
+                let mut text = self.take_until(|c| c == '<');
+                if text.is_empty() {
+                    // a lone `<`
+                    text = &self.rest()[..1];
+                    self.pos += 1;
+                }
+                let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !collapsed.is_empty() {
+                    stack.last_mut().unwrap().2.push(Node::Text(collapsed));
+                }
+            }
+        }
+        while stack.len() > 1 {
+            close_element(&mut stack);
+        }
+        stack.pop().unwrap().2
+    }
+}
+
+fn close_element(stack: &mut Vec<OpenElement>) {
+    let (name, attributes, children) = stack.pop().unwrap();
+    stack.last_mut().unwrap().2.push(Node::Element {
+        name,
+        attributes,
+        children,
+    });
+}
+
+fn write_node(out: &mut String, node: &Node, depth: usize, removed_attributes: &[String]) {
+    let indent = "  ".repeat(depth);
+    match node {
+        Node::Doctype(decl) => {
+            out.push_str(&format!("{}<!DOCTYPE {}>\n", indent, decl));
+        }
+        Node::Text(text) => {
+            out.push_str(&format!("{}{}\n", indent, text));
+        }
+        Node::Raw(raw) => {
+            out.push_str(raw);
+        }
+        Node::Element {
+            name,
+            attributes,
+            children,
+        } => {
+            let mut attributes: Vec<_> = attributes
+                .iter()
+                .filter(|(key, _)| {
+                    !removed_attributes
+                        .iter()
+                        .any(|x| x.eq_ignore_ascii_case(key))
+                })
+                .collect();
+            attributes.sort_by(|a, b| a.0.cmp(&b.0));
+            out.push_str(&indent);
+            out.push('<');
+            out.push_str(name);
+            for (key, value) in attributes {
+                out.push(' ');
+                out.push_str(key);
+                if let Some(value) = value {
+                    out.push_str(&format!("=\"{}\"", value.replace('"', "&quot;")));
+                }
+            }
+            out.push('>');
+            if VOID_ELEMENTS.contains(&name.as_str()) {
+                out.push('\n');
+                return;
+            }
+            match children.as_slice() {
+                [] => {}
+                [Node::Text(text)] => out.push_str(text),
+                [Node::Raw(raw)] if name == "pre" || name == "textarea" => out.push_str(raw),
+                [Node::Raw(raw)] => {
+                    out.push('\n');
+                    for line in dedent(raw).lines() {
+                        if !line.is_empty() {
+                            out.push_str(&indent);
+                            out.push_str("  ");
+                            out.push_str(line);
+                        }
+                        out.push('\n');
+                    }
+                    out.push_str(&indent);
+                }
+                children => {
+                    out.push('\n');
+                    for child in children {
+                        write_node(out, child, depth + 1, removed_attributes);
+                    }
+                    out.push_str(&indent);
+                }
+            }
+            out.push_str(&format!("</{}>\n", name));
+        }
+    }
+}
+
+/// Removes the common indentation and the empty lines around code.
+fn dedent(code: &str) -> String {
+    let lines: Vec<&str> = code
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|x| x.is_empty())
+        .collect();
+    let end = lines
+        .iter()
+        .rposition(|x| !x.is_empty())
+        .map_or(0, |x| x + 1);
+    let lines = &lines[..end];
+    let indent = lines
+        .iter()
+        .filter(|x| !x.is_empty())
+        .map(|x| x.len() - x.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|x| if x.is_empty() { *x } else { &x[indent..] })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats HTML in a normalized layout.
+///
+/// The markup is parsed leniently like browsers do and written again with
+/// one element per line indented by two spaces.  Attributes are sorted by
+/// name and always quoted with double quotes, whitespace in text is
+/// collapsed and comments are dropped.  The contents of `pre` and
+/// `textarea` elements are kept as they are, those of `script` and `style`
+/// elements are only re-indented.  The attributes in `removed_attributes`
+/// are left out.
+pub fn format_html(html: &str, removed_attributes: &[String]) -> String {
+    let nodes = Parser { html, pos: 0 }.parse();
+    let mut out = String::new();
+    for node in &nodes {
+        write_node(&mut out, node, 0, removed_attributes);
+    }
+    if out.ends_with('\n') {
+        out.truncate(out.len() - 1);
+    }
+    out
+}
+
+/// Formats HTML with the settings of the current thread.
+pub fn format_html_snapshot(html: &str) -> String {
+    Settings::with(|settings| format_html(html, settings.html_removed_attributes()))
+}
+
+#[test]
+fn test_format_html() {
+    let html = r#"<!doctype html>
+<html><head><title>Test</title>
+<script nonce=abc123>
+    if (x) {
+        run();
+    }
+</script></head>
+<body class="main   page" id=top>
+  <!-- rendered by the template -->
+  <ul><li>One<li>Two</ul>
+  <p>Hello
+     <b data-x='a"b'>world</b>!<br/>
+  <input type=checkbox checked>
+  <pre>  keep
+    this</pre>
+</body></html>"#;
+    assert_eq!(
+        format_html(html, &["nonce".to_string()]),
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>Test</title>
+    <script>
+      if (x) {
+          run();
+      }
+    </script>
+  </head>
+  <body class="main page" id="top">
+    <ul>
+      <li>One</li>
+      <li>Two</li>
+    </ul>
+    <p>
+      Hello
+      <b data-x="a&quot;b">world</b>
+      !
+      <br>
+      <input checked type="checkbox">
+      <pre>  keep
+    this</pre>
+    </p>
+  </body>
+</html>"#
+    );
+
+    // the same markup written differently gives the same output
+    assert_eq!(
+        format_html("<DIV   b='2' a=1>x   y</DIV>", &[]),
+        format_html("<div a=\"1\" b=\"2\">\n  x\n  y\n</div>", &[])
+    );
+}
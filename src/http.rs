@@ -0,0 +1,200 @@
+use serde::{Serialize, Serializer};
+
+use crate::content::{Content, ContentSerializer};
+use crate::settings::Settings;
+
+/// Headers that change with every request and are redacted by default.
+pub(crate) const VOLATILE_HEADERS: &[&str] = &[
+    "date",
+    "set-cookie",
+    "request-id",
+    "x-request-id",
+    "x-correlation-id",
+];
+
+/// An HTTP request with its response for snapshotting.
+///
+/// Exchanges are built from the parts of whatever HTTP client or test
+/// server is used and asserted with
+/// [`assert_http_snapshot!`](crate::assert_http_snapshot!):
+///
+/// ```no_run
+/// # use insta::{assert_http_snapshot, HttpExchange};
+/// let exchange = HttpExchange::new("GET", "/users/1")
+///     .request_header("Accept", "application/json")
+///     .status(200)
+///     .response_header("Content-Type", "application/json")
+///     .response_header("Date", "Tue, 01 Jun 2021 12:00:00 GMT")
+///     .response_body(r#"{"id": 1, "name": "John"}"#);
+/// assert_http_snapshot!(exchange);
+/// ```
+///
+/// Header names are lowercased and sorted.  Bodies with a JSON content type
+/// are parsed so that they show up as structured data and can be redacted
+/// like other values, other bodies are stored as text.  Which headers show
+/// up and which are redacted is configured with
+/// [`Settings::set_http_headers`](crate::Settings::set_http_headers) and
+/// [`Settings::set_http_redacted_headers`](crate::Settings::set_http_redacted_headers).
+#[derive(Debug, Clone)]
+pub struct HttpExchange {
+    method: String,
+    url: String,
+    request_headers: Vec<(String, String)>,
+    request_body: Option<Vec<u8>>,
+    status: u16,
+    response_headers: Vec<(String, String)>,
+    response_body: Option<Vec<u8>>,
+}
+
+impl HttpExchange {
+    /// Creates an exchange for a request with the given method and URL.
+    pub fn new<M: Into<String>, U: Into<String>>(method: M, url: U) -> HttpExchange {
+        HttpExchange {
+            method: method.into(),
+            url: url.into(),
+            request_headers: vec![],
+            request_body: None,
+            status: 200,
+            response_headers: vec![],
+            response_body: None,
+        }
+    }
+
+    /// Adds a header of the request.
+    pub fn request_header<K: Into<String>, V: Into<String>>(
+        mut self,
+        name: K,
+        value: V,
+    ) -> HttpExchange {
+        self.request_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the body of the request.
+    pub fn request_body<B: Into<Vec<u8>>>(mut self, body: B) -> HttpExchange {
+        self.request_body = Some(body.into());
+        self
+    }
+
+    /// Sets the status code of the response.
+    ///
+    /// The default is `200`.
+    pub fn status(mut self, status: u16) -> HttpExchange {
+        self.status = status;
+        self
+    }
+
+    /// Adds a header of the response.
+    pub fn response_header<K: Into<String>, V: Into<String>>(
+        mut self,
+        name: K,
+        value: V,
+    ) -> HttpExchange {
+        self.response_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the body of the response.
+    pub fn response_body<B: Into<Vec<u8>>>(mut self, body: B) -> HttpExchange {
+        self.response_body = Some(body.into());
+        self
+    }
+
+    fn to_content(&self, settings: &Settings) -> Content {
+        let mut request = vec![
+            (Content::from("method"), Content::from(self.method.as_str())),
+            (Content::from("url"), Content::from(self.url.as_str())),
+        ];
+        add_headers_and_body(
+            &mut request,
+            &self.request_headers,
+            self.request_body.as_deref(),
+            settings,
+        );
+        let mut response = vec![(Content::from("status"), Content::from(self.status))];
+        add_headers_and_body(
+            &mut response,
+            &self.response_headers,
+            self.response_body.as_deref(),
+            settings,
+        );
+        Content::Map(vec![
+            (Content::from("request"), Content::Map(request)),
+            (Content::from("response"), Content::Map(response)),
+        ])
+    }
+}
+
+impl Serialize for HttpExchange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Settings::with(|settings| self.to_content(settings)).serialize(serializer)
+    }
+}
+
+fn add_headers_and_body(
+    entries: &mut Vec<(Content, Content)>,
+    headers: &[(String, String)],
+    body: Option<&[u8]>,
+    settings: &Settings,
+) {
+    // repeated headers are joined like HTTP allows for most of them
+    let mut merged: Vec<(String, String)> = vec![];
+    for (name, value) in headers {
+        let name = name.to_ascii_lowercase();
+        match merged.iter_mut().find(|x| x.0 == name) {
+            Some(existing) => {
+                existing.1.push_str(", ");
+                existing.1.push_str(value);
+            }
+            None => merged.push((name, value.clone())),
+        }
+    }
+    let json = merged
+        .iter()
+        .any(|(name, value)| name == "content-type" && value.contains("json"));
+
+    merged.retain(|(name, _)| {
+        settings.http_headers().map_or(true, |selected| {
+            selected.iter().any(|x| x.eq_ignore_ascii_case(name))
+        })
+    });
+    merged.sort_by(|a, b| a.0.cmp(&b.0));
+    if !merged.is_empty() {
+        let redacted = settings.http_redacted_headers();
+        entries.push((
+            Content::from("headers"),
+            Content::Map(
+                merged
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let value = if redacted.iter().any(|x| x.eq_ignore_ascii_case(&name)) {
+                            format!("[{}]", name)
+                        } else {
+                            value
+                        };
+                        (Content::from(name), Content::from(value))
+                    })
+                    .collect(),
+            ),
+        ));
+    }
+
+    if let Some(body) = body.filter(|x| !x.is_empty()) {
+        entries.push((Content::from("body"), body_to_content(body, json)));
+    }
+}
+
+/// Parses a JSON body, other bodies become text if they are UTF-8.
+fn body_to_content(body: &[u8], json: bool) -> Content {
+    if json {
+        if let Ok(value) = serde_json::from_slice::<serde_json::Value>(body) {
+            if let Ok(content) = value.serialize(ContentSerializer::<serde_json::Error>::new()) {
+                return content;
+            }
+        }
+    }
+    match std::str::from_utf8(body) {
+        Ok(text) => Content::from(text),
+        Err(_) => Content::from(body),
+    }
+}
@@ -0,0 +1,233 @@
+use std::error::Error;
+
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+
+/// The largest possible YIQ color difference between two pixels.
+const MAX_DELTA: f32 = 35215.0;
+
+/// A decoded image with 8 bit RGBA pixels.
+pub(crate) struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Decodes a PNG image.
+    pub fn from_png(bytes: &[u8]) -> Result<Image, Box<dyn Error>> {
+        let mut decoder = Decoder::new(bytes);
+        decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+        let (info, mut reader) = decoder.read_info()?;
+        let mut buf = vec![0; info.buffer_size()];
+        reader.next_frame(&mut buf)?;
+
+        let pixels = match reader.output_color_type() {
+            (ColorType::RGBA, BitDepth::Eight) => buf,
+            (ColorType::RGB, BitDepth::Eight) => buf
+                .chunks(3)
+                .flat_map(|px| vec![px[0], px[1], px[2], 255])
+                .collect(),
+            (ColorType::GrayscaleAlpha, BitDepth::Eight) => buf
+                .chunks(2)
+                .flat_map(|px| vec![px[0], px[0], px[0], px[1]])
+                .collect(),
+            (ColorType::Grayscale, BitDepth::Eight) => {
+                buf.iter().flat_map(|&px| vec![px, px, px, 255]).collect()
+            }
+            (color_type, bit_depth) => {
+                return Err(
+                    format!("unsupported png format ({:?}, {:?})", color_type, bit_depth).into(),
+                )
+            }
+        };
+
+        Ok(Image {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// Encodes the image as PNG.
+    pub fn to_png(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut rv = vec![];
+        {
+            let mut encoder = Encoder::new(&mut rv, self.width, self.height);
+            encoder.set_color(ColorType::RGBA);
+            encoder.set_depth(BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&self.pixels)?;
+        }
+        Ok(rv)
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let offset = ((y * self.width + x) * 4) as usize;
+        Some(&self.pixels[offset..offset + 4])
+    }
+}
+
+/// The result of comparing two images.
+pub(crate) struct ImageDiff {
+    /// The number of pixels that differ perceptually.
+    pub differing_pixels: usize,
+    /// The number of pixels that were compared.
+    pub total_pixels: usize,
+    /// An image highlighting the differing pixels in red.
+    pub diff_image: Image,
+}
+
+impl ImageDiff {
+    /// Returns the percentage of differing pixels.
+    pub fn percentage(&self) -> f64 {
+        if self.total_pixels == 0 {
+            0.0
+        } else {
+            self.differing_pixels as f64 / self.total_pixels as f64 * 100.0
+        }
+    }
+}
+
+/// Compares two PNG images perceptually.
+///
+/// Two pixels are considered different if their difference in the YIQ
+/// color space exceeds `threshold` (`0.0` to `1.0`).  If the images have
+/// different dimensions, all pixels outside of the common area count as
+/// differing.
+pub(crate) fn diff_images(
+    old: &[u8],
+    new: &[u8],
+    threshold: f32,
+) -> Result<ImageDiff, Box<dyn Error>> {
+    let old = Image::from_png(old)?;
+    let new = Image::from_png(new)?;
+    let width = old.width.max(new.width);
+    let height = old.height.max(new.height);
+    let max_delta = MAX_DELTA * threshold * threshold;
+
+    let mut differing_pixels = 0;
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let differs = match (old.pixel(x, y), new.pixel(x, y)) {
+                (Some(a), Some(b)) => color_delta(a, b) > max_delta,
+                _ => true,
+            };
+            if differs {
+                differing_pixels += 1;
+                pixels.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                // fade out matching pixels so the differences stand out
+                let gray = new.pixel(x, y).map_or(255, |px| {
+                    let luma = blend(
+                        rgb_to_y(f32::from(px[0]), f32::from(px[1]), f32::from(px[2])),
+                        px[3],
+                    );
+                    (255.0 - (255.0 - luma) * 0.1) as u8
+                });
+                pixels.extend_from_slice(&[gray, gray, gray, 255]);
+            }
+        }
+    }
+
+    Ok(ImageDiff {
+        differing_pixels,
+        total_pixels: (width * height) as usize,
+        diff_image: Image {
+            width,
+            height,
+            pixels,
+        },
+    })
+}
+
+/// Checks if two PNG images match within the given threshold.
+///
+/// Images that cannot be decoded fall back to byte equality.
+pub(crate) fn images_match(old: &[u8], new: &[u8], threshold: f32) -> bool {
+    match diff_images(old, new, threshold) {
+        Ok(diff) => diff.differing_pixels == 0,
+        Err(_) => old == new,
+    }
+}
+
+fn blend(value: f32, alpha: u8) -> f32 {
+    255.0 + (value - 255.0) * (f32::from(alpha) / 255.0)
+}
+
+fn rgb_to_y(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23
+}
+
+fn rgb_to_i(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_9
+}
+
+fn rgb_to_q(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.211_470_17 - g * 0.522_617_66 + b * 0.311_147_49
+}
+
+/// Calculates the squared perceptual difference of two RGBA pixels.
+///
+/// Transparent pixels are blended against white first.
+fn color_delta(a: &[u8], b: &[u8]) -> f32 {
+    if a == b {
+        return 0.0;
+    }
+    let (r1, g1, b1) = (
+        blend(f32::from(a[0]), a[3]),
+        blend(f32::from(a[1]), a[3]),
+        blend(f32::from(a[2]), a[3]),
+    );
+    let (r2, g2, b2) = (
+        blend(f32::from(b[0]), b[3]),
+        blend(f32::from(b[1]), b[3]),
+        blend(f32::from(b[2]), b[3]),
+    );
+    let y = rgb_to_y(r1, g1, b1) - rgb_to_y(r2, g2, b2);
+    let i = rgb_to_i(r1, g1, b1) - rgb_to_i(r2, g2, b2);
+    let q = rgb_to_q(r1, g1, b1) - rgb_to_q(r2, g2, b2);
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+#[test]
+fn test_diff_images() {
+    let mut pixels = vec![255; 4 * 4 * 4];
+    let old = Image {
+        width: 4,
+        height: 4,
+        pixels: pixels.clone(),
+    }
+    .to_png()
+    .unwrap();
+
+    // a barely visible change stays below the threshold
+    pixels[0] = 254;
+    let similar = Image {
+        width: 4,
+        height: 4,
+        pixels: pixels.clone(),
+    }
+    .to_png()
+    .unwrap();
+    assert!(images_match(&old, &similar, 0.1));
+    assert!(!images_match(&old, &similar, 0.0));
+
+    // a black pixel does not
+    pixels[4..7].copy_from_slice(&[0, 0, 0]);
+    let different = Image {
+        width: 4,
+        height: 4,
+        pixels,
+    }
+    .to_png()
+    .unwrap();
+    let diff = diff_images(&old, &different, 0.1).unwrap();
+    assert_eq!(diff.differing_pixels, 1);
+    assert_eq!(diff.total_pixels, 16);
+    assert_eq!(diff.percentage(), 6.25);
+    assert_eq!(diff.diff_image.pixel(1, 0), Some(&[255, 0, 0, 255][..]));
+}
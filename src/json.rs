@@ -0,0 +1,72 @@
+use serde_json::{Number, Value};
+
+use crate::floats::numbers_match;
+
+/// Compares two JSON values structurally.
+///
+/// Objects match if they have the same keys with matching values no matter
+/// the order of the keys.  Numbers match if they have the same value, so
+/// `1.0` matches `1`, or if they differ within the tolerances.
+pub(crate) fn json_values_match(old: &Value, new: &Value, absolute: f64, relative: f64) -> bool {
+    match (old, new) {
+        (Value::Number(old), Value::Number(new)) => {
+            json_numbers_match(old, new, absolute, relative)
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            old.len() == new.len()
+                && old
+                    .iter()
+                    .zip(new.iter())
+                    .all(|(old, new)| json_values_match(old, new, absolute, relative))
+        }
+        (Value::Object(old), Value::Object(new)) => {
+            old.len() == new.len()
+                && old.iter().all(|(key, old)| {
+                    new.get(key)
+                        .map_or(false, |new| json_values_match(old, new, absolute, relative))
+                })
+        }
+        _ => old == new,
+    }
+}
+
+fn json_numbers_match(old: &Number, new: &Number, absolute: f64, relative: f64) -> bool {
+    if old == new {
+        return true;
+    }
+    // integers too large for a float must not match their neighbours
+    let integers = !old.is_f64() && !new.is_f64();
+    if integers && absolute <= 0.0 && relative <= 0.0 {
+        return false;
+    }
+    match (old.as_f64(), new.as_f64()) {
+        (Some(old), Some(new)) => numbers_match(old, new, absolute, relative),
+        _ => false,
+    }
+}
+
+#[test]
+fn test_json_values_match() {
+    let matches = |old: &str, new: &str| {
+        json_values_match(
+            &serde_json::from_str(old).unwrap(),
+            &serde_json::from_str(new).unwrap(),
+            0.0,
+            0.0,
+        )
+    };
+    assert!(matches(
+        r#"{"a": 1.0, "b": [1, 2]}"#,
+        r#"{"b": [1.0, 2], "a": 1}"#
+    ));
+    assert!(!matches(r#"{"a": 1}"#, r#"{"a": 1, "b": 2}"#));
+    assert!(!matches("[1, 2]", "[2, 1]"));
+    assert!(!matches("9007199254740993", "9007199254740992"));
+    assert!(!matches(r#""1""#, "1"));
+    assert!(json_values_match(
+        &serde_json::json!({"x": 0.30000000000000004}),
+        &serde_json::json!({"x": 0.3}),
+        1e-9,
+        0.0
+    ));
+}
@@ -32,7 +32,7 @@
 //! * [Read the main documentation](https://insta.rs/docs/) which does not just
 //!   cover the API of the crate but also many of the details of how it works.
 //! * There is a screencast that shows the entire workflow: [watch the insta
-//! introduction screencast](https://www.youtube.com/watch?v=rCHrMqE4JOY&feature=youtu.be).
+//!   introduction screencast](https://www.youtube.com/watch?v=rCHrMqE4JOY&feature=youtu.be).
 //!
 //! # Writing Tests
 //!
@@ -63,8 +63,9 @@
 //! - `assert_snapshot!` for comparing basic string snapshots.
 //! - `assert_debug_snapshot!` for comparing `Debug` outputs of values.
 //! - `assert_display_snapshot!` for comparing `Display` outputs of values.
-//! - `assert_csv_snapshot!` for comparing CSV serialized output of
-//!   types implementing `serde::Serialize`. (requires the `csv` feature)
+//! - `assert_csv_snapshot!` and `assert_tsv_snapshot!` for comparing CSV
+//!   or TSV serialized output of types implementing `serde::Serialize` or
+//!   raw CSV strings. (requires the `csv` feature)
 //! - `assert_toml_snapshot!` for comparing TOML serialized output of
 //!   types implementing `serde::Serialize`. (requires the `toml` feature)
 //! - `assert_yaml_snapshot!` for comparing YAML serialized
@@ -73,10 +74,46 @@
 //!   types implementing `serde::Serialize`. (requires the `ron` feature)
 //! - `assert_json_snapshot!` for comparing JSON serialized output of
 //!   types implementing `serde::Serialize`.
+//! - `assert_json_value_snapshot!` for comparing `serde_json::Value`s
+//!   structurally, ignoring key order and number formatting.
+//! - `assert_xml_snapshot!` for comparing canonicalized XML output of
+//!   types implementing `serde::Serialize` or raw XML strings.
+//!   (requires the `xml` feature)
+//! - `assert_msgpack_snapshot!` and `assert_cbor_snapshot!` for comparing
+//!   MessagePack or CBOR encoded bytes decoded into readable YAML.
+//!   (requires the `msgpack` or `cbor` feature)
+//! - `assert_protobuf_snapshot!` for comparing encoded protobuf messages
+//!   decoded without a schema into readable YAML. (requires the `protobuf` feature)
+//! - `assert_table_snapshot!` for comparing rows or columns
+//!   rendered as an aligned table. (requires the `table` feature)
+//! - `assert_matrix_snapshot!` for comparing numeric vectors, matrices and
+//!   arrays elementwise. (requires the `matrix` feature)
+//! - `assert_custom_snapshot!` for comparing serialized output of types
+//!   implementing `serde::Serialize` in a format of your choice by way of a
+//!   [`SnapshotSerializer`].
+//! - `assert_sql_snapshot!` for comparing SQL queries in a normalized
+//!   layout. (requires the `sql` feature)
+//! - `assert_html_snapshot!` for comparing HTML markup in a normalized
+//!   layout. (requires the `html` feature)
+//! - `assert_cmd_snapshot!` for comparing the exit status, stdout and
+//!   stderr of a command. (requires the `cmd` feature)
+//! - `assert_http_snapshot!` for comparing an HTTP request with its
+//!   response in YAML format. (requires the `http` feature)
+//! - `assert_log_snapshot!` for comparing the records logged with the `log`
+//!   crate while running some code. (requires the `log` feature)
+//! - `assert_binary_snapshot!` for comparing raw bytes which are stored in
+//!   a separate file next to the snapshot (eg: images or compiled output).
+//! - `snapshot_writer!` for writing large binary or text snapshots piece by
+//!   piece without holding them in memory.
+//! - `assert_image_snapshot!` for comparing PNG images perceptually.
+//!   (requires the `image` feature)
+//! - `assert_dir_snapshot!` for comparing the files and contents of a
+//!   directory. (requires the `dir` feature)
 //!
 //! For macros that work with `serde::Serialize` this crate also permits
 //! redacting of partial values.  See [redactions in the documentation](https://insta.rs/docs/redactions/)
-//! for more information.
+//! for more information.  Besides the jq-like selectors (`.items[0].id`)
+//! selectors can also be written as JSON pointers (`/items/0/id`).
 //!
 //! # Snapshot updating
 //!
@@ -93,6 +130,28 @@
 //! - `unseen`: behaves like `always` for new snapshots and `new` for others
 //! - `new`: write new snapshots into `.snap.new` files
 //! - `no`: does not update snapshot files at all (just runs tests)
+//! - `new-only`: writes snapshot files for assertions that do not have a
+//!   snapshot yet but leaves existing snapshots alone, so mismatches still
+//!   fail
+//! - `ask`: shows the diff of every failing assertion on the terminal and
+//!   asks right away whether to accept ("a" or "enter"), reject ("r" or
+//!   "escape") or skip ("s" or "space") it.  Accepted snapshots are written
+//!   immediately, skipped ones into `.snap.new` files.  Inline snapshots are
+//!   always skipped.  Without a terminal this behaves like `auto`.
+//!
+//! The `update` section of an `insta.yaml` file in the workspace root can
+//! set the mode for some tests, for instance to keep snapshots of generated
+//! files up to date on their own.  The first rule matching the path of a
+//! test applies, `*` matches within a module and `**` across modules.  The
+//! rules replace the `auto` and `new` modes, other modes still apply to
+//! all tests:
+//!
+//! ```yaml
+//! update:
+//!   rules:
+//!     - tests: "generated::**"
+//!       mode: always
+//! ```
 //!
 //! When `new` or `auto` is used as mode the [`cargo-insta`](https://crates.io/crates/cargo-insta)
 //! command can be used to review the snapshots conveniently:
@@ -132,15 +191,102 @@
 //! `cargo insta review` to accept the change.  The file will then be updated
 //! automatically.
 //!
+//! Assertions can be wrapped in your own `macro_rules!` macros.  The
+//! snapshot is then found in the arguments of the wrapper: either after an
+//! `@` or, if the wrapper adds the `@` itself, by its current contents.
+//!
+//! ```no_run
+//! # use insta::*;
+//! macro_rules! assert_output {
+//!     ($cmd:expr, $snapshot:literal) => {
+//!         assert_snapshot!(format!("$ {}", $cmd), @$snapshot)
+//!     };
+//! }
+//!
+//! assert_output!("ls", "");
+//! ```
+//!
+//! Inline snapshots also work in doctests.  `cargo-insta` then updates the
+//! string within the doc comment.
+//!
+//! # Snapshot Files
+//!
+//! Snapshots are stored as `<module>__<name>.snap` in a `snapshots` folder
+//! next to the test.  The `snapshots` section of an `insta.yaml` file in the
+//! workspace root can change the file names with a template.  The
+//! placeholders are `{crate}`, `{module}` (the module path below the crate,
+//! joined with `module_separator`), `{test}` (the test function), `{name}`
+//! (the snapshot name) and `{suffix}` (see
+//! [`Settings::set_snapshot_suffix`]).  Separators next to placeholders
+//! that are empty are left out:
+//!
+//! ```yaml
+//! snapshots:
+//!   filename: "{module}.{name}.{suffix}"
+//!   module_separator: "."
+//! ```
+//!
+//! To keep snapshots out of the source folders, `root` stores the
+//! snapshots of all packages of the workspace in one folder with a
+//! subfolder per package, for instance `golden/parser/parser__expr.snap`:
+//!
+//! ```yaml
+//! snapshots:
+//!   root: golden
+//! ```
+//!
+//! Tools that work with snapshot files can read and write them with
+//! [`Snapshot`].  [`Snapshot::from_file`] loads a `.snap` or `.snap.new`
+//! file, [`Snapshot::metadata`] and [`Snapshot::contents`] give access to
+//! what is stored in it and [`Snapshot::save`] writes it back:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use insta::{Snapshot, SnapshotContents};
+//!
+//! let snapshot = Snapshot::from_file("tests/snapshots/parser__tokens.snap.new")?;
+//! println!("{:?}", snapshot.metadata().expression());
+//! let snapshot = snapshot.with_contents(SnapshotContents::from("updated"));
+//! snapshot.save("tests/snapshots/parser__tokens.snap")?;
+//! # Ok(()) }
+//! ```
+//!
 //! # Features
 //!
 //! The following features exist:
 //!
-//! * `csv`: enables CSV support ([`assert_csv_snapshot!`])
+//! * `csv`: enables CSV and TSV support ([`assert_csv_snapshot!`],
+//!   [`assert_tsv_snapshot!`])
 //! * `ron`: enables RON support ([`assert_ron_snapshot!`])
 //! * `toml`: enables TOML support ([`assert_toml_snapshot!`])
+//! * `xml`: enables XML support ([`assert_xml_snapshot!`])
+//! * `msgpack`: enables MessagePack support ([`assert_msgpack_snapshot!`])
+//! * `cbor`: enables CBOR support ([`assert_cbor_snapshot!`])
+//! * `protobuf`: enables protobuf support ([`assert_protobuf_snapshot!`])
+//! * `table`: enables aligned table snapshots ([`assert_table_snapshot!`])
+//! * `matrix`: enables numeric matrix snapshots ([`assert_matrix_snapshot!`])
+//! * `sql`: enables SQL snapshots ([`assert_sql_snapshot!`])
+//! * `html`: enables HTML snapshots ([`assert_html_snapshot!`])
+//! * `cmd`: enables snapshots of the output of commands
+//!   ([`assert_cmd_snapshot!`])
+//! * `http`: enables snapshots of HTTP exchanges ([`assert_http_snapshot!`])
+//! * `log`: enables snapshots of captured log records
+//!   ([`assert_log_snapshot!`])
+//! * `image`: enables perceptual image snapshots ([`assert_image_snapshot!`])
 //! * `redactions`: enables support for redactions
 //! * `glob`: enables support for globbing ([`glob!`])
+//! * `dir`: enables directory snapshots ([`assert_dir_snapshot!`])
+//! * `filters`: enables regex filters for snapshot contents
+//!   ([`Settings::add_filter`]) and placeholders in snapshots
+//!   ([`Settings::set_allow_placeholders`])
+//! * `compression`: enables compressed snapshot files
+//!   ([`Settings::set_compression`])
+//! * `blobs`: enables content addressed storage of large snapshots
+//!   ([`Settings::set_blob_threshold`])
+//! * `schema`: enables validation of serialized values against a JSON
+//!   Schema ([`Settings::set_json_schema`])
+//! * `macros`: enables the [`test`] attribute for async tests and validates
+//!   redaction selectors that are string literals at compile time
 //! * `colors`: enables color output (enabled by default)
 //!
 //! # Settings
@@ -149,24 +295,91 @@
 //! per-test) basis.  For more information see [Settings].
 #[macro_use]
 mod macros;
+mod ansi;
+mod config;
 mod content;
+mod floats;
+mod json;
+mod line_diff;
+mod placeholders;
 mod runtime;
+mod secrets;
 mod serialization;
 mod settings;
 mod snapshot;
+mod streaming;
 mod utils;
 
 #[cfg(feature = "redactions")]
 mod redaction;
 
+#[cfg(feature = "redactions")]
+mod debug;
+
 #[cfg(feature = "glob")]
 mod glob;
 
+#[cfg(feature = "xml")]
+mod xml;
+
+#[cfg(feature = "msgpack")]
+mod msgpack;
+
+#[cfg(feature = "cbor")]
+mod cbor;
+
+#[cfg(feature = "protobuf")]
+mod protobuf;
+
+#[cfg(feature = "table")]
+mod table;
+
+#[cfg(feature = "matrix")]
+mod matrix;
+
+#[cfg(feature = "image")]
+mod image;
+
+#[cfg(feature = "dir")]
+mod dir;
+
+#[cfg(feature = "filters")]
+mod filters;
+
+#[cfg(feature = "compression")]
+mod compression;
+
+#[cfg(feature = "blobs")]
+mod blobs;
+
+#[cfg(feature = "schema")]
+mod schema;
+
+#[cfg(feature = "sql")]
+mod sql;
+
+#[cfg(feature = "html")]
+mod html;
+
+#[cfg(feature = "cmd")]
+mod cmd;
+
+#[cfg(feature = "http")]
+mod http;
+
+#[cfg(feature = "log")]
+mod logs;
+
 #[cfg(test)]
 mod test;
 
-pub use crate::settings::Settings;
-pub use crate::snapshot::{MetaData, Snapshot};
+pub use crate::serialization::SnapshotSerializer;
+pub use crate::settings::{AnsiEscapes, DiffAlgorithm, Settings, SnapshotLayout};
+
+#[cfg(feature = "csv")]
+pub use crate::settings::CsvQuoteStyle;
+pub use crate::snapshot::{MetaData, Snapshot, SnapshotContents};
+pub use crate::streaming::SnapshotWriter;
 
 /// Exposes some library internals.
 ///
@@ -174,11 +387,13 @@ pub use crate::snapshot::{MetaData, Snapshot};
 /// are exposed for documentation primarily.
 pub mod internals {
     pub use crate::content::Content;
+    #[cfg(feature = "filters")]
+    pub use crate::filters::Filters;
     pub use crate::runtime::AutoName;
     pub use crate::snapshot::{MetaData, SnapshotContents};
     #[cfg(feature = "redactions")]
     pub use crate::{
-        redaction::{ContentPath, Redaction},
+        redaction::{ContentPath, Redaction, RedactionContext},
         settings::Redactions,
     };
 }
@@ -186,25 +401,106 @@ pub mod internals {
 // exported for cargo-insta only
 #[doc(hidden)]
 pub use crate::{
-    runtime::print_snapshot_diff, snapshot::PendingInlineSnapshot, snapshot::SnapshotContents,
+    config::pending_dir, config::BlobsConfig, config::ChecksumMode, config::DiffConfig,
+    config::FilterConfig, config::FilterSetConfig, config::HooksConfig, config::InlineConfig,
+    config::NotifyMode, config::PendingConfig, config::PendingLocation, config::ReviewConfig,
+    config::RustfmtMode, config::SecretsCheck, config::SecretsConfig, config::SnapshotsConfig,
+    config::TestConfig, config::ToolConfig, config::UpdateConfig, config::UpdateRule,
+    runtime::print_snapshot, runtime::print_snapshot_diff, runtime::print_snapshot_diff_limited,
+    runtime::snapshot_variant_file, runtime::AssertionResult, runtime::AssertionStatus,
+    secrets::find_secrets, secrets::Secret, secrets::SecretKind, snapshot::metadata_from_file,
+    snapshot::PendingInlineSnapshot, utils::format_date, utils::is_iso_date,
 };
 
+#[cfg(feature = "compression")]
+#[doc(hidden)]
+pub use crate::compression::{gunzip, gzip};
+
 // useful for redactions
 #[cfg(feature = "redactions")]
-pub use crate::redaction::dynamic_redaction;
+pub use crate::redaction::{
+    contextual_redaction, dynamic_redaction, exclude_redaction, key_redaction, rounded_redaction,
+    sorted_redaction, sorted_redaction_by,
+};
+
+#[cfg(feature = "dir")]
+pub use crate::dir::FileRedaction;
+
+#[cfg(feature = "filters")]
+pub use crate::filters::StandardFilter;
+
+#[cfg(feature = "compression")]
+pub use crate::snapshot::Compression;
+
+#[cfg(feature = "blobs")]
+pub use crate::blobs::{BlobBackend, DirBlobBackend};
+
+#[cfg(feature = "schema")]
+pub use crate::schema::JsonSchema;
+
+#[cfg(feature = "http")]
+pub use crate::http::HttpExchange;
+
+/// Marks a test that makes snapshot assertions.
+///
+/// Snapshot names are derived from the name of the test thread which does
+/// not work for async tests as the runtime is free to poll the future on
+/// any thread.  The same is true for settings bound with
+/// [`Settings::bind_to_thread`].  This attribute binds both the test name
+/// and the settings to whatever thread is currently polling the test.
+///
+/// For async tests pass the test attribute of the runtime:
+///
+/// ```rust,ignore
+/// #[insta::test(tokio::test)]
+/// async fn test_fetch() {
+///     let mut settings = insta::Settings::clone_current();
+///     settings.set_snapshot_suffix("remote");
+///     settings.bind_to_thread();
+///     insta::assert_debug_snapshot!(fetch().await);
+/// }
+/// ```
+///
+/// Without an argument `#[test]` is used, which also works for synchronous
+/// tests.  (requires the `macros` feature)
+#[cfg(feature = "macros")]
+pub use insta_macros::test;
 
 // these are here to make the macros work
 #[doc(hidden)]
 pub mod _macro_support {
     pub use crate::content::Content;
-    pub use crate::runtime::{assert_snapshot, get_cargo_workspace, AutoName, ReferenceValue};
+    pub use crate::runtime::{
+        assert_binary_snapshot, assert_snapshot, get_cargo_workspace, run_test, run_test_future,
+        AutoName, ReferenceValue,
+    };
     pub use crate::serialization::{serialize_value, SerializationFormat, SnapshotLocation};
+    pub use crate::streaming::snapshot_writer;
 
     #[cfg(feature = "glob")]
     pub use crate::glob::glob_exec;
 
+    #[cfg(feature = "dir")]
+    pub use crate::dir::dir_snapshot;
+
+    #[cfg(feature = "sql")]
+    pub use crate::sql::format_sql_snapshot;
+
+    #[cfg(feature = "html")]
+    pub use crate::html::format_html_snapshot;
+
+    #[cfg(feature = "cmd")]
+    pub use crate::cmd::run_cmd_snapshot;
+
+    #[cfg(feature = "log")]
+    pub use crate::logs::capture_logs;
+
+    #[cfg(feature = "macros")]
+    pub use insta_macros::validate_selectors;
+
     #[cfg(feature = "redactions")]
     pub use crate::{
-        redaction::Redaction, redaction::Selector, serialization::serialize_value_redacted,
+        debug::redact_debug_output, redaction::Redaction, redaction::Selector,
+        serialization::serialize_value_redacted,
     };
 }
@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use similar::{capture_diff_slices_deadline, Algorithm, DiffTag};
+
+/// The number of lines of each text that are diffed at once.
+///
+/// Only this many lines of both texts are held in memory besides the texts
+/// themselves, which keeps diffs of huge snapshots cheap.
+const WINDOW: usize = 2000;
+
+/// How long the diff of one window may take before it is approximated.
+const WINDOW_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Iterates over the lines of a text including their line endings.
+struct Lines<'a>(&'a str);
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let end = self.0.find('\n').map_or(self.0.len(), |x| x + 1);
+        let (line, rest) = self.0.split_at(end);
+        self.0 = rest;
+        Some(line)
+    }
+}
+
+/// An operation of a line diff with the lines it covers.
+pub(crate) struct LineOp<'a, 'b> {
+    pub tag: DiffTag,
+    /// The index of the first old line.
+    pub old_start: usize,
+    /// The index of the first new line.
+    pub new_start: usize,
+    pub old_lines: &'b [&'a str],
+    pub new_lines: &'b [&'a str],
+}
+
+/// Diffs two texts line by line and passes the operations on in order.
+///
+/// The texts are diffed in windows of a few thousand lines.  Everything up
+/// to the last run of equal lines in a window is passed on before the next
+/// lines are read, so the memory used does not grow with the size of the
+/// texts.  Changes that span more than a window are reported as replaced
+/// lines which is correct but not always minimal.
+pub(crate) fn diff_lines<'a, F>(old: &'a str, new: &'a str, algorithm: Algorithm, mut f: F)
+where
+    F: FnMut(LineOp<'a, '_>),
+{
+    let mut old_iter = Lines(old);
+    let mut new_iter = Lines(new);
+    let mut old_buf = Vec::with_capacity(WINDOW);
+    let mut new_buf = Vec::with_capacity(WINDOW);
+    let mut old_offset = 0;
+    let mut new_offset = 0;
+    loop {
+        old_buf.extend(old_iter.by_ref().take(WINDOW - old_buf.len()));
+        new_buf.extend(new_iter.by_ref().take(WINDOW - new_buf.len()));
+        if old_buf.is_empty() && new_buf.is_empty() {
+            return;
+        }
+        let at_end = old_iter.0.is_empty() && new_iter.0.is_empty();
+        // windows without a single common line would otherwise run into the
+        // timeout, which adds up for large rewritten snapshots.
+        let has_common_line = {
+            let old_set: HashSet<&str> = old_buf.iter().copied().collect();
+            new_buf.iter().any(|line| old_set.contains(line))
+        };
+        let ops = if has_common_line {
+            capture_diff_slices_deadline(
+                algorithm,
+                &old_buf,
+                &new_buf,
+                Some(Instant::now() + WINDOW_TIMEOUT),
+            )
+        } else {
+            Vec::new()
+        };
+
+        // the lines after the last equal ones might match lines that were
+        // not read yet, they are diffed again with the next window.
+        let (old_end, new_end, ops_end) = if at_end {
+            (old_buf.len(), new_buf.len(), ops.len())
+        } else {
+            match ops.iter().rposition(|op| op.tag() == DiffTag::Equal) {
+                Some(idx) => (ops[idx].old_range().end, ops[idx].new_range().end, idx + 1),
+                None => (
+                    old_buf.len().min(WINDOW / 2),
+                    new_buf.len().min(WINDOW / 2),
+                    0,
+                ),
+            }
+        };
+        if ops_end == 0 && (old_end > 0 || new_end > 0) {
+            // nothing in the window matches
+            f(LineOp {
+                tag: match (old_end, new_end) {
+                    (0, _) => DiffTag::Insert,
+                    (_, 0) => DiffTag::Delete,
+                    _ => DiffTag::Replace,
+                },
+                old_start: old_offset,
+                new_start: new_offset,
+                old_lines: &old_buf[..old_end],
+                new_lines: &new_buf[..new_end],
+            });
+        }
+        for op in &ops[..ops_end] {
+            let old_range = op.old_range();
+            let new_range = op.new_range();
+            f(LineOp {
+                tag: op.tag(),
+                old_start: old_offset + old_range.start,
+                new_start: new_offset + new_range.start,
+                old_lines: &old_buf[old_range],
+                new_lines: &new_buf[new_range],
+            });
+        }
+        old_buf.drain(..old_end);
+        new_buf.drain(..new_end);
+        old_offset += old_end;
+        new_offset += new_end;
+    }
+}
+
+#[cfg(test)]
+fn apply_ops(old: &str, new: &str) -> (String, String, usize) {
+    let mut rebuilt_old = String::new();
+    let mut rebuilt_new = String::new();
+    let mut changed = 0;
+    diff_lines(old, new, Algorithm::Myers, |op| {
+        assert_eq!(op.old_start, Lines(&rebuilt_old).count());
+        assert_eq!(op.new_start, Lines(&rebuilt_new).count());
+        assert!(op.old_lines.len() <= WINDOW && op.new_lines.len() <= WINDOW);
+        if op.tag == DiffTag::Equal {
+            assert_eq!(op.old_lines, op.new_lines);
+        } else {
+            changed += op.old_lines.len() + op.new_lines.len();
+        }
+        rebuilt_old.extend(op.old_lines.iter().copied());
+        rebuilt_new.extend(op.new_lines.iter().copied());
+    });
+    (rebuilt_old, rebuilt_new, changed)
+}
+
+#[test]
+fn test_diff_lines() {
+    let (old, new, changed) = apply_ops("a\nb\nc", "a\nx\nc\n");
+    assert_eq!((old.as_str(), new.as_str()), ("a\nb\nc", "a\nx\nc\n"));
+    assert_eq!(changed, 4);
+    assert_eq!(apply_ops("", "").2, 0);
+    assert_eq!(
+        apply_ops("a\nb\n", "c\n"),
+        ("a\nb\n".into(), "c\n".into(), 3)
+    );
+    assert_eq!(apply_ops("", "c\n").2, 1);
+}
+
+#[test]
+fn test_diff_lines_large() {
+    let old: String = (0..200_000).map(|x| format!("line {}\n", x)).collect();
+    let new = old
+        .replace("line 1000\n", "changed\n")
+        .replace("line 150000\n", "")
+        + "added\n";
+    let (rebuilt_old, rebuilt_new, changed) = apply_ops(&old, &new);
+    assert!(rebuilt_old == old && rebuilt_new == new);
+    assert_eq!(changed, 4);
+
+    // nothing in common
+    let new: String = (0..5000).map(|x| format!("other {}\n", x)).collect();
+    let (rebuilt_old, rebuilt_new, changed) = apply_ops(&old, &new);
+    assert!(rebuilt_old == old && rebuilt_new == new);
+    assert_eq!(changed, 205_000);
+}
@@ -0,0 +1,210 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::settings::Settings;
+
+thread_local! {
+    /// The records captured on this thread and the level they are captured
+    /// at, `None` if nothing is captured.
+    static CAPTURED: RefCell<Option<(LevelFilter, Vec<String>)>> = RefCell::new(None);
+}
+
+/// Keys of values in log messages that identify spans and traces.
+const ID_KEYS: &[(&str, &str)] = &[
+    ("span_id=", "[SPAN_ID]"),
+    ("span=", "[SPAN_ID]"),
+    ("trace_id=", "[TRACE_ID]"),
+];
+
+struct CaptureLogger;
+
+impl Log for CaptureLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        CAPTURED.with(|captured| {
+            captured
+                .borrow()
+                .as_ref()
+                .map_or(false, |(level, _)| metadata.level() <= *level)
+        })
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_record(record);
+        CAPTURED.with(|captured| {
+            if let Some((_, lines)) = captured.borrow_mut().as_mut() {
+                lines.push(line);
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger;
+
+/// Installs the capturing logger and returns if it is installed.
+///
+/// This fails if another logger was installed first.
+fn install_logger() -> bool {
+    static INSTALL: Once = Once::new();
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    INSTALL.call_once(|| {
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(LevelFilter::Trace);
+            INSTALLED.store(true, Ordering::SeqCst);
+        }
+    });
+    INSTALLED.load(Ordering::SeqCst)
+}
+
+/// Formats a record as the level, the target and the message.
+///
+/// Continuation lines of messages are indented so that every record starts
+/// on its own line.
+fn format_record(record: &Record<'_>) -> String {
+    let message = redact_volatile(&record.args().to_string());
+    format!(
+        "{} {}: {}",
+        record.level(),
+        record.target(),
+        message.replace('\n', "\n  ")
+    )
+}
+
+/// Matches a timestamp like `2021-06-01T12:00:00.123Z` at the start of the
+/// bytes and returns its length.
+fn match_timestamp(bytes: &[u8]) -> Option<usize> {
+    let digits = |start: usize, count: usize| {
+        bytes.len() >= start + count && bytes[start..start + count].iter().all(u8::is_ascii_digit)
+    };
+    let byte = |idx: usize| bytes.get(idx).copied();
+    if !(digits(0, 4)
+        && byte(4) == Some(b'-')
+        && digits(5, 2)
+        && byte(7) == Some(b'-')
+        && digits(8, 2)
+        && (byte(10) == Some(b'T') || byte(10) == Some(b' '))
+        && digits(11, 2)
+        && byte(13) == Some(b':')
+        && digits(14, 2)
+        && byte(16) == Some(b':')
+        && digits(17, 2))
+    {
+        return None;
+    }
+    let mut len = 19;
+    if (byte(len) == Some(b'.') || byte(len) == Some(b',')) && digits(len + 1, 1) {
+        len += 1;
+        while digits(len, 1) {
+            len += 1;
+        }
+    }
+    match byte(len) {
+        Some(b'Z') => len += 1,
+        Some(b'+') | Some(b'-') if digits(len + 1, 2) => {
+            if byte(len + 3) == Some(b':') && digits(len + 4, 2) {
+                len += 6;
+            } else if digits(len + 3, 2) {
+                len += 5;
+            }
+        }
+        _ => {}
+    }
+    Some(len)
+}
+
+/// Replaces timestamps and span or trace ids in a log message.
+fn redact_volatile(message: &str) -> String {
+    let bytes = message.as_bytes();
+    let mut rv = String::new();
+    let mut idx = 0;
+    let mut last = 0;
+    while idx < bytes.len() {
+        let at_word_start = idx == 0 || !bytes[idx - 1].is_ascii_alphanumeric();
+        if !at_word_start {
+            idx += 1;
+            continue;
+        }
+        if let Some(len) = match_timestamp(&bytes[idx..]) {
+            rv.push_str(&message[last..idx]);
+            rv.push_str("[TIMESTAMP]");
+            idx += len;
+            last = idx;
+            continue;
+        }
+        let id = ID_KEYS
+            .iter()
+            .find(|(key, _)| bytes[idx..].starts_with(key.as_bytes()));
+        if let Some((key, replacement)) = id {
+            let start = idx + key.len();
+            let end = bytes[start..]
+                .iter()
+                .position(|x| !x.is_ascii_alphanumeric() && *x != b'-')
+                .map_or(bytes.len(), |x| start + x);
+            if end > start {
+                rv.push_str(&message[last..start]);
+                rv.push_str(replacement);
+                last = end;
+            }
+            idx = end;
+            continue;
+        }
+        idx += 1;
+    }
+    rv.push_str(&message[last..]);
+    rv
+}
+
+/// Restores the previous capture when a capture ends, even on panics.
+struct CaptureGuard(Option<Option<(LevelFilter, Vec<String>)>>);
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            CAPTURED.with(|captured| *captured.borrow_mut() = previous);
+        }
+    }
+}
+
+/// Runs a function and returns the log records it emitted as a snapshot.
+///
+/// Records are captured with the `log` crate on the current thread only,
+/// records of threads spawned by the function are not captured.  Each
+/// record is a line with the level, the target and the message.
+/// Timestamps and span or trace ids in messages are redacted.
+///
+/// Panics if another logger is installed as the records cannot be captured
+/// then.
+pub fn capture_logs<F: FnOnce() -> R, R>(f: F) -> String {
+    if !install_logger() {
+        panic!(
+            "cannot capture logs for a snapshot because another logger (like \
+             env_logger) is already installed in this test binary"
+        );
+    }
+    let level = Settings::with(|settings| settings.log_level());
+    let previous = CAPTURED.with(|captured| captured.replace(Some((level, vec![]))));
+    let mut guard = CaptureGuard(Some(previous));
+    f();
+    let previous = guard.0.take().unwrap();
+    let lines = CAPTURED.with(|captured| captured.replace(previous));
+    lines.map_or_else(Vec::new, |x| x.1).join("\n")
+}
+
+#[test]
+fn test_redact_volatile() {
+    assert_eq!(
+        redact_volatile("started at 2021-06-01T12:00:00.123Z (span_id=3f2a, trace_id=abc-123)"),
+        "started at [TIMESTAMP] (span_id=[SPAN_ID], trace_id=[TRACE_ID])"
+    );
+    assert_eq!(
+        redact_volatile("at 2021-06-01 12:00:00+02:00, x2021-06-01T12:00:00, myspan=1"),
+        "at [TIMESTAMP], x2021-06-01T12:00:00, myspan=1"
+    );
+}
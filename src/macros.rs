@@ -4,7 +4,10 @@
 ///
 /// This works exactly like [`assert_yaml_snapshot!`]
 /// but serializes in [CSV](https://github.com/burntsushi/rust-csv) format instead of
-/// YAML.
+/// YAML.  A string is parsed as raw CSV and written again, which takes
+/// care of line endings and quoting.  The dialect is configured with the
+/// settings, eg: [`Settings::set_csv_delimiter`](crate::Settings::set_csv_delimiter)
+/// and [`Settings::set_csv_columns`](crate::Settings::set_csv_columns).
 ///
 /// Example:
 ///
@@ -21,22 +24,58 @@
 #[macro_export]
 macro_rules! assert_csv_snapshot {
     ($value:expr, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, Csv, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Csv, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, Csv, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Csv, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, Csv);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Csv);
     }};
     ($name:expr, $value:expr) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, Csv);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Csv);
     }};
     ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, Csv);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Csv);
     }};
     ($value:expr) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, Csv);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Csv);
+    }};
+}
+
+/// Asserts a `Serialize` snapshot in TSV format.
+///
+/// **Feature:** `csv` (disabled by default)
+///
+/// This works exactly like [`assert_csv_snapshot!`] but the fields are
+/// delimited by tabs.  Raw TSV strings are parsed, so exports with
+/// Windows line endings can be asserted directly.
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_tsv_snapshot!(fs::read_to_string("export.tsv").unwrap());
+/// ```
+#[cfg(feature = "csv")]
+#[macro_export]
+macro_rules! assert_tsv_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Tsv, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Tsv, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Tsv);
+    }};
+    ($name:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Tsv);
+    }};
+    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Tsv);
+    }};
+    ($value:expr) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Tsv);
     }};
 }
 
@@ -64,22 +103,22 @@ macro_rules! assert_csv_snapshot {
 #[macro_export]
 macro_rules! assert_toml_snapshot {
     ($value:expr, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, Toml, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Toml, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, Toml, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Toml, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, Toml);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Toml);
     }};
     ($name:expr, $value:expr) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, Toml);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Toml);
     }};
     ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, Toml);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Toml);
     }};
     ($value:expr) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, Toml);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Toml);
     }};
 }
 
@@ -128,22 +167,22 @@ macro_rules! assert_toml_snapshot {
 #[macro_export]
 macro_rules! assert_yaml_snapshot {
     ($value:expr, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, Yaml, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Yaml, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, Yaml, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Yaml, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, Yaml);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Yaml);
     }};
     ($name:expr, $value:expr) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, Yaml);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Yaml);
     }};
     ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, Yaml);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Yaml);
     }};
     ($value:expr) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, Yaml);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Yaml);
     }};
 }
 
@@ -171,22 +210,22 @@ macro_rules! assert_yaml_snapshot {
 #[macro_export]
 macro_rules! assert_ron_snapshot {
     ($value:expr, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, Ron, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Ron, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, Ron, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Ron, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, Ron);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Ron);
     }};
     ($name:expr, $value:expr) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, Ron);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Ron);
     }};
     ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, Ron);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Ron);
     }};
     ($value:expr) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, Ron);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Ron);
     }};
 }
 
@@ -211,32 +250,385 @@ macro_rules! assert_ron_snapshot {
 #[macro_export]
 macro_rules! assert_json_snapshot {
     ($value:expr, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, Json, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Json, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Json, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Json);
+    }};
+    ($name:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Json);
+    }};
+    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Json);
+    }};
+    ($value:expr) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Json);
+    }};
+}
+
+/// Asserts a `serde_json::Value` snapshot compared structurally.
+///
+/// The value is stored like [`assert_json_snapshot!`] stores it but the
+/// comparison is done on the parsed JSON instead of the text.  The order of
+/// keys in objects does not matter and numbers are compared by value, so
+/// `1.0` matches `1`.  This is useful for responses of other services that
+/// are not stable in these regards.  A stored snapshot is only replaced
+/// once the value changes for other reasons.  See
+/// [`Settings::set_structural_json`](crate::Settings::set_structural_json)
+/// to enable this for other JSON snapshots.
+///
+/// Example:
+///
+/// ```no_run
+/// # use insta::*;
+/// let value = serde_json::json!({"id": 1.0, "name": "John"});
+/// assert_json_value_snapshot!(value);
+/// ```
+///
+/// It takes the same arguments as [`assert_json_snapshot!`].
+#[macro_export]
+macro_rules! assert_json_value_snapshot {
+    ($($arg:tt)*) => {{
+        let mut settings = $crate::Settings::clone_current();
+        settings.set_structural_json(true);
+        settings.bind(|| {
+            $crate::assert_json_snapshot!($($arg)*);
+        });
+    }};
+}
+
+/// Asserts a `Serialize` snapshot or raw XML string in canonical XML format.
+///
+/// **Feature:** `xml` (disabled by default)
+///
+/// This works exactly like [`assert_yaml_snapshot!`] but renders the value
+/// as XML.  If the value is a string containing XML it's parsed first,
+/// strings that are not valid XML are shown as text.  In either case the
+/// output is canonicalized: attributes are sorted by name, insignificant
+/// whitespace and comments are dropped and elements are consistently
+/// indented.  Text mixed with child elements keeps its place between them.
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_xml_snapshot!(r#"<user b="2" a="1"><name> John </name></user>"#);
+/// ```
+///
+/// The third argument to the macro can be an object expression for redaction.
+/// It's in the form `{ selector => replacement }`.  Attributes are addressed
+/// with a leading `@` (eg: `.user["@id"]`) and text nodes as `$text`.  For
+/// more information about redactions refer to the [redactions feature in
+/// the guide](https://insta.rs/docs/redactions/).
+///
+/// The snapshot name is optional but can be provided as first argument.
+#[cfg(feature = "xml")]
+#[macro_export]
+macro_rules! assert_xml_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Xml, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Xml, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Xml);
+    }};
+    ($name:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Xml);
+    }};
+    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Xml);
+    }};
+    ($value:expr) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Xml);
+    }};
+}
+
+/// Asserts MessagePack encoded bytes as a readable YAML snapshot.
+///
+/// **Feature:** `msgpack` (disabled by default)
+///
+/// The value is a byte slice or vector holding one MessagePack value.  It's
+/// decoded and stored like [`assert_yaml_snapshot!`] stores serialized
+/// values so that changes show up as readable diffs instead of changed
+/// bytes.  Integers are shown without their encoded width, extension
+/// values as maps with the type under `$ext` and the data under `$data`.
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_msgpack_snapshot!(rmp_serde::to_vec_named(&response).unwrap());
+/// ```
+///
+/// The third argument to the macro can be an object expression for redaction.
+/// It's in the form `{ selector => replacement }`.  For more information
+/// about redactions refer to the [redactions feature in the guide](https://insta.rs/docs/redactions/).
+///
+/// The snapshot name is optional but can be provided as first argument.
+#[cfg(feature = "msgpack")]
+#[macro_export]
+macro_rules! assert_msgpack_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Msgpack, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
-        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, Json, @$snapshot);
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Msgpack, @$snapshot);
     }};
     ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, Json);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Msgpack);
     }};
     ($name:expr, $value:expr) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, Json);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Msgpack);
     }};
     ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
-        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, Json);
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Msgpack);
     }};
     ($value:expr) => {{
-        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, Json);
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Msgpack);
+    }};
+}
+
+/// Asserts CBOR encoded bytes as a readable YAML snapshot.
+///
+/// **Feature:** `cbor` (disabled by default)
+///
+/// This works like [`assert_msgpack_snapshot!`] for bytes holding one CBOR
+/// data item.  Tagged values are shown as maps with the tag under `$tag`
+/// and the value under `$value`.
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_cbor_snapshot!(serde_cbor::to_vec(&message).unwrap());
+/// ```
+#[cfg(feature = "cbor")]
+#[macro_export]
+macro_rules! assert_cbor_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Cbor, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Cbor, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Cbor);
+    }};
+    ($name:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Cbor);
+    }};
+    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Cbor);
+    }};
+    ($value:expr) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Cbor);
+    }};
+}
+
+/// Asserts an encoded protobuf message as a readable YAML snapshot.
+///
+/// **Feature:** `protobuf` (disabled by default)
+///
+/// This works like [`assert_msgpack_snapshot!`] for bytes holding one
+/// protobuf message, for instance encoded with `prost::Message::encode_to_vec`.
+/// As the schema is not known the message is decoded like
+/// `protoc --decode_raw` does: fields are keyed by their number, repeated
+/// fields become sequences and length delimited fields become strings,
+/// nested messages or bytes depending on what they hold.  Fields can be
+/// redacted by number with subscripts like `"[2][1]"`.
+///
+/// This is a fallback for messages without a schema at hand.  The wire
+/// format is ambiguous, so a nested message made of printable bytes shows
+/// as a string, negative `int32`/`int64` fields show as large unsigned
+/// integers and `sint32`/`sint64` fields show their zigzag encoding.
+/// Messages that also have a serde mapping (eg: generated with `pbjson`)
+/// should be snapshotted in their JSON mapping with
+/// [`assert_json_snapshot!`] instead, which keeps field names and types.
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_protobuf_snapshot!(response.encode_to_vec(), {
+///     "[1]" => "[id]",
+/// });
+/// ```
+#[cfg(feature = "protobuf")]
+#[macro_export]
+macro_rules! assert_protobuf_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Protobuf, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Protobuf, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Protobuf);
+    }};
+    ($name:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Protobuf);
+    }};
+    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Protobuf);
+    }};
+    ($value:expr) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Protobuf);
+    }};
+}
+
+/// Asserts rows or columns as an aligned table.
+///
+/// **Feature:** `table` (disabled by default)
+///
+/// The value is serialized like for [`assert_yaml_snapshot!`] and then
+/// rendered as a table.  It can either be a sequence of rows (structs or
+/// maps) or a map of equally long columns such as a
+/// `BTreeMap<String, Vec<T>>`.  Polars dataframes can be passed directly
+/// if Polars is built with its `serde` feature, their columns then show
+/// the Polars data types.  Arrow record batches need to be converted to
+/// rows first, for instance with `arrow_json::ArrayWriter`.  Above the rows
+/// the table shows its shape, the column names and the types of the
+/// columns:
+///
+/// ```text
+/// shape: (2, 3)
+/// | id  | name  | score |
+/// | int | str   | float |
+/// |-----|-------|-------|
+/// |   1 | peter |   1.5 |
+/// |   2 | john  |  null |
+/// ```
+///
+/// The rows shown, the precision of floats and the columns shown can be
+/// changed with [`Settings::set_table_max_rows`](crate::Settings::set_table_max_rows),
+/// [`Settings::set_table_float_precision`](crate::Settings::set_table_float_precision)
+/// and [`Settings::set_table_excluded_columns`](crate::Settings::set_table_excluded_columns).
+///
+/// The third argument to the macro can be an object expression for redaction.
+/// It's in the form `{ selector => replacement }`.  For more information
+/// about redactions refer to the [redactions feature in the guide](https://insta.rs/docs/redactions/).
+///
+/// The snapshot name is optional but can be provided as first argument.
+#[cfg(feature = "table")]
+#[macro_export]
+macro_rules! assert_table_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Table, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Table, @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Table);
+    }};
+    ($name:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Table);
+    }};
+    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Table);
+    }};
+    ($value:expr) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Table);
+    }};
+}
+
+/// Asserts a numeric vector, matrix or array compared elementwise.
+///
+/// **Feature:** `matrix` (disabled by default)
+///
+/// The value can be nested sequences of numbers like `Vec<Vec<f64>>` or
+/// arrays, an `ndarray` array or a dynamically sized `nalgebra` matrix
+/// serialized with their `serde` features.  It's stored as its shape
+/// followed by the aligned rows:
+///
+/// ```text
+/// shape: [2, 3]
+///  1.0  2.5  -3.0
+///  4.0  5.0   6.0
+/// ```
+///
+/// The elements are compared one by one within the tolerances set with
+/// [`Settings::set_float_tolerance`](crate::Settings::set_float_tolerance)
+/// and [`Settings::set_relative_float_tolerance`](crate::Settings::set_relative_float_tolerance),
+/// the formatting of the rows does not matter.  If an assertion fails the
+/// largest deviation is reported with the index of the element.
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// with_settings!({float_tolerance => 1e-9}, {
+///     assert_matrix_snapshot!(solve(&system));
+/// });
+/// ```
+///
+/// The snapshot name is optional.
+#[cfg(feature = "matrix")]
+#[macro_export]
+macro_rules! assert_matrix_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Matrix, @$snapshot);
+    }};
+    ($name:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Matrix);
+    }};
+    ($value:expr) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Matrix);
+    }};
+}
+
+/// Asserts a `Serialize` snapshot in a custom format.
+///
+/// The first argument is a [`SnapshotSerializer`](crate::SnapshotSerializer)
+/// which turns the serialized value into text.  This makes it possible to
+/// snapshot formats insta does not support itself, like KDL, HCL or EDN.
+/// Maps are sorted and redactions applied before the serializer is invoked
+/// like for the built-in formats.
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_custom_snapshot!(Kdl, &config);
+/// ```
+///
+/// The third argument to the macro can be an object expression for redaction.
+/// It's in the form `{ selector => replacement }`.  For more information
+/// about redactions refer to the [redactions feature in the guide](https://insta.rs/docs/redactions/).
+///
+/// The snapshot name is optional but can be provided as second argument.
+#[macro_export]
+macro_rules! assert_custom_snapshot {
+    ($format:expr, $value:expr, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, $crate::_macro_support::SerializationFormat::Custom(&$format), @$snapshot);
+    }};
+    ($format:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        $crate::_assert_serialized_snapshot!($value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Custom(&$format), @$snapshot);
+    }};
+    ($format:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Custom(&$format));
+    }};
+    ($format:expr, $name:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, $crate::_macro_support::SerializationFormat::Custom(&$format));
+    }};
+    ($format:expr, $name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        $crate::_assert_serialized_snapshot!(Some($name), $value, {$($k => $v),*}, $crate::_macro_support::SerializationFormat::Custom(&$format));
+    }};
+    ($format:expr, $value:expr) => {{
+        $crate::_assert_serialized_snapshot!($crate::_macro_support::AutoName, $value, $crate::_macro_support::SerializationFormat::Custom(&$format));
     }};
 }
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _assert_serialized_snapshot {
-    ($value:expr, $format:ident, @$snapshot:literal) => {{
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, $format:expr, @$snapshot:literal) => {{
+        let (vec, value) = $crate::_prepare_snapshot_for_redaction!($value, {$($k => $v),*}, $format, Inline);
+        $crate::assert_snapshot!(value, stringify!($value), @$snapshot);
+    }};
+    ($value:expr, $format:expr, @$snapshot:literal) => {{
         let value = $crate::_macro_support::serialize_value(
             &$value,
-            $crate::_macro_support::SerializationFormat::$format,
+            $format,
             $crate::_macro_support::SnapshotLocation::Inline
         );
         $crate::assert_snapshot!(
@@ -245,14 +637,14 @@ macro_rules! _assert_serialized_snapshot {
             @$snapshot
         );
     }};
-    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, $format:ident, @$snapshot:literal) => {{
-        let (vec, value) = $crate::_prepare_snapshot_for_redaction!($value, {$($k => $v),*}, $format, Inline);
-        $crate::assert_snapshot!(value, stringify!($value), @$snapshot);
+    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}, $format:expr) => {{
+        let (vec, value) = $crate::_prepare_snapshot_for_redaction!($value, {$($k => $v),*}, $format, File);
+        $crate::assert_snapshot!($name, value, stringify!($value));
     }};
-    ($name:expr, $value:expr, $format:ident) => {{
+    ($name:expr, $value:expr, $format:expr) => {{
         let value = $crate::_macro_support::serialize_value(
             &$value,
-            $crate::_macro_support::SerializationFormat::$format,
+            $format,
             $crate::_macro_support::SnapshotLocation::File
         );
         $crate::assert_snapshot!(
@@ -261,18 +653,31 @@ macro_rules! _assert_serialized_snapshot {
             stringify!($value)
         );
     }};
-    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}, $format:ident) => {{
-        let (vec, value) = $crate::_prepare_snapshot_for_redaction!($value, {$($k => $v),*}, $format, File);
-        $crate::assert_snapshot!($name, value, stringify!($value));
-    }}
+}
+
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _validate_selectors {
+    ($($k:expr),*) => {
+        $crate::_macro_support::validate_selectors!($($k),*);
+    };
+}
+
+#[cfg(not(feature = "macros"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _validate_selectors {
+    ($($k:expr),*) => {};
 }
 
 #[cfg(feature = "redactions")]
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _prepare_snapshot_for_redaction {
-    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, $format:ident, $location:ident) => {
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, $format:expr, $location:ident) => {
         {
+            $crate::_validate_selectors!($($k),*);
             let vec = vec![
                 $((
                     $crate::_macro_support::Selector::parse($k).unwrap(),
@@ -282,7 +687,7 @@ macro_rules! _prepare_snapshot_for_redaction {
             let value = $crate::_macro_support::serialize_value_redacted(
                 &$value,
                 &vec,
-                $crate::_macro_support::SerializationFormat::$format,
+                $format,
                 $crate::_macro_support::SnapshotLocation::$location
             );
             (vec, value)
@@ -294,7 +699,34 @@ macro_rules! _prepare_snapshot_for_redaction {
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _prepare_snapshot_for_redaction {
-    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, $format:ident, $location:ident) => {
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, $format:expr, $location:ident) => {
+        compile_error!("insta was compiled without redaction support.");
+    };
+}
+
+#[cfg(feature = "redactions")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _prepare_debug_snapshot_for_redaction {
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {
+        {
+            $crate::_validate_selectors!($($k),*);
+            let vec = vec![
+                $((
+                    $crate::_macro_support::Selector::parse($k).unwrap(),
+                    $crate::_macro_support::Redaction::from($v)
+                ),)*
+            ];
+            $crate::_macro_support::redact_debug_output(&format!("{:#?}", $value), &vec)
+        }
+    }
+}
+
+#[cfg(not(feature = "redactions"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _prepare_debug_snapshot_for_redaction {
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {
         compile_error!("insta was compiled without redaction support.");
     };
 }
@@ -302,16 +734,42 @@ macro_rules! _prepare_snapshot_for_redaction {
 /// Asserts a `Debug` snapshot.
 ///
 /// The value needs to implement the `fmt::Debug` trait.  This is useful for
-/// simple values that do not implement the `Serialize` trait but does not
-/// permit redactions.
+/// simple values that do not implement the `Serialize` trait.
 ///
 /// The snapshot name is optional.
+///
+/// Redactions can be passed like for [`assert_yaml_snapshot!`].  For this
+/// the `Debug` output is parsed back into a structure which works for the
+/// output of derived `Debug` implementations.  The redactions of the
+/// settings are only applied if redactions are passed to the macro.
+/// (requires the `redactions` feature)
+///
+#[cfg_attr(feature = "redactions", doc = " ```no_run")]
+#[cfg_attr(not(feature = "redactions"), doc = " ```ignore")]
+/// # use insta::*;
+/// # #[derive(Debug)] struct Session { token: &'static str }
+/// assert_debug_snapshot!(Session { token: "e4f2" }, {
+///     ".token" => "[token]",
+/// });
+/// ```
 #[macro_export]
 macro_rules! assert_debug_snapshot {
     ($value:expr, @$snapshot:literal) => {{
         let value = format!("{:#?}", $value);
         $crate::assert_snapshot!(value, stringify!($value), @$snapshot);
     }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        let value = $crate::_prepare_debug_snapshot_for_redaction!($value, {$($k => $v),*});
+        $crate::assert_snapshot!(value, stringify!($value), @$snapshot);
+    }};
+    ($value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        let value = $crate::_prepare_debug_snapshot_for_redaction!($value, {$($k => $v),*});
+        $crate::assert_snapshot!($crate::_macro_support::AutoName, value, stringify!($value));
+    }};
+    ($name:expr, $value:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        let value = $crate::_prepare_debug_snapshot_for_redaction!($value, {$($k => $v),*});
+        $crate::assert_snapshot!(Some($name), value, stringify!($value));
+    }};
     ($name:expr, $value:expr) => {{
         let value = format!("{:#?}", $value);
         $crate::assert_snapshot!(Some($name), value, stringify!($value));
@@ -343,6 +801,222 @@ macro_rules! assert_display_snapshot {
     }};
 }
 
+/// Asserts an SQL snapshot in a normalized layout.
+///
+/// **Feature:** `sql` (disabled by default)
+///
+/// The value is a string holding one or more SQL statements.  Before it is
+/// stored or compared the SQL is formatted: keywords are upper cased,
+/// whitespace and comments are normalized and every clause goes on a line
+/// of its own, which also makes diffs of queries readable.  This way only
+/// changes to the queries show up, not changes to how they are formatted.
+/// Literals can be replaced with
+/// [`Settings::set_sql_strip_literals`](crate::Settings::set_sql_strip_literals).
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_sql_snapshot!(query.to_sql(), @r###"
+/// SELECT
+///   id
+/// FROM
+///   users
+/// "###);
+/// ```
+///
+/// The snapshot name is optional.
+#[cfg(feature = "sql")]
+#[macro_export]
+macro_rules! assert_sql_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        let value = $crate::_macro_support::format_sql_snapshot(&$value);
+        $crate::assert_snapshot!(value, stringify!($value), @$snapshot);
+    }};
+    ($name:expr, $value:expr) => {{
+        let value = $crate::_macro_support::format_sql_snapshot(&$value);
+        $crate::assert_snapshot!(Some($name), value, stringify!($value));
+    }};
+    ($value:expr) => {{
+        let value = $crate::_macro_support::format_sql_snapshot(&$value);
+        $crate::assert_snapshot!($crate::_macro_support::AutoName, value, stringify!($value));
+    }};
+}
+
+/// Asserts an HTML snapshot in a normalized layout.
+///
+/// **Feature:** `html` (disabled by default)
+///
+/// The value is a string holding HTML markup, a whole document or a
+/// fragment.  It's parsed and written again with one element per line,
+/// sorted attributes and collapsed whitespace, so that differences in how
+/// template engines serialize markup do not fail the assertion.  Volatile
+/// attributes can be left out with
+/// [`Settings::set_html_removed_attributes`](crate::Settings::set_html_removed_attributes).
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_html_snapshot!(template.render(&context), @r###"
+/// <p class="greeting">
+///   Hello
+///   <b>World</b>
+/// </p>
+/// "###);
+/// ```
+///
+/// The snapshot name is optional.
+#[cfg(feature = "html")]
+#[macro_export]
+macro_rules! assert_html_snapshot {
+    ($value:expr, @$snapshot:literal) => {{
+        let value = $crate::_macro_support::format_html_snapshot(&$value);
+        $crate::assert_snapshot!(value, stringify!($value), @$snapshot);
+    }};
+    ($name:expr, $value:expr) => {{
+        let value = $crate::_macro_support::format_html_snapshot(&$value);
+        $crate::assert_snapshot!(Some($name), value, stringify!($value));
+    }};
+    ($value:expr) => {{
+        let value = $crate::_macro_support::format_html_snapshot(&$value);
+        $crate::assert_snapshot!($crate::_macro_support::AutoName, value, stringify!($value));
+    }};
+}
+
+/// Asserts the output of a command.
+///
+/// **Feature:** `cmd` (disabled by default)
+///
+/// The command is run to completion and its exit status, stdout and stderr
+/// are stored in one snapshot.  The command line is recorded in the
+/// metadata of the snapshot.  The value can be a
+/// [`Command`](std::process::Command) or a mutable reference to one.
+///
+/// Filters only for one stream can be registered with
+/// [`Settings::add_stdout_filter`](crate::Settings::add_stdout_filter) and
+/// [`Settings::add_stderr_filter`](crate::Settings::add_stderr_filter)
+/// (requires the `filters` feature).
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_cmd_snapshot!(Command::new("echo").arg("Hello World!"), @r###"
+/// success: true
+/// exit_code: 0
+/// ----- stdout -----
+/// Hello World!
+///
+/// ----- stderr -----
+/// "###);
+/// ```
+///
+/// The snapshot name is optional.
+#[cfg(feature = "cmd")]
+#[macro_export]
+macro_rules! assert_cmd_snapshot {
+    ($cmd:expr, @$snapshot:literal) => {{
+        let (value, settings) = $crate::_macro_support::run_cmd_snapshot($cmd);
+        settings.bind(|| {
+            $crate::assert_snapshot!(value, stringify!($cmd), @$snapshot);
+        });
+    }};
+    ($name:expr, $cmd:expr) => {{
+        let (value, settings) = $crate::_macro_support::run_cmd_snapshot($cmd);
+        settings.bind(|| {
+            $crate::assert_snapshot!(Some($name), value, stringify!($cmd));
+        });
+    }};
+    ($cmd:expr) => {{
+        let (value, settings) = $crate::_macro_support::run_cmd_snapshot($cmd);
+        settings.bind(|| {
+            $crate::assert_snapshot!($crate::_macro_support::AutoName, value, stringify!($cmd));
+        });
+    }};
+}
+
+/// Asserts an HTTP exchange in YAML format.
+///
+/// **Feature:** `http` (disabled by default)
+///
+/// The value is an [`HttpExchange`](crate::HttpExchange) holding the
+/// method, URL, status, headers and bodies of a request and its response.
+/// Volatile headers like `date` are redacted.  Otherwise this works exactly
+/// like [`assert_yaml_snapshot!`], including redactions of the parsed
+/// bodies:
+///
+/// ```no_run,ignore
+/// assert_http_snapshot!(exchange, {
+///     ".response.body.id" => "[id]"
+/// }, @r###"
+/// ---
+/// request:
+///   method: POST
+///   url: /users
+/// response:
+///   status: 201
+///   headers:
+///     content-type: application/json
+///     date: "[date]"
+///   body:
+///     id: "[id]"
+/// "###);
+/// ```
+///
+/// The snapshot name is optional.
+#[cfg(feature = "http")]
+#[macro_export]
+macro_rules! assert_http_snapshot {
+    ($($arg:tt)*) => {{
+        $crate::assert_yaml_snapshot!($($arg)*);
+    }};
+}
+
+/// Asserts the log records emitted while running some code.
+///
+/// **Feature:** `log` (disabled by default)
+///
+/// A logger for the [`log`](https://docs.rs/log) crate is installed the
+/// first time this is used and the records the code logs on the current
+/// thread are stored as one line each with the level, the target and the
+/// message.  Timestamps and span or trace ids in messages are replaced with
+/// `[TIMESTAMP]`, `[SPAN_ID]` and `[TRACE_ID]` so that the snapshot is
+/// deterministic.  The most verbose level that is captured can be changed
+/// with [`Settings::set_log_level`](crate::Settings::set_log_level).
+///
+/// Only one logger can be installed for the `log` crate.  If another
+/// logger such as `env_logger` was installed first, the assertion fails
+/// with an error saying so.  Events of the `tracing` crate are only
+/// captured if `tracing` passes them on as log records (its `log` feature)
+/// and no subscriber is set.
+///
+/// Example:
+///
+/// ```no_run,ignore
+/// assert_log_snapshot!({
+///     connect("db://localhost");
+/// }, @r###"
+/// INFO app::db: connecting to db://localhost
+/// WARN app::db: retrying at [TIMESTAMP]
+/// "###);
+/// ```
+///
+/// The snapshot name is optional.
+#[cfg(feature = "log")]
+#[macro_export]
+macro_rules! assert_log_snapshot {
+    ($body:expr, @$snapshot:literal) => {{
+        let value = $crate::_macro_support::capture_logs(|| $body);
+        $crate::assert_snapshot!(value, stringify!($body), @$snapshot);
+    }};
+    ($name:expr, $body:expr) => {{
+        let value = $crate::_macro_support::capture_logs(|| $body);
+        $crate::assert_snapshot!(Some($name), value, stringify!($body));
+    }};
+    ($body:expr) => {{
+        let value = $crate::_macro_support::capture_logs(|| $body);
+        $crate::assert_snapshot!($crate::_macro_support::AutoName, value, stringify!($body));
+    }};
+}
+
 /// Asserts a string snapshot.
 ///
 /// This is the most simplistic of all assertion methods.  It just accepts
@@ -363,14 +1037,20 @@ macro_rules! assert_display_snapshot {
 macro_rules! assert_snapshot {
     ($value:expr, @$snapshot:literal) => {
         $crate::assert_snapshot!(
-            $crate::_macro_support::ReferenceValue::Inline($snapshot),
+            $crate::_macro_support::ReferenceValue::inline(
+                $snapshot,
+                option_env!("UNSTABLE_RUSTDOC_TEST_LINE"),
+            ),
             $value,
             stringify!($value)
         )
     };
     ($value:expr, $debug_expr:expr, @$snapshot:literal) => {
         $crate::assert_snapshot!(
-            $crate::_macro_support::ReferenceValue::Inline($snapshot),
+            $crate::_macro_support::ReferenceValue::inline(
+                $snapshot,
+                option_env!("UNSTABLE_RUSTDOC_TEST_LINE"),
+            ),
             $value,
             $debug_expr
         )
@@ -389,13 +1069,170 @@ macro_rules! assert_snapshot {
             line!(),
             $debug_expr,
         )
-        .unwrap();
+        .unwrap()
     };
     ($value:expr) => {
         $crate::assert_snapshot!($crate::_macro_support::AutoName, $value, stringify!($value))
     };
 }
 
+/// Asserts a binary snapshot.
+///
+/// The first argument is the name of the snapshot including the extension
+/// of the file the bytes are stored in (eg: `"logo.png"`).  The value can
+/// be anything that derefs into `[u8]`.  The bytes are written next to the
+/// `.snap` file into a file with that extension.  If only the extension
+/// is given (eg: `".png"`) the snapshot name is picked automatically.
+///
+/// ```no_run
+/// # use insta::*;
+/// assert_binary_snapshot!("hello.bin", b"\x00\x01\x02".to_vec());
+/// ```
+///
+/// By default the contents have to match byte for byte.  A custom comparison
+/// can be registered per extension with
+/// [`Settings::add_binary_comparator`](crate::Settings::add_binary_comparator).
+/// Binary snapshots cannot be stored inline.
+#[macro_export]
+macro_rules! assert_binary_snapshot {
+    ($name_and_extension:expr, $value:expr) => {
+        $crate::_macro_support::assert_binary_snapshot(
+            $name_and_extension,
+            ::std::convert::AsRef::<[u8]>::as_ref(&$value),
+            env!("CARGO_MANIFEST_DIR"),
+            module_path!(),
+            file!(),
+            line!(),
+            stringify!($value),
+        )
+        .unwrap()
+    };
+}
+
+/// Creates a writer to build a binary snapshot incrementally.
+///
+/// The argument is the name of the snapshot with an extension like for
+/// [`assert_binary_snapshot!`](crate::assert_binary_snapshot!).  The
+/// returned [`SnapshotWriter`](crate::SnapshotWriter) implements
+/// [`std::io::Write`] and the snapshot is asserted once
+/// [`finish`](crate::SnapshotWriter::finish) is called:
+///
+/// ```no_run
+/// # use std::io::Write;
+/// let mut writer = insta::snapshot_writer!("report.csv");
+/// for row in 0..1_000_000 {
+///     writeln!(writer, "{},{}", row, row * row).unwrap();
+/// }
+/// writer.finish();
+/// ```
+///
+/// The contents are spooled to a temporary file and compared with the stored
+/// snapshot in chunks, so snapshots much larger than memory can be asserted.
+/// The name part is optional (eg: `".txt"`).
+#[macro_export]
+macro_rules! snapshot_writer {
+    ($name_and_extension:expr) => {
+        $crate::_macro_support::snapshot_writer(
+            $name_and_extension,
+            env!("CARGO_MANIFEST_DIR"),
+            module_path!(),
+            file!(),
+            line!(),
+            stringify!($name_and_extension),
+        )
+    };
+}
+
+/// Asserts a PNG image snapshot.
+///
+/// **Feature:** `image` (disabled by default)
+///
+/// The value needs to be PNG encoded bytes.  The image is stored next to the
+/// `.snap` file and compared perceptually instead of byte for byte, so that
+/// tiny rendering differences do not fail the assertion.  The sensitivity is
+/// controlled with [`Settings::set_image_threshold`](crate::Settings::set_image_threshold).
+/// If the snapshot does not match, the review shows the percentage of
+/// differing pixels and
+/// [`Settings::set_write_image_diff`](crate::Settings::set_write_image_diff)
+/// can be used to write a visual diff next to the pending snapshot.
+///
+/// The snapshot name is optional.
+///
+/// ```no_run
+/// # use insta::*;
+/// # let png_bytes = vec![];
+/// assert_image_snapshot!("logo", png_bytes);
+/// ```
+#[cfg(feature = "image")]
+#[macro_export]
+macro_rules! assert_image_snapshot {
+    ($name:expr, $value:expr) => {
+        $crate::assert_binary_snapshot!(&format!("{}.png", $name), $value)
+    };
+    ($value:expr) => {
+        $crate::assert_binary_snapshot!(".png", $value)
+    };
+}
+
+/// Asserts a snapshot of a directory tree.
+///
+/// **Feature:** `dir` (disabled by default)
+///
+/// The snapshot records the sorted list of all files below the given path
+/// followed by the contents of every file.  Files that are not valid UTF-8
+/// are recorded by size and hash instead.  When a directory snapshot fails,
+/// the diff is shown per file.
+///
+/// ```no_run
+/// # use insta::*;
+/// assert_dir_snapshot!("generated", "target/codegen");
+/// ```
+///
+/// The last argument can be an object expression mapping glob patterns
+/// (relative to the directory) to a [`FileRedaction`](crate::FileRedaction).
+/// The first matching pattern wins.  Strings replace the contents of the
+/// file:
+///
+/// ```no_run
+/// # use insta::*;
+/// assert_dir_snapshot!("target/codegen", {
+///     "cache/**" => FileRedaction::Exclude,
+///     "**/*.lock" => "[lockfile]",
+/// });
+/// ```
+///
+/// The snapshot name is optional but can be provided as first argument.
+#[cfg(feature = "dir")]
+#[macro_export]
+macro_rules! assert_dir_snapshot {
+    ($path:expr, @$snapshot:literal) => {
+        $crate::assert_dir_snapshot!($path, {}, @$snapshot)
+    };
+    ($path:expr, {$($k:expr => $v:expr),*$(,)?}, @$snapshot:literal) => {{
+        let value = $crate::_macro_support::dir_snapshot(
+            ::std::path::Path::new(&$path),
+            vec![$(($k, $v.into())),*],
+        );
+        $crate::assert_snapshot!(value, stringify!($path), @$snapshot);
+    }};
+    ($path:expr, {$($k:expr => $v:expr),*$(,)?}) => {
+        $crate::assert_dir_snapshot!($crate::_macro_support::AutoName, $path, {$($k => $v),*})
+    };
+    ($name:expr, $path:expr) => {
+        $crate::assert_dir_snapshot!($name, $path, {})
+    };
+    ($name:expr, $path:expr, {$($k:expr => $v:expr),*$(,)?}) => {{
+        let value = $crate::_macro_support::dir_snapshot(
+            ::std::path::Path::new(&$path),
+            vec![$(($k, $v.into())),*],
+        );
+        $crate::assert_snapshot!($name, value, stringify!($path));
+    }};
+    ($path:expr) => {
+        $crate::assert_dir_snapshot!($crate::_macro_support::AutoName, $path, {})
+    };
+}
+
 /// Settings configuration macro.
 ///
 /// This macro lets you bind some settings temporarily.  The first argument
@@ -419,6 +1256,43 @@ macro_rules! with_settings {
     }}
 }
 
+/// Builds a snapshot suffix from the active cfgs.
+///
+/// Each cfg that is active in the calling crate contributes its value
+/// (`feature = "big-decimal"` adds `big-decimal`) or its name (`unix`
+/// adds `unix`) and the parts are joined with `-`.  Used as snapshot suffix
+/// this keeps test runs with different features from fighting over the
+/// same snapshot file:
+///
+/// ```rust
+/// let mut settings = insta::Settings::clone_current();
+/// settings.set_snapshot_suffix(insta::cfg_suffix!(feature = "big-decimal", unix));
+/// settings.bind(|| {
+///     // snapshots are now named `name@big-decimal-unix` or `name@unix`
+/// });
+/// ```
+///
+/// If none of the cfgs are active the suffix is empty and the snapshot
+/// names stay unchanged.
+#[macro_export]
+macro_rules! cfg_suffix {
+    (@name $key:ident) => {
+        stringify!($key)
+    };
+    (@name $key:ident = $value:tt) => {
+        $value
+    };
+    ($($key:ident $(= $value:tt)?),+ $(,)?) => {{
+        let mut active: Vec<&str> = Vec::new();
+        $(
+            if cfg!($key $(= $value)?) {
+                active.push($crate::cfg_suffix!(@name $key $(= $value)?));
+            }
+        )+
+        active.join("-")
+    }};
+}
+
 /// Executes a closure for all input files matching a glob.
 ///
 /// The closure is passed the path to the file.
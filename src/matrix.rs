@@ -0,0 +1,285 @@
+use crate::content::Content;
+use crate::floats::numbers_match;
+
+/// Returns the value of a field of a map or struct.
+fn field<'a>(content: &'a Content, name: &str) -> Option<&'a Content> {
+    match content.resolve_inner() {
+        Content::Struct(_, fields) => fields
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value),
+        Content::Map(entries) => entries
+            .iter()
+            .find(|(key, _)| key.as_str() == Some(name))
+            .map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+/// Formats a number of a matrix.
+fn format_element(content: &Content) -> Option<String> {
+    match content.as_f64() {
+        Some(value) => Some(format!("{:?}", value)),
+        None => content
+            .as_i128()
+            .map(|x| x.to_string())
+            .or_else(|| content.as_u128().map(|x| x.to_string())),
+    }
+}
+
+/// Collects the elements of nested sequences in row-major order.
+fn flatten(content: &Content, depth: usize, shape: &mut Vec<usize>, out: &mut Vec<String>) -> bool {
+    match content.as_slice() {
+        Some(items) => {
+            if shape.len() == depth && out.is_empty() {
+                shape.push(items.len());
+            } else if shape.get(depth) != Some(&items.len()) {
+                return false;
+            }
+            items
+                .iter()
+                .all(|item| flatten(item, depth + 1, shape, out))
+        }
+        None if shape.len() == depth => format_element(content).map_or(false, |x| {
+            out.push(x);
+            true
+        }),
+        None => false,
+    }
+}
+
+/// Reads the shape and the elements in row-major order of a matrix.
+///
+/// This understands nested sequences (`Vec<Vec<f64>>`, arrays), the serde
+/// representation of `ndarray` arrays (`{v, dim, data}`) and of dynamically
+/// sized `nalgebra` matrices (`{data, nrows, ncols}` in column-major order).
+fn read_matrix(content: &Content) -> Option<(Vec<usize>, Vec<String>)> {
+    let elements = |data: &Content| -> Option<Vec<String>> {
+        data.as_slice()?.iter().map(format_element).collect()
+    };
+    if let (Some(dim), Some(data)) = (field(content, "dim"), field(content, "data")) {
+        let shape = dim
+            .as_slice()?
+            .iter()
+            .map(|x| x.as_u64().map(|x| x as usize))
+            .collect::<Option<Vec<_>>>()?;
+        return Some((shape, elements(data)?));
+    }
+    if let (Some(nrows), Some(ncols), Some(data)) = (
+        field(content, "nrows").and_then(Content::as_u64),
+        field(content, "ncols").and_then(Content::as_u64),
+        field(content, "data"),
+    ) {
+        let (nrows, ncols) = (nrows as usize, ncols as usize);
+        let column_major = elements(data)?;
+        if column_major.len() != nrows * ncols {
+            return None;
+        }
+        let mut row_major = Vec::with_capacity(column_major.len());
+        for row in 0..nrows {
+            for col in 0..ncols {
+                row_major.push(column_major[col * nrows + row].clone());
+            }
+        }
+        return Some((vec![nrows, ncols], row_major));
+    }
+    let mut shape = vec![];
+    let mut values = vec![];
+    if flatten(content, 0, &mut shape, &mut values) {
+        Some((shape, values))
+    } else {
+        None
+    }
+}
+
+fn format_index(index: &[usize]) -> String {
+    format!(
+        "[{}]",
+        index
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Converts a flat row-major offset into an index.
+fn unravel(mut offset: usize, shape: &[usize]) -> Vec<usize> {
+    let mut rv = vec![0; shape.len()];
+    for (idx, &dim) in shape.iter().enumerate().rev() {
+        if dim > 0 {
+            rv[idx] = offset % dim;
+            offset /= dim;
+        }
+    }
+    rv
+}
+
+/// Formats a matrix as its shape followed by aligned rows.
+///
+/// Arrays with more than two dimensions are written as one block of rows
+/// per index of the leading dimensions, headed by the index of the block.
+pub fn format_matrix(content: &Content) -> String {
+    let (shape, values) = read_matrix(content).unwrap_or_else(|| {
+        panic!("matrix snapshots expect rectangular nested sequences or arrays of numbers")
+    });
+    if shape.iter().product::<usize>() != values.len() {
+        panic!(
+            "matrix snapshot has {} elements but a shape of {}",
+            values.len(),
+            format_index(&shape)
+        );
+    }
+    let width = values.iter().map(|x| x.len()).max().unwrap_or(0);
+    let ncols = shape.last().copied().unwrap_or(1).max(1);
+    let block_len = if shape.len() > 2 {
+        shape[shape.len() - 2] * ncols
+    } else {
+        values.len().max(1)
+    };
+
+    let mut rv = format!("shape: {}", format_index(&shape));
+    for (block_idx, block) in values.chunks(block_len).enumerate() {
+        if shape.len() > 2 {
+            let mut index: Vec<String> = unravel(block_idx * block_len, &shape)[..shape.len() - 2]
+                .iter()
+                .map(|x| x.to_string())
+                .collect();
+            index.push(":".into());
+            index.push(":".into());
+            rv.push_str(&format!("\n\n[{}]", index.join(", ")));
+        }
+        for row in block.chunks(ncols) {
+            rv.push('\n');
+            rv.push_str(
+                &row.iter()
+                    .map(|x| format!("{:>1$}", x, width))
+                    .collect::<Vec<_>>()
+                    .join("  "),
+            );
+        }
+    }
+    rv
+}
+
+/// Parses a matrix written by [`format_matrix`] into its shape and values.
+pub(crate) fn parse_matrix(text: &str) -> Option<(Vec<usize>, Vec<f64>)> {
+    let mut lines = text.lines();
+    let header = lines.next()?.trim();
+    if !header.starts_with("shape: [") || !header.ends_with(']') {
+        return None;
+    }
+    let dims = header["shape: [".len()..header.len() - 1].trim();
+    let shape = if dims.is_empty() {
+        vec![]
+    } else {
+        dims.split(',')
+            .map(|x| x.trim().parse().ok())
+            .collect::<Option<Vec<usize>>>()?
+    };
+    let mut values = vec![];
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('[') {
+            continue;
+        }
+        for value in line.split_whitespace() {
+            values.push(value.parse().ok()?);
+        }
+    }
+    if values.len() == shape.iter().product::<usize>() {
+        Some((shape, values))
+    } else {
+        None
+    }
+}
+
+fn elements_match(old: f64, new: f64, absolute: f64, relative: f64) -> bool {
+    old == new || (old.is_nan() && new.is_nan()) || numbers_match(old, new, absolute, relative)
+}
+
+/// Compares two formatted matrices elementwise within the tolerances.
+///
+/// Returns `None` if the texts are not both matrices.
+pub(crate) fn matrices_match(old: &str, new: &str, absolute: f64, relative: f64) -> Option<bool> {
+    let (old_shape, old_values) = parse_matrix(old)?;
+    let (new_shape, new_values) = parse_matrix(new)?;
+    Some(
+        old_shape == new_shape
+            && old_values
+                .iter()
+                .zip(new_values.iter())
+                .all(|(&old, &new)| elements_match(old, new, absolute, relative)),
+    )
+}
+
+/// Describes how two formatted matrices differ.
+///
+/// This reports a changed shape or the largest deviation of an element
+/// with its index and how many elements differ beyond the tolerances.
+/// Returns `None` if the texts are not both matrices.
+pub(crate) fn describe_deviation(
+    old: &str,
+    new: &str,
+    absolute: f64,
+    relative: f64,
+) -> Option<String> {
+    let (old_shape, old_values) = parse_matrix(old)?;
+    let (new_shape, new_values) = parse_matrix(new)?;
+    if old_shape != new_shape {
+        return Some(format!(
+            "Shape changed: {} -> {}",
+            format_index(&old_shape),
+            format_index(&new_shape)
+        ));
+    }
+    let mut max: Option<(usize, f64)> = None;
+    let mut differing = 0;
+    for (offset, (&old, &new)) in old_values.iter().zip(new_values.iter()).enumerate() {
+        if elements_match(old, new, absolute, relative) {
+            continue;
+        }
+        differing += 1;
+        let deviation = (old - new).abs();
+        let deviation = if deviation.is_nan() {
+            std::f64::INFINITY
+        } else {
+            deviation
+        };
+        if max.map_or(true, |(_, max)| deviation > max) {
+            max = Some((offset, deviation));
+        }
+    }
+    let (offset, deviation) = max?;
+    Some(format!(
+        "Max deviation: {:?} at {} ({:?} -> {:?}), {} of {} elements differ",
+        deviation,
+        format_index(&unravel(offset, &new_shape)),
+        old_values[offset],
+        new_values[offset],
+        differing,
+        new_values.len()
+    ))
+}
+
+#[test]
+fn test_matrix_roundtrip() {
+    let content = Content::Seq(vec![
+        Content::Seq(vec![Content::F64(1.0), Content::F64(-2.5)]),
+        Content::Seq(vec![Content::F64(10.25), Content::F64(0.0)]),
+    ]);
+    let text = format_matrix(&content);
+    assert_eq!(text, "shape: [2, 2]\n  1.0   -2.5\n10.25    0.0");
+    assert_eq!(
+        parse_matrix(&text),
+        Some((vec![2, 2], vec![1.0, -2.5, 10.25, 0.0]))
+    );
+
+    let changed = "shape: [2, 2]\n1.0  -2.5\n10.5  0.001";
+    assert_eq!(matrices_match(&text, changed, 0.0, 0.0), Some(false));
+    assert_eq!(matrices_match(&text, changed, 0.3, 0.0), Some(true));
+    assert_eq!(
+        describe_deviation(&text, changed, 0.01, 0.0).as_deref(),
+        Some("Max deviation: 0.25 at [1, 0] (10.25 -> 10.5), 1 of 4 elements differ")
+    );
+}
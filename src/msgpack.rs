@@ -0,0 +1,159 @@
+use crate::content::Content;
+
+/// Key of the extension type when an extension value is represented as
+/// content.
+const EXT_TYPE_KEY: &str = "$ext";
+
+/// Key of the data when an extension value is represented as content.
+const EXT_DATA_KEY: &str = "$data";
+
+/// Decodes a MessagePack value into the content model.
+///
+/// Integers become `u64` or `i64` no matter how they were encoded so that
+/// only the values show up in snapshots.  Strings that are not valid UTF-8
+/// are kept as bytes and extension values become maps with the type under
+/// `$ext` and the data under `$data`.
+pub fn parse_msgpack(bytes: &[u8]) -> Result<Content, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let rv = reader.read_value()?;
+    if reader.pos != bytes.len() {
+        return Err(format!(
+            "{} trailing bytes after value",
+            bytes.len() - reader.pos
+        ));
+    }
+    Ok(rv)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| format!("unexpected end of data at offset {}", self.pos))?;
+        let rv = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(rv)
+    }
+
+    fn read_uint(&mut self, len: usize) -> Result<u64, String> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |rv, &byte| (rv << 8) | u64::from(byte)))
+    }
+
+    fn read_int(&mut self, len: usize) -> Result<i64, String> {
+        let value = self.read_uint(len)?;
+        let shift = 64 - 8 * len as u32;
+        Ok(((value << shift) as i64) >> shift)
+    }
+
+    fn read_value(&mut self) -> Result<Content, String> {
+        let marker = self.take(1)?[0];
+        Ok(match marker {
+            0x00..=0x7f => Content::U64(u64::from(marker)),
+            0x80..=0x8f => self.read_map(usize::from(marker & 0x0f))?,
+            0x90..=0x9f => self.read_array(usize::from(marker & 0x0f))?,
+            0xa0..=0xbf => self.read_str(usize::from(marker & 0x1f))?,
+            0xc0 => Content::None,
+            0xc2 => Content::Bool(false),
+            0xc3 => Content::Bool(true),
+            0xc4..=0xc6 => {
+                let len = self.read_len(marker - 0xc4)?;
+                Content::Bytes(self.take(len)?.to_vec())
+            }
+            0xc7..=0xc9 => {
+                let len = self.read_len(marker - 0xc7)?;
+                self.read_ext(len)?
+            }
+            0xca => Content::F32(f32::from_bits(self.read_uint(4)? as u32)),
+            0xcb => Content::F64(f64::from_bits(self.read_uint(8)?)),
+            0xcc..=0xcf => Content::U64(self.read_uint(1 << (marker - 0xcc))?),
+            0xd0..=0xd3 => Content::I64(self.read_int(1 << (marker - 0xd0))?),
+            0xd4..=0xd8 => self.read_ext(1 << (marker - 0xd4))?,
+            0xd9..=0xdb => {
+                let len = self.read_len(marker - 0xd9)?;
+                self.read_str(len)?
+            }
+            0xdc | 0xdd => {
+                let len = self.read_len(marker - 0xdc + 1)?;
+                self.read_array(len)?
+            }
+            0xde | 0xdf => {
+                let len = self.read_len(marker - 0xde + 1)?;
+                self.read_map(len)?
+            }
+            0xe0..=0xff => Content::I64(i64::from(marker as i8)),
+            0xc1 => return Err(format!("invalid marker 0xc1 at offset {}", self.pos - 1)),
+        })
+    }
+
+    /// Reads a length of 1, 2 or 4 bytes (`size` 0, 1 or 2).
+    fn read_len(&mut self, size: u8) -> Result<usize, String> {
+        Ok(self.read_uint(1 << size)? as usize)
+    }
+
+    fn read_str(&mut self, len: usize) -> Result<Content, String> {
+        let bytes = self.take(len)?;
+        Ok(match std::str::from_utf8(bytes) {
+            Ok(value) => Content::from(value),
+            Err(_) => Content::Bytes(bytes.to_vec()),
+        })
+    }
+
+    fn read_ext(&mut self, len: usize) -> Result<Content, String> {
+        let ext_type = self.read_int(1)?;
+        let data = self.take(len)?.to_vec();
+        Ok(Content::Map(vec![
+            (Content::from(EXT_TYPE_KEY), Content::I64(ext_type)),
+            (Content::from(EXT_DATA_KEY), Content::Bytes(data)),
+        ]))
+    }
+
+    fn read_array(&mut self, len: usize) -> Result<Content, String> {
+        let mut items = vec![];
+        for _ in 0..len {
+            items.push(self.read_value()?);
+        }
+        Ok(Content::Seq(items))
+    }
+
+    fn read_map(&mut self, len: usize) -> Result<Content, String> {
+        let mut entries = vec![];
+        for _ in 0..len {
+            let key = self.read_value()?;
+            let value = self.read_value()?;
+            entries.push((key, value));
+        }
+        Ok(Content::Map(entries))
+    }
+}
+
+#[test]
+fn test_parse_msgpack() {
+    // {"id": 7, "tags": ["a", -3], "ok": true, "none": nil}
+    let bytes = b"\x84\xa2id\x07\xa4tags\x92\xa1a\xfd\xa2ok\xc3\xa4none\xc0";
+    let content = parse_msgpack(bytes).unwrap();
+    assert_eq!(
+        serde_json::to_string(&content).unwrap(),
+        r#"{"id":7,"tags":["a",-3],"ok":true,"none":null}"#
+    );
+
+    assert_eq!(
+        serde_json::to_string(&parse_msgpack(b"\xd1\xff\x00").unwrap()).unwrap(),
+        "-256"
+    );
+    assert_eq!(
+        serde_json::to_string(&parse_msgpack(b"\xd4\x05\x2a").unwrap()).unwrap(),
+        r#"{"$ext":5,"$data":[42]}"#
+    );
+    assert!(parse_msgpack(b"\x92\x01").is_err());
+    assert!(parse_msgpack(b"\x01\x02").is_err());
+}
@@ -0,0 +1,133 @@
+#[cfg(feature = "filters")]
+use regex::Regex;
+#[cfg(feature = "filters")]
+use similar::{DiffOp, TextDiff};
+
+const PLACEHOLDER_START: &str = "[[~";
+const PLACEHOLDER_END: &str = "~]]";
+#[cfg(feature = "filters")]
+const REGEX_PREFIX: &str = "regex:";
+
+/// Splits a line into literal text and placeholders.
+///
+/// The boolean is `true` for placeholders which are returned including
+/// their delimiters.
+pub(crate) fn split_placeholders(line: &str) -> Vec<(bool, &str)> {
+    let mut rv = vec![];
+    let mut rest = line;
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        let end = match rest[start..].find(PLACEHOLDER_END) {
+            Some(end) => start + end + PLACEHOLDER_END.len(),
+            None => break,
+        };
+        if start > 0 {
+            rv.push((false, &rest[..start]));
+        }
+        rv.push((true, &rest[start..end]));
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        rv.push((false, rest));
+    }
+    rv
+}
+
+/// Compiles a line with placeholders into a regex that matches the line.
+///
+/// Returns `None` if the line has no valid placeholders.
+#[cfg(feature = "filters")]
+fn compile_line(line: &str) -> Option<Regex> {
+    let mut pattern = String::from("^");
+    let mut has_placeholders = false;
+    for (is_placeholder, segment) in split_placeholders(line) {
+        let inner = segment
+            .get(PLACEHOLDER_START.len()..segment.len() - PLACEHOLDER_END.len())
+            .map(str::trim)
+            .filter(|inner| is_placeholder && inner.starts_with(REGEX_PREFIX));
+        match inner {
+            Some(inner) => {
+                pattern.push_str("(?:");
+                pattern.push_str(inner[REGEX_PREFIX.len()..].trim());
+                pattern.push(')');
+                has_placeholders = true;
+            }
+            None => pattern.push_str(&regex::escape(segment)),
+        }
+    }
+    pattern.push('$');
+    if has_placeholders {
+        Regex::new(&pattern).ok()
+    } else {
+        None
+    }
+}
+
+/// Carries the placeholders of the old contents over to the new contents.
+///
+/// Every line of the new contents that is matched by a line with
+/// placeholders of the old contents is replaced by that line.  This way
+/// matching contents compare equal to the old contents and accepting new
+/// contents does not overwrite placeholders.  Returns `None` if the old
+/// contents do not have placeholders.
+#[cfg(feature = "filters")]
+pub(crate) fn merge_placeholders(old: &str, new: &str) -> Option<String> {
+    if !old.contains(PLACEHOLDER_START) {
+        return None;
+    }
+    let old_lines: Vec<_> = old.lines().collect();
+    let mut new_lines: Vec<_> = new.lines().collect();
+    let diff = TextDiff::from_lines(old, new);
+    for op in diff.ops() {
+        if let DiffOp::Replace {
+            old_index,
+            old_len,
+            new_index,
+            new_len,
+        } = *op
+        {
+            for offset in 0..old_len.min(new_len) {
+                let old_line = old_lines[old_index + offset];
+                let new_line = &mut new_lines[new_index + offset];
+                if let Some(regex) = compile_line(old_line) {
+                    if regex.is_match(new_line) {
+                        *new_line = old_line;
+                    }
+                }
+            }
+        }
+    }
+    let mut rv = new_lines.join("\n");
+    if new.ends_with('\n') {
+        rv.push('\n');
+    }
+    Some(rv)
+}
+
+#[test]
+fn test_split_placeholders() {
+    assert_eq!(
+        split_placeholders("id: [[~ regex: \\d+ ~]] at [[~ regex: .* ~]]"),
+        vec![
+            (false, "id: "),
+            (true, "[[~ regex: \\d+ ~]]"),
+            (false, " at "),
+            (true, "[[~ regex: .* ~]]"),
+        ]
+    );
+    assert_eq!(split_placeholders("a [[~ b"), vec![(false, "a [[~ b")]);
+}
+
+#[cfg(feature = "filters")]
+#[test]
+fn test_merge_placeholders() {
+    let old = "name: insta\nid: [[~ regex: \\d+ ~]]\ntime: [[~ regex: .* ~]]\n";
+    assert_eq!(
+        merge_placeholders(old, "name: insta\nid: 42\ntime: now\n").as_deref(),
+        Some(old)
+    );
+    assert_eq!(
+        merge_placeholders(old, "name: other\nid: abc\ntime: now\n").as_deref(),
+        Some("name: other\nid: abc\ntime: [[~ regex: .* ~]]\n")
+    );
+    assert_eq!(merge_placeholders("id: 42", "id: 23"), None);
+}
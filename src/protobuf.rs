@@ -0,0 +1,215 @@
+use crate::content::Content;
+
+/// Decodes a protobuf message into the content model without its schema.
+///
+/// This works like `protoc --decode_raw`: fields are keyed by their number
+/// and fields that occur more than once become sequences.  As the wire
+/// format does not say what a length delimited field holds, printable UTF-8
+/// becomes a string, anything that decodes as a message a nested message
+/// and the rest bytes.  A nested message that happens to be printable (like
+/// `{4: 65}` which is encoded as `" A"`) is therefore shown as a string.
+///
+/// Varints and fixed width values are shown as unsigned integers as the
+/// wire format does not tell `uint64` apart from `int64`.  Negative `int32`
+/// and `int64` values thus show as their two's complement and zigzag
+/// encoded `sint32` and `sint64` values stay encoded.
+///
+/// Messages are decoded up to [`MAX_DEPTH`] levels deep, deeper length
+/// delimited fields stay bytes and deeper groups are an error.
+pub fn parse_protobuf(bytes: &[u8]) -> Result<Content, String> {
+    parse_nested(bytes, 0)
+}
+
+/// How deep messages and groups are nested at most.
+///
+/// This is the recursion limit `prost` uses when decoding.
+const MAX_DEPTH: usize = 100;
+
+fn parse_nested(bytes: &[u8], depth: usize) -> Result<Content, String> {
+    Reader {
+        bytes,
+        pos: 0,
+        depth,
+    }
+    .read_message(None)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| format!("unexpected end of data at offset {}", self.pos))?;
+        let rv = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(rv)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, String> {
+        let start = self.pos;
+        let mut rv = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            rv |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(rv);
+            }
+        }
+        Err(format!("varint too long at offset {}", start))
+    }
+
+    fn read_fixed(&mut self, len: usize) -> Result<u64, String> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .rev()
+            .fold(0, |rv, &byte| (rv << 8) | u64::from(byte)))
+    }
+
+    /// Reads fields up to the end or the end of the group with the number.
+    fn read_message(&mut self, group: Option<u64>) -> Result<Content, String> {
+        let mut fields: Vec<(u64, Vec<Content>)> = vec![];
+        loop {
+            if self.pos == self.bytes.len() {
+                if let Some(number) = group {
+                    return Err(format!("unterminated group {}", number));
+                }
+                break;
+            }
+            let start = self.pos;
+            let key = self.read_varint()?;
+            let number = key >> 3;
+            if number == 0 {
+                return Err(format!("invalid field number 0 at offset {}", start));
+            }
+            let value = match key & 0x07 {
+                0 => Content::U64(self.read_varint()?),
+                1 => Content::U64(self.read_fixed(8)?),
+                2 => {
+                    let len = self.read_varint()?;
+                    let data = self.take(len as usize)?;
+                    decode_length_delimited(data, self.depth + 1)
+                }
+                3 => {
+                    if self.depth >= MAX_DEPTH {
+                        return Err(format!("groups nested too deep at offset {}", start));
+                    }
+                    self.depth += 1;
+                    let group = self.read_message(Some(number))?;
+                    self.depth -= 1;
+                    group
+                }
+                4 if group == Some(number) => break,
+                5 => Content::U32(self.read_fixed(4)? as u32),
+                wire_type => {
+                    return Err(format!(
+                        "invalid wire type {} at offset {}",
+                        wire_type, start
+                    ))
+                }
+            };
+            match fields.iter_mut().find(|x| x.0 == number) {
+                Some(field) => field.1.push(value),
+                None => fields.push((number, vec![value])),
+            }
+        }
+        Ok(Content::Map(
+            fields
+                .into_iter()
+                .map(|(number, mut values)| {
+                    let value = if values.len() == 1 {
+                        values.pop().unwrap()
+                    } else {
+                        Content::Seq(values)
+                    };
+                    (Content::U64(number), value)
+                })
+                .collect(),
+        ))
+    }
+}
+
+fn decode_length_delimited(data: &[u8], depth: usize) -> Content {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if text
+            .chars()
+            .all(|c| !c.is_control() || c == '\n' || c == '\t' || c == '\r')
+        {
+            return Content::from(text);
+        }
+    }
+    if depth > MAX_DEPTH {
+        return Content::from(data);
+    }
+    match parse_nested(data, depth) {
+        Ok(message) => message,
+        Err(_) => Content::from(data),
+    }
+}
+
+#[test]
+fn test_parse_protobuf() {
+    // id: 150, name: "peter", tags: "a", tags: "b", address { zip: 1234 }
+    let bytes = b"\x08\x96\x01\x12\x05peter\x1a\x01a\x1a\x01b\x22\x03\x08\xd2\x09";
+    assert_eq!(
+        serde_json::to_string(&parse_protobuf(bytes).unwrap()).unwrap(),
+        r#"{"1":150,"2":"peter","3":["a","b"],"4":{"1":1234}}"#
+    );
+
+    // fixed width values, bytes and a group
+    assert_eq!(
+        serde_json::to_string(
+            &parse_protobuf(b"\x0d\x01\x00\x00\x00\x12\x02\xff\x00\x1b\x08\x01\x1c").unwrap()
+        )
+        .unwrap(),
+        r#"{"1":1,"2":[255,0],"3":{"1":1}}"#
+    );
+    // uint64 values above the range of int64 stay unsigned
+    assert_eq!(
+        serde_json::to_string(
+            &parse_protobuf(b"\x08\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01").unwrap()
+        )
+        .unwrap(),
+        r#"{"1":18446744073709551615}"#
+    );
+    assert!(parse_protobuf(b"\x08").is_err());
+    assert!(parse_protobuf(b"\x12\x05ab").is_err());
+    assert!(parse_protobuf(b"\x00\x01").is_err());
+}
+
+#[test]
+fn test_parse_protobuf_depth() {
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut rv = b"\x08\x01".to_vec();
+        for _ in 0..depth {
+            let len = rv.len();
+            let mut outer = vec![0x0a];
+            if len < 0x80 {
+                outer.push(len as u8);
+            } else {
+                outer.extend(&[(len as u8) | 0x80, (len >> 7) as u8]);
+            }
+            outer.extend(rv);
+            rv = outer;
+        }
+        rv
+    }
+    let json = |bytes: &[u8]| serde_json::to_string(&parse_protobuf(bytes).unwrap()).unwrap();
+    assert!(json(&nested(MAX_DEPTH)).contains(r#"{"1":1}"#));
+    // the innermost message is too deep and stays bytes
+    assert!(!json(&nested(MAX_DEPTH + 1)).contains(r#"{"1":1}"#));
+
+    let groups = [b"\x0b".repeat(MAX_DEPTH + 1), b"\x0c".repeat(MAX_DEPTH + 1)].concat();
+    assert!(parse_protobuf(&groups)
+        .unwrap_err()
+        .starts_with("groups nested too deep"));
+    let groups = [b"\x0b".repeat(MAX_DEPTH), b"\x0c".repeat(MAX_DEPTH)].concat();
+    assert!(parse_protobuf(&groups).is_ok());
+}
@@ -1,9 +1,10 @@
 use pest::Parser;
 use pest_derive::Parser;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
 
-use crate::content::Content;
+use crate::content::{Content, Key};
 
 #[derive(Debug)]
 pub struct SelectorParseError(pest::error::Error<Rule>);
@@ -45,14 +46,18 @@ impl<'a> fmt::Display for ContentPath<'a> {
     }
 }
 
-/// Replaces a value with another one.
-
 /// Represents a redaction.
 pub enum Redaction {
     /// Static redaction with new content.
     Static(Content),
     /// Redaction with new content.
     Dynamic(Box<dyn Fn(Content, ContentPath<'_>) -> Content + Sync + Send>),
+    /// Redaction with new content that can look at the surrounding values.
+    Contextual(Box<dyn Fn(Content, RedactionContext<'_>) -> Content + Sync + Send>),
+    /// Redaction of the map key of the selected value.
+    Key(Box<Redaction>),
+    /// Removes the selected value from its container.
+    Exclude,
 }
 
 macro_rules! impl_from {
@@ -129,12 +134,224 @@ where
     Redaction::Dynamic(Box::new(move |c, p| func(c, p).into()))
 }
 
+/// The context a contextual redaction is invoked with.
+///
+/// Besides the path of the value this gives access to the container the
+/// value is stored in so that redactions can depend on sibling values.
+#[derive(Clone, Debug)]
+pub struct RedactionContext<'a> {
+    path: ContentPath<'a>,
+    parent: Option<&'a Content>,
+}
+
+impl<'a> RedactionContext<'a> {
+    /// Returns the path of the value.
+    pub fn path(&self) -> &ContentPath<'a> {
+        &self.path
+    }
+
+    /// Returns the map, struct or sequence that contains the value.
+    ///
+    /// This is `None` for the root value.  The parent is passed as it was
+    /// before the redaction was applied to any of its values.
+    pub fn parent(&self) -> Option<&'a Content> {
+        self.parent
+    }
+
+    /// Looks up a sibling of the value by its key or field name.
+    pub fn sibling(&self, key: &str) -> Option<&'a Content> {
+        match *self.parent?.resolve_inner() {
+            Content::Map(ref map) => map
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            Content::Struct(_, ref fields) | Content::StructVariant(_, _, _, ref fields) => {
+                fields.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Creates a dynamic redaction that can look at the surrounding values.
+///
+/// This works like [`dynamic_redaction`] but the closure is passed a
+/// [`RedactionContext`] instead of the path.  This can be used to redact
+/// values conditionally, for instance only the ids of temporary objects:
+///
+/// ```rust
+/// # use insta::{Settings, contextual_redaction};
+/// # let mut settings = Settings::new();
+/// settings.add_redaction(".**.id", contextual_redaction(|value, context| {
+///     match context.sibling("type").and_then(|x| x.as_str()) {
+///         Some("temp") => "[temp id]".into(),
+///         _ => value,
+///     }
+/// }));
+/// ```
+pub fn contextual_redaction<I, F>(func: F) -> Redaction
+where
+    I: Into<Content>,
+    F: Fn(Content, RedactionContext<'_>) -> I + Send + Sync + 'static,
+{
+    Redaction::Contextual(Box::new(move |c, ctx| func(c, ctx).into()))
+}
+
+/// Creates a redaction for map keys.
+///
+/// Instead of the selected value the key it is stored under in the
+/// containing map is replaced.  This makes maps keyed by random values
+/// stable.  The redaction for the key can be static or dynamic:
+///
+/// ```rust
+/// # use insta::{Settings, key_redaction};
+/// # let mut settings = Settings::new();
+/// settings.add_redaction(".cache.*", key_redaction("[hash]"));
+/// ```
+///
+/// Only map keys can be redacted, struct fields are left alone.  If string
+/// keys of a map end up the same a counter is appended to them (`[hash]`,
+/// `[hash]-2`, ...) so that no entries are lost.
+pub fn key_redaction<R: Into<Redaction>>(redaction: R) -> Redaction {
+    Redaction::Key(Box::new(redaction.into()))
+}
+
+/// Appends a counter to string keys that were already produced by a key
+/// redaction in the same map so that no entries get lost.
+fn number_key(key: Content, key_counts: &mut BTreeMap<String, usize>) -> Content {
+    let name = match key.as_str() {
+        Some(name) => name.to_string(),
+        None => return key,
+    };
+    let count = key_counts.entry(name.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        key
+    } else {
+        Content::from(format!("{}-{}", name, count))
+    }
+}
+
+/// Creates a redaction that removes values.
+///
+/// Selected fields, map entries and sequence items are dropped from the
+/// snapshot entirely instead of being replaced by a placeholder.  This is
+/// useful for large noisy subtrees:
+///
+/// ```rust
+/// # use insta::{Settings, exclude_redaction};
+/// # let mut settings = Settings::new();
+/// settings.add_redaction(".metrics", exclude_redaction());
+/// ```
+pub fn exclude_redaction() -> Redaction {
+    Redaction::Exclude
+}
+
+/// Creates a redaction that rounds floats to a number of decimal places.
+///
+/// Floats anywhere within the selected value are rounded, so it can also
+/// select whole lists of coordinates or measurements:
+///
+/// ```rust
+/// # use insta::{Settings, rounded_redaction};
+/// # let mut settings = Settings::new();
+/// settings.add_redaction(".location", rounded_redaction(4));
+/// ```
+pub fn rounded_redaction(decimals: usize) -> Redaction {
+    let factor = 10f64.powi(decimals as i32);
+    dynamic_redaction(move |mut value: Content, _| {
+        value.walk(&mut |content| {
+            match *content {
+                Content::F32(ref mut value) => {
+                    *value = ((f64::from(*value) * factor).round() / factor) as f32
+                }
+                Content::F64(ref mut value) => *value = (*value * factor).round() / factor,
+                _ => {}
+            }
+            true
+        });
+        value
+    })
+}
+
+/// Creates a redaction that sorts a sequence or map.
+///
+/// Sequences are sorted by their values and maps by their keys.  This is
+/// useful when the order of items is not deterministic and
+/// [`Settings::set_sort_maps`](crate::Settings::set_sort_maps) does not
+/// apply because the items are stored in a `Vec`:
+///
+/// ```rust
+/// # use insta::{Settings, sorted_redaction};
+/// # let mut settings = Settings::new();
+/// settings.add_redaction(".tags", sorted_redaction());
+/// ```
+pub fn sorted_redaction() -> Redaction {
+    dynamic_redaction(|mut value, _| {
+        match value {
+            Content::Seq(ref mut items) => items.sort_by(|a, b| a.as_key().cmp(&b.as_key())),
+            Content::Map(ref mut items) => items.sort_by(|a, b| a.0.as_key().cmp(&b.0.as_key())),
+            _ => {}
+        }
+        value
+    })
+}
+
+/// Creates a redaction that sorts a sequence by a value in each item.
+///
+/// The selector is evaluated relative to each item of the sequence and the
+/// items are sorted by the first value it matches.  Items where the
+/// selector does not match anything go last.  For instance to sort the
+/// items by their `id`:
+///
+/// ```rust
+/// # use insta::{Settings, sorted_redaction_by};
+/// # let mut settings = Settings::new();
+/// settings.add_redaction(".items", sorted_redaction_by(".id"));
+/// ```
+///
+/// This panics if the selector is invalid.
+pub fn sorted_redaction_by(selector: &str) -> Redaction {
+    let selector = Selector::parse(selector).unwrap().make_static();
+    dynamic_redaction(move |mut value, _| {
+        if let Content::Seq(ref mut items) = value {
+            items.sort_by(|a, b| {
+                let a = selector.find(a).map_or(Key::Other, Content::as_key);
+                let b = selector.find(b).map_or(Key::Other, Content::as_key);
+                a.cmp(&b)
+            });
+        }
+        value
+    })
+}
+
 impl Redaction {
+    /// Returns `true` if the redaction needs the parent of the value.
+    fn uses_context(&self) -> bool {
+        match *self {
+            Redaction::Contextual(_) => true,
+            Redaction::Key(ref inner) => inner.uses_context(),
+            _ => false,
+        }
+    }
+
     /// Performs the redaction of the value at the given path.
-    fn redact(&self, value: Content, path: &[PathItem]) -> Content {
+    fn redact(&self, value: Content, path: &[PathItem], parent: Option<&Content>) -> Content {
         match *self {
             Redaction::Static(ref new_val) => new_val.clone(),
             Redaction::Dynamic(ref callback) => callback(value, ContentPath(path)),
+            Redaction::Contextual(ref callback) => callback(
+                value,
+                RedactionContext {
+                    path: ContentPath(path),
+                    parent,
+                },
+            ),
+            // keys are redacted by the containing map
+            Redaction::Key(_) => value,
+            // excluded values are dropped by their container, only the
+            // root value is left which is replaced by unit
+            Redaction::Exclude => Content::Unit,
         }
     }
 }
@@ -196,6 +413,7 @@ pub enum Segment<'a> {
     Wildcard,
     Key(Cow<'a, str>),
     Index(u64),
+    IndexOrKey(u64, Cow<'a, str>),
     Range(Option<i64>, Option<i64>),
 }
 
@@ -206,6 +424,9 @@ pub struct Selector<'a> {
 
 impl<'a> Selector<'a> {
     pub fn parse(selector: &'a str) -> Result<Selector<'a>, SelectorParseError> {
+        if selector.starts_with('/') {
+            return Selector::parse_pointer(selector);
+        }
         let pair = SelectParser::parse(Rule::selectors, selector)
             .map_err(SelectorParseError)?
             .next()
@@ -291,6 +512,52 @@ impl<'a> Selector<'a> {
         Ok(Selector { selectors: rv })
     }
 
+    /// Parses a JSON pointer (RFC 6901) like `/items/0/id`.
+    ///
+    /// Segments that are numbers match both indexes and keys.
+    fn parse_pointer(selector: &'a str) -> Result<Selector<'a>, SelectorParseError> {
+        let mut segments = vec![];
+        let mut offset = 1;
+        for token in selector[1..].split('/') {
+            let key = if token.contains('~') {
+                let mut rv = String::with_capacity(token.len());
+                let mut chars = token.char_indices();
+                while let Some((idx, c)) = chars.next() {
+                    if c != '~' {
+                        rv.push(c);
+                        continue;
+                    }
+                    rv.push(match chars.next() {
+                        Some((_, '0')) => '~',
+                        Some((_, '1')) => '/',
+                        _ => {
+                            return Err(SelectorParseError(pest::error::Error::new_from_pos(
+                                pest::error::ErrorVariant::CustomError {
+                                    message: "invalid escape in JSON pointer".into(),
+                                },
+                                pest::Position::new(selector, offset + idx).unwrap(),
+                            )));
+                        }
+                    });
+                }
+                Cow::Owned(rv)
+            } else {
+                Cow::Borrowed(token)
+            };
+            let is_index = !key.is_empty()
+                && key.bytes().all(|x| x.is_ascii_digit())
+                && (key.len() == 1 || !key.starts_with('0'));
+            segments.push(match key.parse() {
+                Ok(idx) if is_index => Segment::IndexOrKey(idx, key),
+                _ => Segment::Key(key),
+            });
+            offset += token.len() + 1;
+        }
+        Ok(Selector {
+            selectors: vec![segments],
+        })
+    }
+
     pub fn make_static(self) -> Selector<'static> {
         Selector {
             selectors: self
@@ -302,6 +569,9 @@ impl<'a> Selector<'a> {
                         .map(|x| match x {
                             Segment::Key(x) => Segment::Key(Cow::Owned(x.into_owned())),
                             Segment::Index(x) => Segment::Index(x),
+                            Segment::IndexOrKey(x, k) => {
+                                Segment::IndexOrKey(x, Cow::Owned(k.into_owned()))
+                            }
                             Segment::Wildcard => Segment::Wildcard,
                             Segment::DeepWildcard => Segment::DeepWildcard,
                             Segment::Range(a, b) => Segment::Range(a, b),
@@ -316,8 +586,11 @@ impl<'a> Selector<'a> {
         match *segment {
             Segment::Wildcard => true,
             Segment::DeepWildcard => true,
-            Segment::Key(ref k) => element.as_str() == Some(&k),
+            Segment::Key(ref k) => element.as_str() == Some(k),
             Segment::Index(i) => element.as_u64() == Some(i),
+            Segment::IndexOrKey(i, ref k) => {
+                element.as_u64() == Some(i) || element.as_str() == Some(k)
+            }
             Segment::Range(start, end) => element.range_check(start, end),
         }
     }
@@ -359,15 +632,53 @@ impl<'a> Selector<'a> {
 
     pub fn is_match(&self, path: &[PathItem]) -> bool {
         for selector in &self.selectors {
-            if self.selector_is_match(&selector, path) {
+            if self.selector_is_match(selector, path) {
                 return true;
             }
         }
         false
     }
 
+    /// Returns the first value the selector matches within the content.
+    fn find<'c>(&self, content: &'c Content) -> Option<&'c Content> {
+        self.find_impl(content, &mut vec![])
+    }
+
+    fn find_impl<'c>(&self, content: &'c Content, path: &mut Vec<PathItem>) -> Option<&'c Content> {
+        if self.is_match(path) {
+            return Some(content);
+        }
+        let mut find_in = |item: PathItem, value: &'c Content| {
+            path.push(item);
+            let rv = self.find_impl(value, path);
+            path.pop();
+            rv
+        };
+        match *content {
+            Content::Map(ref map) => map
+                .iter()
+                .find_map(|(key, value)| find_in(PathItem::Content(key.clone()), value)),
+            Content::Seq(ref seq)
+            | Content::Tuple(ref seq)
+            | Content::TupleStruct(_, ref seq)
+            | Content::TupleVariant(_, _, _, ref seq) => {
+                let len = seq.len() as u64;
+                seq.iter()
+                    .enumerate()
+                    .find_map(|(idx, value)| find_in(PathItem::Index(idx as u64, len), value))
+            }
+            Content::Struct(_, ref fields) | Content::StructVariant(_, _, _, ref fields) => fields
+                .iter()
+                .find_map(|&(key, ref value)| find_in(PathItem::Field(key), value)),
+            Content::NewtypeStruct(_, ref inner)
+            | Content::NewtypeVariant(_, _, _, ref inner)
+            | Content::Some(ref inner) => self.find_impl(inner, path),
+            _ => None,
+        }
+    }
+
     pub fn redact(&self, value: Content, redaction: &Redaction) -> Content {
-        self.redact_impl(value, redaction, &mut vec![])
+        self.redact_impl(value, redaction, &mut vec![], None)
     }
 
     fn redact_seq(
@@ -375,13 +686,14 @@ impl<'a> Selector<'a> {
         seq: Vec<Content>,
         redaction: &Redaction,
         path: &mut Vec<PathItem>,
+        parent: Option<&Content>,
     ) -> Vec<Content> {
         let len = seq.len();
         seq.into_iter()
             .enumerate()
-            .map(|(idx, value)| {
+            .filter_map(|(idx, value)| {
                 path.push(PathItem::Index(idx as u64, len as u64));
-                let new_value = self.redact_impl(value, redaction, path);
+                let new_value = self.redact_child(value, redaction, path, parent);
                 path.pop();
                 new_value
             })
@@ -393,76 +705,123 @@ impl<'a> Selector<'a> {
         seq: Vec<(&'static str, Content)>,
         redaction: &Redaction,
         path: &mut Vec<PathItem>,
+        parent: Option<&Content>,
     ) -> Vec<(&'static str, Content)> {
         seq.into_iter()
-            .map(|(key, value)| {
+            .filter_map(|(key, value)| {
                 path.push(PathItem::Field(key));
-                let new_value = self.redact_impl(value, redaction, path);
+                let new_value = self.redact_child(value, redaction, path, parent);
                 path.pop();
-                (key, new_value)
+                new_value.map(|new_value| (key, new_value))
             })
             .collect()
     }
 
+    /// Redacts a value within a container.
+    ///
+    /// Returns `None` if the value is excluded.
+    fn redact_child(
+        &self,
+        value: Content,
+        redaction: &Redaction,
+        path: &mut Vec<PathItem>,
+        parent: Option<&Content>,
+    ) -> Option<Content> {
+        if let Redaction::Exclude = *redaction {
+            if self.is_match(path) {
+                return None;
+            }
+        }
+        Some(self.redact_impl(value, redaction, path, parent))
+    }
+
     fn redact_impl(
         &self,
         value: Content,
         redaction: &Redaction,
         path: &mut Vec<PathItem>,
+        parent: Option<&Content>,
     ) -> Content {
-        if self.is_match(&path) {
-            redaction.redact(value, path)
-        } else {
-            match value {
-                Content::Map(map) => Content::Map(
+        let key_redaction = match *redaction {
+            Redaction::Key(ref key_redaction) => Some(&**key_redaction),
+            _ => None,
+        };
+        if key_redaction.is_none() && self.is_match(path) {
+            return redaction.redact(value, path, parent);
+        }
+
+        // contextual redactions get to see the container of the value
+        let container = match value {
+            Content::Map(_)
+            | Content::Seq(_)
+            | Content::Tuple(_)
+            | Content::TupleStruct(..)
+            | Content::TupleVariant(..)
+            | Content::Struct(..)
+            | Content::StructVariant(..)
+                if redaction.uses_context() =>
+            {
+                Some(value.clone())
+            }
+            _ => None,
+        };
+        let container = container.as_ref();
+
+        match value {
+            Content::Map(map) => {
+                let mut key_counts = BTreeMap::new();
+                Content::Map(
                     map.into_iter()
-                        .map(|(key, value)| {
+                        .filter_map(|(key, value)| {
                             path.push(PathItem::Content(key.clone()));
-                            let new_value = self.redact_impl(value, redaction, path);
+                            let new_value = self.redact_child(value, redaction, path, container);
+                            let new_key = match key_redaction {
+                                Some(key_redaction) if self.is_match(path) => number_key(
+                                    key_redaction.redact(key, path, container),
+                                    &mut key_counts,
+                                ),
+                                _ => key,
+                            };
                             path.pop();
-                            (key, new_value)
+                            new_value.map(|new_value| (new_key, new_value))
                         })
                         .collect(),
-                ),
-                Content::Seq(seq) => Content::Seq(self.redact_seq(seq, redaction, path)),
-                Content::Tuple(seq) => Content::Tuple(self.redact_seq(seq, redaction, path)),
-                Content::TupleStruct(name, seq) => {
-                    Content::TupleStruct(name, self.redact_seq(seq, redaction, path))
-                }
-                Content::TupleVariant(name, variant_index, variant, seq) => Content::TupleVariant(
-                    name,
-                    variant_index,
-                    variant,
-                    self.redact_seq(seq, redaction, path),
-                ),
-                Content::Struct(name, seq) => {
-                    Content::Struct(name, self.redact_struct(seq, redaction, path))
-                }
-                Content::StructVariant(name, variant_index, variant, seq) => {
-                    Content::StructVariant(
-                        name,
-                        variant_index,
-                        variant,
-                        self.redact_struct(seq, redaction, path),
-                    )
-                }
-                Content::NewtypeStruct(name, inner) => Content::NewtypeStruct(
-                    name,
-                    Box::new(self.redact_impl(*inner, redaction, path)),
-                ),
-                Content::NewtypeVariant(name, index, variant_name, inner) => {
-                    Content::NewtypeVariant(
-                        name,
-                        index,
-                        variant_name,
-                        Box::new(self.redact_impl(*inner, redaction, path)),
-                    )
-                }
-                Content::Some(contents) => {
-                    Content::Some(Box::new(self.redact_impl(*contents, redaction, path)))
-                }
-                other => other,
+                )
+            }
+            Content::Seq(seq) => Content::Seq(self.redact_seq(seq, redaction, path, container)),
+            Content::Tuple(seq) => Content::Tuple(self.redact_seq(seq, redaction, path, container)),
+            Content::TupleStruct(name, seq) => {
+                Content::TupleStruct(name, self.redact_seq(seq, redaction, path, container))
             }
+            Content::TupleVariant(name, variant_index, variant, seq) => Content::TupleVariant(
+                name,
+                variant_index,
+                variant,
+                self.redact_seq(seq, redaction, path, container),
+            ),
+            Content::Struct(name, seq) => {
+                Content::Struct(name, self.redact_struct(seq, redaction, path, container))
+            }
+            Content::StructVariant(name, variant_index, variant, seq) => Content::StructVariant(
+                name,
+                variant_index,
+                variant,
+                self.redact_struct(seq, redaction, path, container),
+            ),
+            Content::NewtypeStruct(name, inner) => Content::NewtypeStruct(
+                name,
+                Box::new(self.redact_impl(*inner, redaction, path, parent)),
+            ),
+            Content::NewtypeVariant(name, index, variant_name, inner) => Content::NewtypeVariant(
+                name,
+                index,
+                variant_name,
+                Box::new(self.redact_impl(*inner, redaction, path, parent)),
+            ),
+            Content::Some(contents) => Content::Some(Box::new(
+                self.redact_impl(*contents, redaction, path, parent),
+            )),
+            other => other,
         }
     }
 }
@@ -479,3 +838,20 @@ fn test_range_checks() {
     assert_eq!(PathItem::Index(1, 10).range_check(Some(1), None), true);
     assert_eq!(PathItem::Index(9, 10).range_check(Some(1), None), true);
 }
+
+#[test]
+fn test_parse_pointer() {
+    use similar_asserts::assert_eq;
+    let selector = Selector::parse("/items/0/a~1b~0c/007/").unwrap();
+    assert_eq!(
+        selector.selectors,
+        vec![vec![
+            Segment::Key("items".into()),
+            Segment::IndexOrKey(0, "0".into()),
+            Segment::Key("a/b~c".into()),
+            Segment::Key("007".into()),
+            Segment::Key("".into()),
+        ]]
+    );
+    assert_eq!(Selector::parse("/a~2").unwrap_err().column(), 3);
+}
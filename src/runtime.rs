@@ -1,32 +1,57 @@
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
 use std::env;
 use std::error::Error;
+use std::fmt::Write as _;
 use std::fs;
-use std::io::Write;
+use std::future::Future;
+use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::process::{Command, Stdio};
 use std::str;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 use std::thread;
 use std::time::Duration;
 
 use lazy_static::lazy_static;
-use similar::{Algorithm, ChangeTag, TextDiff};
+use similar::{Algorithm, ChangeTag, DiffTag, TextDiff};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::settings::Settings;
-use crate::snapshot::{MetaData, PendingInlineSnapshot, Snapshot, SnapshotContents};
-use crate::utils::{is_ci, style};
+use crate::ansi::process_ansi;
+use crate::config::{pending_dir, ChecksumMode, PendingLocation, SecretsCheck, ToolConfig};
+use crate::floats::{matches_with_tolerance, normalize_numbers};
+use crate::json::json_values_match;
+use crate::placeholders::split_placeholders;
+use crate::secrets::find_secrets;
+use crate::settings::{DiffAlgorithm, Settings, SnapshotLayout};
+use crate::snapshot::{Compression, MetaData, PendingInlineSnapshot, Snapshot, SnapshotContents};
+use crate::utils::{colors_enabled, is_ci, is_interactive, style};
+
+/// Where a snapshot file was first asserted and with which contents.
+///
+/// No snapshot is kept if the contents matched the stored file.
+type SnapshotFileLocation = (String, u32, Option<Snapshot>);
 
 lazy_static! {
     static ref WORKSPACES: Mutex<BTreeMap<String, &'static Path>> = Mutex::new(BTreeMap::new());
+    static ref TARGET_DIRECTORIES: Mutex<BTreeMap<PathBuf, PathBuf>> = Mutex::new(BTreeMap::new());
+    static ref TOOL_CONFIGS: Mutex<BTreeMap<PathBuf, Result<Arc<ToolConfig>, String>>> =
+        Mutex::new(BTreeMap::new());
     static ref TEST_NAME_COUNTERS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
     static ref TEST_NAME_CLASH_DETECTION: Mutex<BTreeMap<String, bool>> =
         Mutex::new(BTreeMap::new());
+    static ref SNAPSHOT_FILE_LOCATIONS: Mutex<BTreeMap<PathBuf, SnapshotFileLocation>> =
+        Mutex::new(BTreeMap::new());
+    /// Keeps tests running in parallel from asking at the same time.
+    static ref ASK_LOCK: Mutex<()> = Mutex::new(());
 }
 
+thread_local!(static CURRENT_TEST_NAME: Cell<Option<&'static str>> = Cell::new(None));
+
 // This macro is basically eprintln but without being captured and
 // hidden by the test runner.
 macro_rules! elog {
@@ -41,6 +66,17 @@ enum UpdateBehavior {
     InPlace,
     NewFile,
     NoUpdate,
+    Ask,
+    NewOnly,
+}
+
+/// What the user decided when asked about a changed snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "colors"), allow(dead_code))]
+enum AskDecision {
+    Accept,
+    Reject,
+    Skip,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -107,7 +143,6 @@ fn format_rust_expression(value: &str) -> Cow<'_, str> {
 
 #[test]
 fn test_format_rust_expression() {
-    use crate::assert_snapshot;
     assert_snapshot!(format_rust_expression("vec![1,2,3]"), @"vec![1, 2, 3]");
     assert_snapshot!(format_rust_expression("vec![1,2,3].iter()"), @"vec![1, 2, 3].iter()");
     assert_snapshot!(format_rust_expression(r#"    "aoeu""#), @r###""aoeu""###);
@@ -115,9 +150,41 @@ fn test_format_rust_expression() {
     assert_snapshot!(format_rust_expression("😄😄😄😄😄"), @"😄😄😄😄😄")
 }
 
-fn update_snapshot_behavior(unseen: bool) -> UpdateBehavior {
-    match env::var("INSTA_UPDATE").ok().as_deref() {
-        None | Some("") | Some("auto") => {
+/// Returns the update mode of the running test from the rules in the
+/// workspace config.
+fn configured_update_mode(workspace_root: &Path, module_path: &str) -> Option<String> {
+    let config = get_tool_config(workspace_root);
+    if config.update.rules.is_empty() {
+        return None;
+    }
+    let mut parts = module_path.splitn(2, "::").skip(1);
+    let module = parts.next();
+    let test_path = match (CURRENT_TEST_NAME.with(|x| x.get()), module) {
+        (Some(name), Some(module)) => format!("{}::{}", module, name),
+        (Some(name), None) => name.to_string(),
+        (None, _) => match thread::current().name() {
+            Some(name) if name != "main" => name.to_string(),
+            _ => module.unwrap_or("").to_string(),
+        },
+    };
+    config
+        .update
+        .mode_for(&test_path)
+        .map(|mode| mode.to_string())
+}
+
+fn update_snapshot_behavior(unseen: bool, configured_mode: Option<&str>) -> UpdateBehavior {
+    let mode = env::var("INSTA_UPDATE").ok();
+    let mode = match mode.as_deref() {
+        None | Some("") | Some("auto") | Some("new") if configured_mode.is_some() => {
+            configured_mode
+        }
+        mode => mode,
+    };
+    match mode {
+        Some("ask") if is_interactive() => UpdateBehavior::Ask,
+        // without a terminal to ask on this behaves like `auto`
+        None | Some("") | Some("auto") | Some("ask") => {
             if is_ci() {
                 UpdateBehavior::NoUpdate
             } else {
@@ -133,7 +200,11 @@ fn update_snapshot_behavior(unseen: bool) -> UpdateBehavior {
                 UpdateBehavior::InPlace
             }
         }
+        Some("new-only") => UpdateBehavior::NewOnly,
         Some("no") => UpdateBehavior::NoUpdate,
+        Some(mode) if Some(mode) == configured_mode => {
+            panic!("invalid update mode '{}' in insta.yaml", mode)
+        }
         _ => panic!("invalid value for INSTA_UPDATE"),
     }
 }
@@ -141,7 +212,6 @@ fn update_snapshot_behavior(unseen: bool) -> UpdateBehavior {
 fn memoize_snapshot_file(snapshot_file: &Path) {
     if let Ok(path) = env::var("INSTA_SNAPSHOT_REFERENCES_FILE") {
         let mut f = fs::OpenOptions::new()
-            .write(true)
             .append(true)
             .create(true)
             .open(path)
@@ -151,6 +221,80 @@ fn memoize_snapshot_file(snapshot_file: &Path) {
     }
 }
 
+/// The outcome of a single snapshot assertion.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssertionStatus {
+    /// The snapshot matched.
+    Pass,
+    /// The snapshot did not match the stored one.
+    Fail,
+    /// There was no stored snapshot yet.
+    New,
+}
+
+/// Records the result of a snapshot assertion.
+///
+/// These are written as newline delimited JSON to the file referenced by
+/// `INSTA_ASSERTION_RESULTS_FILE` so that tools can report on a test run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssertionResult {
+    pub status: AssertionStatus,
+    pub snapshot_name: Option<String>,
+    pub snapshot_file: Option<PathBuf>,
+    pub source_file: String,
+    pub line: u32,
+    pub expression: Option<String>,
+    /// The old contents, only set for failed assertions.
+    pub old: Option<String>,
+    /// The new contents, only set for failed and new assertions.
+    pub new: Option<String>,
+}
+
+fn record_assertion_result(
+    cargo_workspace: &Path,
+    snapshot_file: Option<&Path>,
+    new: &Snapshot,
+    old: Option<&Snapshot>,
+    line: u32,
+    passed: bool,
+) {
+    let path = match env::var("INSTA_ASSERTION_RESULTS_FILE") {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    let status = match (passed, old) {
+        (true, _) => AssertionStatus::Pass,
+        (false, Some(_)) => AssertionStatus::Fail,
+        (false, None) => AssertionStatus::New,
+    };
+    let result = AssertionResult {
+        status,
+        snapshot_name: new.snapshot_name().map(|x| x.to_string()),
+        snapshot_file: snapshot_file
+            .map(|x| x.strip_prefix(cargo_workspace).unwrap_or(x).to_path_buf()),
+        source_file: new.metadata().source().unwrap_or("").to_string(),
+        line,
+        expression: new.metadata().expression().map(|x| x.to_string()),
+        old: old
+            .filter(|_| !passed)
+            .map(|x| x.contents().to_diffable_string().into_owned()),
+        new: if passed {
+            None
+        } else {
+            Some(new.contents().to_diffable_string().into_owned())
+        },
+    };
+    let mut line = serde_json::to_string(&result).unwrap();
+    line.push('\n');
+    let mut f = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .unwrap();
+    f.write_all(line.as_bytes()).unwrap();
+}
+
 fn output_snapshot_behavior() -> OutputBehavior {
     match env::var("INSTA_OUTPUT").ok().as_deref() {
         None | Some("") | Some("diff") => OutputBehavior::Diff,
@@ -192,6 +336,8 @@ pub fn get_cargo_workspace(manifest_dir: &str) -> &Path {
         #[derive(Deserialize)]
         struct Manifest {
             workspace_root: String,
+            #[serde(default)]
+            target_directory: Option<String>,
         }
         let output = std::process::Command::new(get_cargo())
             .arg("metadata")
@@ -202,121 +348,715 @@ pub fn get_cargo_workspace(manifest_dir: &str) -> &Path {
             .unwrap();
         let manifest: Manifest = serde_json::from_slice(&output.stdout).unwrap();
         let path = Box::leak(Box::new(PathBuf::from(manifest.workspace_root)));
+        if let Some(target_directory) = manifest.target_directory {
+            TARGET_DIRECTORIES
+                .lock()
+                .unwrap_or_else(|x| x.into_inner())
+                .insert(path.clone(), PathBuf::from(target_directory));
+        }
         workspaces.insert(manifest_dir.to_string(), path.as_path());
         workspaces.get(manifest_dir).unwrap()
     }
 }
 
-fn print_changeset(old: &str, new: &str, expr: Option<&str>) {
-    let width = term_width();
-    let diff = TextDiff::configure()
-        .algorithm(Algorithm::Patience)
-        .timeout(Duration::from_millis(500))
-        .diff_lines(old, new);
+/// Returns the `insta.yaml` config of a workspace.
+///
+/// The config is loaded once per workspace.  If it is invalid the defaults
+/// are returned, the error is reported by [`check_tool_config`] when a
+/// snapshot is asserted.
+pub fn get_tool_config(workspace_root: &Path) -> Arc<ToolConfig> {
+    load_tool_config(workspace_root).unwrap_or_default()
+}
 
-    if let Some(expr) = expr {
-        println!("{:─^1$}", "", width,);
-        println!("{}", style(format_rust_expression(expr)));
+fn load_tool_config(workspace_root: &Path) -> Result<Arc<ToolConfig>, String> {
+    let mut configs = TOOL_CONFIGS.lock().unwrap_or_else(|x| x.into_inner());
+    configs
+        .entry(workspace_root.to_path_buf())
+        .or_insert_with(|| {
+            ToolConfig::from_workspace(workspace_root)
+                .map(Arc::new)
+                .map_err(|err| err.to_string())
+        })
+        .clone()
+}
+
+/// Fails the assertion if the config of the workspace is invalid.
+///
+/// The defaults must not be used silently in that case as they could turn
+/// off policies like the secrets check.
+fn check_tool_config(workspace_root: &Path) {
+    if let Err(err) = load_tool_config(workspace_root) {
+        panic!("could not load the insta config: {}", err);
     }
-    println!("────────────┬{:─^1$}", "", width.saturating_sub(13));
-    let mut has_changes = false;
-    for (idx, group) in diff.grouped_ops(4).iter().enumerate() {
-        if idx > 0 {
-            println!("┈┈┈┈┈┈┈┈┈┈┈┈┼{:┈^1$}", "", width.saturating_sub(13));
-        }
-        for op in group {
-            for change in diff.iter_inline_changes(&op) {
-                match change.tag() {
-                    ChangeTag::Insert => {
-                        has_changes = true;
-                        print!(
-                            "{:>5} {:>5} │{}",
-                            "",
-                            style(change.new_index().unwrap()).cyan().dim().bold(),
-                            style("+").green(),
-                        );
-                        for &(emphasized, change) in change.values() {
-                            if emphasized {
-                                print!("{}", style(change).green().underlined());
-                            } else {
-                                print!("{}", style(change).green());
-                            }
-                        }
-                    }
-                    ChangeTag::Delete => {
-                        has_changes = true;
-                        print!(
-                            "{:>5} {:>5} │{}",
-                            style(change.old_index().unwrap()).cyan().dim(),
-                            "",
-                            style("-").red(),
-                        );
-                        for &(emphasized, change) in change.values() {
-                            if emphasized {
-                                print!("{}", style(change).red().underlined());
-                            } else {
-                                print!("{}", style(change).red());
-                            }
-                        }
-                    }
-                    ChangeTag::Equal => {
-                        print!(
-                            "{:>5} {:>5} │ ",
-                            style(change.old_index().unwrap()).cyan().dim(),
-                            style(change.new_index().unwrap()).cyan().dim().bold(),
-                        );
-                        for &(_, change) in change.values() {
-                            print!("{}", style(change).dim());
+}
+
+#[test]
+fn test_get_tool_config() {
+    let workspace_root = env::temp_dir().join(format!("insta-config-{}", std::process::id()));
+    fs::create_dir_all(&workspace_root).unwrap();
+    fs::write(
+        workspace_root.join("insta.yaml"),
+        "secrets:\n  check: deny\n",
+    )
+    .unwrap();
+    assert_eq!(
+        get_tool_config(&workspace_root).secrets.check,
+        SecretsCheck::Deny
+    );
+    // the config is cached per workspace
+    fs::write(workspace_root.join("insta.yaml"), "secrets: [").unwrap();
+    assert_eq!(
+        get_tool_config(&workspace_root).secrets.check,
+        SecretsCheck::Deny
+    );
+    let invalid_root = workspace_root.join("invalid");
+    fs::create_dir_all(&invalid_root).unwrap();
+    fs::write(invalid_root.join("insta.yaml"), "secrets: [").unwrap();
+    assert_eq!(
+        get_tool_config(&invalid_root).secrets.check,
+        SecretsCheck::Off
+    );
+    let err = std::panic::catch_unwind(|| check_tool_config(&invalid_root)).unwrap_err();
+    fs::remove_dir_all(&workspace_root).unwrap();
+    assert!(err
+        .downcast_ref::<String>()
+        .unwrap()
+        .starts_with("could not load the insta config: invalid config in"));
+}
+
+fn get_checksum_mode(workspace_root: &Path) -> ChecksumMode {
+    get_tool_config(workspace_root).snapshots.checksums
+}
+
+/// Warns about or fails for a snapshot file that was edited by hand.
+fn report_edited_snapshot(workspace_root: &Path, snapshot_file: &Path) {
+    let message = format!(
+        "snapshot file {} was edited by hand, run `cargo insta normalize` to \
+         rewrite it and update its checksum",
+        snapshot_file
+            .strip_prefix(workspace_root)
+            .unwrap_or(snapshot_file)
+            .display()
+    );
+    match get_checksum_mode(workspace_root) {
+        ChecksumMode::Off => {}
+        ChecksumMode::Warn => elog!("{} {}", style("warning:").yellow().bold(), message),
+        ChecksumMode::Fail => panic!("{}", message),
+    }
+}
+
+/// Returns where the pending file `path` of a snapshot is written.
+///
+/// This is `path` itself unless `pending.location` in the `insta.yaml` of
+/// the workspace moves pending files into the target directory.
+fn get_pending_path(workspace_root: &Path, path: PathBuf) -> PathBuf {
+    let location = get_tool_config(workspace_root).pending.location;
+    let relative_path = match path.strip_prefix(workspace_root) {
+        Ok(relative_path) if location == PendingLocation::Target => relative_path,
+        _ => return path,
+    };
+    let target_dir = TARGET_DIRECTORIES
+        .lock()
+        .unwrap_or_else(|x| x.into_inner())
+        .get(workspace_root)
+        .cloned()
+        .unwrap_or_else(|| workspace_root.join("target"));
+    pending_dir(&target_dir).join(relative_path)
+}
+
+/// Renders the values of an inserted or deleted line.
+///
+/// Words that changed within the line are highlighted.  Without colors
+/// they are wrapped in `[-...-]` and `{+...+}` markers instead so that
+/// intra-line changes stay visible in plain logs.
+fn format_inline_values<S: AsRef<str>>(
+    tag: ChangeTag,
+    values: &[(bool, S)],
+    colors: bool,
+) -> String {
+    let paint = |text: &str, emphasized: bool| {
+        let styled = match tag {
+            ChangeTag::Delete => style(text).red(),
+            _ => style(text).green(),
+        };
+        if emphasized {
+            styled.underlined().bold().to_string()
+        } else {
+            styled.to_string()
+        }
+    };
+    let mut rv = String::new();
+    for (emphasized, value) in values {
+        let value = value.as_ref();
+        if !emphasized {
+            rv.push_str(&paint(value, false));
+            continue;
+        }
+        let (text, newline) = if value.ends_with('\n') {
+            value.split_at(value.len() - 1)
+        } else {
+            (value, "")
+        };
+        if colors {
+            rv.push_str(&paint(text, true));
+        } else if !text.is_empty() {
+            match tag {
+                ChangeTag::Delete => rv.push_str(&format!("[-{}-]", text)),
+                _ => rv.push_str(&format!("{{+{}+}}", text)),
+            }
+        }
+        rv.push_str(newline);
+    }
+    rv
+}
+
+#[test]
+fn test_format_inline_values() {
+    let values = [(false, "let x = "), (true, "42"), (false, ";\n")];
+    assert_eq!(
+        format_inline_values(ChangeTag::Delete, &values, false),
+        "let x = [-42-];\n"
+    );
+    assert_eq!(
+        format_inline_values(ChangeTag::Insert, &values, false),
+        "let x = {+42+};\n"
+    );
+    assert_eq!(
+        format_inline_values(ChangeTag::Insert, &[(true, "tail\n")], false),
+        "{+tail+}\n"
+    );
+}
+
+/// Changes spanning more lines than this are not diffed within lines.
+///
+/// Inline diffs of large changes are expensive to compute and hard to read.
+const MAX_INLINE_DIFF_LINES: usize = 100;
+
+/// The number of diff lines shown when an assertion fails.
+///
+/// The full diff can be looked at with `cargo insta review`.
+const MAX_ASSERTION_DIFF_LINES: usize = 500;
+
+/// Prints a marker for unchanged lines left out of a diff.
+fn print_unchanged_marker(lines: usize, width: usize) {
+    let label = format!(
+        " … {} unchanged line{} … ",
+        lines,
+        if lines != 1 { "s" } else { "" }
+    );
+    println!(
+        "┈┈┈┈┈┈┈┈┈┈┈┈┼{}",
+        style(format!("{:┈^1$}", label, width.saturating_sub(13))).dim()
+    );
+}
+
+/// Returns the diff algorithm from the settings or the workspace config.
+fn get_diff_algorithm(workspace_root: &Path) -> Algorithm {
+    let algorithm = Settings::with(|settings| settings.diff_algorithm())
+        .or_else(|| get_tool_config(workspace_root).diff.algorithm)
+        .unwrap_or_default();
+    match algorithm {
+        DiffAlgorithm::Myers => Algorithm::Myers,
+        DiffAlgorithm::Patience => Algorithm::Patience,
+        DiffAlgorithm::Lcs => Algorithm::Lcs,
+    }
+}
+
+/// Returns the maximum snapshot size from the settings or the workspace
+/// config.
+fn get_max_snapshot_size(workspace_root: &Path) -> Option<usize> {
+    Settings::with(|settings| settings.max_snapshot_size())
+        .or_else(|| get_tool_config(workspace_root).snapshots.max_size)
+}
+
+/// A line of a diff with the values that changed within the line.
+struct RenderedChange {
+    tag: ChangeTag,
+    old_index: Option<usize>,
+    new_index: Option<usize>,
+    values: Vec<(bool, String)>,
+}
+
+/// Prints the lines of a diff with the unchanged lines around the changes.
+///
+/// Only the unchanged lines that might still be shown as context before
+/// the next change are kept.
+struct ChangesetPrinter {
+    width: usize,
+    colors: bool,
+    context: usize,
+    budget: usize,
+    hidden: usize,
+    has_changes: bool,
+    /// The number of unchanged lines to show after the last change.
+    trailing: usize,
+    /// Unchanged lines that are shown if a change follows.
+    leading: VecDeque<RenderedChange>,
+    /// The number of unchanged lines that were left out.
+    skipped: usize,
+}
+
+impl ChangesetPrinter {
+    fn add(&mut self, change: RenderedChange) {
+        if change.tag == ChangeTag::Equal {
+            if self.trailing > 0 {
+                self.trailing -= 1;
+                self.print_line(&change);
+            } else {
+                self.leading.push_back(change);
+                if self.leading.len() > self.context {
+                    self.leading.pop_front();
+                    self.skipped += 1;
+                }
+            }
+            return;
+        }
+        if self.skipped > 0 {
+            if self.budget > 0 {
+                print_unchanged_marker(self.skipped, self.width);
+            }
+            self.skipped = 0;
+        }
+        while let Some(leading) = self.leading.pop_front() {
+            self.print_line(&leading);
+        }
+        self.print_line(&change);
+        self.has_changes = true;
+        self.trailing = self.context;
+    }
+
+    fn print_line(&mut self, change: &RenderedChange) {
+        if self.budget == 0 {
+            self.hidden += 1;
+            return;
+        }
+        self.budget -= 1;
+        let mut out = match change.tag {
+            ChangeTag::Insert => format!(
+                "{:>5} {:>5} │{}{}",
+                "",
+                style(change.new_index.unwrap()).cyan().dim().bold(),
+                style("+").green(),
+                format_inline_values(ChangeTag::Insert, &change.values, self.colors)
+            ),
+            ChangeTag::Delete => format!(
+                "{:>5} {:>5} │{}{}",
+                style(change.old_index.unwrap()).cyan().dim(),
+                "",
+                style("-").red(),
+                format_inline_values(ChangeTag::Delete, &change.values, self.colors)
+            ),
+            ChangeTag::Equal => {
+                let mut out = format!(
+                    "{:>5} {:>5} │ ",
+                    style(change.old_index.unwrap()).cyan().dim(),
+                    style(change.new_index.unwrap()).cyan().dim().bold(),
+                );
+                for (_, value) in change.values.iter() {
+                    for (is_placeholder, segment) in split_placeholders(value) {
+                        if is_placeholder {
+                            write!(out, "{}", style(segment).magenta()).ok();
+                        } else {
+                            write!(out, "{}", style(segment).dim()).ok();
                         }
                     }
                 }
-                if change.missing_newline() {
-                    println!();
+                out
+            }
+        };
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        // printed line by line with `print!` so that the test harness
+        // captures it like the rest of the output.
+        print!("{}", out);
+    }
+
+    fn finish(self) -> usize {
+        if self.hidden > 0 {
+            println!(
+                "{:>5} {:>5} │{}",
+                "",
+                style("…").dim(),
+                style(format!(" {} more lines not shown", self.hidden)).cyan(),
+            );
+        } else if self.has_changes {
+            let unchanged = self.skipped + self.leading.len();
+            if unchanged > 0 {
+                print_unchanged_marker(unchanged, self.width);
+            }
+        } else {
+            println!(
+                "{:>5} {:>5} │{}",
+                "",
+                style("-").dim(),
+                style(" snapshots are matching").cyan(),
+            );
+        }
+        self.hidden
+    }
+}
+
+/// Prints the changes between two texts.
+///
+/// The texts are diffed a window of lines at a time and the changes are
+/// printed as they are found, so huge snapshots neither need memory for
+/// the whole diff nor delay the output.  At most `max_lines` lines of
+/// changes are printed, the number of lines that were left out is returned.
+fn print_changeset(
+    old: &str,
+    new: &str,
+    expr: Option<&str>,
+    algorithm: Algorithm,
+    max_lines: Option<usize>,
+) -> usize {
+    let width = term_width();
+    if let Some(expr) = expr {
+        println!("{:─^1$}", "", width);
+        println!("{}", style(format_rust_expression(expr)));
+    }
+    println!("────────────┬{:─^1$}", "", width.saturating_sub(13));
+    let mut printer = ChangesetPrinter {
+        width,
+        colors: colors_enabled(),
+        context: Settings::with(|settings| settings.diff_context()),
+        budget: max_lines.unwrap_or(std::usize::MAX),
+        hidden: 0,
+        has_changes: false,
+        trailing: 0,
+        leading: VecDeque::new(),
+        skipped: 0,
+    };
+    crate::line_diff::diff_lines(old, new, algorithm, |op| {
+        let inline = op.tag == DiffTag::Replace
+            && op.old_lines.len() + op.new_lines.len() <= MAX_INLINE_DIFF_LINES;
+        if inline {
+            let old_text = op.old_lines.concat();
+            let new_text = op.new_lines.concat();
+            let diff = TextDiff::configure()
+                .algorithm(algorithm)
+                .timeout(Duration::from_millis(500))
+                .diff_lines(&old_text, &new_text);
+            for inner_op in diff.ops() {
+                for change in diff.iter_inline_changes(inner_op) {
+                    printer.add(RenderedChange {
+                        tag: change.tag(),
+                        old_index: change.old_index().map(|x| x + op.old_start),
+                        new_index: change.new_index().map(|x| x + op.new_start),
+                        values: change
+                            .values()
+                            .iter()
+                            .map(|&(emphasized, value)| (emphasized, value.to_string()))
+                            .collect(),
+                    });
                 }
             }
+            return;
+        }
+        if op.tag == DiffTag::Equal {
+            for (idx, line) in op.old_lines.iter().enumerate() {
+                printer.add(RenderedChange {
+                    tag: ChangeTag::Equal,
+                    old_index: Some(op.old_start + idx),
+                    new_index: Some(op.new_start + idx),
+                    values: vec![(false, line.to_string())],
+                });
+            }
+            return;
+        }
+        for (idx, line) in op.old_lines.iter().enumerate() {
+            printer.add(RenderedChange {
+                tag: ChangeTag::Delete,
+                old_index: Some(op.old_start + idx),
+                new_index: None,
+                values: vec![(false, line.to_string())],
+            });
+        }
+        for (idx, line) in op.new_lines.iter().enumerate() {
+            printer.add(RenderedChange {
+                tag: ChangeTag::Insert,
+                old_index: None,
+                new_index: Some(op.new_start + idx),
+                values: vec![(false, line.to_string())],
+            });
+        }
+    });
+    let hidden = printer.finish();
+    println!("────────────┴{:─^1$}", "", width.saturating_sub(13));
+    hidden
+}
+
+#[test]
+fn test_print_changeset_large() {
+    let old: String = (0..200_000).map(|x| format!("line {}\n", x)).collect();
+    let new = old.replace("line 1000\n", "changed\n") + "added\n";
+    assert_eq!(print_changeset(&old, &new, None, Algorithm::Myers, None), 0);
+    assert_eq!(
+        print_changeset(&old, &old, None, Algorithm::Myers, Some(10)),
+        0
+    );
+
+    // every line differs, only the first ten are shown
+    let new: String = (0..200_000).map(|x| format!("other {}\n", x)).collect();
+    assert_eq!(
+        print_changeset(&old, &new, None, Algorithm::Myers, Some(10)),
+        399_990
+    );
+}
+
+/// Highlights keys, strings and literals in a line of snapshot contents.
+///
+/// This only knows enough about YAML, JSON and debug output to make the
+/// contents easier to scan, it does not attempt to parse them.
+fn highlight_line(line: &str) -> String {
+    let mut rv = String::new();
+    for (is_placeholder, segment) in split_placeholders(line) {
+        if is_placeholder {
+            rv.push_str(&style(segment).magenta().to_string());
+            continue;
         }
+        let bytes = segment.as_bytes();
+        let is_word_byte = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+        let is_key_end = |end: usize| {
+            bytes.get(end) == Some(&b':') && bytes.get(end + 1).map_or(true, |&b| b == b' ')
+        };
+        let mut plain_start = 0;
+        let mut idx = 0;
+        while idx < bytes.len() {
+            let start = idx;
+            let styled = if bytes[idx] == b'"' {
+                idx += 1;
+                while idx < bytes.len() && bytes[idx] != b'"' {
+                    idx += if bytes[idx] == b'\\' { 2 } else { 1 };
+                }
+                idx = (idx + 1).min(bytes.len());
+                if is_key_end(idx) {
+                    style(&segment[start..idx]).yellow()
+                } else {
+                    style(&segment[start..idx]).green()
+                }
+            } else if is_word_byte(bytes[idx])
+                || (bytes[idx] == b'-' && bytes.get(idx + 1).map_or(false, u8::is_ascii_digit))
+            {
+                if idx > 0 && is_word_byte(bytes[idx - 1]) {
+                    idx += 1;
+                    continue;
+                }
+                idx += 1;
+                while idx < bytes.len() && (is_word_byte(bytes[idx]) || bytes[idx] == b'.') {
+                    idx += 1;
+                }
+                let word = &segment[start..idx];
+                let is_number = !bytes[start].is_ascii_alphabetic() && word.parse::<f64>().is_ok();
+                if is_key_end(idx) {
+                    style(word).yellow()
+                } else if is_number || word == "true" || word == "false" || word == "null" {
+                    style(word).cyan()
+                } else {
+                    continue;
+                }
+            } else {
+                idx += 1;
+                continue;
+            };
+            rv.push_str(&segment[plain_start..start]);
+            rv.push_str(&styled.to_string());
+            plain_start = idx;
+        }
+        rv.push_str(&segment[plain_start..]);
     }
+    rv
+}
 
-    if !has_changes {
+/// Prints a snapshot with its metadata and highlighted contents.
+pub fn print_snapshot(workspace_root: &Path, snapshot: &Snapshot, snapshot_file: Option<&Path>) {
+    print_snapshot_summary(workspace_root, snapshot, snapshot_file, None);
+    let metadata = snapshot.metadata();
+    if let Some(extension) = metadata.extension() {
+        println!("Extension: {}", style(extension).cyan());
+    }
+    if let Some(compression) = metadata.compression() {
         println!(
-            "{:>5} {:>5} │{}",
-            "",
-            style("-").dim(),
-            style(" snapshots are matching").cyan(),
+            "Compression: {}",
+            style(match compression {
+                Compression::Gzip => "gzip",
+            })
+            .cyan()
         );
     }
+    if let Some(blob) = metadata.blob() {
+        println!("Blob: {}", style(blob).cyan());
+    }
 
-    println!("────────────┴{:─^1$}", "", width.saturating_sub(13),);
+    let width = term_width();
+    if let Some(expr) = metadata.expression() {
+        println!("{:─^1$}", "", width);
+        println!("{}", style(format_rust_expression(expr)));
+    }
+    println!("──────┬{:─^1$}", "", width.saturating_sub(7));
+    let contents = snapshot.contents().to_diffable_string();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = if snapshot.contents().is_binary() {
+            line.to_string()
+        } else {
+            highlight_line(line)
+        };
+        println!("{:>5} │ {}", style(idx + 1).cyan().dim(), line);
+    }
+    println!("──────┴{:─^1$}", "", width.saturating_sub(7));
 }
 
 pub fn get_snapshot_filename(
     module_path: &str,
     snapshot_name: &str,
     cargo_workspace: &Path,
+    manifest_dir: &str,
     base: &str,
 ) -> PathBuf {
     let root = Path::new(cargo_workspace);
     let base = Path::new(base);
+    let config = get_tool_config(cargo_workspace);
     Settings::with(|settings| {
-        root.join(base.parent().unwrap())
-            .join(settings.snapshot_path())
-            .join({
-                use std::fmt::Write;
-                let mut f = String::new();
-                if settings.prepend_module_to_snapshot() {
-                    write!(&mut f, "{}__", module_path.replace("::", "__")).unwrap();
-                }
-                write!(
-                    &mut f,
-                    "{}.snap",
-                    snapshot_name.replace("/", "__").replace("\\", "__")
+        let layout = settings
+            .snapshot_layout()
+            .or(config.snapshots.layout)
+            .unwrap_or_default();
+        let mut rv = match config.snapshots.root {
+            // an absolute snapshot path still takes precedence
+            Some(ref central_root) if !settings.snapshot_path().is_absolute() => {
+                // cargo passes the package name to the tests it runs
+                let package = env::var("CARGO_PKG_NAME").unwrap_or_else(|_| {
+                    Path::new(manifest_dir)
+                        .file_name()
+                        .map_or_else(String::new, |x| x.to_string_lossy().into_owned())
+                });
+                root.join(central_root).join(package)
+            }
+            _ => root
+                .join(base.parent().unwrap())
+                .join(settings.snapshot_path()),
+        };
+        let prepend_module = settings.prepend_module_to_snapshot();
+        if prepend_module && layout == SnapshotLayout::Nested {
+            rv.extend(module_path.split("::"));
+        }
+        let module_path = if prepend_module && layout == SnapshotLayout::Flat {
+            module_path
+        } else {
+            ""
+        };
+        let snapshot_name = snapshot_name.replace("/", "__").replace("\\", "__");
+
+        let file_name = match config.snapshots.filename {
+            Some(ref template) => {
+                let suffix = settings.snapshot_suffix().unwrap_or("");
+                let name = if suffix.is_empty() {
+                    snapshot_name
+                } else {
+                    snapshot_name.replacen(&format!("@{}", suffix), "", 1)
+                };
+                let mut modules = module_path.split("::").filter(|x| !x.is_empty());
+                let crate_name = modules.next().unwrap_or("");
+                let module = modules
+                    .collect::<Vec<_>>()
+                    .join(&config.snapshots.module_separator);
+                let test = current_test_name().unwrap_or_default();
+                render_filename_template(
+                    template,
+                    &[
+                        ("crate", crate_name),
+                        ("module", &module),
+                        ("test", &test),
+                        ("name", &name),
+                        ("suffix", suffix),
+                    ],
                 )
-                .unwrap();
-                f
-            })
+                .unwrap_or_else(|err| {
+                    panic!("invalid snapshot filename template in insta.yaml: {}", err)
+                })
+            }
+            None if module_path.is_empty() => snapshot_name,
+            None => format!("{}__{}", module_path.replace("::", "__"), snapshot_name),
+        };
+        rv.join(format!("{}.snap", file_name))
     })
 }
 
+/// Returns the name of the running test function.
+fn current_test_name() -> Option<String> {
+    let name = match CURRENT_TEST_NAME.with(|x| x.get()) {
+        Some(name) => name.to_string(),
+        None => thread::current().name()?.to_string(),
+    };
+    match name.rsplit("::").next() {
+        Some("main") | None => None,
+        Some(name) => Some(name.to_string()),
+    }
+}
+
+/// Fills in the placeholders of a snapshot filename template.
+///
+/// The separator in front of an empty placeholder is left out, or the one
+/// after it if the placeholder comes first.
+fn render_filename_template(template: &str, values: &[(&str, &str)]) -> Result<String, String> {
+    // placeholders are values, everything else is separators
+    let mut parts: Vec<(bool, String)> = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed placeholder in '{}'", template))?;
+        let key = &rest[start + 1..start + end];
+        let value = values
+            .iter()
+            .find(|x| x.0 == key)
+            .ok_or_else(|| format!("unknown placeholder '{{{}}}'", key))?
+            .1;
+        parts.push((false, rest[..start].to_string()));
+        parts.push((true, value.to_string()));
+        rest = &rest[start + end + 1..];
+    }
+    parts.push((false, rest.to_string()));
+    parts.retain(|x| x.0 || !x.1.is_empty());
+
+    for idx in 0..parts.len() {
+        if !parts[idx].0 || !parts[idx].1.is_empty() {
+            continue;
+        }
+        if idx > 0 && !parts[idx - 1].0 {
+            parts[idx - 1].1.clear();
+        } else if idx + 1 < parts.len() && !parts[idx + 1].0 {
+            parts[idx + 1].1.clear();
+        }
+    }
+    let rv: String = parts.into_iter().map(|x| x.1).collect();
+    if rv.is_empty() {
+        return Err(format!("'{}' gives an empty file name", template));
+    }
+    Ok(rv)
+}
+
+#[test]
+fn test_render_filename_template() {
+    let values = [("module", "parser"), ("name", "expr"), ("suffix", "")];
+    assert_eq!(
+        render_filename_template("{module}.{name}", &values).unwrap(),
+        "parser.expr"
+    );
+    assert_eq!(
+        render_filename_template("{module}.{name}.{suffix}", &values).unwrap(),
+        "parser.expr"
+    );
+    assert_eq!(
+        render_filename_template("{suffix}-{module}-{name}", &values).unwrap(),
+        "parser-expr"
+    );
+    assert_eq!(
+        render_filename_template("{module}__{test}", &values).unwrap_err(),
+        "unknown placeholder '{test}'"
+    );
+}
+
 /// Prints the summary of a snapshot
 pub fn print_snapshot_summary(
     workspace_root: &Path,
@@ -342,6 +1082,10 @@ pub fn print_snapshot_summary(
         println!("Snapshot: {}", style("<inline>").dim());
     }
 
+    if let Some(description) = snapshot.metadata().description() {
+        println!("Description: {}", style(description).bold());
+    }
+
     if let Some(ref value) = snapshot.metadata().get_relative_source(workspace_root) {
         println!(
             "Source: {}{}",
@@ -357,6 +1101,28 @@ pub fn print_snapshot_summary(
     if let Some(ref value) = snapshot.metadata().input_file() {
         println!("Input file: {}", style(value).cyan());
     }
+
+    if let Some(expires) = snapshot.metadata().expires() {
+        println!(
+            "Expires: {}",
+            if snapshot.metadata().is_expired() {
+                style(expires).red()
+            } else {
+                style(expires).cyan()
+            }
+        );
+    }
+
+    let info = snapshot.metadata().info();
+    if !info.is_empty() {
+        println!(
+            "Info: {}",
+            info.iter()
+                .map(|(key, value)| format!("{}={}", key, style(value).cyan()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
 }
 
 /// Prints a diff against an old snapshot.
@@ -367,20 +1133,174 @@ pub fn print_snapshot_diff(
     snapshot_file: Option<&Path>,
     line: Option<u32>,
 ) {
+    print_snapshot_diff_limited(workspace_root, new, old_snapshot, snapshot_file, line, None);
+}
+
+/// Prints a diff against an old snapshot showing at most `max_lines` lines.
+///
+/// Returns the number of lines of the diff that were not shown.
+pub fn print_snapshot_diff_limited(
+    workspace_root: &Path,
+    new: &Snapshot,
+    old_snapshot: Option<&Snapshot>,
+    snapshot_file: Option<&Path>,
+    line: Option<u32>,
+    max_lines: Option<usize>,
+) -> usize {
     print_snapshot_summary(workspace_root, new, snapshot_file, line);
-    let old_contents = old_snapshot.as_ref().map_or("", |x| x.contents_str());
-    let new_contents = new.contents_str();
+    #[cfg(feature = "image")]
+    {
+        if print_image_diff(new, old_snapshot) {
+            return 0;
+        }
+    }
+    let old_contents =
+        old_snapshot.map_or(Cow::Borrowed(""), |x| x.contents().to_diffable_string());
+    let new_contents = new.contents().to_diffable_string();
     if !old_contents.is_empty() {
         println!("{}", style("-old snapshot").red());
         println!("{}", style("+new results").green());
     } else {
         println!("{}", style("+new results").green());
     }
+    #[cfg(feature = "matrix")]
+    {
+        let (absolute, relative) = Settings::with(|settings| {
+            (
+                settings.float_tolerance(),
+                settings.relative_float_tolerance(),
+            )
+        });
+        if let Some(deviation) =
+            crate::matrix::describe_deviation(&old_contents, &new_contents, absolute, relative)
+        {
+            println!("{}", style(deviation).red().bold());
+        }
+    }
+    let algorithm = get_diff_algorithm(workspace_root);
+    #[cfg(feature = "dir")]
+    {
+        if let Some(hidden) = print_dir_diff(
+            &old_contents,
+            &new_contents,
+            new.metadata().expression.as_deref(),
+            algorithm,
+            max_lines,
+        ) {
+            return hidden;
+        }
+    }
     print_changeset(
-        old_contents,
-        new_contents,
+        &old_contents,
+        &new_contents,
         new.metadata().expression.as_deref(),
-    );
+        algorithm,
+        max_lines,
+    )
+}
+
+/// Prints the changes of a directory snapshot file by file.
+///
+/// At most `max_lines` lines are shown per file.  Returns the number of
+/// lines that were not shown or `None` if the snapshots are not directory
+/// snapshots.
+#[cfg(feature = "dir")]
+fn print_dir_diff(
+    old: &str,
+    new: &str,
+    expr: Option<&str>,
+    algorithm: Algorithm,
+    max_lines: Option<usize>,
+) -> Option<usize> {
+    let changes = crate::dir::diff_dir_snapshots(old, new)?;
+    let mut hidden = 0;
+    if let Some(expr) = expr {
+        println!("{:─^1$}", "", term_width());
+        println!("{}", style(format_rust_expression(expr)));
+    }
+    for change in changes {
+        let status = match (&change.old, &change.new) {
+            (None, _) => style("added").green(),
+            (_, None) => style("removed").red(),
+            _ => style("modified").yellow(),
+        };
+        println!(
+            "File: {} ({})",
+            style(&change.path).cyan().underlined(),
+            status
+        );
+        hidden += print_changeset(
+            change.old.as_deref().unwrap_or(""),
+            change.new.as_deref().unwrap_or(""),
+            None,
+            algorithm,
+            max_lines,
+        );
+    }
+    Some(hidden)
+}
+
+/// Prints the percentage of differing pixels for PNG snapshots.
+///
+/// Returns `false` if the snapshots are not images that can be compared.
+#[cfg(feature = "image")]
+fn print_image_diff(new: &Snapshot, old_snapshot: Option<&Snapshot>) -> bool {
+    if new.metadata().extension() != Some("png") {
+        return false;
+    }
+    let old = match old_snapshot {
+        Some(old) if old.contents().is_binary() => old,
+        _ => {
+            println!("{}", style("+new image").green());
+            return true;
+        }
+    };
+    let threshold = Settings::with(|settings| settings.image_threshold());
+    match crate::image::diff_images(
+        old.contents().as_bytes(),
+        new.contents().as_bytes(),
+        threshold,
+    ) {
+        Ok(diff) => {
+            println!(
+                "Differing pixels: {} ({} of {}, threshold {})",
+                style(format!("{:.2}%", diff.percentage())).red().bold(),
+                diff.differing_pixels,
+                diff.total_pixels,
+                threshold
+            );
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Writes an image highlighting the differences next to a pending snapshot.
+#[cfg(feature = "image")]
+fn write_image_diff(
+    new_path: &Path,
+    new: &Snapshot,
+    old: Option<&Snapshot>,
+) -> Result<(), Box<dyn Error>> {
+    let (old, binary_file) = match (old, new.binary_file(new_path)) {
+        (Some(old), Some(binary_file)) if new.metadata().extension() == Some("png") => {
+            (old, binary_file)
+        }
+        _ => return Ok(()),
+    };
+    if !Settings::with(|settings| settings.write_image_diff()) {
+        return Ok(());
+    }
+    let threshold = Settings::with(|settings| settings.image_threshold());
+    let diff = crate::image::diff_images(
+        old.contents().as_bytes(),
+        new.contents().as_bytes(),
+        threshold,
+    )?;
+    let mut diff_path = binary_file;
+    diff_path.set_extension("diff.png");
+    fs::write(diff_path, diff.diff_image.to_png()?)?;
+    Ok(())
 }
 
 fn print_snapshot_diff_with_title(
@@ -396,12 +1316,13 @@ fn print_snapshot_diff_with_title(
         title = style(" Snapshot Differences ").bold(),
         width = width
     );
-    print_snapshot_diff(
+    print_snapshot_diff_limited(
         workspace_root,
         new_snapshot,
         old_snapshot,
         snapshot_file,
         Some(line),
+        Some(MAX_ASSERTION_DIFF_LINES),
     );
 }
 
@@ -464,6 +1385,23 @@ impl<'a> From<&'a str> for ReferenceValue<'a> {
 pub enum ReferenceValue<'a> {
     Named(Option<Cow<'a, str>>),
     Inline(&'a str),
+    /// An inline snapshot within a doctest and the offset of the doctest
+    /// lines to the lines of the source file.
+    DocTestInline(&'a str, u32),
+}
+
+impl<'a> ReferenceValue<'a> {
+    /// Creates the reference value of an inline snapshot.
+    ///
+    /// Rustdoc sets `UNSTABLE_RUSTDOC_TEST_LINE` when compiling doctests
+    /// which is passed as `doctest_line`.  Added to `line!()` it gives the
+    /// line in the source file.
+    pub fn inline(contents: &'a str, doctest_line: Option<&str>) -> ReferenceValue<'a> {
+        match doctest_line.and_then(|x| x.parse().ok()) {
+            Some(offset) => ReferenceValue::DocTestInline(contents, offset),
+            None => ReferenceValue::Inline(contents),
+        }
+    }
 }
 
 #[cfg(feature = "backtrace")]
@@ -498,14 +1436,65 @@ fn test_name_from_backtrace(module_path: &str) -> Result<String, &'static str> {
     )
 }
 
+/// Runs a test function with its name bound to the thread.
+///
+/// This is used by `#[insta::test]`.
+pub fn run_test<R, F: FnOnce() -> R>(name: &'static str, f: F) -> R {
+    let old = CURRENT_TEST_NAME.with(|x| x.replace(Some(name)));
+    let rv = f();
+    CURRENT_TEST_NAME.with(|x| x.set(old));
+    rv
+}
+
+/// Runs the future of an async test.
+///
+/// The test name and the settings are bound to whatever thread polls the
+/// future.  Settings that the test binds to the thread are carried over to
+/// the next poll, so they survive the runtime moving the future between
+/// worker threads.  This is used by `#[insta::test]`.
+pub fn run_test_future<F: Future>(
+    name: &'static str,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    struct TestFuture<F> {
+        name: &'static str,
+        settings: Settings,
+        future: F,
+    }
+
+    impl<F: Future> Future for TestFuture<F> {
+        type Output = F::Output;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+            let this = unsafe { self.get_unchecked_mut() };
+            let future = unsafe { Pin::new_unchecked(&mut this.future) };
+            let name = this.name;
+            let (rv, settings) = this.settings.bind(|| {
+                let rv = run_test(name, || future.poll(cx));
+                (rv, Settings::clone_current())
+            });
+            this.settings = settings;
+            rv
+        }
+    }
+
+    TestFuture {
+        name,
+        settings: Settings::clone_current(),
+        future,
+    }
+}
+
 fn generate_snapshot_name_for_thread(module_path: &str) -> Result<String, &'static str> {
     let thread = thread::current();
+    let test_name = CURRENT_TEST_NAME.with(|x| x.get());
     #[allow(unused_mut)]
-    let mut name = Cow::Borrowed(
-        thread
+    let mut name = Cow::Borrowed(match test_name {
+        Some(name) => name,
+        None => thread
             .name()
             .ok_or("test thread is unnamed, no snapshot name can be generated.")?,
-    );
+    });
     if name == "main" {
         #[cfg(feature = "backtrace")]
         {
@@ -807,18 +1796,50 @@ enum SnapshotUpdateResult {
     NoUpdate,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_snapshots(
+    workspace_root: &Path,
     snapshot_file: Option<&Path>,
     new: Snapshot,
     old: Option<Snapshot>,
     line: u32,
+    doctest: bool,
     pending_snapshots: Option<PathBuf>,
     output_behavior: OutputBehavior,
+    configured_mode: Option<&str>,
 ) -> Result<SnapshotUpdateResult, Box<dyn Error>> {
     let unseen = snapshot_file.map_or(false, |x| fs::metadata(x).is_ok());
     let should_print = output_behavior != OutputBehavior::Nothing;
+    let behavior = match update_snapshot_behavior(unseen, configured_mode) {
+        // inline snapshots can only be written by cargo-insta
+        UpdateBehavior::Ask if snapshot_file.is_none() => UpdateBehavior::NewFile,
+        UpdateBehavior::Ask => match ask_for_decision(&new) {
+            AskDecision::Accept => UpdateBehavior::InPlace,
+            AskDecision::Reject => UpdateBehavior::NoUpdate,
+            AskDecision::Skip => UpdateBehavior::NewFile,
+        },
+        // stored snapshots are never touched, new inline snapshots (the ones
+        // that are still empty) are left to cargo-insta
+        UpdateBehavior::NewOnly if snapshot_file.is_none() => {
+            if old
+                .as_ref()
+                .map_or(true, |x| x.contents().as_bytes().is_empty())
+            {
+                UpdateBehavior::NewFile
+            } else {
+                UpdateBehavior::NoUpdate
+            }
+        }
+        UpdateBehavior::NewOnly if unseen || old.is_some() => UpdateBehavior::NoUpdate,
+        UpdateBehavior::NewOnly => UpdateBehavior::InPlace,
+        behavior => behavior,
+    };
+
+    if behavior != UpdateBehavior::NoUpdate {
+        check_for_secrets(workspace_root, snapshot_file, line, &new, should_print);
+    }
 
-    match update_snapshot_behavior(unseen) {
+    match behavior {
         UpdateBehavior::InPlace => {
             if let Some(ref snapshot_file) = snapshot_file {
                 new.save(snapshot_file)?;
@@ -845,9 +1866,13 @@ fn update_snapshots(
         }
         UpdateBehavior::NewFile => {
             if let Some(snapshot_file) = snapshot_file {
-                let mut new_path = snapshot_file.to_path_buf();
-                new_path.set_extension("snap.new");
+                let new_path =
+                    get_pending_path(workspace_root, snapshot_file.with_extension("snap.new"));
                 new.save(&new_path)?;
+                #[cfg(feature = "image")]
+                {
+                    write_image_diff(&new_path, &new, old.as_ref())?;
+                }
                 if should_print {
                     elog!(
                         "{} {}",
@@ -856,12 +1881,190 @@ fn update_snapshots(
                     );
                 }
             } else {
-                PendingInlineSnapshot::new(Some(new), old, line)
-                    .save(pending_snapshots.unwrap())?;
+                let mut pending = PendingInlineSnapshot::new(Some(new), old, line);
+                pending.doctest = doctest;
+                pending.save(pending_snapshots.unwrap())?;
             }
             Ok(SnapshotUpdateResult::WroteNewFile)
         }
         UpdateBehavior::NoUpdate => Ok(SnapshotUpdateResult::NoUpdate),
+        // resolved into one of the other behaviors above
+        UpdateBehavior::Ask | UpdateBehavior::NewOnly => unreachable!(),
+    }
+}
+
+/// Asks the user on the terminal what to do with a changed snapshot.
+#[cfg(feature = "colors")]
+fn ask_for_decision(new: &Snapshot) -> AskDecision {
+    use console::{Key, Term};
+
+    let term = Term::stderr();
+    elog!(
+        "{} {}  {} accept  {} reject  {} skip",
+        style("Snapshot").bold(),
+        style(new.snapshot_name().unwrap_or("<unnamed>")).yellow(),
+        style("a").green().bold(),
+        style("r").red().bold(),
+        style("s").yellow().bold(),
+    );
+    loop {
+        match term.read_key() {
+            Ok(Key::Char('a')) | Ok(Key::Enter) => return AskDecision::Accept,
+            Ok(Key::Char('r')) | Ok(Key::Escape) => return AskDecision::Reject,
+            Ok(Key::Char('s')) | Ok(Key::Char(' ')) => return AskDecision::Skip,
+            Ok(_) => {}
+            Err(_) => return AskDecision::Skip,
+        }
+    }
+}
+
+#[cfg(not(feature = "colors"))]
+fn ask_for_decision(_new: &Snapshot) -> AskDecision {
+    AskDecision::Skip
+}
+
+/// Checks new snapshot contents for likely credentials before they are
+/// written if the workspace config asks for it.
+fn check_for_secrets(
+    workspace_root: &Path,
+    snapshot_file: Option<&Path>,
+    line: u32,
+    snapshot: &Snapshot,
+    should_print: bool,
+) {
+    let check = get_tool_config(workspace_root).secrets.check;
+    if check == SecretsCheck::Off {
+        return;
+    }
+    let secrets = match snapshot.contents().as_str() {
+        Some(contents) => find_secrets(contents),
+        None => return,
+    };
+    if secrets.is_empty() {
+        return;
+    }
+    let location = match snapshot_file {
+        Some(snapshot_file) => snapshot_file.display().to_string(),
+        None => format!(
+            "inline snapshot in {}:{}",
+            snapshot.metadata().source().unwrap_or("<unknown>"),
+            line
+        ),
+    };
+    let found = secrets
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if check == SecretsCheck::Deny {
+        panic!(
+            "Insta refused to write {} because it likely contains credentials: {}. \
+             Filter or redact them.",
+            location, found
+        );
+    }
+    if should_print {
+        elog!(
+            "{} {} likely contains credentials: {}",
+            style("warning:").bold().yellow(),
+            location,
+            found
+        );
+    }
+}
+
+/// Returns the path of the snapshot file of a variant.
+///
+/// Variants of `name.snap` are stored as `name@variant.snap`.
+pub fn snapshot_variant_file(snapshot_file: &Path, variant: &str) -> PathBuf {
+    snapshot_file.with_file_name(format!(
+        "{}@{}.snap",
+        snapshot_file_stem(snapshot_file),
+        variant
+    ))
+}
+
+/// Returns the file name of a snapshot file without the extension.
+fn snapshot_file_stem(snapshot_file: &Path) -> &str {
+    let file_name = snapshot_file
+        .file_name()
+        .and_then(|x| x.to_str())
+        .unwrap_or("");
+    if file_name.ends_with(".snap") {
+        &file_name[..file_name.len() - 5]
+    } else {
+        file_name
+    }
+}
+
+/// Finds the stored variants of a snapshot file.
+fn find_snapshot_variants(snapshot_file: &Path) -> Vec<PathBuf> {
+    let folder = match snapshot_file.parent() {
+        Some(folder) => folder,
+        None => return vec![],
+    };
+    let prefix = format!("{}@", snapshot_file_stem(snapshot_file));
+    let mut rv: Vec<_> = fs::read_dir(folder)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|x| x.to_str())
+                .map_or(false, |x| x.starts_with(&prefix) && x.ends_with(".snap"))
+        })
+        .collect();
+    rv.sort();
+    rv
+}
+
+/// Checks if any of the variant snapshot files matches a snapshot.
+fn matches_snapshot_variant(variants: &[PathBuf], snapshot: &Snapshot) -> bool {
+    variants.iter().any(|path| {
+        Snapshot::from_file(path).map_or(false, |variant| contents_match(&variant, snapshot))
+    })
+}
+
+/// Makes sure that assertions in different places do not write different
+/// contents to the same snapshot file.
+///
+/// Otherwise the last one to run would silently overwrite the snapshot.
+/// The same assertion can run more than once, for instance in a loop.
+/// A missing `snapshot` stands for contents that matched the stored file
+/// byte for byte, which is then loaded only if it needs to be compared.
+fn detect_snapshot_file_clash(
+    snapshot_file: &Path,
+    file: &str,
+    line: u32,
+    snapshot: Option<&Snapshot>,
+) {
+    // we really do not care about poisoning here.
+    let mut locations = SNAPSHOT_FILE_LOCATIONS
+        .lock()
+        .unwrap_or_else(|x| x.into_inner());
+    let location = locations
+        .entry(snapshot_file.to_path_buf())
+        .or_insert_with(|| (file.to_string(), line, snapshot.cloned()));
+    if location.0 == file && location.1 == line {
+        return;
+    }
+    let same_contents = match (&location.2, snapshot) {
+        (Some(old), Some(new)) => contents_match(old, new),
+        (None, None) => true,
+        (Some(present), None) | (None, Some(present)) => Snapshot::from_file(snapshot_file)
+            .map_or(false, |stored| contents_match(&stored, present)),
+    };
+    if !same_contents {
+        panic!(
+            "Insta snapshot name clash detected: {} is asserted with different \
+             contents in {}:{} and {}:{}. Use different snapshot names.",
+            snapshot_file.display(),
+            location.0,
+            location.1,
+            file,
+            line
+        );
     }
 }
 
@@ -875,6 +2078,85 @@ fn add_suffix_to_snapshot_name(name: Cow<'_, str>) -> Cow<'_, str> {
     })
 }
 
+/// Checks if the contents of two snapshots match.
+///
+/// Numbers in text snapshots are compared with the configured float
+/// tolerance and JSON snapshots structurally if enabled.  With a number
+/// precision numbers are rounded on both sides first.  Binary snapshots
+/// are compared with the comparator registered for their extension if
+/// there is one.
+fn contents_match(old: &Snapshot, new: &Snapshot) -> bool {
+    if let (SnapshotContents::Binary(old_contents), SnapshotContents::Binary(new_contents)) =
+        (old.contents(), new.contents())
+    {
+        let comparator = new
+            .metadata()
+            .extension()
+            .and_then(|extension| Settings::with(|settings| settings.binary_comparator(extension)));
+        if let Some(comparator) = comparator {
+            return comparator(old_contents, new_contents);
+        }
+        #[cfg(feature = "image")]
+        {
+            if new.metadata().extension() == Some("png") {
+                let threshold = Settings::with(|settings| settings.image_threshold());
+                return crate::image::images_match(old_contents, new_contents, threshold);
+            }
+        }
+    }
+    if let (Some(old_contents), Some(new_contents)) =
+        (old.contents().as_str(), new.contents().as_str())
+    {
+        let (absolute, relative, structural_json, precision) = Settings::with(|settings| {
+            (
+                settings.float_tolerance(),
+                settings.relative_float_tolerance(),
+                settings.structural_json(),
+                settings.number_precision(),
+            )
+        });
+        let normalized;
+        let (old_contents, new_contents) = match precision {
+            Some(precision) => {
+                normalized = (
+                    normalize_numbers(old_contents, precision),
+                    normalize_numbers(new_contents, precision),
+                );
+                (&*normalized.0, &*normalized.1)
+            }
+            None => (old_contents, new_contents),
+        };
+        #[cfg(feature = "matrix")]
+        {
+            if let Some(rv) =
+                crate::matrix::matrices_match(old_contents, new_contents, absolute, relative)
+            {
+                return rv;
+            }
+        }
+        if structural_json {
+            if let (Ok(old_value), Ok(new_value)) = (
+                serde_json::from_str(old_contents),
+                serde_json::from_str(new_contents),
+            ) {
+                return json_values_match(&old_value, &new_value, absolute, relative);
+            }
+        }
+        if absolute > 0.0 || relative > 0.0 {
+            return matches_with_tolerance(
+                old_contents.trim_end(),
+                new_contents.trim_end(),
+                absolute,
+                relative,
+            );
+        }
+        if precision.is_some() {
+            return old_contents.trim_end() == new_contents.trim_end();
+        }
+    }
+    old.contents() == new.contents()
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn assert_snapshot(
     refval: ReferenceValue<'_>,
@@ -885,10 +2167,282 @@ pub fn assert_snapshot(
     line: u32,
     expr: &str,
 ) -> Result<(), Box<dyn Error>> {
+    let processed = Settings::with(|settings| process_ansi(new_snapshot, settings.ansi_escapes()));
+    let new_snapshot = &*processed;
+    #[cfg(feature = "filters")]
+    let filtered = Settings::with(|settings| settings.filters().apply_to(new_snapshot));
+    #[cfg(feature = "filters")]
+    let new_snapshot = &*filtered;
+    #[cfg(feature = "schema")]
+    let schema_violations = crate::schema::take_violations();
+    #[cfg(feature = "schema")]
+    {
+        if !schema_violations.is_empty() {
+            print_schema_violations(&schema_violations);
+        }
+    }
+    assert_snapshot_contents(
+        refval,
+        new_snapshot.into(),
+        None,
+        manifest_dir,
+        module_path,
+        file,
+        line,
+        expr,
+    )?;
+    #[cfg(feature = "schema")]
+    {
+        if !schema_violations.is_empty() {
+            panic!(
+                "snapshot value in {}:{} violates the JSON schema, see the schema \
+                 violations above",
+                file, line
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Prints the JSON Schema violations of a snapshot value.
+#[cfg(feature = "schema")]
+fn print_schema_violations(violations: &[String]) {
+    let width = term_width();
+    println!(
+        "{title:━^width$}",
+        title = style(" Schema Violations ").bold(),
+        width = width
+    );
+    for violation in violations {
+        println!("{} {}", style("-").red(), violation);
+    }
+    println!("{title:━^width$}", title = "", width = width);
+}
+
+/// Splits the name of a binary snapshot from its extension.
+fn split_name_and_extension(name_and_extension: &str) -> (&str, &str) {
+    match name_and_extension.rfind('.') {
+        Some(idx) if idx + 1 < name_and_extension.len() => {
+            (&name_and_extension[..idx], &name_and_extension[idx + 1..])
+        }
+        _ => panic!(
+            "binary snapshot name '{}' needs an extension (eg: name.bin)",
+            name_and_extension
+        ),
+    }
+}
+
+/// Asserts a binary snapshot.
+///
+/// The name is in the form `name.extension`.  If the name part is empty
+/// (eg: `".png"`) the snapshot name is generated automatically.
+#[allow(clippy::too_many_arguments)]
+pub fn assert_binary_snapshot(
+    name_and_extension: &str,
+    new_snapshot: &[u8],
+    manifest_dir: &str,
+    module_path: &str,
+    file: &str,
+    line: u32,
+    expr: &str,
+) -> Result<(), Box<dyn Error>> {
+    let (name, extension) = split_name_and_extension(name_and_extension);
+    let refval = if name.is_empty() {
+        ReferenceValue::Named(None)
+    } else {
+        ReferenceValue::Named(Some(Cow::Borrowed(name)))
+    };
+    assert_snapshot_contents(
+        refval,
+        SnapshotContents::Binary(new_snapshot.to_vec()),
+        Some(extension),
+        manifest_dir,
+        module_path,
+        file,
+        line,
+        expr,
+    )
+}
+
+/// Asserts a binary snapshot of contents spooled to a file.
+///
+/// The spooled contents are compared in chunks against the file of the
+/// stored snapshot so that large snapshots which match never have to be
+/// held in memory.  Only if they do not match, or if the comparison needs
+/// more than plain equality, they are loaded and asserted like any other
+/// binary snapshot.  Matching contents go through the same name clash
+/// detection and result recording; since nothing is written for them
+/// there is nothing to check for secrets.
+#[allow(clippy::too_many_arguments)]
+pub fn assert_streamed_snapshot(
+    name_and_extension: &str,
+    spool_file: &Path,
+    manifest_dir: &str,
+    module_path: &str,
+    file: &str,
+    line: u32,
+    expr: &str,
+) -> Result<(), Box<dyn Error>> {
+    let (name, extension) = split_name_and_extension(name_and_extension);
     let cargo_workspace = get_cargo_workspace(manifest_dir);
+    check_tool_config(cargo_workspace);
+    let name = if name.is_empty() {
+        generate_snapshot_name_for_thread(module_path)?
+    } else {
+        name.to_string()
+    };
+    let snapshot_file = get_snapshot_filename(
+        module_path,
+        &add_suffix_to_snapshot_name(Cow::Borrowed(&name)),
+        cargo_workspace,
+        manifest_dir,
+        file,
+    );
+
+    if streamed_snapshot_matches(cargo_workspace, &snapshot_file, extension, spool_file)? {
+        let new = Snapshot::from_components(
+            module_path.replace("::", "__"),
+            Some(name),
+            MetaData {
+                source: Some(path_to_storage(file)),
+                expression: Some(expr.to_string()),
+                extension: Some(extension.to_string()),
+                ..MetaData::default()
+            },
+            SnapshotContents::Binary(vec![]),
+        );
+        memoize_snapshot_file(&snapshot_file);
+        detect_snapshot_file_clash(&snapshot_file, file, line, None);
+        record_assertion_result(
+            cargo_workspace,
+            Some(&snapshot_file),
+            &new,
+            None,
+            line,
+            true,
+        );
+        remove_pending_snapshots(cargo_workspace, &snapshot_file, &new);
+        return Ok(());
+    }
+
+    // the name is already generated and must not be generated again
+    assert_snapshot_contents(
+        ReferenceValue::Named(Some(Cow::Owned(name))),
+        SnapshotContents::Binary(fs::read(spool_file)?),
+        Some(extension),
+        manifest_dir,
+        module_path,
+        file,
+        line,
+        expr,
+    )
+}
+
+/// Checks if spooled contents are byte for byte those of a stored snapshot.
+///
+/// This is only attempted if plain equality decides whether the snapshot
+/// matches, otherwise `false` is returned.  Files with a checksum are left
+/// to the regular assertion so that hand edits are reported.
+fn streamed_snapshot_matches(
+    cargo_workspace: &Path,
+    snapshot_file: &Path,
+    extension: &str,
+    spool_file: &Path,
+) -> Result<bool, Box<dyn Error>> {
+    let metadata = match crate::snapshot::metadata_from_file(snapshot_file) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(false),
+    };
+    let image = cfg!(feature = "image") && extension == "png";
+    let compared_by_bytes = !image
+        && Settings::with(|settings| {
+            settings.snapshot_variant().is_none() && settings.binary_comparator(extension).is_none()
+        });
+    if !compared_by_bytes
+        || metadata.extension() != Some(extension)
+        || metadata.blob().is_some()
+        || metadata.checksum().is_some()
+        || metadata.is_expired()
+        || force_update_snapshots()
+    {
+        return Ok(false);
+    }
+    let len = fs::metadata(spool_file)?.len();
+    if get_max_snapshot_size(cargo_workspace).map_or(false, |max_size| len > max_size as u64) {
+        return Ok(false);
+    }
+    let stored_file = crate::snapshot::binary_path(snapshot_file, extension);
+    match fs::metadata(&stored_file) {
+        Ok(stored) if stored.len() == len => {}
+        _ => return Ok(false),
+    }
+    let mut stored = io::BufReader::new(fs::File::open(&stored_file)?);
+    let mut spooled = io::BufReader::new(fs::File::open(spool_file)?);
+    loop {
+        let stored_chunk = stored.fill_buf()?;
+        let spooled_chunk = spooled.fill_buf()?;
+        let len = stored_chunk.len().min(spooled_chunk.len());
+        if len == 0 {
+            return Ok(stored_chunk.is_empty() && spooled_chunk.is_empty());
+        }
+        if stored_chunk[..len] != spooled_chunk[..len] {
+            return Ok(false);
+        }
+        stored.consume(len);
+        spooled.consume(len);
+    }
+}
+
+/// Removes lingering pending snapshots of a snapshot that matched.
+fn remove_pending_snapshots(cargo_workspace: &Path, snapshot_file: &Path, new: &Snapshot) {
+    let new_path = snapshot_file.with_extension("snap.new");
+    let pending_path = get_pending_path(cargo_workspace, new_path.clone());
+    for path in &[new_path, pending_path] {
+        if let Some(mut binary_file) = new.binary_file(path) {
+            fs::remove_file(&binary_file).ok();
+            binary_file.set_extension("diff.png");
+            fs::remove_file(&binary_file).ok();
+        }
+        fs::remove_file(path).ok();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn assert_snapshot_contents(
+    refval: ReferenceValue<'_>,
+    new_snapshot_contents: SnapshotContents,
+    extension: Option<&str>,
+    manifest_dir: &str,
+    module_path: &str,
+    file: &str,
+    line: u32,
+    expr: &str,
+) -> Result<(), Box<dyn Error>> {
+    let cargo_workspace = get_cargo_workspace(manifest_dir);
+    check_tool_config(cargo_workspace);
     let output_behavior = output_snapshot_behavior();
+    let update_mode = configured_update_mode(cargo_workspace, module_path);
 
-    let (snapshot_name, snapshot_file, old, pending_snapshots) = match refval {
+    // doctests report lines relative to the doctest
+    let (line, doctest) = match refval {
+        ReferenceValue::DocTestInline(_, offset) => (line + offset, true),
+        _ => (line, false),
+    };
+
+    if let Some(max_size) = get_max_snapshot_size(cargo_workspace) {
+        let size = new_snapshot_contents.as_bytes().len();
+        if size > max_size {
+            panic!(
+                "Insta snapshot in {}:{} is {} bytes which exceeds the maximum snapshot size \
+                 of {} bytes. Leave out data that does not matter to the test, for instance \
+                 with redactions or filters or by snapshotting a smaller part of the value, \
+                 or raise the limit with Settings::set_max_snapshot_size or \
+                 snapshots.max_size in insta.yaml.",
+                file, line, size, max_size
+            );
+        }
+    }
+    let (snapshot_name, snapshot_file, old, pending_snapshots, other_variants) = match refval {
         ReferenceValue::Named(snapshot_name) => {
             let snapshot_name = match snapshot_name {
                 Some(snapshot_name) => add_suffix_to_snapshot_name(snapshot_name),
@@ -896,16 +2450,49 @@ pub fn assert_snapshot(
                     .unwrap()
                     .into(),
             };
-            let snapshot_file =
-                get_snapshot_filename(module_path, &snapshot_name, &cargo_workspace, file);
+            let mut snapshot_file = get_snapshot_filename(
+                module_path,
+                &snapshot_name,
+                cargo_workspace,
+                manifest_dir,
+                file,
+            );
+            // with variants the snapshot of the current variant is compared
+            // and updated if there is one, the others are accepted as well.
+            let mut other_variants = vec![];
+            if let Some(variant) =
+                Settings::with(|settings| settings.snapshot_variant().map(String::from))
+            {
+                other_variants = find_snapshot_variants(&snapshot_file);
+                let variant_file = snapshot_variant_file(&snapshot_file, &variant);
+                if other_variants.contains(&variant_file) {
+                    other_variants.push(snapshot_file);
+                    snapshot_file = variant_file;
+                }
+                other_variants.retain(|x| *x != snapshot_file && fs::metadata(x).is_ok());
+            }
             let old = if fs::metadata(&snapshot_file).is_ok() {
-                Some(Snapshot::from_file(&snapshot_file)?)
+                let old = Snapshot::from_file(&snapshot_file)?;
+                if old.is_edited() {
+                    report_edited_snapshot(cargo_workspace, &snapshot_file);
+                }
+                Some(old)
             } else {
                 None
             };
-            (Some(snapshot_name), Some(snapshot_file), old, None)
+            (
+                Some(snapshot_name),
+                Some(snapshot_file),
+                old,
+                None,
+                other_variants,
+            )
         }
-        ReferenceValue::Inline(contents) => {
+        ReferenceValue::Inline(contents) | ReferenceValue::DocTestInline(contents, _) => {
+            assert!(
+                extension.is_none(),
+                "binary snapshots cannot be stored inline"
+            );
             let snapshot_name = generate_snapshot_name_for_thread(module_path)
                 .ok()
                 .map(Cow::Owned);
@@ -927,18 +2514,44 @@ pub fn assert_snapshot(
                     MetaData::default(),
                     SnapshotContents::from_inline(contents),
                 )),
-                Some(filename),
+                Some(get_pending_path(cargo_workspace, filename)),
+                vec![],
             )
         }
     };
 
-    let new_snapshot_contents: SnapshotContents = new_snapshot.into();
+    // lines matched by placeholders of the old snapshot keep the placeholders
+    #[cfg(feature = "filters")]
+    let new_snapshot_contents = {
+        let merged = match (old.as_ref(), new_snapshot_contents.as_str()) {
+            (Some(old), Some(new_contents))
+                if Settings::with(|settings| settings.allow_placeholders()) =>
+            {
+                old.contents().as_str().and_then(|old_contents| {
+                    crate::placeholders::merge_placeholders(old_contents, new_contents)
+                })
+            }
+            _ => None,
+        };
+        merged.map_or(new_snapshot_contents, SnapshotContents::from)
+    };
+
+    // large contents of file snapshots are stored as blobs
+    #[cfg(feature = "blobs")]
+    let blob = Settings::with(|settings| settings.blob_threshold())
+        .filter(|&threshold| {
+            snapshot_file.is_some() && new_snapshot_contents.as_bytes().len() > threshold
+        })
+        .map(|_| crate::blobs::blob_hash(new_snapshot_contents.as_bytes()));
+    #[cfg(not(feature = "blobs"))]
+    let blob = None;
 
     let new = Snapshot::from_components(
         module_path.replace("::", "__"),
         snapshot_name.as_ref().map(|x| x.to_string()),
         MetaData {
             source: Some(path_to_storage(file)),
+            description: Settings::with(|settings| settings.description().map(Into::into)),
             expression: Some(expr.to_string()),
             input_file: Settings::with(|settings| {
                 settings
@@ -951,47 +2564,118 @@ pub fn assert_snapshot(
                     })
                     .map(path_to_storage)
             }),
+            info: Settings::with(|settings| settings.info().clone()),
+            variant: Settings::with(|settings| settings.snapshot_variant().map(Into::into)),
+            expires: Settings::with(|settings| settings.expires().map(Into::into)),
+            extension: extension.map(|x| x.to_string()),
+            // only text snapshots stored in files can be compressed
+            #[cfg(feature = "compression")]
+            compression: if snapshot_file.is_some() && extension.is_none() && blob.is_none() {
+                Settings::with(|settings| settings.compression())
+            } else {
+                None
+            },
+            #[cfg(not(feature = "compression"))]
+            compression: None,
+            blob,
+            checksum: None,
         },
         new_snapshot_contents,
     );
+    let new = if snapshot_file.is_some() && get_checksum_mode(cargo_workspace) != ChecksumMode::Off
+    {
+        new.with_checksum()
+    } else {
+        new
+    };
 
     // memoize the snapshot file if requested.
     if let Some(ref snapshot_file) = snapshot_file {
         memoize_snapshot_file(snapshot_file);
+        detect_snapshot_file_clash(snapshot_file, file, line, Some(&new));
     }
 
-    // if the snapshot matches we're done.
-    if let Some(ref old_snapshot) = old {
-        if old_snapshot.contents() == new.contents() {
-            // let's just make sure there are no more pending files lingering
-            // around.
-            if let Some(ref snapshot_file) = snapshot_file {
-                let mut snapshot_file = snapshot_file.clone();
-                snapshot_file.set_extension("snap.new");
-                fs::remove_file(snapshot_file).ok();
-            }
-            // and add a null pending snapshot to a pending snapshot file if needed
-            if let Some(ref pending_snapshots) = pending_snapshots {
-                if fs::metadata(pending_snapshots).is_ok() {
-                    PendingInlineSnapshot::new(None, None, line).save(pending_snapshots)?;
-                }
+    // if the snapshot matches we're done, unless it was only accepted for
+    // a while in which case it is reviewed again.
+    let expired = old.as_ref().and_then(|old| {
+        old.metadata()
+            .expires()
+            .filter(|_| old.metadata().is_expired())
+    });
+    let old_matches =
+        old.as_ref().map_or(false, |old| contents_match(old, &new)) && expired.is_none();
+    if old_matches || matches_snapshot_variant(&other_variants, &new) {
+        record_assertion_result(
+            cargo_workspace,
+            snapshot_file.as_deref(),
+            &new,
+            old.as_ref(),
+            line,
+            true,
+        );
+        // let's just make sure there are no more pending files lingering
+        // around.
+        if let Some(ref snapshot_file) = snapshot_file {
+            remove_pending_snapshots(cargo_workspace, snapshot_file, &new);
+        }
+        // and add a null pending snapshot to a pending snapshot file if needed
+        if let Some(ref pending_snapshots) = pending_snapshots {
+            if fs::metadata(pending_snapshots).is_ok() {
+                let mut pending = PendingInlineSnapshot::new(None, None, line);
+                pending.doctest = doctest;
+                pending.save(pending_snapshots)?;
             }
+        }
 
-            if force_update_snapshots() {
-                update_snapshots(
-                    snapshot_file.as_deref(),
-                    new,
-                    old,
-                    line,
-                    pending_snapshots,
-                    output_behavior,
-                )?;
-            }
+        if old_matches && force_update_snapshots() {
+            update_snapshots(
+                cargo_workspace,
+                snapshot_file.as_deref(),
+                new,
+                old,
+                line,
+                doctest,
+                pending_snapshots,
+                output_behavior,
+                update_mode.as_deref(),
+            )?;
+        }
+
+        return Ok(());
+    }
 
-            return Ok(());
+    record_assertion_result(
+        cargo_workspace,
+        snapshot_file.as_deref(),
+        &new,
+        old.as_ref(),
+        line,
+        false,
+    );
+
+    if let Some(expires) = expired {
+        if output_behavior != OutputBehavior::Nothing {
+            elog!(
+                "{} {}",
+                style("snapshot was provisionally accepted until").yellow(),
+                style(expires).yellow().bold()
+            );
+            elog!(
+                "{}",
+                style("review it again, accept it without an expiry date to keep it").dim()
+            );
         }
     }
 
+    // the diff and the question of one assertion must not be mixed up with
+    // those of tests that run in parallel.
+    let _ask_guard =
+        if update_snapshot_behavior(false, update_mode.as_deref()) == UpdateBehavior::Ask {
+            Some(ASK_LOCK.lock().unwrap_or_else(|err| err.into_inner()))
+        } else {
+            None
+        };
+
     match output_behavior {
         OutputBehavior::Summary => {
             print_snapshot_summary_with_title(
@@ -1015,12 +2699,15 @@ pub fn assert_snapshot(
     }
 
     let update_result = update_snapshots(
+        cargo_workspace,
         snapshot_file.as_deref(),
         new,
         old,
         line,
+        doctest,
         pending_snapshots,
         output_behavior,
+        update_mode.as_deref(),
     )?;
 
     if update_result == SnapshotUpdateResult::WroteNewFile
@@ -1035,7 +2722,7 @@ pub fn assert_snapshot(
     if update_result != SnapshotUpdateResult::UpdatedInPlace && should_fail_in_tests() {
         panic!(
             "snapshot assertion for '{}' failed in line {}",
-            snapshot_name.as_ref().map_or("unnamed snapshot", |x| &*x),
+            snapshot_name.as_ref().map_or("unnamed snapshot", |x| x),
             line
         );
     }
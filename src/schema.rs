@@ -0,0 +1,504 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::content::Content;
+use crate::json::json_values_match;
+use crate::settings::Settings;
+
+thread_local!(static VIOLATIONS: RefCell<Vec<String>> = RefCell::new(Vec::new()));
+
+/// A JSON Schema that serialized snapshot values are validated against.
+///
+/// A schema is either given inline as a JSON value or as the path of a
+/// file holding the schema.  Relative paths are resolved against the
+/// directory the tests run in, which for cargo is the root of the package.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonSchema {
+    Inline(Value),
+    File(PathBuf),
+}
+
+impl From<Value> for JsonSchema {
+    fn from(value: Value) -> JsonSchema {
+        JsonSchema::Inline(value)
+    }
+}
+
+impl From<PathBuf> for JsonSchema {
+    fn from(value: PathBuf) -> JsonSchema {
+        JsonSchema::File(value)
+    }
+}
+
+impl<'a> From<&'a Path> for JsonSchema {
+    fn from(value: &'a Path) -> JsonSchema {
+        JsonSchema::File(value.to_path_buf())
+    }
+}
+
+impl JsonSchema {
+    fn load(&self) -> Result<Value, String> {
+        match *self {
+            JsonSchema::Inline(ref schema) => Ok(schema.clone()),
+            JsonSchema::File(ref path) => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|contents| serde_json::from_str(&contents).map_err(|err| err.to_string()))
+                .map_err(|err| format!("cannot load schema {}: {}", path.display(), err)),
+        }
+    }
+
+    /// Validates a value against the schema.
+    ///
+    /// Returns the violations as messages prefixed with the selector of the
+    /// offending value.
+    pub fn validate(&self, value: &Value) -> Result<Vec<String>, String> {
+        let schema = self.load()?;
+        let mut validator = Validator {
+            root: &schema,
+            violations: vec![],
+        };
+        validator.validate(&schema, value, ".")?;
+        Ok(validator.violations)
+    }
+}
+
+/// Validates serialized content against the schema in the settings.
+///
+/// The violations are kept until the following snapshot assertion takes
+/// them with [`take_violations`].
+pub(crate) fn validate_content(content: &Content) {
+    let schema = match Settings::with(|settings| settings.json_schema().cloned()) {
+        Some(schema) => schema,
+        None => return,
+    };
+    let violations = match serde_json::to_value(content) {
+        Ok(value) => schema
+            .validate(&value)
+            .unwrap_or_else(|err| panic!("invalid JSON schema: {}", err)),
+        Err(err) => vec![format!(".: value cannot be represented as JSON: {}", err)],
+    };
+    VIOLATIONS.with(|x| *x.borrow_mut() = violations);
+}
+
+/// Takes the violations of the last validated value.
+pub(crate) fn take_violations() -> Vec<String> {
+    VIOLATIONS.with(|x| std::mem::take(&mut *x.borrow_mut()))
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    violations: Vec<String>,
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match (ty, value) {
+        ("integer", Value::Number(number)) => {
+            number.is_i64()
+                || number.is_u64()
+                || number.as_f64().map_or(false, |x| x.fract() == 0.0)
+        }
+        _ => type_name(value) == ty,
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    let is_ident = !key.is_empty()
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !key.starts_with(|c: char| c.is_ascii_digit());
+    let sep = if path == "." { "" } else { path };
+    if is_ident {
+        format!("{}.{}", sep, key)
+    } else {
+        format!("{}[{}]", path.trim_end_matches('.'), Value::from(key))
+    }
+}
+
+fn index_path(path: &str, idx: usize) -> String {
+    format!("{}[{}]", path.trim_end_matches('.'), idx)
+}
+
+impl<'a> Validator<'a> {
+    fn fail(&mut self, path: &str, msg: String) {
+        self.violations.push(format!("{}: {}", path, msg));
+    }
+
+    /// Checks if a value matches a schema without recording violations.
+    fn matches(&mut self, schema: &Value, value: &Value, path: &str) -> Result<bool, String> {
+        let mut validator = Validator {
+            root: self.root,
+            violations: vec![],
+        };
+        validator.validate(schema, value, path)?;
+        Ok(validator.violations.is_empty())
+    }
+
+    fn resolve(&self, reference: &str) -> Result<&'a Value, String> {
+        if !reference.starts_with('#') {
+            return Err(format!("unsupported reference {}", reference));
+        }
+        self.root
+            .pointer(&reference[1..])
+            .ok_or_else(|| format!("unresolved reference {}", reference))
+    }
+
+    fn validate(&mut self, schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+        let schema = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => {
+                self.fail(path, "no value is allowed here".into());
+                return Ok(());
+            }
+            Value::Object(schema) => schema,
+            _ => return Err(format!("schema at {} is not an object", path)),
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let resolved = self.resolve(reference)?;
+            self.validate(resolved, value, path)?;
+        }
+
+        if let Some(ty) = schema.get("type") {
+            let types: Vec<&str> = match ty {
+                Value::String(ty) => vec![ty],
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                _ => vec![],
+            };
+            if !types.is_empty() && !types.iter().any(|ty| has_type(value, ty)) {
+                self.fail(
+                    path,
+                    format!("expected {}, got {}", types.join(" or "), type_name(value)),
+                );
+                return Ok(());
+            }
+        }
+
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed
+                .iter()
+                .any(|x| json_values_match(x, value, 0.0, 0.0))
+            {
+                self.fail(
+                    path,
+                    format!("{} is not one of {}", value, Value::from(allowed.clone())),
+                );
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if !json_values_match(expected, value, 0.0, 0.0) {
+                self.fail(path, format!("expected {}, got {}", expected, value));
+            }
+        }
+
+        match value {
+            Value::Number(number) => self.validate_number(schema, number.as_f64(), path),
+            Value::String(string) => self.validate_string(schema, string, path)?,
+            Value::Array(items) => self.validate_array(schema, items, path)?,
+            Value::Object(object) => self.validate_object(schema, object, path)?,
+            _ => {}
+        }
+
+        self.validate_combinators(schema, value, path)
+    }
+
+    fn validate_number(&mut self, schema: &Map<String, Value>, number: Option<f64>, path: &str) {
+        let number = match number {
+            Some(number) => number,
+            None => return,
+        };
+        let limit = |key| schema.get(key).and_then(Value::as_f64);
+        if let Some(minimum) = limit("minimum") {
+            if number < minimum {
+                self.fail(
+                    path,
+                    format!("{} is less than the minimum of {}", number, minimum),
+                );
+            }
+        }
+        if let Some(maximum) = limit("maximum") {
+            if number > maximum {
+                self.fail(
+                    path,
+                    format!("{} is greater than the maximum of {}", number, maximum),
+                );
+            }
+        }
+        if let Some(minimum) = limit("exclusiveMinimum") {
+            if number <= minimum {
+                self.fail(path, format!("{} is not greater than {}", number, minimum));
+            }
+        }
+        if let Some(maximum) = limit("exclusiveMaximum") {
+            if number >= maximum {
+                self.fail(path, format!("{} is not less than {}", number, maximum));
+            }
+        }
+        if let Some(factor) = limit("multipleOf") {
+            if factor > 0.0 && (number / factor).fract() != 0.0 {
+                self.fail(path, format!("{} is not a multiple of {}", number, factor));
+            }
+        }
+    }
+
+    fn validate_string(
+        &mut self,
+        schema: &Map<String, Value>,
+        string: &str,
+        path: &str,
+    ) -> Result<(), String> {
+        let len = string.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+            if len < min {
+                self.fail(path, format!("string is shorter than {} characters", min));
+            }
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if len > max {
+                self.fail(path, format!("string is longer than {} characters", max));
+            }
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+            let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+            if !regex.is_match(string) {
+                self.fail(path, format!("{:?} does not match {:?}", string, pattern));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_array(
+        &mut self,
+        schema: &Map<String, Value>,
+        items: &[Value],
+        path: &str,
+    ) -> Result<(), String> {
+        let len = items.len() as u64;
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if len < min {
+                self.fail(path, format!("array has fewer than {} items", min));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if len > max {
+                self.fail(path, format!("array has more than {} items", max));
+            }
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            for (idx, item) in items.iter().enumerate() {
+                if items[..idx]
+                    .iter()
+                    .any(|other| json_values_match(other, item, 0.0, 0.0))
+                {
+                    self.fail(&index_path(path, idx), "item is not unique".into());
+                }
+            }
+        }
+        match schema.get("items") {
+            Some(Value::Array(tuple)) => {
+                for (idx, (schema, item)) in tuple.iter().zip(items.iter()).enumerate() {
+                    self.validate(schema, item, &index_path(path, idx))?;
+                }
+            }
+            Some(item_schema) => {
+                for (idx, item) in items.iter().enumerate() {
+                    self.validate(item_schema, item, &index_path(path, idx))?;
+                }
+            }
+            None => {}
+        }
+        if let Some(contains) = schema.get("contains") {
+            let mut found = false;
+            for (idx, item) in items.iter().enumerate() {
+                if self.matches(contains, item, &index_path(path, idx))? {
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                self.fail(path, "no item matches the schema in contains".into());
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_object(
+        &mut self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+    ) -> Result<(), String> {
+        let len = object.len() as u64;
+        if let Some(min) = schema.get("minProperties").and_then(Value::as_u64) {
+            if len < min {
+                self.fail(path, format!("object has fewer than {} properties", min));
+            }
+        }
+        if let Some(max) = schema.get("maxProperties").and_then(Value::as_u64) {
+            if len > max {
+                self.fail(path, format!("object has more than {} properties", max));
+            }
+        }
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    self.fail(path, format!("missing required property {:?}", key));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let mut pattern_properties = vec![];
+        if let Some(patterns) = schema.get("patternProperties").and_then(Value::as_object) {
+            for (pattern, schema) in patterns {
+                let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+                pattern_properties.push((regex, schema));
+            }
+        }
+        for (key, value) in object {
+            let child = child_path(path, key);
+            let mut known = false;
+            if let Some(schema) = properties.and_then(|x| x.get(key)) {
+                known = true;
+                self.validate(schema, value, &child)?;
+            }
+            for (regex, schema) in &pattern_properties {
+                if regex.is_match(key) {
+                    known = true;
+                    self.validate(schema, value, &child)?;
+                }
+            }
+            if known {
+                continue;
+            }
+            match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => {
+                    self.fail(path, format!("unexpected property {:?}", key));
+                }
+                Some(additional) => self.validate(additional, value, &child)?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_combinators(
+        &mut self,
+        schema: &Map<String, Value>,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), String> {
+        if let Some(schemas) = schema.get("allOf").and_then(Value::as_array) {
+            for schema in schemas {
+                self.validate(schema, value, path)?;
+            }
+        }
+        if let Some(schemas) = schema.get("anyOf").and_then(Value::as_array) {
+            let mut any = false;
+            for schema in schemas {
+                if self.matches(schema, value, path)? {
+                    any = true;
+                    break;
+                }
+            }
+            if !any {
+                self.fail(path, "value does not match any schema in anyOf".into());
+            }
+        }
+        if let Some(schemas) = schema.get("oneOf").and_then(Value::as_array) {
+            let mut count = 0;
+            for schema in schemas {
+                if self.matches(schema, value, path)? {
+                    count += 1;
+                }
+            }
+            if count != 1 {
+                self.fail(
+                    path,
+                    format!("value matches {} schemas in oneOf instead of one", count),
+                );
+            }
+        }
+        if let Some(schema) = schema.get("not") {
+            if self.matches(schema, value, path)? {
+                self.fail(path, "value matches the schema in not".into());
+            }
+        }
+        if let Some(condition) = schema.get("if") {
+            let branch = if self.matches(condition, value, path)? {
+                schema.get("then")
+            } else {
+                schema.get("else")
+            };
+            if let Some(branch) = branch {
+                self.validate(branch, value, path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_validate() {
+    let schema = JsonSchema::from(serde_json::json!({
+        "type": "object",
+        "required": ["id", "name"],
+        "additionalProperties": false,
+        "properties": {
+            "id": {"type": "integer", "minimum": 1},
+            "name": {"type": "string", "minLength": 1},
+            "email": {"type": "string", "pattern": "@"},
+            "roles": {"type": "array", "items": {"$ref": "#/$defs/role"}, "uniqueItems": true},
+        },
+        "$defs": {
+            "role": {"enum": ["admin", "user"]},
+        },
+    }));
+    let valid = serde_json::json!({"id": 1, "name": "john", "roles": ["admin"]});
+    assert_eq!(schema.validate(&valid).unwrap(), Vec::<String>::new());
+
+    let invalid = serde_json::json!({
+        "id": 0,
+        "email": "john",
+        "roles": ["admin", "root", "admin"],
+        "age": 42,
+    });
+    assert_eq!(
+        schema.validate(&invalid).unwrap(),
+        vec![
+            ".: missing required property \"name\"",
+            ".: unexpected property \"age\"",
+            ".email: \"john\" does not match \"@\"",
+            ".id: 0 is less than the minimum of 1",
+            ".roles[2]: item is not unique",
+            ".roles[1]: \"root\" is not one of [\"admin\",\"user\"]",
+        ]
+    );
+
+    let nested = JsonSchema::from(serde_json::json!({
+        "type": "array",
+        "items": {"anyOf": [{"type": "null"}, {"type": "object", "required": ["a b"]}]},
+    }));
+    assert_eq!(
+        nested
+            .validate(&serde_json::json!([null, {"a b": 1}, 42]))
+            .unwrap(),
+        vec!["[2]: value does not match any schema in anyOf",]
+    );
+    assert!(JsonSchema::from(serde_json::json!({"$ref": "#/missing"}))
+        .validate(&Value::Null)
+        .is_err());
+}
@@ -1,18 +1,66 @@
+use std::error::Error;
+
 use serde::de::value::Error as ValueError;
 use serde::Serialize;
 
 use crate::content::{Content, ContentSerializer};
+#[cfg(feature = "csv")]
+use crate::settings::CsvQuoteStyle;
 use crate::settings::Settings;
 
-pub enum SerializationFormat {
+/// Serializes snapshot values into a custom format.
+///
+/// This is used by [`assert_custom_snapshot!`](crate::assert_custom_snapshot!)
+/// for formats insta does not support itself.  Values are passed as
+/// [`Content`](crate::internals::Content) after sorting maps and applying
+/// redactions.  As the content implements `Serialize` it can be handed to
+/// any serde serializer:
+///
+/// ```rust,ignore
+/// struct Kdl;
+///
+/// impl insta::SnapshotSerializer for Kdl {
+///     fn format(&self) -> &str {
+///         "KDL"
+///     }
+///
+///     fn serialize(&self, content: &Content) -> Result<String, Box<dyn Error>> {
+///         Ok(serde_kdl::to_string(content)?)
+///     }
+/// }
+/// ```
+pub trait SnapshotSerializer {
+    /// Returns the name of the format, used in error messages.
+    fn format(&self) -> &str;
+
+    /// Serializes the content of a snapshot value.
+    fn serialize(&self, content: &Content) -> Result<String, Box<dyn Error>>;
+}
+
+pub enum SerializationFormat<'a> {
     #[cfg(feature = "csv")]
     Csv,
+    #[cfg(feature = "csv")]
+    Tsv,
     #[cfg(feature = "ron")]
     Ron,
     #[cfg(feature = "toml")]
     Toml,
+    #[cfg(feature = "xml")]
+    Xml,
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+    #[cfg(feature = "table")]
+    Table,
+    #[cfg(feature = "matrix")]
+    Matrix,
     Yaml,
     Json,
+    Custom(&'a dyn SnapshotSerializer),
 }
 
 pub enum SnapshotLocation {
@@ -35,48 +83,40 @@ pub fn serialize_content(
                 content = selector.redact(content, redaction);
             }
         }
+        if let Some(precision) = settings.number_precision() {
+            content.normalize_numbers(precision);
+        }
         content
     });
 
     match format {
-        SerializationFormat::Yaml => {
-            let serialized = serde_yaml::to_string(&content).unwrap();
-            match location {
-                SnapshotLocation::Inline => serialized,
-                SnapshotLocation::File => serialized[4..].to_string(),
-            }
-        }
+        SerializationFormat::Yaml => to_yaml(&content, location),
         SerializationFormat::Json => serde_json::to_string_pretty(&content).unwrap(),
         #[cfg(feature = "csv")]
-        SerializationFormat::Csv => {
-            let mut buf = Vec::with_capacity(128);
-            {
-                let mut writer = csv::Writer::from_writer(&mut buf);
-                // if the top-level content we're serializing is a vector we
-                // want to serialize it multiple times once for each item.
-                if let Some(content_slice) = content.as_slice() {
-                    for content in content_slice {
-                        writer.serialize(content).unwrap();
-                    }
-                } else {
-                    writer.serialize(&content).unwrap();
-                }
-                writer.flush().unwrap();
-            }
-            if buf.ends_with(b"\n") {
-                buf.truncate(buf.len() - 1);
-            }
-            String::from_utf8(buf).unwrap()
-        }
+        SerializationFormat::Csv => to_csv(&content, csv_delimiter(&format)),
+        #[cfg(feature = "csv")]
+        SerializationFormat::Tsv => to_csv(&content, csv_delimiter(&format)),
         #[cfg(feature = "ron")]
         SerializationFormat::Ron => {
+            let (struct_names, indent, compact_width) = Settings::with(|settings| {
+                (
+                    settings.ron_struct_names(),
+                    settings.ron_indent(),
+                    settings.ron_compact_width(),
+                )
+            });
             let mut buf = Vec::new();
             let mut config = ron::ser::PrettyConfig::new();
             config.new_line = "\n".to_string();
-            config.indentor = "  ".to_string();
-            let mut serializer = ron::ser::Serializer::new(&mut buf, Some(config), true).unwrap();
+            config.indentor = " ".repeat(indent);
+            let mut serializer =
+                ron::ser::Serializer::new(&mut buf, Some(config), struct_names).unwrap();
             content.serialize(&mut serializer).unwrap();
-            String::from_utf8(buf).unwrap()
+            let serialized = String::from_utf8(buf).unwrap();
+            match compact_width {
+                Some(width) => compact_ron_collections(&serialized, width),
+                None => serialized,
+            }
         }
         #[cfg(feature = "toml")]
         SerializationFormat::Toml => {
@@ -86,6 +126,285 @@ pub fn serialize_content(
             }
             rv
         }
+        #[cfg(feature = "xml")]
+        SerializationFormat::Xml => crate::xml::to_canonical_xml(&content),
+        // decoded binary formats are shown as YAML
+        #[cfg(feature = "msgpack")]
+        SerializationFormat::Msgpack => to_yaml(&content, location),
+        #[cfg(feature = "cbor")]
+        SerializationFormat::Cbor => to_yaml(&content, location),
+        #[cfg(feature = "protobuf")]
+        SerializationFormat::Protobuf => to_yaml(&content, location),
+        #[cfg(feature = "table")]
+        SerializationFormat::Table => crate::table::to_table(&content),
+        #[cfg(feature = "matrix")]
+        SerializationFormat::Matrix => crate::matrix::format_matrix(&content),
+        SerializationFormat::Custom(serializer) => {
+            serializer.serialize(&content).unwrap_or_else(|err| {
+                panic!(
+                    "failed to serialize snapshot as {}: {}",
+                    serializer.format(),
+                    err
+                )
+            })
+        }
+    }
+}
+
+/// Returns the delimiter of fields of a CSV format.
+#[cfg(feature = "csv")]
+fn csv_delimiter(format: &SerializationFormat) -> u8 {
+    match format {
+        SerializationFormat::Tsv => b'\t',
+        _ => Settings::with(|settings| settings.csv_delimiter()),
+    }
+}
+
+/// Serializes content as CSV in the dialect of the settings.
+///
+/// If the content is a sequence each item becomes a row.
+#[cfg(feature = "csv")]
+fn to_csv(content: &Content, delimiter: u8) -> String {
+    let (quote_style, has_headers, columns) = Settings::with(|settings| {
+        (
+            match settings.csv_quote_style() {
+                CsvQuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+                CsvQuoteStyle::Always => csv::QuoteStyle::Always,
+                CsvQuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+                CsvQuoteStyle::Never => csv::QuoteStyle::Never,
+            },
+            settings.csv_headers(),
+            settings.csv_columns().map(|x| x.to_vec()),
+        )
+    });
+    let mut buf = Vec::with_capacity(128);
+    {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .quote_style(quote_style)
+            .has_headers(has_headers)
+            .from_writer(&mut buf);
+        // if the top-level content we're serializing is a vector we
+        // want to serialize it multiple times once for each item.
+        let rows = match content.as_slice() {
+            Some(rows) => rows.iter().collect(),
+            None => vec![content],
+        };
+        for (idx, row) in rows.into_iter().enumerate() {
+            let selected;
+            let row = match columns {
+                Some(ref columns) => {
+                    selected = select_csv_columns(row, columns);
+                    &selected
+                }
+                None => row,
+            };
+            // the csv crate cannot serialize maps so they are written as
+            // sequences after a header row of their keys.
+            if let Content::Map(entries) = row.resolve_inner() {
+                if has_headers && idx == 0 {
+                    writer
+                        .write_record(entries.iter().map(|(key, _)| match key.as_str() {
+                            Some(key) => key.to_string(),
+                            None => serde_json::to_string(key).unwrap(),
+                        }))
+                        .unwrap();
+                }
+                writer
+                    .serialize(entries.iter().map(|(_, value)| value).collect::<Vec<_>>())
+                    .unwrap();
+            } else {
+                writer.serialize(row).unwrap();
+            }
+        }
+        writer.flush().unwrap();
+    }
+    if buf.ends_with(b"\n") {
+        buf.truncate(buf.len() - 1);
+    }
+    String::from_utf8(buf).unwrap()
+}
+
+/// Turns a row into a map with only the given columns.
+#[cfg(feature = "csv")]
+fn select_csv_columns(row: &Content, columns: &[String]) -> Content {
+    let fields: Vec<(&str, &Content)> = match row.resolve_inner() {
+        Content::Struct(_, fields) | Content::StructVariant(_, _, _, fields) => {
+            fields.iter().map(|(key, value)| (*key, value)).collect()
+        }
+        Content::Map(entries) => entries
+            .iter()
+            .filter_map(|(key, value)| key.as_str().map(|key| (key, value)))
+            .collect(),
+        _ => return row.clone(),
+    };
+    Content::Map(
+        columns
+            .iter()
+            .map(|column| {
+                let value = fields
+                    .iter()
+                    .find(|(key, _)| key == column)
+                    .map_or(Content::None, |(_, value)| (*value).clone());
+                (Content::from(column.as_str()), value)
+            })
+            .collect(),
+    )
+}
+
+/// Parses raw CSV into rows.
+///
+/// With headers each row is a map from the names in the first row to the
+/// fields, otherwise a sequence of the fields.
+#[cfg(feature = "csv")]
+fn parse_csv(text: &str, delimiter: u8) -> Result<Content, csv::Error> {
+    let has_headers = Settings::with(|settings| settings.csv_headers());
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(has_headers)
+        .from_reader(text.as_bytes());
+    let headers = if has_headers {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+    let mut rows = vec![];
+    for record in reader.records() {
+        let fields = record?.iter().map(Content::from).collect::<Vec<_>>();
+        rows.push(match headers {
+            Some(ref headers) => {
+                Content::Map(headers.iter().map(Content::from).zip(fields).collect())
+            }
+            None => Content::Seq(fields),
+        });
+    }
+    Ok(Content::Seq(rows))
+}
+
+/// Puts collections in pretty printed RON on one line if they fit.
+///
+/// Collections are only joined if all their items are on one line, so
+/// nested collections are joined from the inside out.
+#[cfg(feature = "ron")]
+fn compact_ron_collections(ron: &str, width: usize) -> String {
+    let mut lines: Vec<String> = vec![];
+    // the line that opens each collection and if it holds multi-line items
+    let mut open: Vec<(usize, bool)> = vec![];
+    for line in ron.lines() {
+        let trimmed = line.trim_start();
+        let is_close = trimmed.starts_with(|c| c == ']' || c == ')' || c == '}');
+        if is_close && !open.is_empty() {
+            let (start, has_multi_line_items) = open.pop().unwrap();
+            if !has_multi_line_items {
+                let items: Vec<_> = lines[start + 1..]
+                    .iter()
+                    .map(|item| item.trim().trim_end_matches(','))
+                    .collect();
+                let joined = format!("{}{}{}", lines[start], items.join(", "), trimmed);
+                if joined.chars().count() <= width {
+                    lines.truncate(start);
+                    lines.push(joined);
+                    continue;
+                }
+            }
+            if let Some(parent) = open.last_mut() {
+                parent.1 = true;
+            }
+            lines.push(line.to_string());
+        } else {
+            lines.push(line.to_string());
+            if line.ends_with(|c| c == '[' || c == '(' || c == '{') {
+                open.push((lines.len() - 1, false));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(feature = "ron")]
+#[test]
+fn test_compact_ron_collections() {
+    let ron = "User(\n  id: 42,\n  roles: [\n    \"admin\",\n    \"user\",\n  ],\n  tags: {\n    \"a\": [\n      1,\n      2,\n    ],\n  },\n)";
+    assert_eq!(
+        compact_ron_collections(ron, 80),
+        "User(id: 42, roles: [\"admin\", \"user\"], tags: {\"a\": [1, 2]})"
+    );
+    assert_eq!(
+        compact_ron_collections(ron, 30),
+        "User(\n  id: 42,\n  roles: [\"admin\", \"user\"],\n  tags: {\"a\": [1, 2]},\n)"
+    );
+    assert_eq!(compact_ron_collections(ron, 10), ron);
+}
+
+fn to_yaml(content: &Content, location: SnapshotLocation) -> String {
+    let serialized = serde_yaml::to_string(content).unwrap();
+    match location {
+        SnapshotLocation::Inline => serialized,
+        SnapshotLocation::File => serialized[4..].to_string(),
+    }
+}
+
+/// Returns the bytes of serialized byte slices and vectors.
+#[cfg(any(feature = "msgpack", feature = "cbor", feature = "protobuf"))]
+fn content_bytes(content: &Content) -> Option<Vec<u8>> {
+    match content {
+        Content::Bytes(bytes) => Some(bytes.clone()),
+        Content::Seq(items) => items
+            .iter()
+            .map(|item| match item {
+                Content::U8(byte) => Some(*byte),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// Converts serialized content into the shape expected by a format.
+///
+/// For XML a raw XML string is parsed into its element tree so that it can
+/// be redacted and canonicalized like a serialized value, the same goes for
+/// raw CSV strings.  MessagePack, CBOR and protobuf bytes are decoded.
+fn prepare_content(content: Content, format: &SerializationFormat) -> Content {
+    match format {
+        #[cfg(feature = "csv")]
+        SerializationFormat::Csv | SerializationFormat::Tsv => match content.as_str() {
+            Some(text) => parse_csv(text, csv_delimiter(format))
+                .unwrap_or_else(|err| panic!("invalid CSV passed to snapshot: {}", err)),
+            None => content,
+        },
+        #[cfg(feature = "xml")]
+        // strings that are not XML documents are shown as text
+        SerializationFormat::Xml => match content
+            .as_str()
+            .filter(|text| text.trim_start().starts_with('<'))
+            .map(crate::xml::parse_xml)
+        {
+            Some(Ok(parsed)) => parsed,
+            _ => content,
+        },
+        #[cfg(feature = "msgpack")]
+        SerializationFormat::Msgpack => {
+            let bytes = content_bytes(&content)
+                .expect("assert_msgpack_snapshot! expects the encoded bytes");
+            crate::msgpack::parse_msgpack(&bytes)
+                .unwrap_or_else(|err| panic!("invalid MessagePack passed to snapshot: {}", err))
+        }
+        #[cfg(feature = "cbor")]
+        SerializationFormat::Cbor => {
+            let bytes =
+                content_bytes(&content).expect("assert_cbor_snapshot! expects the encoded bytes");
+            crate::cbor::parse_cbor(&bytes)
+                .unwrap_or_else(|err| panic!("invalid CBOR passed to snapshot: {}", err))
+        }
+        #[cfg(feature = "protobuf")]
+        SerializationFormat::Protobuf => {
+            let bytes = content_bytes(&content)
+                .expect("assert_protobuf_snapshot! expects the encoded bytes");
+            crate::protobuf::parse_protobuf(&bytes)
+                .unwrap_or_else(|err| panic!("invalid protobuf passed to snapshot: {}", err))
+        }
+        _ => content,
     }
 }
 
@@ -95,7 +414,9 @@ pub fn serialize_value<S: Serialize>(
     location: SnapshotLocation,
 ) -> String {
     let serializer = ContentSerializer::<ValueError>::new();
-    let content = Serialize::serialize(s, serializer).unwrap();
+    let content = prepare_content(Serialize::serialize(s, serializer).unwrap(), &format);
+    #[cfg(feature = "schema")]
+    crate::schema::validate_content(&content);
     serialize_content(content, format, location)
 }
 
@@ -107,9 +428,11 @@ pub fn serialize_value_redacted<S: Serialize>(
     location: SnapshotLocation,
 ) -> String {
     let serializer = ContentSerializer::<ValueError>::new();
-    let mut content = Serialize::serialize(s, serializer).unwrap();
+    let mut content = prepare_content(Serialize::serialize(s, serializer).unwrap(), &format);
+    #[cfg(feature = "schema")]
+    crate::schema::validate_content(&content);
     for (selector, redaction) in redactions {
-        content = selector.redact(content, &redaction);
+        content = selector.redact(content, redaction);
     }
     serialize_content(content, format, location)
 }
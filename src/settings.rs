@@ -1,16 +1,36 @@
 use lazy_static::lazy_static;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
 use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
+use serde::Deserialize;
+
+#[cfg(feature = "blobs")]
+use crate::blobs::BlobBackend;
+#[cfg(feature = "http")]
+use crate::http::VOLATILE_HEADERS;
+#[cfg(feature = "schema")]
+use crate::schema::JsonSchema;
+#[cfg(feature = "compression")]
+use crate::snapshot::Compression;
+use crate::utils::is_iso_date;
 #[cfg(feature = "redactions")]
 use crate::{
     content::Content,
     redaction::{dynamic_redaction, ContentPath, Redaction, Selector},
 };
+#[cfg(feature = "filters")]
+use crate::{
+    filters::{Filters, StandardFilter},
+    runtime::{get_cargo_workspace, get_tool_config},
+};
 
 lazy_static! {
     static ref DEFAULT_SETTINGS: Arc<ActualSettings> = Arc::new(ActualSettings {
@@ -18,11 +38,80 @@ lazy_static! {
         snapshot_path: "snapshots".into(),
         snapshot_suffix: "".into(),
         input_file: None,
+        description: None,
+        info: BTreeMap::new(),
+        expires: None,
+        snapshot_variant: None,
         prepend_module_to_snapshot: true,
+        snapshot_layout: None,
+        float_tolerance: 0.0,
+        relative_float_tolerance: 0.0,
+        structural_json: false,
+        number_precision: None,
+        ansi_escapes: AnsiEscapes::Keep,
+        diff_context: env::var("INSTA_DIFF_CONTEXT")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(4),
+        diff_algorithm: env::var("INSTA_DIFF_ALGORITHM")
+            .ok()
+            .and_then(|x| x.parse().ok()),
+        max_snapshot_size: None,
         #[cfg(feature = "redactions")]
         redactions: Redactions::default(),
+        #[cfg(feature = "filters")]
+        filters: Filters::default(),
+        #[cfg(feature = "filters")]
+        allow_placeholders: false,
+        #[cfg(all(feature = "cmd", feature = "filters"))]
+        stdout_filters: Filters::default(),
+        #[cfg(all(feature = "cmd", feature = "filters"))]
+        stderr_filters: Filters::default(),
         #[cfg(feature = "glob")]
         allow_empty_glob: false,
+        #[cfg(feature = "schema")]
+        json_schema: None,
+        #[cfg(feature = "sql")]
+        sql_strip_literals: false,
+        #[cfg(feature = "html")]
+        html_removed_attributes: Vec::new(),
+        #[cfg(feature = "log")]
+        log_level: log::LevelFilter::Trace,
+        #[cfg(feature = "http")]
+        http_headers: None,
+        #[cfg(feature = "http")]
+        http_redacted_headers: VOLATILE_HEADERS.iter().map(|x| x.to_string()).collect(),
+        #[cfg(feature = "csv")]
+        csv_delimiter: b',',
+        #[cfg(feature = "csv")]
+        csv_quote_style: CsvQuoteStyle::Necessary,
+        #[cfg(feature = "csv")]
+        csv_headers: true,
+        #[cfg(feature = "csv")]
+        csv_columns: None,
+        #[cfg(feature = "table")]
+        table_max_rows: None,
+        #[cfg(feature = "table")]
+        table_float_precision: None,
+        #[cfg(feature = "table")]
+        table_excluded_columns: Vec::new(),
+        #[cfg(feature = "ron")]
+        ron_struct_names: true,
+        #[cfg(feature = "ron")]
+        ron_indent: 2,
+        #[cfg(feature = "ron")]
+        ron_compact_width: None,
+        #[cfg(feature = "image")]
+        image_threshold: 0.1,
+        #[cfg(feature = "image")]
+        write_image_diff: false,
+        #[cfg(feature = "compression")]
+        compression: None,
+        #[cfg(feature = "blobs")]
+        blob_threshold: None,
+        #[cfg(feature = "blobs")]
+        blob_backend: None,
+        binary_comparators: BinaryComparators::default(),
     });
 }
 thread_local!(static CURRENT_SETTINGS: RefCell<Settings> = RefCell::new(Settings::new()));
@@ -44,6 +133,108 @@ impl<'a> From<Vec<(&'a str, Redaction)>> for Redactions {
     }
 }
 
+/// A function that decides if two binary snapshots match.
+pub type BinaryComparator = dyn Fn(&[u8], &[u8]) -> bool + Send + Sync;
+
+/// Represents the registered binary comparators keyed by extension.
+#[derive(Clone, Default)]
+pub struct BinaryComparators(BTreeMap<String, Arc<BinaryComparator>>);
+
+/// The algorithm used to diff snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffAlgorithm {
+    /// The myers algorithm, which finds the smallest diff.
+    Myers,
+    /// The patience algorithm, which aligns diffs at unique lines.  This
+    /// usually keeps reordered blocks together.
+    Patience,
+    /// A diff based on the longest common subsequence.
+    Lcs,
+}
+
+impl Default for DiffAlgorithm {
+    fn default() -> DiffAlgorithm {
+        DiffAlgorithm::Patience
+    }
+}
+
+impl fmt::Display for DiffAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DiffAlgorithm::Myers => "myers",
+            DiffAlgorithm::Patience => "patience",
+            DiffAlgorithm::Lcs => "lcs",
+        })
+    }
+}
+
+impl FromStr for DiffAlgorithm {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<DiffAlgorithm, String> {
+        match value {
+            "myers" => Ok(DiffAlgorithm::Myers),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            "lcs" => Ok(DiffAlgorithm::Lcs),
+            _ => Err(format!(
+                "unknown diff algorithm '{}': use myers, patience or lcs",
+                value
+            )),
+        }
+    }
+}
+
+/// How snapshot files are laid out in the snapshot folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotLayout {
+    /// All snapshot files are in the snapshot folder and the module path is
+    /// part of the file name (`parser__expr__binary.snap`).
+    Flat,
+    /// The snapshot files are in folders mirroring the module path
+    /// (`parser/expr/binary.snap`).
+    Nested,
+}
+
+impl Default for SnapshotLayout {
+    fn default() -> SnapshotLayout {
+        SnapshotLayout::Flat
+    }
+}
+
+/// What happens to ANSI escape sequences in snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiEscapes {
+    /// Escape sequences are stored as they are.
+    Keep,
+    /// All escape sequences are removed.
+    Strip,
+    /// Colors and styles are rendered as tags like `<red>text</red>`, all
+    /// other escape sequences are removed.
+    Render,
+}
+
+impl Default for AnsiEscapes {
+    fn default() -> AnsiEscapes {
+        AnsiEscapes::Keep
+    }
+}
+
+/// When fields in CSV snapshots are quoted.
+#[cfg(feature = "csv")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvQuoteStyle {
+    /// Only fields that need it are quoted.
+    Necessary,
+    /// All fields are quoted.
+    Always,
+    /// All fields that are not numbers are quoted.
+    NonNumeric,
+    /// No field is quoted.
+    Never,
+}
+
 #[derive(Clone)]
 #[doc(hidden)]
 pub struct ActualSettings {
@@ -51,11 +242,75 @@ pub struct ActualSettings {
     pub snapshot_path: PathBuf,
     pub snapshot_suffix: String,
     pub input_file: Option<PathBuf>,
+    pub description: Option<String>,
+    pub info: BTreeMap<String, String>,
+    pub expires: Option<String>,
+    pub snapshot_variant: Option<String>,
     pub prepend_module_to_snapshot: bool,
+    pub snapshot_layout: Option<SnapshotLayout>,
+    pub float_tolerance: f64,
+    pub relative_float_tolerance: f64,
+    pub structural_json: bool,
+    pub number_precision: Option<u32>,
+    pub ansi_escapes: AnsiEscapes,
+    pub diff_context: usize,
+    pub diff_algorithm: Option<DiffAlgorithm>,
+    pub max_snapshot_size: Option<usize>,
     #[cfg(feature = "redactions")]
     pub redactions: Redactions,
+    #[cfg(feature = "filters")]
+    pub filters: Filters,
+    #[cfg(feature = "filters")]
+    pub allow_placeholders: bool,
+    #[cfg(all(feature = "cmd", feature = "filters"))]
+    pub stdout_filters: Filters,
+    #[cfg(all(feature = "cmd", feature = "filters"))]
+    pub stderr_filters: Filters,
     #[cfg(feature = "glob")]
     pub allow_empty_glob: bool,
+    #[cfg(feature = "schema")]
+    pub json_schema: Option<JsonSchema>,
+    #[cfg(feature = "sql")]
+    pub sql_strip_literals: bool,
+    #[cfg(feature = "html")]
+    pub html_removed_attributes: Vec<String>,
+    #[cfg(feature = "log")]
+    pub log_level: log::LevelFilter,
+    #[cfg(feature = "http")]
+    pub http_headers: Option<Vec<String>>,
+    #[cfg(feature = "http")]
+    pub http_redacted_headers: Vec<String>,
+    #[cfg(feature = "csv")]
+    pub csv_delimiter: u8,
+    #[cfg(feature = "csv")]
+    pub csv_quote_style: CsvQuoteStyle,
+    #[cfg(feature = "csv")]
+    pub csv_headers: bool,
+    #[cfg(feature = "csv")]
+    pub csv_columns: Option<Vec<String>>,
+    #[cfg(feature = "table")]
+    pub table_max_rows: Option<usize>,
+    #[cfg(feature = "table")]
+    pub table_float_precision: Option<usize>,
+    #[cfg(feature = "table")]
+    pub table_excluded_columns: Vec<String>,
+    #[cfg(feature = "ron")]
+    pub ron_struct_names: bool,
+    #[cfg(feature = "ron")]
+    pub ron_indent: usize,
+    #[cfg(feature = "ron")]
+    pub ron_compact_width: Option<usize>,
+    #[cfg(feature = "image")]
+    pub image_threshold: f32,
+    #[cfg(feature = "image")]
+    pub write_image_diff: bool,
+    #[cfg(feature = "compression")]
+    pub compression: Option<Compression>,
+    #[cfg(feature = "blobs")]
+    pub blob_threshold: Option<usize>,
+    #[cfg(feature = "blobs")]
+    pub blob_backend: Option<Arc<dyn BlobBackend>>,
+    pub binary_comparators: BinaryComparators,
 }
 
 /// Configures how insta operates at test time.
@@ -73,6 +328,9 @@ pub struct ActualSettings {
 /// files.
 ///
 /// Settings can also be configured with the [`with_settings!`] macro.
+/// Work that runs on other threads or in spawned tasks does not see the
+/// settings unless it is wrapped with [`Settings::propagate`] or
+/// [`Settings::propagate_async`].
 ///
 /// Example:
 ///
@@ -150,6 +408,516 @@ impl Settings {
         self.inner.prepend_module_to_snapshot
     }
 
+    /// Sets how snapshot files are laid out in the snapshot folder.
+    ///
+    /// With [`SnapshotLayout::Nested`] the snapshot of `parser::expr` named
+    /// `binary` is stored as `snapshots/parser/expr/binary.snap` instead of
+    /// `snapshots/parser__expr__binary.snap`, which keeps large snapshot
+    /// folders navigable.  Unless set here the layout is taken from the
+    /// `insta.yaml` file in the workspace root:
+    ///
+    /// ```yaml
+    /// snapshots:
+    ///   layout: nested
+    /// ```
+    ///
+    /// The default is [`SnapshotLayout::Flat`].
+    pub fn set_snapshot_layout(&mut self, value: SnapshotLayout) {
+        self._private_inner_mut().snapshot_layout = Some(value);
+    }
+
+    /// Returns the snapshot layout if one was set.
+    pub fn snapshot_layout(&self) -> Option<SnapshotLayout> {
+        self.inner.snapshot_layout
+    }
+
+    /// Replaces literals in SQL snapshots with `?`.
+    ///
+    /// This keeps values that change between runs, like timestamps or
+    /// generated ids, out of the queries asserted with
+    /// [`assert_sql_snapshot!`](crate::assert_sql_snapshot!).  Bind
+    /// parameters are kept.
+    ///
+    /// The default value is `false`.
+    #[cfg(feature = "sql")]
+    pub fn set_sql_strip_literals(&mut self, value: bool) {
+        self._private_inner_mut().sql_strip_literals = value;
+    }
+
+    /// Returns if literals in SQL snapshots are replaced.
+    #[cfg(feature = "sql")]
+    pub fn sql_strip_literals(&self) -> bool {
+        self.inner.sql_strip_literals
+    }
+
+    /// Sets attributes that are left out of HTML snapshots.
+    ///
+    /// This is useful for attributes that change on every render like
+    /// nonces or generated ids in markup asserted with
+    /// [`assert_html_snapshot!`](crate::assert_html_snapshot!).  Names are
+    /// matched case insensitively.
+    #[cfg(feature = "html")]
+    pub fn set_html_removed_attributes<I, S>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self._private_inner_mut().html_removed_attributes =
+            names.into_iter().map(Into::into).collect();
+    }
+
+    /// Returns the attributes that are left out of HTML snapshots.
+    #[cfg(feature = "html")]
+    pub fn html_removed_attributes(&self) -> &[String] {
+        &self.inner.html_removed_attributes
+    }
+
+    /// Sets the most verbose level of log records that are captured.
+    ///
+    /// This applies to [`assert_log_snapshot!`](crate::assert_log_snapshot!)
+    /// and keeps chatty debug or trace records out of snapshots.  The
+    /// default is [`LevelFilter::Trace`](log::LevelFilter::Trace) which
+    /// captures all records.
+    #[cfg(feature = "log")]
+    pub fn set_log_level(&mut self, value: log::LevelFilter) {
+        self._private_inner_mut().log_level = value;
+    }
+
+    /// Returns the most verbose level of log records that are captured.
+    #[cfg(feature = "log")]
+    pub fn log_level(&self) -> log::LevelFilter {
+        self.inner.log_level
+    }
+
+    /// Sets the headers that show up in HTTP snapshots.
+    ///
+    /// By default all headers of an [`HttpExchange`](crate::HttpExchange)
+    /// show up.  With this only the given ones do, which keeps snapshots
+    /// stable when a framework adds headers.  Names are matched case
+    /// insensitively.
+    #[cfg(feature = "http")]
+    pub fn set_http_headers<I, S>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self._private_inner_mut().http_headers = Some(names.into_iter().map(Into::into).collect());
+    }
+
+    /// Lets all headers show up in HTTP snapshots again.
+    #[cfg(feature = "http")]
+    pub fn remove_http_headers(&mut self) {
+        self._private_inner_mut().http_headers = None;
+    }
+
+    /// Returns the headers that show up in HTTP snapshots if they are
+    /// limited.
+    #[cfg(feature = "http")]
+    pub fn http_headers(&self) -> Option<&[String]> {
+        self.inner.http_headers.as_deref()
+    }
+
+    /// Sets the headers that are redacted in HTTP snapshots.
+    ///
+    /// The values of these headers are replaced with the header name in
+    /// brackets (eg: `[date]`).  The default is `date`, `set-cookie`,
+    /// `request-id`, `x-request-id` and `x-correlation-id` as they change
+    /// with every request.  Names are matched case insensitively.
+    #[cfg(feature = "http")]
+    pub fn set_http_redacted_headers<I, S>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self._private_inner_mut().http_redacted_headers =
+            names.into_iter().map(Into::into).collect();
+    }
+
+    /// Returns the headers that are redacted in HTTP snapshots.
+    #[cfg(feature = "http")]
+    pub fn http_redacted_headers(&self) -> &[String] {
+        &self.inner.http_redacted_headers
+    }
+
+    /// Sets the delimiter of fields in CSV snapshots.
+    ///
+    /// This is also the delimiter raw CSV strings passed to
+    /// [`assert_csv_snapshot!`](crate::assert_csv_snapshot!) are parsed
+    /// with.  [`assert_tsv_snapshot!`](crate::assert_tsv_snapshot!) always
+    /// uses tabs.
+    ///
+    /// The default value is `b','`.
+    #[cfg(feature = "csv")]
+    pub fn set_csv_delimiter(&mut self, value: u8) {
+        self._private_inner_mut().csv_delimiter = value;
+    }
+
+    /// Returns the delimiter of fields in CSV snapshots.
+    #[cfg(feature = "csv")]
+    pub fn csv_delimiter(&self) -> u8 {
+        self.inner.csv_delimiter
+    }
+
+    /// Sets when fields in CSV snapshots are quoted.
+    ///
+    /// The default value is [`CsvQuoteStyle::Necessary`].
+    #[cfg(feature = "csv")]
+    pub fn set_csv_quote_style(&mut self, value: CsvQuoteStyle) {
+        self._private_inner_mut().csv_quote_style = value;
+    }
+
+    /// Returns when fields in CSV snapshots are quoted.
+    #[cfg(feature = "csv")]
+    pub fn csv_quote_style(&self) -> CsvQuoteStyle {
+        self.inner.csv_quote_style
+    }
+
+    /// Enables the header row of CSV snapshots.
+    ///
+    /// With headers the field names of structs and maps are written as the
+    /// first row and the first row of raw CSV strings is read as the names
+    /// of the columns.  Without headers only the values are written and the
+    /// rows of raw CSV strings are sequences.
+    ///
+    /// The default value is `true`.
+    #[cfg(feature = "csv")]
+    pub fn set_csv_headers(&mut self, value: bool) {
+        self._private_inner_mut().csv_headers = value;
+    }
+
+    /// Returns if CSV snapshots have a header row.
+    #[cfg(feature = "csv")]
+    pub fn csv_headers(&self) -> bool {
+        self.inner.csv_headers
+    }
+
+    /// Selects the columns of CSV snapshots.
+    ///
+    /// Only the named columns are written, in the given order.  Columns
+    /// missing in a row are left empty.  To replace the values of a column
+    /// instead use a redaction like `"[].email" => "[email]"`.
+    #[cfg(feature = "csv")]
+    pub fn set_csv_columns<I, S>(&mut self, columns: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self._private_inner_mut().csv_columns = Some(columns.into_iter().map(Into::into).collect());
+    }
+
+    /// Writes all columns of CSV snapshots again.
+    #[cfg(feature = "csv")]
+    pub fn remove_csv_columns(&mut self) {
+        self._private_inner_mut().csv_columns = None;
+    }
+
+    /// Returns the selected columns of CSV snapshots.
+    #[cfg(feature = "csv")]
+    pub fn csv_columns(&self) -> Option<&[String]> {
+        self.inner.csv_columns.as_deref()
+    }
+
+    /// Limits the number of rows shown in table snapshots.
+    ///
+    /// Rows after the limit are left out and only counted below the table.
+    /// The shape of the table still shows the number of all rows.  By
+    /// default all rows are shown.
+    #[cfg(feature = "table")]
+    pub fn set_table_max_rows(&mut self, value: usize) {
+        self._private_inner_mut().table_max_rows = Some(value);
+    }
+
+    /// Shows all rows in table snapshots again.
+    #[cfg(feature = "table")]
+    pub fn remove_table_max_rows(&mut self) {
+        self._private_inner_mut().table_max_rows = None;
+    }
+
+    /// Returns the number of rows shown in table snapshots if it is limited.
+    #[cfg(feature = "table")]
+    pub fn table_max_rows(&self) -> Option<usize> {
+        self.inner.table_max_rows
+    }
+
+    /// Sets the number of decimal places of floats in table snapshots.
+    ///
+    /// By default floats are written with as many decimal places as needed.
+    #[cfg(feature = "table")]
+    pub fn set_table_float_precision(&mut self, value: usize) {
+        self._private_inner_mut().table_float_precision = Some(value);
+    }
+
+    /// Writes floats in table snapshots with all decimal places again.
+    #[cfg(feature = "table")]
+    pub fn remove_table_float_precision(&mut self) {
+        self._private_inner_mut().table_float_precision = None;
+    }
+
+    /// Returns the number of decimal places of floats in table snapshots if
+    /// it is set.
+    #[cfg(feature = "table")]
+    pub fn table_float_precision(&self) -> Option<usize> {
+        self.inner.table_float_precision
+    }
+
+    /// Sets the columns that are left out of table snapshots.
+    ///
+    /// This is useful for columns like generated ids or timestamps.  The
+    /// default is empty.
+    #[cfg(feature = "table")]
+    pub fn set_table_excluded_columns<I, S>(&mut self, columns: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self._private_inner_mut().table_excluded_columns =
+            columns.into_iter().map(Into::into).collect();
+    }
+
+    /// Returns the columns that are left out of table snapshots.
+    #[cfg(feature = "table")]
+    pub fn table_excluded_columns(&self) -> &[String] {
+        &self.inner.table_excluded_columns
+    }
+
+    /// Enables the names of structs in RON snapshots.
+    ///
+    /// Without them structs are written like tuples and maps with named
+    /// fields, `(id: 1)` instead of `User(id: 1)`.
+    ///
+    /// The default value is `true`.
+    #[cfg(feature = "ron")]
+    pub fn set_ron_struct_names(&mut self, value: bool) {
+        self._private_inner_mut().ron_struct_names = value;
+    }
+
+    /// Returns whether RON snapshots include struct names.
+    #[cfg(feature = "ron")]
+    pub fn ron_struct_names(&self) -> bool {
+        self.inner.ron_struct_names
+    }
+
+    /// Sets the number of spaces RON snapshots are indented with.
+    ///
+    /// The default value is `2`.
+    #[cfg(feature = "ron")]
+    pub fn set_ron_indent(&mut self, value: usize) {
+        self._private_inner_mut().ron_indent = value;
+    }
+
+    /// Returns the indentation of RON snapshots.
+    #[cfg(feature = "ron")]
+    pub fn ron_indent(&self) -> usize {
+        self.inner.ron_indent
+    }
+
+    /// Puts small collections in RON snapshots on a single line.
+    ///
+    /// Lists, maps, tuples and structs that do not contain other multi-line
+    /// values are written on one line if that line is no longer than the
+    /// given number of characters, for instance `roles: ["admin", "user"],`.
+    ///
+    /// The default value is `None` which puts every item on a line of its
+    /// own.
+    #[cfg(feature = "ron")]
+    pub fn set_ron_compact_width(&mut self, value: Option<usize>) {
+        self._private_inner_mut().ron_compact_width = value;
+    }
+
+    /// Returns the width up to which RON collections are put on one line.
+    #[cfg(feature = "ron")]
+    pub fn ron_compact_width(&self) -> Option<usize> {
+        self.inner.ron_compact_width
+    }
+
+    /// Sets the absolute tolerance for numbers in snapshots.
+    ///
+    /// When comparing a text snapshot against the stored one, numbers that
+    /// differ by no more than this value are considered equal.  This avoids
+    /// failures from tiny floating point differences across platforms.  The
+    /// stored value is only replaced once the snapshot changes for other
+    /// reasons.
+    ///
+    /// The default value is `0.0`.
+    pub fn set_float_tolerance(&mut self, value: f64) {
+        self._private_inner_mut().float_tolerance = value;
+    }
+
+    /// Returns the current absolute tolerance for numbers.
+    pub fn float_tolerance(&self) -> f64 {
+        self.inner.float_tolerance
+    }
+
+    /// Sets the relative tolerance for numbers in snapshots.
+    ///
+    /// Like [`set_float_tolerance`](Self::set_float_tolerance) but the
+    /// allowed difference is this value times the larger of the two
+    /// magnitudes.
+    ///
+    /// The default value is `0.0`.
+    pub fn set_relative_float_tolerance(&mut self, value: f64) {
+        self._private_inner_mut().relative_float_tolerance = value;
+    }
+
+    /// Returns the current relative tolerance for numbers.
+    pub fn relative_float_tolerance(&self) -> f64 {
+        self.inner.relative_float_tolerance
+    }
+
+    /// Sets a JSON Schema serialized values are validated against.
+    ///
+    /// On every run the value passed to a serializing assertion like
+    /// [`assert_json_snapshot!`](crate::assert_json_snapshot!) or
+    /// [`assert_yaml_snapshot!`](crate::assert_yaml_snapshot!) is checked
+    /// against the schema before redactions are applied.  Violations are
+    /// reported in a section of their own and fail the assertion even if
+    /// the snapshot matches, so breaking a contract is noticed even when
+    /// the snapshot was accepted.
+    ///
+    /// The schema can be given inline or as a path:
+    ///
+    /// ```rust,ignore
+    /// settings.set_json_schema(serde_json::json!({"type": "object"}));
+    /// settings.set_json_schema(Path::new("schemas/user.json"));
+    /// ```
+    ///
+    /// Supported are the validation keywords of JSON Schema (draft 7) and
+    /// references within the schema.  `format` is ignored.
+    #[cfg(feature = "schema")]
+    pub fn set_json_schema<S: Into<JsonSchema>>(&mut self, schema: S) {
+        self._private_inner_mut().json_schema = Some(schema.into());
+    }
+
+    /// Removes the JSON Schema.
+    #[cfg(feature = "schema")]
+    pub fn remove_json_schema(&mut self) {
+        self._private_inner_mut().json_schema = None;
+    }
+
+    /// Returns the current JSON Schema.
+    #[cfg(feature = "schema")]
+    pub fn json_schema(&self) -> Option<&JsonSchema> {
+        self.inner.json_schema.as_ref()
+    }
+
+    /// Sets the number of decimal places floats are rounded to.
+    ///
+    /// This makes the rendering of numbers in serialized snapshots
+    /// independent of the platform: floats are rounded to this many
+    /// decimal places, negative zero is written as zero and floats holding
+    /// integers are written as integers (`2` instead of `2.0`).  When
+    /// comparing text snapshots the numbers on both sides are rounded the
+    /// same way, so snapshots written before enabling this still match.
+    ///
+    /// The default value is `None` which keeps numbers as they are.
+    pub fn set_number_precision(&mut self, value: Option<u32>) {
+        self._private_inner_mut().number_precision = value;
+    }
+
+    /// Returns the number of decimal places floats are rounded to.
+    pub fn number_precision(&self) -> Option<u32> {
+        self.inner.number_precision
+    }
+
+    /// Enables structural comparison of JSON snapshots.
+    ///
+    /// When enabled, snapshots where both the stored and the new contents
+    /// are JSON are compared as values instead of text.  The order of keys
+    /// in objects does not matter and numbers are compared by value, so
+    /// `1.0` matches `1`.  The float tolerances apply to the numbers.  This
+    /// is enabled for [`assert_json_value_snapshot!`](crate::assert_json_value_snapshot!).
+    ///
+    /// The default value is `false`.
+    pub fn set_structural_json(&mut self, value: bool) {
+        self._private_inner_mut().structural_json = value;
+    }
+
+    /// Returns if JSON snapshots are compared structurally.
+    pub fn structural_json(&self) -> bool {
+        self.inner.structural_json
+    }
+
+    /// Sets the number of unchanged lines shown around changes in diffs.
+    ///
+    /// Longer runs of unchanged lines are collapsed into a marker with the
+    /// number of lines left out.
+    ///
+    /// The default value is `4` unless overridden by the `INSTA_DIFF_CONTEXT`
+    /// environment variable.
+    pub fn set_diff_context(&mut self, value: usize) {
+        self._private_inner_mut().diff_context = value;
+    }
+
+    /// Returns the current number of context lines in diffs.
+    pub fn diff_context(&self) -> usize {
+        self.inner.diff_context
+    }
+
+    /// Sets what happens to ANSI escape sequences in snapshots.
+    ///
+    /// This is useful for snapshots of terminal output.  With
+    /// [`AnsiEscapes::Strip`] colors and other escape sequences are removed
+    /// before the snapshot is stored, with [`AnsiEscapes::Render`] colors and
+    /// styles are kept in a readable notation instead:
+    ///
+    /// ```text
+    /// <bold><red>error</red></bold>: file not found
+    /// ```
+    ///
+    /// As the snapshot is stored in that form it is also what shows up in
+    /// diffs and in `cargo insta review`.  The default is
+    /// [`AnsiEscapes::Keep`].
+    pub fn set_ansi_escapes(&mut self, value: AnsiEscapes) {
+        self._private_inner_mut().ansi_escapes = value;
+    }
+
+    /// Returns what happens to ANSI escape sequences in snapshots.
+    pub fn ansi_escapes(&self) -> AnsiEscapes {
+        self.inner.ansi_escapes
+    }
+
+    /// Sets the algorithm used to diff snapshots.
+    ///
+    /// Unless set here or with the `INSTA_DIFF_ALGORITHM` environment
+    /// variable the algorithm is taken from the `insta.yaml` file in the
+    /// workspace root:
+    ///
+    /// ```yaml
+    /// diff:
+    ///   algorithm: myers
+    /// ```
+    ///
+    /// The default is [`DiffAlgorithm::Patience`].
+    pub fn set_diff_algorithm(&mut self, value: DiffAlgorithm) {
+        self._private_inner_mut().diff_algorithm = Some(value);
+    }
+
+    /// Returns the diff algorithm if one was set.
+    pub fn diff_algorithm(&self) -> Option<DiffAlgorithm> {
+        self.inner.diff_algorithm
+    }
+
+    /// Sets the maximum size of snapshots in bytes.
+    ///
+    /// Assertions of larger values fail right away instead of writing huge
+    /// snapshot files.  The default for a workspace can be set in an
+    /// `insta.yaml` file in the workspace root:
+    ///
+    /// ```yaml
+    /// snapshots:
+    ///   max_size: 1048576
+    /// ```
+    ///
+    /// By default snapshots are not limited in size.
+    pub fn set_max_snapshot_size(&mut self, value: usize) {
+        self._private_inner_mut().max_snapshot_size = Some(value);
+    }
+
+    /// Returns the maximum snapshot size if one was set.
+    pub fn max_snapshot_size(&self) -> Option<usize> {
+        self.inner.max_snapshot_size
+    }
+
     /// Allows the [`glob!`] macro to succeed if it matches no files.
     ///
     /// By default the glob macro will fail the test if it does not find
@@ -168,13 +936,111 @@ impl Settings {
         self.inner.allow_empty_glob
     }
 
+    /// Sets the perceptual threshold for image snapshots.
+    ///
+    /// Two pixels of a PNG snapshot are considered different if their
+    /// perceived color difference exceeds this threshold.  It ranges from
+    /// `0.0` (exact match) to `1.0` (everything matches).
+    ///
+    /// The default value is `0.1`.
+    #[cfg(feature = "image")]
+    pub fn set_image_threshold(&mut self, value: f32) {
+        self._private_inner_mut().image_threshold = value;
+    }
+
+    /// Returns the current perceptual threshold for image snapshots.
+    #[cfg(feature = "image")]
+    pub fn image_threshold(&self) -> f32 {
+        self.inner.image_threshold
+    }
+
+    /// Enables writing a visual diff for failing image snapshots.
+    ///
+    /// When enabled, a PNG highlighting the differing pixels in red is
+    /// written next to the pending snapshot (`.snap.new.diff.png`).
+    ///
+    /// The default value is `false`.
+    #[cfg(feature = "image")]
+    pub fn set_write_image_diff(&mut self, value: bool) {
+        self._private_inner_mut().write_image_diff = value;
+    }
+
+    /// Returns the current value for the image diff setting.
+    #[cfg(feature = "image")]
+    pub fn write_image_diff(&self) -> bool {
+        self.inner.write_image_diff
+    }
+
+    /// Sets the compression for snapshot files.
+    ///
+    /// When set, the contents of text snapshots are stored compressed in
+    /// the `.snap` file.  This is useful for very large snapshots.  Reading
+    /// compressed snapshots is transparent and works regardless of this
+    /// setting.  Inline and binary snapshots are never compressed.
+    ///
+    /// The default value is `None`.
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, value: Option<Compression>) {
+        self._private_inner_mut().compression = value;
+    }
+
+    /// Returns the current compression for snapshot files.
+    #[cfg(feature = "compression")]
+    pub fn compression(&self) -> Option<Compression> {
+        self.inner.compression
+    }
+
+    /// Sets the size from which snapshot contents are stored as blobs.
+    ///
+    /// Snapshots whose contents are larger than this many bytes keep only
+    /// their metadata in the `.snap` file.  The contents go into a `blobs`
+    /// folder next to it, named by their hash.  Identical contents of
+    /// different snapshots are thus only stored once.  Inline snapshots are
+    /// never stored as blobs.
+    ///
+    /// The default value is `None` which disables blobs.
+    #[cfg(feature = "blobs")]
+    pub fn set_blob_threshold(&mut self, value: Option<usize>) {
+        self._private_inner_mut().blob_threshold = value;
+    }
+
+    /// Returns the current blob threshold.
+    #[cfg(feature = "blobs")]
+    pub fn blob_threshold(&self) -> Option<usize> {
+        self.inner.blob_threshold
+    }
+
+    /// Sets the remote backend blobs are fetched from.
+    ///
+    /// Blobs missing from the local `blobs` folder are fetched from this
+    /// backend.  If no backend is set, the `INSTA_BLOB_DIR` environment
+    /// variable can point to a shared folder instead (see
+    /// [`DirBlobBackend::from_env`](crate::DirBlobBackend::from_env)).
+    #[cfg(feature = "blobs")]
+    pub fn set_blob_backend<B: BlobBackend + 'static>(&mut self, backend: B) {
+        self._private_inner_mut().blob_backend = Some(Arc::new(backend));
+    }
+
+    /// Removes the blob backend.
+    #[cfg(feature = "blobs")]
+    pub fn clear_blob_backend(&mut self) {
+        self._private_inner_mut().blob_backend = None;
+    }
+
+    /// Returns the current blob backend.
+    #[cfg(feature = "blobs")]
+    pub fn blob_backend(&self) -> Option<Arc<dyn BlobBackend>> {
+        self.inner.blob_backend.clone()
+    }
+
     /// Sets the snapshot suffix.
     ///
     /// The snapshot suffix is added to all snapshot names with an `@` sign
     /// between.  For instance if the snapshot suffix is set to `"foo"` and
     /// the snapshot would be named `"snapshot"` it turns into `"snapshot@foo"`.
     /// This is useful to separate snapshots if you want to use test
-    /// parameterization.
+    /// parameterization.  [`cfg_suffix!`](crate::cfg_suffix) builds a
+    /// suffix from the active features and cfgs.
     pub fn set_snapshot_suffix<I: Into<String>>(&mut self, suffix: I) {
         self._private_inner_mut().snapshot_suffix = suffix.into();
     }
@@ -213,13 +1079,133 @@ impl Settings {
         self.inner.input_file.as_deref()
     }
 
+    /// Sets the description of snapshots.
+    ///
+    /// The description is stored with the snapshot and shown above the
+    /// changes when an assertion fails and when the snapshot is reviewed.
+    /// It's most useful for a single assertion:
+    ///
+    /// ```no_run
+    /// # use insta::*;
+    /// let mut settings = Settings::clone_current();
+    /// settings.set_description("the parsed config of an empty file");
+    /// settings.bind(|| {
+    ///     assert_debug_snapshot!(vec![1, 2, 3]);
+    /// });
+    /// ```
+    pub fn set_description<S: Into<String>>(&mut self, value: S) {
+        self._private_inner_mut().description = Some(value.into());
+    }
+
+    /// Removes the description.
+    pub fn remove_description(&mut self) {
+        self._private_inner_mut().description = None;
+    }
+
+    /// Returns the current description.
+    pub fn description(&self) -> Option<&str> {
+        self.inner.description.as_deref()
+    }
+
+    /// Adds custom metadata to snapshots.
+    ///
+    /// The entries are stored in the `info` section of the snapshot files,
+    /// for instance to link snapshots to a ticket.  They are not compared,
+    /// changing them alone does not fail assertions.
+    ///
+    /// ```no_run
+    /// # use insta::*;
+    /// let mut settings = Settings::clone_current();
+    /// settings.add_info("ticket", "JIRA-123");
+    /// settings.add_info("schema_version", 4);
+    /// settings.bind(|| {
+    ///     assert_debug_snapshot!(vec![1, 2, 3]);
+    /// });
+    /// ```
+    pub fn add_info<K: Into<String>, V: ToString>(&mut self, key: K, value: V) {
+        self._private_inner_mut()
+            .info
+            .insert(key.into(), value.to_string());
+    }
+
+    /// Removes an entry of the custom metadata.
+    pub fn remove_info(&mut self, key: &str) {
+        self._private_inner_mut().info.remove(key);
+    }
+
+    /// Returns the custom metadata.
+    pub fn info(&self) -> &BTreeMap<String, String> {
+        &self.inner.info
+    }
+
+    /// Sets the variant of snapshots for output that differs between
+    /// platforms.
+    ///
+    /// With a variant set a snapshot `name.snap` can have variants stored
+    /// as `name@variant.snap` and the assertion passes if any of them
+    /// matches.  If the snapshot of the current variant exists, changes are
+    /// written to it, otherwise to `name.snap`.  `cargo insta review` can
+    /// accept such a change as a new variant instead.
+    ///
+    /// ```no_run
+    /// # use insta::*;
+    /// let mut settings = Settings::clone_current();
+    /// settings.set_snapshot_variant(std::env::consts::OS);
+    /// settings.bind(|| {
+    ///     assert_snapshot!(std::path::Path::new("a").join("b").display().to_string());
+    /// });
+    /// ```
+    pub fn set_snapshot_variant<S: Into<String>>(&mut self, variant: S) {
+        self._private_inner_mut().snapshot_variant = Some(variant.into());
+    }
+
+    /// Removes the snapshot variant.
+    pub fn remove_snapshot_variant(&mut self) {
+        self._private_inner_mut().snapshot_variant = None;
+    }
+
+    /// Returns the snapshot variant.
+    pub fn snapshot_variant(&self) -> Option<&str> {
+        self.inner.snapshot_variant.as_deref()
+    }
+
+    /// Provisionally accepts snapshots until a date (`YYYY-MM-DD`).
+    ///
+    /// The date is stored with the snapshot.  Once it has passed the
+    /// assertion fails even if the snapshot matches, as a reminder to look
+    /// at the snapshot again.  Accepting the snapshot without a date then
+    /// makes the acceptance permanent.  Only snapshots stored in files can
+    /// expire.
+    ///
+    /// `cargo insta review --expires` does the same when snapshots are
+    /// accepted.
+    pub fn set_expires<S: Into<String>>(&mut self, date: S) {
+        let date = date.into();
+        assert!(
+            is_iso_date(&date),
+            "invalid expiry date '{}', use YYYY-MM-DD",
+            date
+        );
+        self._private_inner_mut().expires = Some(date);
+    }
+
+    /// Removes the expiry date.
+    pub fn remove_expires(&mut self) {
+        self._private_inner_mut().expires = None;
+    }
+
+    /// Returns the date until which snapshots are provisionally accepted.
+    pub fn expires(&self) -> Option<&str> {
+        self.inner.expires.as_deref()
+    }
+
     /// Registers redactions that should be applied.
     ///
     /// This can be useful if redactions must be shared across multiple
     /// snapshots.
     ///
     /// Note that this only applies to snapshots that undergo serialization
-    /// (eg: does not work for `assert_debug_snapshot!`.)
+    /// and to `assert_debug_snapshot!` if it is passed redactions itself.
     #[cfg(feature = "redactions")]
     pub fn add_redaction<R: Into<Redaction>>(&mut self, selector: &str, replacement: R) {
         self._private_inner_mut().redactions.0.push((
@@ -260,12 +1246,203 @@ impl Settings {
 
     /// Iterate over the redactions.
     #[cfg(feature = "redactions")]
-    pub(crate) fn iter_redactions(&self) -> impl Iterator<Item = (&Selector, &Redaction)> {
-        self.inner
-            .redactions
+    pub(crate) fn iter_redactions(&self) -> impl Iterator<Item = (&Selector<'_>, &Redaction)> {
+        self.inner.redactions.0.iter().map(|(a, b)| (a, &**b))
+    }
+
+    /// Registers a filter.
+    ///
+    /// Filters are regular expressions that are replaced in the snapshot
+    /// contents before they are compared.  Unlike redactions they also work
+    /// for `assert_debug_snapshot!` and `assert_snapshot!`.  The replacement
+    /// can refer to capture groups with `$1` or `$name`.
+    ///
+    /// Filters are applied in the order they were added.  This panics if
+    /// the regular expression is invalid.
+    #[cfg(feature = "filters")]
+    pub fn add_filter<S: Into<String>>(&mut self, regex: &str, replacement: S) {
+        self._private_inner_mut().filters.add(regex, replacement);
+    }
+
+    /// Registers some of the built-in filters.
+    ///
+    /// ```rust
+    /// # use insta::{Settings, StandardFilter};
+    /// let mut settings = Settings::clone_current();
+    /// settings.add_standard_filters(&[StandardFilter::Uuid, StandardFilter::Timestamp]);
+    /// ```
+    ///
+    /// [`StandardFilter::TempDir`] and [`StandardFilter::HomeDir`] should
+    /// come before [`StandardFilter::PathSeparator`] so the directories are
+    /// matched with the native separators.  As the temporary directory is
+    /// often within the home directory it goes first.
+    #[cfg(feature = "filters")]
+    pub fn add_standard_filters(&mut self, filters: &[StandardFilter]) {
+        for filter in filters {
+            let (regex, replacement) = filter.rule();
+            self.add_filter(&regex, replacement);
+        }
+    }
+
+    /// Registers the filters of a named filter set.
+    ///
+    /// Filter sets are defined once in the `insta.yaml` file in the
+    /// workspace root and can then be shared by all tests:
+    ///
+    /// ```yaml
+    /// filter_sets:
+    ///   timestamps:
+    ///     filters:
+    ///       - regex: '\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}Z'
+    ///         replacement: '[TIMESTAMP]'
+    ///     redactions:
+    ///       '.**.created_at': '[created_at]'
+    /// ```
+    ///
+    /// Redactions require the `redactions` feature.  This panics if the set
+    /// does not exist.
+    #[cfg(feature = "filters")]
+    pub fn enable_filter_set(&mut self, name: &str) {
+        let manifest_dir = env::var("CARGO_MANIFEST_DIR")
+            .expect("filter sets can only be used in tests run by cargo");
+        let config = get_tool_config(get_cargo_workspace(&manifest_dir));
+        let set = config
+            .filter_sets
+            .get(name)
+            .unwrap_or_else(|| panic!("unknown filter set '{}'", name));
+        for filter in &set.filters {
+            self.add_filter(&filter.regex, filter.replacement.as_str());
+        }
+        #[cfg(feature = "redactions")]
+        {
+            for (selector, replacement) in &set.redactions {
+                self.add_redaction(selector, replacement.as_str());
+            }
+        }
+        #[cfg(not(feature = "redactions"))]
+        {
+            if !set.redactions.is_empty() {
+                panic!(
+                    "filter set '{}' contains redactions which require the redactions feature",
+                    name
+                );
+            }
+        }
+    }
+
+    /// Replaces the currently set filters.
+    ///
+    /// The default set is empty.
+    #[cfg(feature = "filters")]
+    pub fn set_filters<F: Into<Filters>>(&mut self, filters: F) {
+        self._private_inner_mut().filters = filters.into();
+    }
+
+    /// Removes all filters.
+    #[cfg(feature = "filters")]
+    pub fn clear_filters(&mut self) {
+        self._private_inner_mut().filters.clear();
+    }
+
+    /// Returns the current filters.
+    #[cfg(feature = "filters")]
+    pub(crate) fn filters(&self) -> &Filters {
+        &self.inner.filters
+    }
+
+    /// Registers a filter for the stdout of commands.
+    ///
+    /// This works like [`add_filter`](Self::add_filter) but only applies to
+    /// the stdout section of snapshots taken with
+    /// [`assert_cmd_snapshot!`](crate::assert_cmd_snapshot!).  The filters
+    /// run before the filters for the whole snapshot.
+    #[cfg(all(feature = "cmd", feature = "filters"))]
+    pub fn add_stdout_filter<S: Into<String>>(&mut self, regex: &str, replacement: S) {
+        self._private_inner_mut()
+            .stdout_filters
+            .add(regex, replacement);
+    }
+
+    /// Registers a filter for the stderr of commands.
+    ///
+    /// This works like [`add_stdout_filter`](Self::add_stdout_filter) for
+    /// the stderr section.
+    #[cfg(all(feature = "cmd", feature = "filters"))]
+    pub fn add_stderr_filter<S: Into<String>>(&mut self, regex: &str, replacement: S) {
+        self._private_inner_mut()
+            .stderr_filters
+            .add(regex, replacement);
+    }
+
+    /// Replaces the currently set filters for the stdout of commands.
+    #[cfg(all(feature = "cmd", feature = "filters"))]
+    pub fn set_stdout_filters<F: Into<Filters>>(&mut self, filters: F) {
+        self._private_inner_mut().stdout_filters = filters.into();
+    }
+
+    /// Replaces the currently set filters for the stderr of commands.
+    #[cfg(all(feature = "cmd", feature = "filters"))]
+    pub fn set_stderr_filters<F: Into<Filters>>(&mut self, filters: F) {
+        self._private_inner_mut().stderr_filters = filters.into();
+    }
+
+    /// Returns the current filters for the stdout of commands.
+    #[cfg(all(feature = "cmd", feature = "filters"))]
+    pub(crate) fn stdout_filters(&self) -> &Filters {
+        &self.inner.stdout_filters
+    }
+
+    /// Returns the current filters for the stderr of commands.
+    #[cfg(all(feature = "cmd", feature = "filters"))]
+    pub(crate) fn stderr_filters(&self) -> &Filters {
+        &self.inner.stderr_filters
+    }
+
+    /// Enables regex placeholders in stored snapshots.
+    ///
+    /// When enabled a line of a stored snapshot can contain placeholders
+    /// like `[[~ regex: \d+ ~]]` for regions that legitimately differ
+    /// between runs.  A line with placeholders matches any new line that
+    /// the regular expressions match.  New snapshots keep the placeholders
+    /// of lines that still match so accepting them does not overwrite the
+    /// placeholders.
+    ///
+    /// The default value is `false`.
+    #[cfg(feature = "filters")]
+    pub fn set_allow_placeholders(&mut self, value: bool) {
+        self._private_inner_mut().allow_placeholders = value;
+    }
+
+    /// Returns the current value for placeholders.
+    #[cfg(feature = "filters")]
+    pub fn allow_placeholders(&self) -> bool {
+        self.inner.allow_placeholders
+    }
+
+    /// Registers a comparator for binary snapshots.
+    ///
+    /// By default binary snapshots only match if they are byte for byte
+    /// identical.  A comparator registered for an extension (eg: `"png"`)
+    /// is invoked with the old and new contents instead and decides if the
+    /// snapshot still matches.
+    pub fn add_binary_comparator<F>(&mut self, extension: &str, func: F)
+    where
+        F: Fn(&[u8], &[u8]) -> bool + Send + Sync + 'static,
+    {
+        self._private_inner_mut()
+            .binary_comparators
             .0
-            .iter()
-            .map(|&(ref a, ref b)| (a, &**b))
+            .insert(extension.to_string(), Arc::new(func));
+    }
+
+    /// Removes all binary comparators.
+    pub fn clear_binary_comparators(&mut self) {
+        self._private_inner_mut().binary_comparators.0.clear();
+    }
+
+    /// Returns the binary comparator registered for an extension.
+    pub(crate) fn binary_comparator(&self, extension: &str) -> Option<Arc<BinaryComparator>> {
+        self.inner.binary_comparators.0.get(extension).cloned()
     }
 
     /// Sets the snapshot path.
@@ -283,7 +1460,9 @@ impl Settings {
     }
 
     /// Runs a function with the current settings bound to the thread.
-    pub fn bind<F: FnOnce()>(&self, f: F) {
+    ///
+    /// The return value of the function is passed through.
+    pub fn bind<F: FnOnce() -> R, R>(&self, f: F) -> R {
         CURRENT_SETTINGS.with(|x| {
             let old = {
                 let mut current = x.borrow_mut();
@@ -291,9 +1470,10 @@ impl Settings {
                 current.inner = self.inner.clone();
                 old
             };
-            f();
+            let rv = f();
             let mut current = x.borrow_mut();
             current.inner = old;
+            rv
         })
     }
 
@@ -337,6 +1517,45 @@ impl Settings {
         BindingFuture(self.inner.clone(), future)
     }
 
+    /// Wraps a function so that it runs with the settings that are current
+    /// now.
+    ///
+    /// Settings are bound to a thread, so work that is moved to another
+    /// thread (for instance with `std::thread::spawn` or `rayon`) falls back
+    /// to the default settings.  Wrapping the work carries the current
+    /// settings over:
+    ///
+    /// ```rust
+    /// # use insta::Settings;
+    /// let mut settings = Settings::clone_current();
+    /// settings.set_sort_maps(true);
+    /// settings.bind(|| {
+    ///     std::thread::spawn(Settings::propagate(|| {
+    ///         assert!(Settings::clone_current().sort_maps());
+    ///     }))
+    ///     .join()
+    ///     .unwrap();
+    /// });
+    /// ```
+    pub fn propagate<F: FnOnce() -> R, R>(f: F) -> impl FnOnce() -> R {
+        let settings = Settings::clone_current();
+        move || settings.bind(f)
+    }
+
+    /// Like `propagate` but for futures.
+    ///
+    /// This is useful for tasks spawned on a runtime that are polled on
+    /// other worker threads:
+    ///
+    /// ```rust,ignore
+    /// tokio::spawn(insta::Settings::propagate_async(async {
+    ///     insta::assert_debug_snapshot!(fetch().await);
+    /// }));
+    /// ```
+    pub fn propagate_async<F: Future>(future: F) -> impl Future<Output = F::Output> {
+        Settings::clone_current().bind_async(future)
+    }
+
     /// Binds the settings to the current thread permanently.
     pub fn bind_to_thread(&self) {
         CURRENT_SETTINGS.with(|x| {
@@ -346,6 +1565,6 @@ impl Settings {
 
     /// Runs a function with the current settings.
     pub(crate) fn with<R, F: FnOnce(&Settings) -> R>(f: F) -> R {
-        CURRENT_SETTINGS.with(|x| f(&*x.borrow()))
+        CURRENT_SETTINGS.with(|x| f(&x.borrow()))
     }
 }
@@ -1,6 +1,8 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
 use std::error::Error;
 use std::fs;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -8,6 +10,11 @@ use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 
 use super::runtime::get_inline_snapshot_value;
+use crate::config::InlineConfig;
+use crate::utils::{fnv1a, format_date};
+
+/// The name of the folder holding blobs next to the snapshot files.
+const BLOB_DIR: &str = "blobs";
 
 lazy_static! {
     static ref RUN_ID: String = {
@@ -22,6 +29,13 @@ pub struct PendingInlineSnapshot {
     pub line: u32,
     pub new: Option<Snapshot>,
     pub old: Option<Snapshot>,
+    /// Set if the snapshot is within a doctest.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub doctest: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 impl PendingInlineSnapshot {
@@ -31,6 +45,7 @@ impl PendingInlineSnapshot {
             old,
             line,
             run_id: RUN_ID.clone(),
+            doctest: false,
         }
     }
 
@@ -39,10 +54,23 @@ impl PendingInlineSnapshot {
         let iter = serde_json::Deserializer::from_reader(f).into_iter::<PendingInlineSnapshot>();
         let mut rv = iter.collect::<Result<Vec<PendingInlineSnapshot>, _>>()?;
 
-        // remove all but the last run
-        if let Some(last_run_id) = rv.last().map(|x| x.run_id.clone()) {
-            rv.retain(|x| x.run_id == last_run_id);
-        }
+        // remove all but the last run.  Doctests run in processes of their
+        // own, of them the last snapshot of every line is kept.
+        let last_run_id = rv
+            .iter()
+            .rev()
+            .find(|x| !x.doctest)
+            .map(|x| x.run_id.clone());
+        let mut doctest_lines = HashSet::new();
+        rv.reverse();
+        rv.retain(|x| {
+            if x.doctest {
+                doctest_lines.insert(x.line)
+            } else {
+                Some(&x.run_id) == last_run_id.as_ref()
+            }
+        });
+        rv.reverse();
 
         Ok(rv)
     }
@@ -59,6 +87,9 @@ impl PendingInlineSnapshot {
     }
 
     pub fn save<P: AsRef<Path>>(&self, p: P) -> Result<(), Box<dyn Error>> {
+        if let Some(folder) = p.as_ref().parent() {
+            fs::create_dir_all(folder)?;
+        }
         let mut f = fs::OpenOptions::new().create(true).append(true).open(p)?;
         let mut s = serde_json::to_string(self)?;
         s.push('\n');
@@ -67,18 +98,54 @@ impl PendingInlineSnapshot {
     }
 }
 
+/// The compression applied to the contents of a snapshot file.
+///
+/// Only gzip is supported as it can be implemented with the pure Rust
+/// dependencies insta already uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// gzip compressed and base64 encoded.
+    Gzip,
+}
+
 /// Snapshot metadata information.
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct MetaData {
     /// The source file (relative to workspace root).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) source: Option<String>,
+    /// Optionally a description of what the snapshot shows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) description: Option<String>,
     /// Optionally the expression that created the snapshot.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) expression: Option<String>,
     /// Reference to the input file.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) input_file: Option<String>,
+    /// Custom metadata that is not compared.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) info: BTreeMap<String, String>,
+    /// The variant of the snapshot for output that differs between
+    /// platforms.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) variant: Option<String>,
+    /// The date until which the snapshot is provisionally accepted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) expires: Option<String>,
+    /// The file extension of the contents of a binary snapshot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) extension: Option<String>,
+    /// The compression of the contents in the snapshot file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) compression: Option<Compression>,
+    /// The hash of the contents if they are stored in the blob directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) blob: Option<String>,
+    /// The checksum of the contents as written to the snapshot file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) checksum: Option<String>,
 }
 
 impl MetaData {
@@ -87,6 +154,11 @@ impl MetaData {
         self.source.as_deref()
     }
 
+    /// Returns the description of the snapshot.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
     /// Returns the expression that created the snapshot.
     pub fn expression(&self) -> Option<&str> {
         self.expression.as_deref()
@@ -107,6 +179,51 @@ impl MetaData {
     pub fn input_file(&self) -> Option<&str> {
         self.input_file.as_deref()
     }
+
+    /// Returns the custom metadata.
+    pub fn info(&self) -> &BTreeMap<String, String> {
+        &self.info
+    }
+
+    /// Returns the variant of the snapshot.
+    pub fn variant(&self) -> Option<&str> {
+        self.variant.as_deref()
+    }
+
+    /// Returns the date until which the snapshot is provisionally accepted.
+    pub fn expires(&self) -> Option<&str> {
+        self.expires.as_deref()
+    }
+
+    /// Checks if the provisional acceptance of the snapshot is over.
+    pub fn is_expired(&self) -> bool {
+        self.expires.as_deref().map_or(false, |expires| {
+            expires < format_date(SystemTime::now()).as_str()
+        })
+    }
+
+    /// Returns the extension of the contents of a binary snapshot.
+    ///
+    /// This is only set for binary snapshots in which case the contents
+    /// are stored in a separate file next to the snapshot file.
+    pub fn extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    /// Returns the compression of the snapshot contents.
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Returns the hash of the contents if they are stored as blob.
+    pub fn blob(&self) -> Option<&str> {
+        self.blob.as_deref()
+    }
+
+    /// Returns the checksum of the contents in the snapshot file.
+    pub fn checksum(&self) -> Option<&str> {
+        self.checksum.as_deref()
+    }
 }
 
 /// A helper to work with stored snapshots.
@@ -117,6 +234,10 @@ pub struct Snapshot {
     snapshot_name: Option<String>,
     metadata: MetaData,
     snapshot: SnapshotContents,
+    /// Set if the file the snapshot was loaded from does not match its
+    /// checksum.
+    #[serde(skip)]
+    edited: bool,
 }
 
 impl Snapshot {
@@ -124,56 +245,54 @@ impl Snapshot {
     pub fn from_file<P: AsRef<Path>>(p: P) -> Result<Snapshot, Box<dyn Error>> {
         let mut f = BufReader::new(fs::File::open(p.as_ref())?);
         let mut buf = String::new();
-
-        f.read_line(&mut buf)?;
-
-        // yaml format
-        let metadata = if buf.trim_end() == "---" {
-            loop {
-                let read = f.read_line(&mut buf)?;
-                if read == 0 {
-                    break;
-                }
-                if buf[buf.len() - read..].trim_end() == "---" {
-                    buf.truncate(buf.len() - read);
-                    break;
+        let metadata = read_metadata(&mut f, &mut buf)?;
+
+        let mut edited = false;
+        let contents = if let Some(ref hash) = metadata.blob {
+            let blob_file = blob_path(p.as_ref(), hash);
+            let bytes = match fs::read(&blob_file) {
+                Ok(bytes) => bytes,
+                #[cfg(feature = "blobs")]
+                Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    crate::blobs::fetch_missing_blob(&blob_file, hash)?
                 }
+                Err(err) => return Err(err.into()),
+            };
+            if metadata.extension.is_some() {
+                SnapshotContents::Binary(bytes)
+            } else {
+                String::from_utf8(bytes)?.into()
             }
-            serde_yaml::from_str(&buf)?
-        // legacy format
+        } else if let Some(ref extension) = metadata.extension {
+            SnapshotContents::Binary(fs::read(binary_path(p.as_ref(), extension))?)
         } else {
-            let mut rv = MetaData::default();
-            loop {
-                buf.clear();
-                let read = f.read_line(&mut buf)?;
-                if read == 0 || buf.trim_end().is_empty() {
-                    buf.truncate(buf.len() - read);
-                    break;
+            let mut body = String::new();
+            f.read_to_string(&mut body)?;
+            if let Some(ref checksum) = metadata.checksum {
+                edited = *checksum != text_checksum(&body);
+            }
+            buf.clear();
+            for (idx, line) in body.lines().enumerate() {
+                if idx > 0 {
+                    buf.push('\n');
                 }
-                let mut iter = buf.splitn(2, ':');
-                if let Some(key) = iter.next() {
-                    if let Some(value) = iter.next() {
-                        let value = value.trim();
-                        match key.to_lowercase().as_str() {
-                            "expression" => rv.expression = Some(value.to_string()),
-                            "source" => rv.source = Some(value.into()),
-                            _ => {}
-                        }
-                    }
+                buf.push_str(line);
+            }
+            match metadata.compression {
+                None => buf.into(),
+                #[cfg(feature = "compression")]
+                Some(Compression::Gzip) => crate::compression::decode_gzip(&buf)?.into(),
+                #[cfg(not(feature = "compression"))]
+                Some(_) => {
+                    return Err(format!(
+                        "{} is compressed, enable the compression feature of insta to read it",
+                        p.as_ref().display()
+                    )
+                    .into())
                 }
             }
-            rv
         };
 
-        buf.clear();
-        for (idx, line) in f.lines().enumerate() {
-            let line = line?;
-            if idx > 0 {
-                buf.push('\n');
-            }
-            buf.push_str(&line);
-        }
-
         let module_name = p
             .as_ref()
             .file_name()
@@ -198,16 +317,16 @@ impl Snapshot {
             .nth(1)
             .map(|x| x.to_string());
 
-        Ok(Snapshot::from_components(
-            module_name,
-            snapshot_name,
-            metadata,
-            buf.into(),
-        ))
+        let mut rv = Snapshot::from_components(module_name, snapshot_name, metadata, contents);
+        rv.edited = edited;
+        Ok(rv)
     }
 
-    /// Creates an empty snapshot.
-    pub(crate) fn from_components(
+    /// Creates a snapshot from its parts.
+    ///
+    /// The module and snapshot name make up the file name the snapshot is
+    /// stored under by insta (`module__name.snap`).
+    pub fn from_components(
         module_name: String,
         snapshot_name: Option<String>,
         metadata: MetaData,
@@ -218,6 +337,7 @@ impl Snapshot {
             snapshot_name,
             metadata,
             snapshot,
+            edited: false,
         }
     }
 
@@ -242,43 +362,330 @@ impl Snapshot {
     }
 
     /// The snapshot contents as a &str
+    ///
+    /// For binary snapshots this is an empty string.
     pub fn contents_str(&self) -> &str {
-        &self.snapshot.0
+        self.snapshot.as_str().unwrap_or("")
+    }
+
+    /// Returns the path of the file holding the binary contents.
+    ///
+    /// The path is derived from the path of the snapshot file and is only
+    /// returned for binary snapshots.
+    pub fn binary_file(&self, snapshot_file: &Path) -> Option<PathBuf> {
+        if self.metadata.blob.is_some() {
+            return None;
+        }
+        self.metadata
+            .extension()
+            .map(|extension| binary_path(snapshot_file, extension))
+    }
+
+    /// Returns the path of the blob holding the contents.
+    ///
+    /// Blobs are stored by hash in a `blobs` folder next to the snapshot
+    /// file so that identical contents are only stored once.
+    pub fn blob_file(&self, snapshot_file: &Path) -> Option<PathBuf> {
+        self.metadata
+            .blob()
+            .map(|hash| blob_path(snapshot_file, hash))
+    }
+
+    /// Returns a copy of the snapshot with different contents.
+    pub fn with_contents(&self, contents: SnapshotContents) -> Snapshot {
+        let mut rv = Snapshot {
+            snapshot: contents,
+            ..self.clone()
+        };
+        if rv.metadata.blob.is_some() {
+            // without the hash function the contents are stored in place
+            #[cfg(feature = "blobs")]
+            {
+                rv.metadata.blob = Some(crate::blobs::blob_hash(rv.snapshot.as_bytes()));
+            }
+            #[cfg(not(feature = "blobs"))]
+            {
+                rv.metadata.blob = None;
+            }
+        }
+        if rv.metadata.checksum.is_some() {
+            rv = rv.with_checksum();
+        }
+        rv
     }
 
-    pub(crate) fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+    /// Returns a copy of the snapshot with a checksum of its contents.
+    ///
+    /// Loading the snapshot from a file that was changed afterwards (by
+    /// hand or by other tools) then marks it as edited, see
+    /// [`is_edited`](Self::is_edited).  Only text snapshots that are stored
+    /// uncompressed in the snapshot file get a checksum.
+    pub fn with_checksum(&self) -> Snapshot {
+        let mut rv = self.clone();
+        rv.metadata.checksum = match (&rv.snapshot, &rv.metadata) {
+            (SnapshotContents::Text(ref contents), metadata)
+                if metadata.blob.is_none() && metadata.compression.is_none() =>
+            {
+                Some(text_checksum(&format!("{}\n", contents)))
+            }
+            _ => None,
+        };
+        rv.edited = false;
+        rv
+    }
+
+    /// Returns a copy of the snapshot without a checksum.
+    pub fn without_checksum(&self) -> Snapshot {
+        let mut rv = self.clone();
+        rv.metadata.checksum = None;
+        rv.edited = false;
+        rv
+    }
+
+    /// Checks if the snapshot file was changed since insta wrote it.
+    ///
+    /// This is only known for snapshots with a checksum.
+    pub fn is_edited(&self) -> bool {
+        self.edited
+    }
+
+    /// Returns a copy of the snapshot that is provisionally accepted until
+    /// a date (`YYYY-MM-DD`).
+    pub fn with_expires(&self, expires: Option<String>) -> Snapshot {
+        let mut rv = self.clone();
+        rv.metadata.expires = expires;
+        rv
+    }
+
+    /// Writes the snapshot to a file.
+    ///
+    /// Missing parent folders are created.  Binary contents are written to
+    /// a file next to the snapshot file, see [`binary_file`](Self::binary_file).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
         let path = path.as_ref();
         if let Some(folder) = path.parent() {
-            fs::create_dir_all(&folder)?;
+            fs::create_dir_all(folder)?;
         }
-        let mut f = fs::File::create(&path)?;
+        let mut f = fs::File::create(path)?;
         serde_yaml::to_writer(&mut f, &self.metadata)?;
         f.write_all(b"\n---\n")?;
-        f.write_all(self.contents_str().as_bytes())?;
-        f.write_all(b"\n")?;
+        if let Some(blob_file) = self.blob_file(path) {
+            // blobs are immutable so existing ones can be reused
+            if fs::metadata(&blob_file).is_err() {
+                fs::create_dir_all(blob_file.parent().unwrap())?;
+                fs::write(&blob_file, self.snapshot.as_bytes())?;
+            }
+            return Ok(());
+        }
+        match self.snapshot {
+            #[cfg(feature = "compression")]
+            SnapshotContents::Text(ref contents)
+                if self.metadata.compression == Some(Compression::Gzip) =>
+            {
+                f.write_all(crate::compression::encode_gzip(contents).as_bytes())?;
+                f.write_all(b"\n")?;
+            }
+            SnapshotContents::Text(ref contents) => {
+                f.write_all(contents.as_bytes())?;
+                f.write_all(b"\n")?;
+            }
+            SnapshotContents::Binary(ref contents) => {
+                if let Some(binary_file) = self.binary_file(path) {
+                    fs::write(binary_file, contents)?;
+                }
+            }
+        }
         Ok(())
     }
 }
 
+/// The checksum of the text after the metadata in a snapshot file.
+///
+/// The lines are hashed with normalized line endings so that a checkout
+/// with CRLF line endings does not count as an edit.
+fn text_checksum(text: &str) -> String {
+    let lines: Vec<_> = text.lines().collect();
+    format!("{:016x}", fnv1a(lines.join("\n").as_bytes()))
+}
+
+fn blob_path(snapshot_file: &Path, hash: &str) -> PathBuf {
+    let mut rv = snapshot_file
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+    rv.push(BLOB_DIR);
+    rv.push(hash);
+    rv
+}
+
+/// Reads the metadata at the start of a snapshot file.
+fn read_metadata<R: BufRead>(f: &mut R, buf: &mut String) -> Result<MetaData, Box<dyn Error>> {
+    f.read_line(buf)?;
+
+    // yaml format
+    Ok(if buf.trim_end() == "---" {
+        loop {
+            let read = f.read_line(buf)?;
+            if read == 0 {
+                break;
+            }
+            if buf[buf.len() - read..].trim_end() == "---" {
+                buf.truncate(buf.len() - read);
+                break;
+            }
+        }
+        serde_yaml::from_str(buf)?
+    // legacy format
+    } else {
+        let mut rv = MetaData::default();
+        loop {
+            buf.clear();
+            let read = f.read_line(buf)?;
+            if read == 0 || buf.trim_end().is_empty() {
+                buf.truncate(buf.len() - read);
+                break;
+            }
+            let mut iter = buf.splitn(2, ':');
+            if let Some(key) = iter.next() {
+                if let Some(value) = iter.next() {
+                    let value = value.trim();
+                    match key.to_lowercase().as_str() {
+                        "expression" => rv.expression = Some(value.to_string()),
+                        "source" => rv.source = Some(value.into()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        rv
+    })
+}
+
+/// Loads only the metadata of a snapshot file.
+///
+/// Unlike [`Snapshot::from_file`] this leaves the contents unread.
+pub fn metadata_from_file(p: &Path) -> Result<MetaData, Box<dyn Error>> {
+    let mut f = BufReader::new(fs::File::open(p)?);
+    read_metadata(&mut f, &mut String::new())
+}
+
+pub(crate) fn binary_path(snapshot_file: &Path, extension: &str) -> PathBuf {
+    let mut rv = snapshot_file.as_os_str().to_owned();
+    rv.push(".");
+    rv.push(extension);
+    rv.into()
+}
+
+/// Renders binary contents as a hex dump.
+///
+/// Every line holds 16 bytes so that changes can be diffed line by line.
+pub(crate) fn hex_dump(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    let mut rv = String::new();
+    for (idx, chunk) in bytes.chunks(16).enumerate() {
+        if idx > 0 {
+            rv.push('\n');
+        }
+        write!(rv, "{:08x} ", idx * 16).unwrap();
+        for col in 0..16 {
+            if col == 8 {
+                rv.push(' ');
+            }
+            match chunk.get(col) {
+                Some(byte) => write!(rv, " {:02x}", byte).unwrap(),
+                None => rv.push_str("   "),
+            }
+        }
+        rv.push_str("  |");
+        rv.extend(chunk.iter().map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        }));
+        rv.push('|');
+    }
+    rv
+}
+
 /// The contents of a Snapshot
-// Could be Cow, but I think limited savings
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SnapshotContents(String);
+#[serde(untagged)]
+pub enum SnapshotContents {
+    /// Text contents (always with unix newlines).
+    Text(String),
+    /// Binary contents which are stored in a separate file.
+    Binary(Vec<u8>),
+}
 
 impl SnapshotContents {
     pub fn from_inline(value: &str) -> SnapshotContents {
-        SnapshotContents(get_inline_snapshot_value(value))
+        SnapshotContents::Text(get_inline_snapshot_value(value))
+    }
+
+    /// Returns the contents as string unless they are binary.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            SnapshotContents::Text(ref contents) => Some(contents),
+            SnapshotContents::Binary(_) => None,
+        }
+    }
+
+    /// Returns the raw bytes of the contents.
+    pub fn as_bytes(&self) -> &[u8] {
+        match *self {
+            SnapshotContents::Text(ref contents) => contents.as_bytes(),
+            SnapshotContents::Binary(ref contents) => contents,
+        }
+    }
+
+    /// Returns `true` if these are binary contents.
+    pub fn is_binary(&self) -> bool {
+        match *self {
+            SnapshotContents::Text(_) => false,
+            SnapshotContents::Binary(_) => true,
+        }
+    }
+
+    /// Returns the contents in a form that can be diffed line by line.
+    ///
+    /// Binary contents are rendered as hex dump.
+    pub fn to_diffable_string(&self) -> Cow<'_, str> {
+        match *self {
+            SnapshotContents::Text(ref contents) => Cow::Borrowed(contents),
+            SnapshotContents::Binary(ref contents) => Cow::Owned(hex_dump(contents)),
+        }
     }
 
     pub fn to_inline(&self, indentation: usize) -> String {
-        let contents = &self.0;
-        let mut out = String::new();
-        let is_escape = contents.lines().count() > 1 || contents.contains(&['\\', '"'][..]);
+        self.to_inline_with_config(indentation, &InlineConfig::default())
+    }
 
-        out.push_str(if is_escape { "r###\"" } else { "\"" });
+    /// Like [`to_inline`](Self::to_inline) but formatted as configured.
+    ///
+    /// By default raw strings use as few hashes as the contents allow and
+    /// only multi-line contents are put on lines of their own.
+    pub fn to_inline_with_config(&self, indentation: usize, config: &InlineConfig) -> String {
+        let contents = self.as_str().expect("binary snapshots cannot be inline");
+        let mut out = String::new();
+        let is_block = contents.lines().count() > 1 || config.breaks_line(contents);
+        let is_escape = is_block || contents.contains(&['\\', '"'][..]);
+        let hashes = "#".repeat(
+            config
+                .raw_string_hashes
+                .unwrap_or(0)
+                .max(min_raw_string_hashes(contents)),
+        );
+
+        if is_escape {
+            out.push('r');
+            out.push_str(&hashes);
+        }
+        out.push('"');
         // if we have more than one line we want to change into the block
         // representation mode
-        if contents.lines().count() > 1 {
+        if is_block {
             out.extend(
                 contents
                     .lines()
@@ -293,81 +700,107 @@ impl SnapshotContents {
                         )
                     })
                     // `lines` removes the final line ending - add back
-                    .chain(Some(format!("\n{:width$}", "", width = indentation)).into_iter()),
+                    .chain(Some(format!("\n{:width$}", "", width = indentation))),
             );
         } else {
             out.push_str(contents);
         }
 
-        out.push_str(if is_escape { "\"###" } else { "\"" });
+        out.push('"');
+        if is_escape {
+            out.push_str(&hashes);
+        }
 
         out
     }
 }
 
+/// Returns the number of hashes a raw string needs to hold the contents.
+fn min_raw_string_hashes(contents: &str) -> usize {
+    contents
+        .split('"')
+        .skip(1)
+        .map(|x| x.chars().take_while(|&c| c == '#').count() + 1)
+        .max()
+        .unwrap_or(0)
+}
+
 impl From<&str> for SnapshotContents {
     fn from(value: &str) -> SnapshotContents {
         // make sure we have unix newlines consistently
-        SnapshotContents(value.replace("\r\n", "\n"))
+        SnapshotContents::Text(value.replace("\r\n", "\n"))
     }
 }
 
 impl From<String> for SnapshotContents {
     fn from(value: String) -> SnapshotContents {
         // make sure we have unix newlines consistently
-        SnapshotContents(value.replace("\r\n", "\n"))
+        SnapshotContents::Text(value.replace("\r\n", "\n"))
+    }
+}
+
+impl From<Vec<u8>> for SnapshotContents {
+    fn from(value: Vec<u8>) -> SnapshotContents {
+        SnapshotContents::Binary(value)
     }
 }
 
 impl From<SnapshotContents> for String {
     fn from(value: SnapshotContents) -> String {
-        value.0
+        match value {
+            SnapshotContents::Text(contents) => contents,
+            SnapshotContents::Binary(contents) => hex_dump(&contents),
+        }
     }
 }
 
 impl PartialEq for SnapshotContents {
     fn eq(&self, other: &Self) -> bool {
-        self.0.trim_end() == other.0.trim_end()
+        match (self, other) {
+            (SnapshotContents::Text(a), SnapshotContents::Text(b)) => a.trim_end() == b.trim_end(),
+            (SnapshotContents::Binary(a), SnapshotContents::Binary(b)) => a == b,
+            _ => false,
+        }
     }
 }
 
 #[test]
 fn test_snapshot_contents() {
     use similar_asserts::assert_eq;
-    let snapshot_contents = SnapshotContents("testing".to_string());
+    let snapshot_contents = SnapshotContents::Text("testing".to_string());
     assert_eq!(snapshot_contents.to_inline(0), r#""testing""#);
 
     let t = &"
 a
 b"[1..];
     assert_eq!(
-        SnapshotContents(t.to_string()).to_inline(0),
-        "r###\"
+        SnapshotContents::Text(t.to_string()).to_inline(0),
+        "r\"
 a
 b
-\"###"
+\""
     );
 
     let t = &"
 a
 b"[1..];
     assert_eq!(
-        SnapshotContents(t.to_string()).to_inline(4),
-        "r###\"
+        SnapshotContents::Text(t.to_string()).to_inline(4),
+        "r\"
     a
     b
-    \"###"
+    \""
     );
 
     let t = &"
     a
     b"[1..];
     assert_eq!(
-        SnapshotContents(t.to_string()).to_inline(0),
-        "r###\"
+        SnapshotContents::Text(t.to_string()).to_inline(0),
+        "r\"
     a
     b
-\"###"
+\""
     );
 
     let t = &"
@@ -375,14 +808,161 @@ b"[1..];
 
     b"[1..];
     assert_eq!(
-        SnapshotContents(t.to_string()).to_inline(0),
-        "r###\"
+        SnapshotContents::Text(t.to_string()).to_inline(0),
+        "r\"
     a
 
     b
-\"###"
+\""
     );
 
     let t = "ab";
-    assert_eq!(SnapshotContents(t.to_string()).to_inline(0), r##""ab""##);
+    assert_eq!(
+        SnapshotContents::Text(t.to_string()).to_inline(0),
+        r##""ab""##
+    );
+
+    let t = r#"a\b"#;
+    assert_eq!(
+        SnapshotContents::Text(t.to_string()).to_inline(0),
+        r##"r"a\b""##
+    );
+
+    let t = r#"say "hi""#;
+    assert_eq!(
+        SnapshotContents::Text(t.to_string()).to_inline(0),
+        r###"r#"say "hi""#"###
+    );
+
+    let t = r##"a "# b"##;
+    assert_eq!(
+        SnapshotContents::Text(t.to_string()).to_inline(0),
+        r####"r##"a "# b"##"####
+    );
+
+    let config = InlineConfig {
+        raw_string_hashes: Some(3),
+        ..InlineConfig::default()
+    };
+    let t = r#"say "hi""#;
+    assert_eq!(
+        SnapshotContents::Text(t.to_string()).to_inline_with_config(0, &config),
+        r####"r###"say "hi""###"####
+    );
+    assert_eq!(
+        SnapshotContents::Text("ab".to_string()).to_inline_with_config(0, &config),
+        r##""ab""##
+    );
+
+    let config = InlineConfig {
+        max_single_line: Some(0),
+        ..InlineConfig::default()
+    };
+    assert_eq!(
+        SnapshotContents::Text("a b".to_string()).to_inline_with_config(4, &config),
+        "r\"
+    a b
+    \""
+    );
+    assert_eq!(
+        SnapshotContents::Text("ab".to_string()).to_inline_with_config(4, &config),
+        r##""ab""##
+    );
+
+    let config = InlineConfig {
+        max_single_line: Some(5),
+        ..InlineConfig::default()
+    };
+    assert_eq!(
+        SnapshotContents::Text("a b".to_string()).to_inline_with_config(0, &config),
+        r##""a b""##
+    );
+    assert_eq!(
+        SnapshotContents::Text("a b c".to_string()).to_inline_with_config(0, &config),
+        "r\"
+a b c
+\""
+    );
+}
+
+#[test]
+fn test_hex_dump() {
+    use similar_asserts::assert_eq;
+    assert_eq!(
+        hex_dump(b"\0asm\x01\0\0\0hello world, this is insta"),
+        "00000000  00 61 73 6d 01 00 00 00  68 65 6c 6c 6f 20 77 6f  |.asm....hello wo|
+00000010  72 6c 64 2c 20 74 68 69  73 20 69 73 20 69 6e 73  |rld, this is ins|
+00000020  74 61                                             |ta|"
+    );
+}
+
+#[test]
+fn test_save_and_load() {
+    let path = std::env::temp_dir().join(format!("insta-{}/module__name.snap", std::process::id()));
+    let snapshot = Snapshot::from_components(
+        "module".into(),
+        Some("name".into()),
+        MetaData {
+            description: Some("a description".into()),
+            ..MetaData::default()
+        },
+        "contents".into(),
+    );
+    snapshot.save(&path).unwrap();
+    let loaded = Snapshot::from_file(&path).unwrap();
+    fs::remove_dir_all(path.parent().unwrap()).ok();
+    assert_eq!(loaded.module_name(), "module");
+    assert_eq!(loaded.snapshot_name(), Some("name"));
+    assert_eq!(loaded.metadata().description(), Some("a description"));
+    assert_eq!(loaded.contents(), &SnapshotContents::from("contents"));
+}
+
+#[test]
+fn test_checksum() {
+    let path = std::env::temp_dir().join(format!(
+        "insta-checksum-{}/module__name.snap",
+        std::process::id()
+    ));
+    let snapshot = Snapshot::from_components(
+        "module".into(),
+        Some("name".into()),
+        MetaData::default(),
+        "line 1\nline 2".into(),
+    )
+    .with_checksum();
+    snapshot.save(&path).unwrap();
+    let loaded = Snapshot::from_file(&path).unwrap();
+    assert!(loaded.metadata().checksum().is_some());
+    assert!(!loaded.is_edited());
+
+    let crlf = fs::read_to_string(&path).unwrap().replace('\n', "\r\n");
+    fs::write(&path, crlf).unwrap();
+    assert!(!Snapshot::from_file(&path).unwrap().is_edited());
+
+    let edited = fs::read_to_string(&path)
+        .unwrap()
+        .replace("line 2", "line 2  ");
+    fs::write(&path, edited).unwrap();
+    let loaded = Snapshot::from_file(&path).unwrap();
+    fs::remove_dir_all(path.parent().unwrap()).ok();
+    assert!(loaded.is_edited());
+    assert!(!loaded.with_checksum().is_edited());
+}
+
+#[test]
+fn test_expires() {
+    let snapshot = Snapshot::from_components(
+        "module".into(),
+        None,
+        MetaData::default(),
+        SnapshotContents::from("contents"),
+    );
+    assert!(!snapshot.metadata().is_expired());
+    let expired = snapshot.with_expires(Some("2000-01-01".into()));
+    assert!(expired.metadata().is_expired());
+    assert_eq!(expired.metadata().expires(), Some("2000-01-01"));
+    assert!(!snapshot
+        .with_expires(Some("9999-12-31".into()))
+        .metadata()
+        .is_expired());
 }
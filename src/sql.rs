@@ -0,0 +1,515 @@
+use crate::settings::Settings;
+
+/// Words that are written in upper case.
+const KEYWORDS: &[&str] = &[
+    "ALL",
+    "ALTER",
+    "AND",
+    "ANY",
+    "AS",
+    "ASC",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "CONFLICT",
+    "CREATE",
+    "CROSS",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DO",
+    "DROP",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "EXISTS",
+    "FALSE",
+    "FETCH",
+    "FIRST",
+    "FOR",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "ILIKE",
+    "IN",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "LATERAL",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NATURAL",
+    "NEXT",
+    "NOT",
+    "NOTHING",
+    "NULL",
+    "NULLS",
+    "OFFSET",
+    "ON",
+    "ONLY",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "RECURSIVE",
+    "RETURNING",
+    "RIGHT",
+    "ROWS",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "THEN",
+    "TRUE",
+    "UNION",
+    "UPDATE",
+    "USING",
+    "VALUES",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+];
+
+/// Words that start a clause, with the words that belong to them.
+const CLAUSES: &[&[&str]] = &[
+    &["WITH", "RECURSIVE"],
+    &["WITH"],
+    &["SELECT"],
+    &["INSERT", "INTO"],
+    &["UPDATE"],
+    &["DELETE", "FROM"],
+    &["FROM"],
+    &["WHERE"],
+    &["GROUP", "BY"],
+    &["HAVING"],
+    &["WINDOW"],
+    &["ORDER", "BY"],
+    &["LIMIT"],
+    &["OFFSET"],
+    &["FETCH"],
+    &["VALUES"],
+    &["SET"],
+    &["ON", "CONFLICT"],
+    &["RETURNING"],
+    &["UNION", "ALL"],
+    &["UNION"],
+    &["INTERSECT"],
+    &["EXCEPT"],
+];
+
+/// Words that start a join.
+const JOINS: &[&str] = &["CROSS", "FULL", "INNER", "JOIN", "LEFT", "NATURAL", "RIGHT"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A keyword or identifier.
+    Word(String),
+    /// A quoted identifier with the quotes.
+    Quoted(String),
+    /// A string literal with the quotes.
+    Str(String),
+    Number(String),
+    /// A bind parameter like `$1`, `?` or `:name`.
+    Param(String),
+    Punct(String),
+}
+
+impl Token {
+    fn keyword(&self) -> Option<&str> {
+        match self {
+            Token::Word(word) if is_keyword(word) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        match self {
+            Token::Punct(p) => p == punct,
+            _ => false,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Token::Word(s)
+            | Token::Quoted(s)
+            | Token::Str(s)
+            | Token::Number(s)
+            | Token::Param(s)
+            | Token::Punct(s) => s,
+        }
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    KEYWORDS.binary_search(&word).is_ok()
+}
+
+/// Splits SQL into tokens.
+///
+/// Comments are dropped and keywords are upper cased.
+fn tokenize(sql: &str) -> Vec<Token> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens: Vec<Token> = vec![];
+    let mut idx = 0;
+    let take_until_quote = |idx: usize, quote: char| {
+        // quotes are escaped by doubling them
+        let mut end = idx + 1;
+        while end < chars.len() {
+            if chars[end] == quote {
+                if chars.get(end + 1) == Some(&quote) {
+                    end += 2;
+                    continue;
+                }
+                return end + 1;
+            }
+            end += 1;
+        }
+        end
+    };
+    while idx < chars.len() {
+        let c = chars[idx];
+        let next = chars.get(idx + 1).copied();
+        let start = idx;
+        if c.is_whitespace() {
+            idx += 1;
+            continue;
+        } else if c == '-' && next == Some('-') {
+            while idx < chars.len() && chars[idx] != '\n' {
+                idx += 1;
+            }
+            continue;
+        } else if c == '/' && next == Some('*') {
+            idx += 2;
+            while idx < chars.len() && !(chars[idx] == '*' && chars.get(idx + 1) == Some(&'/')) {
+                idx += 1;
+            }
+            idx += 2;
+            continue;
+        }
+
+        let token = if c == '\'' {
+            idx = take_until_quote(idx, '\'');
+            Token::Str(chars[start..idx.min(chars.len())].iter().collect())
+        } else if c == '"' || c == '`' {
+            idx = take_until_quote(idx, c);
+            Token::Quoted(chars[start..idx.min(chars.len())].iter().collect())
+        } else if c == '[' {
+            idx = take_until_quote(idx, ']');
+            Token::Quoted(chars[start..idx.min(chars.len())].iter().collect())
+        } else if c.is_ascii_digit()
+            || (c == '.' && next.map_or(false, |x| x.is_ascii_digit()))
+            || (c == '-'
+                && next.map_or(false, |x| x.is_ascii_digit())
+                && tokens.last().map_or(true, |last| match last {
+                    Token::Punct(p) => p != ")",
+                    Token::Word(_) => last.keyword().is_some(),
+                    _ => false,
+                }))
+        {
+            idx += 1;
+            while idx < chars.len()
+                && (chars[idx].is_ascii_alphanumeric()
+                    || chars[idx] == '.'
+                    || ((chars[idx] == '-' || chars[idx] == '+')
+                        && (chars[idx - 1] == 'e' || chars[idx - 1] == 'E')))
+            {
+                idx += 1;
+            }
+            Token::Number(chars[start..idx].iter().collect())
+        } else if c.is_alphanumeric() || c == '_' {
+            while idx < chars.len() && (chars[idx].is_alphanumeric() || chars[idx] == '_') {
+                idx += 1;
+            }
+            let word: String = chars[start..idx].iter().collect();
+            // string literals with a prefix like E'..' or N'..'
+            if chars.get(idx) == Some(&'\'') && word.len() == 1 {
+                idx = take_until_quote(idx, '\'');
+                Token::Str(chars[start..idx.min(chars.len())].iter().collect())
+            } else {
+                let upper = word.to_uppercase();
+                if is_keyword(&upper) {
+                    Token::Word(upper)
+                } else {
+                    Token::Word(word)
+                }
+            }
+        } else if (c == '$' || c == ':' || c == '@')
+            && next.map_or(false, |x| x.is_alphanumeric() || x == '_')
+            && !(c == ':' && tokens.last().map_or(false, |x| x.is_punct(":")))
+        {
+            idx += 1;
+            while idx < chars.len() && (chars[idx].is_alphanumeric() || chars[idx] == '_') {
+                idx += 1;
+            }
+            Token::Param(chars[start..idx].iter().collect())
+        } else if c == '?' {
+            idx += 1;
+            Token::Param("?".into())
+        } else {
+            let two: String = chars[idx..(idx + 2).min(chars.len())].iter().collect();
+            let len = match two.as_str() {
+                "<>" | "!=" | "<=" | ">=" | "||" | "::" | "->" | "=>" => 2,
+                _ => 1,
+            };
+            idx += len;
+            Token::Punct(chars[start..idx].iter().collect())
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Returns the number of words of the clause starting at a token.
+fn clause_len(tokens: &[Token]) -> usize {
+    CLAUSES
+        .iter()
+        .find(|clause| {
+            clause.len() <= tokens.len()
+                && clause
+                    .iter()
+                    .zip(tokens.iter())
+                    .all(|(word, token)| token.keyword() == Some(word))
+        })
+        .map_or(0, |clause| clause.len())
+}
+
+struct Formatter {
+    out: String,
+    /// The indentation of the current clause.
+    indent: usize,
+    /// For each open parenthesis the indentation to restore and if it
+    /// holds a subquery.
+    parens: Vec<(usize, bool)>,
+}
+
+impl Formatter {
+    fn newline(&mut self, indent: usize) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        for _ in 0..indent {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with(|c| c == ' ' || c == '\n')
+    }
+
+    /// Checks if the formatter is at the level of clauses rather than
+    /// inside an expression in parentheses.
+    fn at_clause_level(&self) -> bool {
+        self.parens.last().map_or(true, |&(_, subquery)| subquery)
+    }
+
+    fn push(&mut self, token: &str, space_before: bool) {
+        if space_before && !self.at_line_start() {
+            self.out.push(' ');
+        }
+        self.out.push_str(token);
+    }
+}
+
+/// Formats SQL in a normalized layout.
+///
+/// Keywords are upper cased, comments are dropped and every clause starts
+/// on a line of its own followed by its contents indented.  Lists in
+/// clauses have one item per line and conditions are split at `AND` and
+/// `OR`.  With `strip_literals` string and number literals are replaced
+/// with `?`.
+pub fn format_sql(sql: &str, strip_literals: bool) -> String {
+    let mut tokens = tokenize(sql);
+    if strip_literals {
+        for token in tokens.iter_mut() {
+            if let Token::Str(_) | Token::Number(_) = token {
+                *token = Token::Param("?".into());
+            }
+        }
+    }
+
+    let mut f = Formatter {
+        out: String::new(),
+        indent: 0,
+        parens: vec![],
+    };
+    let mut idx = 0;
+    let mut prev: Option<&Token> = None;
+    let mut in_between = false;
+    while idx < tokens.len() {
+        let token = &tokens[idx];
+        let keyword = token.keyword();
+        let clause = if f.at_clause_level() {
+            clause_len(&tokens[idx..])
+        } else {
+            0
+        };
+        let prev_keyword = prev.and_then(Token::keyword);
+
+        // `ON CONFLICT DO UPDATE SET` is one clause
+        if clause > 0 && prev_keyword != Some("DO") && prev_keyword != Some("UPDATE") {
+            let words: Vec<&str> = tokens[idx..idx + clause]
+                .iter()
+                .map(Token::as_str)
+                .collect();
+            f.newline(f.indent);
+            f.push(&words.join(" "), false);
+            f.newline(f.indent + 1);
+            prev = Some(&tokens[idx + clause - 1]);
+            idx += clause;
+            continue;
+        }
+
+        match token {
+            Token::Punct(p) if p == "(" => {
+                let subquery = tokens
+                    .get(idx + 1)
+                    .and_then(Token::keyword)
+                    .map_or(false, |x| x == "SELECT" || x == "WITH");
+                let space = match prev {
+                    Some(Token::Word(_)) => prev_keyword.is_some() || f.out.ends_with('\n'),
+                    Some(Token::Quoted(_)) => false,
+                    Some(Token::Punct(p)) => p != "(" && p != "." && p != "::",
+                    Some(_) => true,
+                    None => false,
+                };
+                // tables are followed by their list of columns
+                let space = space
+                    || (idx >= 2
+                        && matches_keyword(&tokens[idx - 2], &["INTO", "TABLE"])
+                        && !f.at_line_start());
+                f.push("(", space);
+                f.parens.push((f.indent, subquery));
+                // the clauses of subqueries are indented like contents
+                if subquery {
+                    f.indent += 2;
+                }
+            }
+            Token::Punct(p) if p == ")" => {
+                let (indent, subquery) = f.parens.pop().unwrap_or((0, false));
+                if subquery {
+                    f.newline(indent + 1);
+                }
+                f.indent = indent;
+                f.push(")", false);
+            }
+            Token::Punct(p) if p == "," => {
+                f.push(",", false);
+                if f.at_clause_level() {
+                    f.newline(f.indent + 1);
+                }
+            }
+            Token::Punct(p) if p == ";" => {
+                // statements are separated by an empty line
+                f.push(";\n\n", false);
+                f.indent = 0;
+                f.parens.clear();
+            }
+            Token::Punct(p) if p == "." || p == "::" => f.push(p, false),
+            _ => {
+                let after_tight = prev.map_or(false, |prev| {
+                    prev.is_punct("(") || prev.is_punct(".") || prev.is_punct("::")
+                });
+                if (keyword == Some("AND") && !in_between) || keyword == Some("OR") {
+                    if f.at_clause_level() {
+                        f.newline(f.indent + 1);
+                    }
+                } else if keyword.map_or(false, |x| JOINS.contains(&x))
+                    && !prev_keyword.map_or(false, |x| JOINS.contains(&x) || x == "OUTER")
+                    && f.at_clause_level()
+                {
+                    f.newline(f.indent + 1);
+                }
+                if keyword == Some("BETWEEN") {
+                    in_between = true;
+                } else if keyword == Some("AND") {
+                    in_between = false;
+                }
+                f.push(token.as_str(), !after_tight);
+            }
+        }
+        prev = Some(token);
+        idx += 1;
+    }
+    let trimmed = f.out.trim_end().len();
+    f.out.truncate(trimmed);
+    f.out
+}
+
+fn matches_keyword(token: &Token, keywords: &[&str]) -> bool {
+    token.keyword().map_or(false, |x| keywords.contains(&x))
+}
+
+/// Formats SQL with the settings of the current thread.
+pub fn format_sql_snapshot(sql: &str) -> String {
+    let strip_literals = Settings::with(|settings| settings.sql_strip_literals());
+    format_sql(sql, strip_literals)
+}
+
+#[test]
+fn test_keywords_sorted() {
+    let mut sorted = KEYWORDS.to_vec();
+    sorted.sort_unstable();
+    assert_eq!(sorted, KEYWORDS);
+}
+
+#[test]
+fn test_format_sql() {
+    let sql = "select u.id, count(*) as n from \"users\" u left outer join posts p on p.user_id = u.id \
+               where u.age between 18 and 65 and (u.name like 'J%' or u.id in (select id from admins)) \
+               -- volatile comment
+               group by u.id order by n desc limit 10";
+    assert_eq!(
+        format_sql(sql, false),
+        r#"SELECT
+  u.id,
+  count(*) AS n
+FROM
+  "users" u
+  LEFT OUTER JOIN posts p ON p.user_id = u.id
+WHERE
+  u.age BETWEEN 18 AND 65
+  AND (u.name LIKE 'J%' OR u.id IN (
+    SELECT
+      id
+    FROM
+      admins
+  ))
+GROUP BY
+  u.id
+ORDER BY
+  n DESC
+LIMIT
+  10"#
+    );
+    assert_eq!(
+        format_sql(
+            "INSERT INTO users(id,name) VALUES ($1, 'x'), (2, -1.5e3) RETURNING id; update users set name=:name where id = ?",
+            true
+        ),
+        "INSERT INTO
+  users (id, name)
+VALUES
+  ($1, ?),
+  (?, ?)
+RETURNING
+  id;
+
+UPDATE
+  users
+SET
+  name = :name
+WHERE
+  id = ?"
+    );
+}
@@ -0,0 +1,116 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::runtime::assert_streamed_snapshot;
+use crate::utils::create_temp_file;
+
+/// Builds the contents of a snapshot incrementally.
+///
+/// The writer is created with [`snapshot_writer!`](crate::snapshot_writer!)
+/// and implements [`std::io::Write`] so that large generated output can be
+/// written to it piece by piece, for instance from an iterator:
+///
+/// ```no_run
+/// # use std::io::Write;
+/// let mut writer = insta::snapshot_writer!("numbers.txt");
+/// for number in 0..10_000_000 {
+///     writeln!(writer, "{}", number).unwrap();
+/// }
+/// writer.finish();
+/// ```
+///
+/// What is written is spooled to a temporary file instead of memory and
+/// [`finish`](SnapshotWriter::finish) asserts it as a binary snapshot with
+/// the given extension.  The comparison with the stored snapshot reads both
+/// files in chunks, so a matching snapshot is never held in memory.  Only
+/// if the snapshot does not match is it loaded to show the difference and
+/// to write the new snapshot.
+///
+/// Nothing is asserted if the writer is dropped without calling `finish`.
+#[derive(Debug)]
+pub struct SnapshotWriter {
+    spool: Option<BufWriter<fs::File>>,
+    spool_file: PathBuf,
+    name_and_extension: String,
+    manifest_dir: &'static str,
+    module_path: &'static str,
+    file: &'static str,
+    line: u32,
+    expr: &'static str,
+}
+
+impl SnapshotWriter {
+    /// Asserts the snapshot of everything written.
+    ///
+    /// This panics like the assertion macros if the snapshot does not match.
+    pub fn finish(mut self) {
+        let mut spool = self.spool.take().expect("snapshot writer already finished");
+        spool.flush().expect("could not write the spooled snapshot");
+        drop(spool);
+        assert_streamed_snapshot(
+            &self.name_and_extension,
+            &self.spool_file,
+            self.manifest_dir,
+            self.module_path,
+            self.file,
+            self.line,
+            self.expr,
+        )
+        .unwrap();
+    }
+}
+
+impl Write for SnapshotWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.spool
+            .as_mut()
+            .expect("snapshot writer already finished")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.spool
+            .as_mut()
+            .expect("snapshot writer already finished")
+            .flush()
+    }
+}
+
+impl Drop for SnapshotWriter {
+    fn drop(&mut self) {
+        self.spool.take();
+        fs::remove_file(&self.spool_file).ok();
+    }
+}
+
+/// Creates a writer for a streamed snapshot.
+pub fn snapshot_writer(
+    name_and_extension: &str,
+    manifest_dir: &'static str,
+    module_path: &'static str,
+    file: &'static str,
+    line: u32,
+    expr: &'static str,
+) -> SnapshotWriter {
+    let temp_dir = env::temp_dir();
+    let (spool_file, spool) =
+        create_temp_file(&temp_dir, "insta", ".spool").unwrap_or_else(|err| {
+            panic!(
+                "could not create a spool file in {} for a snapshot: {}",
+                temp_dir.display(),
+                err
+            )
+        });
+    SnapshotWriter {
+        spool: Some(BufWriter::new(spool)),
+        spool_file,
+        name_and_extension: name_and_extension.to_string(),
+        manifest_dir,
+        module_path,
+        file,
+        line,
+        expr,
+    }
+}
@@ -0,0 +1,265 @@
+use crate::content::Content;
+use crate::settings::Settings;
+
+/// Returns the fields of maps and structs.
+fn fields(content: &Content) -> Option<Vec<(String, &Content)>> {
+    match content.resolve_inner() {
+        Content::Struct(_, fields) | Content::StructVariant(_, _, _, fields) => Some(
+            fields
+                .iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        ),
+        Content::Map(entries) => Some(
+            entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = match key.as_str() {
+                        Some(key) => key.to_string(),
+                        None => serde_json::to_string(key).unwrap(),
+                    };
+                    (key, value)
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// The column names and the cells of the rows of a table.
+type Table<'a> = (Vec<String>, Vec<Vec<Option<&'a Content>>>);
+
+/// Returns the value of a field of a map or struct.
+fn field<'a>(content: &'a Content, name: &str) -> Option<&'a Content> {
+    fields(content)?
+        .into_iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+/// Reads a Polars dataframe serialized with its `serde` feature.
+///
+/// Dataframes serialize as `{"columns": [...]}` where every series holds
+/// its `name`, `datatype` and `values`.  The data types of the series are
+/// returned along with the table.
+fn read_dataframe(content: &Content) -> Option<(Table<'_>, Vec<String>)> {
+    let series = field(content, "columns")?.as_slice()?;
+    let mut columns = vec![];
+    let mut values = vec![];
+    let mut types = vec![];
+    for series in series {
+        columns.push(field(series, "name")?.as_str()?.to_string());
+        values.push(field(series, "values")?.as_slice()?);
+        let datatype = field(series, "datatype")?.resolve_inner();
+        types.push(match datatype.as_str() {
+            Some(name) => name.to_string(),
+            None => serde_json::to_string(datatype).unwrap(),
+        });
+    }
+    let len = values.first().map_or(0, |x| x.len());
+    if values.iter().any(|x| x.len() != len) {
+        return None;
+    }
+    let rows = (0..len)
+        .map(|idx| values.iter().map(|x| Some(&x[idx])).collect())
+        .collect();
+    Some(((columns, rows), types))
+}
+
+/// Reads a table from a sequence of rows or a map of columns.
+fn read_table(content: &Content) -> Table<'_> {
+    let mut columns: Vec<String> = vec![];
+    let mut rows = vec![];
+
+    // a map of equally long columns
+    if let Some(fields) = fields(content) {
+        let lengths: Vec<_> = fields
+            .iter()
+            .map(|(_, value)| value.as_slice().map(<[Content]>::len))
+            .collect();
+        if !fields.is_empty() && lengths.iter().all(|x| x.is_some() && *x == lengths[0]) {
+            let len = lengths[0].unwrap();
+            columns = fields.iter().map(|(key, _)| key.clone()).collect();
+            for idx in 0..len {
+                rows.push(
+                    fields
+                        .iter()
+                        .map(|(_, value)| value.as_slice().map(|x| &x[idx]))
+                        .collect(),
+                );
+            }
+            return (columns, rows);
+        }
+    }
+
+    let records = match content.as_slice() {
+        Some(records) => records.iter().collect(),
+        None => vec![content],
+    };
+    let records: Vec<_> = records
+        .into_iter()
+        .map(|record| fields(record).unwrap_or_else(|| vec![("value".to_string(), record)]))
+        .collect();
+    for record in &records {
+        for (key, _) in record {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    for record in &records {
+        rows.push(
+            columns
+                .iter()
+                .map(|column| {
+                    record
+                        .iter()
+                        .find(|(key, _)| key == column)
+                        .map(|(_, value)| *value)
+                })
+                .collect(),
+        );
+    }
+    (columns, rows)
+}
+
+/// Returns the type name of a cell, `None` for nulls.
+fn cell_type(cell: Option<&Content>) -> Option<&'static str> {
+    let cell = cell?.resolve_inner();
+    Some(match cell {
+        Content::None | Content::Unit => return None,
+        Content::Bool(_) => "bool",
+        Content::F32(_) | Content::F64(_) => "float",
+        Content::String(_) | Content::Char(_) | Content::UnitVariant(..) => "str",
+        Content::Bytes(_) => "bytes",
+        _ if cell.as_i128().is_some() || cell.as_u128().is_some() => "int",
+        _ if cell.as_slice().is_some() => "list",
+        _ => "struct",
+    })
+}
+
+fn column_type(rows: &[Vec<Option<&Content>>], idx: usize) -> &'static str {
+    let mut rv = None;
+    for row in rows {
+        rv = match (rv, cell_type(row[idx])) {
+            (rv, None) => rv,
+            (None, ty) => ty,
+            (Some(a), Some(b)) if a == b => Some(a),
+            (Some("int"), Some("float")) | (Some("float"), Some("int")) => Some("float"),
+            _ => Some("mixed"),
+        };
+    }
+    rv.unwrap_or("null")
+}
+
+fn format_cell(cell: Option<&Content>, float_precision: Option<usize>) -> String {
+    let cell = match cell {
+        Some(cell) => cell.resolve_inner(),
+        None => return "null".into(),
+    };
+    match cell {
+        Content::None | Content::Unit => "null".into(),
+        Content::Bool(value) => value.to_string(),
+        Content::Char(value) => value.to_string(),
+        Content::String(value) => value.replace('\n', "\\n"),
+        Content::UnitVariant(_, _, name) => name.to_string(),
+        _ => match cell.as_f64() {
+            Some(value) => match float_precision {
+                Some(precision) => format!("{:.*}", precision, value),
+                None => format!("{:?}", value),
+            },
+            None => match cell.as_i128() {
+                Some(value) => value.to_string(),
+                None => match cell.as_u128() {
+                    Some(value) => value.to_string(),
+                    None => serde_json::to_string(cell).unwrap(),
+                },
+            },
+        },
+    }
+}
+
+/// Renders rows or columns as an aligned table with a schema header.
+///
+/// The table starts with its shape (rows and columns) followed by the
+/// column names and the types of the columns.  Numbers are right aligned.
+/// The settings limit the rows shown, round floats and leave out columns.
+/// Polars dataframes show the data types of their series as column types.
+pub fn to_table(content: &Content) -> String {
+    let (max_rows, float_precision, excluded) = Settings::with(|settings| {
+        (
+            settings.table_max_rows(),
+            settings.table_float_precision(),
+            settings.table_excluded_columns().to_vec(),
+        )
+    });
+    let ((columns, rows), declared_types) = match read_dataframe(content) {
+        Some((table, types)) => (table, Some(types)),
+        None => (read_table(content), None),
+    };
+    let keep: Vec<usize> = (0..columns.len())
+        .filter(|&idx| !excluded.contains(&columns[idx]))
+        .collect();
+
+    let types: Vec<&str> = keep.iter().map(|&idx| column_type(&rows, idx)).collect();
+    let shown = max_rows.unwrap_or(rows.len()).min(rows.len());
+    let mut lines: Vec<Vec<String>> = vec![
+        keep.iter().map(|&idx| columns[idx].clone()).collect(),
+        match declared_types {
+            Some(declared_types) => keep
+                .iter()
+                .map(|&idx| declared_types[idx].clone())
+                .collect(),
+            None => types.iter().map(|x| x.to_string()).collect(),
+        },
+    ];
+    for row in &rows[..shown] {
+        lines.push(
+            keep.iter()
+                .map(|&idx| format_cell(row[idx], float_precision))
+                .collect(),
+        );
+    }
+    let widths: Vec<usize> = (0..keep.len())
+        .map(|col| {
+            lines
+                .iter()
+                .map(|line| line[col].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut rv = format!("shape: ({}, {})\n", rows.len(), keep.len());
+    for (line_idx, line) in lines.iter().enumerate() {
+        if line_idx == 2 {
+            rv.push('|');
+            for width in &widths {
+                rv.push_str(&"-".repeat(width + 2));
+                rv.push('|');
+            }
+            rv.push('\n');
+        }
+        rv.push('|');
+        for (col, cell) in line.iter().enumerate() {
+            let numeric = line_idx >= 2 && (types[col] == "int" || types[col] == "float");
+            let padding = " ".repeat(widths[col] - cell.chars().count());
+            if numeric {
+                rv.push_str(&format!(" {}{} |", padding, cell));
+            } else {
+                rv.push_str(&format!(" {}{} |", cell, padding));
+            }
+        }
+        rv.push('\n');
+    }
+    if shown < rows.len() {
+        let more = rows.len() - shown;
+        rv.push_str(&format!(
+            "... {} more row{}\n",
+            more,
+            if more == 1 { "" } else { "s" }
+        ));
+    }
+    rv.truncate(rv.len() - 1);
+    rv
+}
@@ -1,13 +1,110 @@
 use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Are we running in in a CI environment?
 pub fn is_ci() -> bool {
     env::var("CI").is_ok() || env::var("TF_BUILD").is_ok()
 }
 
+/// Formats a point in time as an ISO date (`YYYY-MM-DD`) in UTC.
+pub fn format_date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs() / 86_400);
+    // days to the civil calendar, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Creates a new file with a random name in a folder.
+///
+/// The name cannot be guessed and the file must not exist yet, so that
+/// nobody can place a file or link there in advance.  On unix the file is
+/// only accessible by the current user.
+pub(crate) fn create_temp_file(
+    dir: &std::path::Path,
+    prefix: &str,
+    suffix: &str,
+) -> std::io::Result<(std::path::PathBuf, std::fs::File)> {
+    use std::fs;
+    use uuid::Uuid;
+
+    let path = dir.join(format!(
+        "{}-{}{}",
+        prefix,
+        Uuid::new_v4().to_simple(),
+        suffix
+    ));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(&path)?;
+    Ok((path, file))
+}
+
+/// Hashes bytes with 64 bit FNV-1a.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Checks if a value is an ISO date (`YYYY-MM-DD`).
+pub fn is_iso_date(value: &str) -> bool {
+    let parts: Vec<_> = value.split('-').collect();
+    let number = |idx: usize, len: usize, max: u32| {
+        parts[idx].len() == len
+            && parts[idx].bytes().all(|x| x.is_ascii_digit())
+            && parts[idx]
+                .parse()
+                .map_or(false, |x: u32| x >= 1 && x <= max)
+    };
+    parts.len() == 3 && number(0, 4, 9999) && number(1, 2, 12) && number(2, 2, 31)
+}
+
 #[cfg(feature = "colors")]
 pub use console::style;
 
+/// Are colors enabled for stdout?
+#[cfg(feature = "colors")]
+pub fn colors_enabled() -> bool {
+    console::colors_enabled()
+}
+
+/// Are colors enabled for stdout?
+#[cfg(not(feature = "colors"))]
+pub fn colors_enabled() -> bool {
+    false
+}
+
+/// Can the user be asked for decisions on the terminal?
+#[cfg(feature = "colors")]
+pub fn is_interactive() -> bool {
+    console::user_attended_stderr() && !is_ci()
+}
+
+/// Can the user be asked for decisions on the terminal?
+#[cfg(not(feature = "colors"))]
+pub fn is_interactive() -> bool {
+    false
+}
+
 #[cfg(not(feature = "colors"))]
 mod fake_colors {
     pub struct FakeStyledObject<D>(D);
@@ -22,7 +119,7 @@ mod fake_colors {
     }
 
     impl<D> FakeStyledObject<D> {
-        style_attr!(red green yellow cyan bold dim underlined);
+        style_attr!(red green yellow cyan magenta bold dim underlined);
     }
 
     impl<D: std::fmt::Display> std::fmt::Display for FakeStyledObject<D> {
@@ -38,3 +135,17 @@ mod fake_colors {
 
 #[cfg(not(feature = "colors"))]
 pub use self::fake_colors::*;
+
+#[test]
+fn test_dates() {
+    use std::time::Duration;
+    assert_eq!(format_date(UNIX_EPOCH), "1970-01-01");
+    assert_eq!(
+        format_date(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+        "2000-02-29"
+    );
+    assert!(is_iso_date("2021-03-31"));
+    assert!(!is_iso_date("2021-13-01"));
+    assert!(!is_iso_date("2021-3-31"));
+    assert!(!is_iso_date("31.03.2021"));
+}
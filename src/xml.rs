@@ -0,0 +1,340 @@
+use std::fmt::Write;
+
+use xmlparser::{ElementEnd, Token, Tokenizer};
+
+use crate::content::Content;
+
+/// Key prefix used for attributes when XML is represented as content.
+const ATTRIBUTE_PREFIX: char = '@';
+
+/// Key used for text nodes when XML is represented as content.
+const TEXT_KEY: &str = "$text";
+
+/// Parses an XML document into the content model.
+///
+/// Elements become maps keyed by the element name.  Attributes are stored
+/// with a leading `@` and text nodes under `$text` so that redaction
+/// selectors such as `.catalog.book["@id"]` can address them.  Whitespace
+/// only text, comments, processing instructions and the declaration are
+/// dropped as they carry no meaning for the canonical form.
+pub fn parse_xml(xml: &str) -> Result<Content, String> {
+    let mut stack: Vec<(String, Vec<(Content, Content)>)> = vec![("".into(), vec![])];
+
+    for token in Tokenizer::from(xml) {
+        match token.map_err(|err| err.to_string())? {
+            Token::ElementStart { prefix, local, .. } => {
+                stack.push((qualified_name(prefix.as_str(), local.as_str()), vec![]));
+            }
+            Token::Attribute {
+                prefix,
+                local,
+                value,
+                ..
+            } => {
+                let name = qualified_name(prefix.as_str(), local.as_str());
+                let (_, ref mut entries) = stack.last_mut().unwrap();
+                entries.push((
+                    Content::from(format!("{}{}", ATTRIBUTE_PREFIX, name)),
+                    Content::from(unescape(value.as_str())),
+                ));
+            }
+            Token::ElementEnd { end, .. } => match end {
+                ElementEnd::Open => {}
+                ElementEnd::Close(..) | ElementEnd::Empty => {
+                    let (name, entries) = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some((_, ref mut parent)) => {
+                            parent.push((Content::from(name), Content::Map(entries)))
+                        }
+                        None => return Err("unbalanced closing tag".into()),
+                    }
+                }
+            },
+            Token::Text { text } => {
+                let text = collapse_whitespace(&unescape(text.as_str()));
+                if !text.is_empty() {
+                    if let Some((_, ref mut entries)) = stack.last_mut() {
+                        entries.push((Content::from(TEXT_KEY), Content::from(text)));
+                    }
+                }
+            }
+            Token::Cdata { text, .. } => {
+                if let Some((_, ref mut entries)) = stack.last_mut() {
+                    entries.push((Content::from(TEXT_KEY), Content::from(text.as_str())));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if stack.len() != 1 {
+        return Err("unclosed element".into());
+    }
+    Ok(Content::Map(stack.pop().unwrap().1))
+}
+
+/// Renders content as canonical XML.
+///
+/// Attributes are sorted by name, text is trimmed and child elements are
+/// indented by two spaces.  Structs and maps become elements, sequences
+/// repeat the element they are contained in.
+pub fn to_canonical_xml(content: &Content) -> String {
+    let mut out = String::new();
+    match content {
+        Content::Map(_) => {
+            let entries = element_entries(content);
+            if entries.iter().all(|(key, _)| !is_attribute_or_text(key)) {
+                for (key, value) in entries {
+                    write_element(&mut out, &key, value, 0);
+                }
+            } else {
+                write_element(&mut out, root_name(content), content, 0);
+            }
+        }
+        _ => write_element(&mut out, root_name(content), content, 0),
+    }
+    if out.ends_with('\n') {
+        out.truncate(out.len() - 1);
+    }
+    out
+}
+
+fn qualified_name(prefix: &str, local: &str) -> String {
+    if prefix.is_empty() {
+        local.to_string()
+    } else {
+        format!("{}:{}", prefix, local)
+    }
+}
+
+fn root_name(content: &Content) -> &str {
+    match content {
+        Content::Struct(name, _)
+        | Content::TupleStruct(name, _)
+        | Content::UnitStruct(name)
+        | Content::NewtypeStruct(name, _) => name,
+        Content::StructVariant(_, _, variant, _)
+        | Content::TupleVariant(_, _, variant, _)
+        | Content::UnitVariant(_, _, variant)
+        | Content::NewtypeVariant(_, _, variant, _) => variant,
+        _ => "value",
+    }
+}
+
+fn is_attribute_or_text(key: &str) -> bool {
+    key.starts_with(ATTRIBUTE_PREFIX) || key == TEXT_KEY
+}
+
+fn element_entries(content: &Content) -> Vec<(String, &Content)> {
+    match content.resolve_inner() {
+        Content::Map(map) => map
+            .iter()
+            .map(|(key, value)| (scalar_text(key).unwrap_or_default(), value))
+            .collect(),
+        Content::Struct(_, fields) | Content::StructVariant(_, _, _, fields) => fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn scalar_text(content: &Content) -> Option<String> {
+    Some(match content.resolve_inner() {
+        Content::Bool(val) => val.to_string(),
+        Content::U8(val) => val.to_string(),
+        Content::U16(val) => val.to_string(),
+        Content::U32(val) => val.to_string(),
+        Content::U64(val) => val.to_string(),
+        Content::U128(val) => val.to_string(),
+        Content::I8(val) => val.to_string(),
+        Content::I16(val) => val.to_string(),
+        Content::I32(val) => val.to_string(),
+        Content::I64(val) => val.to_string(),
+        Content::I128(val) => val.to_string(),
+        Content::F32(val) => val.to_string(),
+        Content::F64(val) => val.to_string(),
+        Content::Char(val) => val.to_string(),
+        Content::String(val) => val.clone(),
+        Content::Bytes(val) => String::from_utf8_lossy(val).into_owned(),
+        Content::UnitVariant(_, _, variant) => variant.to_string(),
+        _ => return None,
+    })
+}
+
+fn write_element(out: &mut String, name: &str, content: &Content, depth: usize) {
+    let indent = depth * 2;
+    match content {
+        Content::None => {}
+        Content::Some(inner) | Content::NewtypeStruct(_, inner) => {
+            write_element(out, name, inner, depth)
+        }
+        Content::Seq(items) | Content::Tuple(items) | Content::TupleStruct(_, items) => {
+            for item in items {
+                write_element(out, name, item, depth);
+            }
+        }
+        Content::NewtypeVariant(_, _, variant, inner) => {
+            writeln!(out, "{:indent$}<{}>", "", name, indent = indent).unwrap();
+            write_element(out, variant, inner, depth + 1);
+            writeln!(out, "{:indent$}</{}>", "", name, indent = indent).unwrap();
+        }
+        Content::Map(_) | Content::Struct(..) | Content::StructVariant(..) => {
+            let mut attributes = vec![];
+            // text and child elements in document order
+            let mut body = vec![];
+            for (key, value) in element_entries(content) {
+                if key.starts_with(ATTRIBUTE_PREFIX) {
+                    if let Some(value) = scalar_text(value) {
+                        attributes.push((key[1..].to_string(), value));
+                    }
+                } else {
+                    body.push((key, value));
+                }
+            }
+            attributes.sort();
+
+            write!(out, "{:indent$}<{}", "", name, indent = indent).unwrap();
+            for (attr, value) in &attributes {
+                write!(out, " {}=\"{}\"", attr, escape(value, true)).unwrap();
+            }
+            if body.iter().all(|(key, _)| key == TEXT_KEY) {
+                let texts: Vec<_> = body
+                    .iter()
+                    .filter_map(|(_, value)| scalar_text(value))
+                    .collect();
+                if texts.is_empty() {
+                    out.push_str("/>\n");
+                } else {
+                    writeln!(out, ">{}</{}>", escape(&texts.join(" "), false), name).unwrap();
+                }
+            } else {
+                out.push_str(">\n");
+                for (key, value) in body {
+                    if key != TEXT_KEY {
+                        write_element(out, &key, value, depth + 1);
+                    } else if let Some(text) = scalar_text(value) {
+                        writeln!(
+                            out,
+                            "{:indent$}{}",
+                            "",
+                            escape(&text, false),
+                            indent = indent + 2
+                        )
+                        .unwrap();
+                    }
+                }
+                writeln!(out, "{:indent$}</{}>", "", name, indent = indent).unwrap();
+            }
+        }
+        other => match scalar_text(other) {
+            Some(text) if !text.is_empty() => {
+                writeln!(
+                    out,
+                    "{:indent$}<{}>{}</{}>",
+                    "",
+                    name,
+                    escape(&text, false),
+                    name,
+                    indent = indent
+                )
+                .unwrap();
+            }
+            _ => {
+                writeln!(out, "{:indent$}<{}/>", "", name, indent = indent).unwrap();
+            }
+        },
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn escape(text: &str, in_attribute: bool) -> String {
+    let mut rv = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => rv.push_str("&amp;"),
+            '<' => rv.push_str("&lt;"),
+            '>' => rv.push_str("&gt;"),
+            '"' if in_attribute => rv.push_str("&quot;"),
+            other => rv.push(other),
+        }
+    }
+    rv
+}
+
+fn unescape(text: &str) -> String {
+    let mut rv = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find('&') {
+        rv.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+                .ok()
+                .and_then(std::char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => rv.push(c),
+            None => rv.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    rv.push_str(rest);
+    rv
+}
+
+#[test]
+fn test_canonical_xml() {
+    use similar_asserts::assert_eq;
+    let content = parse_xml(
+        r#"<?xml version="1.0"?>
+        <catalog   b="2" a="1">
+            <!-- a comment -->
+            <book id="bk101" lang="en"><title>  XML &amp;
+               Rust </title></book>
+            <empty />
+        </catalog>"#,
+    )
+    .unwrap();
+    assert_eq!(
+        to_canonical_xml(&content),
+        r#"<catalog a="1" b="2">
+  <book id="bk101" lang="en">
+    <title>XML &amp; Rust</title>
+  </book>
+  <empty/>
+</catalog>"#
+    );
+}
+
+#[test]
+fn test_mixed_content() {
+    let content = parse_xml("<p>a <b>x</b> c<br/>d</p>").unwrap();
+    assert_eq!(
+        to_canonical_xml(&content),
+        "<p>\n  a\n  <b>x</b>\n  c\n  <br/>\n  d\n</p>"
+    );
+}
+
+#[test]
+fn test_unescape() {
+    assert_eq!(
+        unescape("a &lt;b&gt; &#65;&#x42; &unknown; &"),
+        "a <b> AB &unknown; &"
+    );
+}
@@ -0,0 +1,15 @@
+use insta::{assert_binary_snapshot, Settings};
+
+#[test]
+fn test_binary_snapshot() {
+    assert_binary_snapshot!("hello.bin", b"Hello\x00World\xff".to_vec());
+}
+
+#[test]
+fn test_binary_snapshot_comparator() {
+    let mut settings = Settings::clone_current();
+    settings.add_binary_comparator("txt", |old, new| old.eq_ignore_ascii_case(new));
+    settings.bind(|| {
+        assert_binary_snapshot!("case_insensitive.txt", b"HELLO WORLD");
+    });
+}
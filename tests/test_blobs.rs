@@ -0,0 +1,30 @@
+#![cfg(feature = "blobs")]
+
+use insta::{assert_snapshot, Settings, Snapshot};
+
+fn make_payload() -> String {
+    (0..50)
+        .map(|idx| format!("item {}", idx))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_blob_snapshots() {
+    let mut settings = Settings::new();
+    settings.set_blob_threshold(Some(100));
+    settings.bind(|| {
+        assert_snapshot!("payload", make_payload());
+        assert_snapshot!("same_payload", make_payload());
+        assert_snapshot!("small", "too small for a blob");
+    });
+
+    let payload = Snapshot::from_file("tests/snapshots/test_blobs__payload.snap").unwrap();
+    let same_payload =
+        Snapshot::from_file("tests/snapshots/test_blobs__same_payload.snap").unwrap();
+    let small = Snapshot::from_file("tests/snapshots/test_blobs__small.snap").unwrap();
+    assert!(payload.metadata().blob().is_some());
+    assert_eq!(payload.metadata().blob(), same_payload.metadata().blob());
+    assert_eq!(small.metadata().blob(), None);
+    assert_eq!(payload.contents_str(), make_payload());
+}
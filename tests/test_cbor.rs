@@ -0,0 +1,21 @@
+#![cfg(feature = "cbor")]
+
+use insta::assert_cbor_snapshot;
+
+#[test]
+fn test_cbor_decoding() {
+    // {"id": 300, "tags": ["a", "b"], "created": 1(1363896240), "ok": true}
+    let bytes: &[u8] =
+        b"\xa4\x62id\x19\x01\x2c\x64tags\x9f\x61a\x61b\xff\x67created\xc1\x1a\x51\x4b\x67\xb0\x62ok\xf5";
+    assert_cbor_snapshot!(bytes, @r###"
+    ---
+    id: 300
+    tags:
+      - a
+      - b
+    created:
+      $tag: 1
+      $value: 1363896240
+    ok: true
+    "###);
+}
@@ -26,6 +26,20 @@ fn test_clash_detection() {
         .unwrap()
         .join()
         .unwrap_err();
+    let err3 = thread::Builder::new()
+        .spawn(|| {
+            insta::assert_debug_snapshot!("named_clash_always_missing", 42);
+        })
+        .unwrap()
+        .join()
+        .unwrap_err();
+    let err4 = thread::Builder::new()
+        .spawn(|| {
+            insta::assert_debug_snapshot!("named_clash_always_missing", 23);
+        })
+        .unwrap()
+        .join()
+        .unwrap_err();
 
     if let Ok(value) = old_update_value {
         env::set_var("INSTA_UPDATE", value);
@@ -40,10 +54,24 @@ fn test_clash_detection() {
 
     let s1 = err1.downcast_ref::<String>().unwrap();
     let s2 = err2.downcast_ref::<String>().unwrap();
-    let mut values = vec![s1.as_str(), s2.as_str()];
+    let mut values = [s1.as_str(), s2.as_str()];
     values.sort();
     assert_eq!(&values[..], &vec![
         "Insta snapshot name clash detected between \'foo_always_missing\' and \'test_foo_always_missing\' in \'test_clash_detection\'. Rename one function.",
         "snapshot assertion for \'foo_always_missing\' failed in line 16",
     ][..]);
+
+    let s3 = err3.downcast_ref::<String>().unwrap();
+    let s4 = err4.downcast_ref::<String>().unwrap();
+    assert_eq!(
+        s3,
+        "snapshot assertion for \'named_clash_always_missing\' failed in line 31"
+    );
+    assert!(s4.starts_with("Insta snapshot name clash detected: "));
+    assert!(s4.ends_with(
+        "test_clash_detection__named_clash_always_missing.snap is asserted with \
+         different contents in \
+         tests/test_clash_detection.rs:31 and tests/test_clash_detection.rs:38. \
+         Use different snapshot names."
+    ));
 }
@@ -0,0 +1,38 @@
+#![cfg(all(feature = "cmd", unix))]
+
+use std::process::Command;
+
+use insta::assert_cmd_snapshot;
+
+#[test]
+fn test_cmd_output() {
+    assert_cmd_snapshot!(Command::new("sh").arg("-c").arg("echo hello; echo oops >&2; exit 3"), @r###"
+    success: false
+    exit_code: 3
+    ----- stdout -----
+    hello
+
+    ----- stderr -----
+    oops
+    "###);
+}
+
+#[cfg(feature = "filters")]
+#[test]
+fn test_cmd_stream_filters() {
+    let mut settings = insta::Settings::clone_current();
+    settings.add_stdout_filter(r"\d+", "[N]");
+    settings.bind(|| {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo took 42ms; echo 42 errors >&2");
+        assert_cmd_snapshot!(&mut cmd, @r###"
+        success: true
+        exit_code: 0
+        ----- stdout -----
+        took [N]ms
+
+        ----- stderr -----
+        42 errors
+        "###);
+    });
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "compression")]
+
+use insta::{assert_snapshot, Compression, Settings, Snapshot};
+
+fn make_report() -> String {
+    (0..100)
+        .map(|idx| format!("row {}: {}", idx, "x".repeat(idx % 10)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_compressed_snapshot() {
+    let mut settings = Settings::new();
+    settings.set_compression(Some(Compression::Gzip));
+    settings.bind(|| {
+        assert_snapshot!("report", make_report());
+    });
+
+    // the stored file is compressed but reads back transparently
+    let snapshot = Snapshot::from_file("tests/snapshots/test_compression__report.snap").unwrap();
+    assert_eq!(snapshot.metadata().compression(), Some(Compression::Gzip));
+    assert_eq!(snapshot.contents_str(), make_report());
+}
+
+#[test]
+fn test_compressed_snapshot_without_setting() {
+    // reading does not depend on the setting
+    assert_snapshot!("report", make_report());
+}
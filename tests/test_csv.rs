@@ -0,0 +1,76 @@
+#![cfg(feature = "csv")]
+
+use insta::{assert_csv_snapshot, assert_tsv_snapshot, with_settings, CsvQuoteStyle};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct User {
+    id: u32,
+    name: &'static str,
+    email: &'static str,
+}
+
+fn users() -> Vec<User> {
+    vec![
+        User {
+            id: 1,
+            name: "John Doe",
+            email: "john@example.com",
+        },
+        User {
+            id: 2,
+            name: "Jane; Doe",
+            email: "jane@example.com",
+        },
+    ]
+}
+
+#[test]
+fn test_raw_tsv() {
+    assert_tsv_snapshot!("id\tname\r\n1\tJohn Doe\r\n2\t\"Jane\"\r\n", @r###"
+    id	name
+    1	John Doe
+    2	Jane
+    "###);
+}
+
+#[test]
+fn test_csv_dialect() {
+    with_settings!({
+        csv_delimiter => b';',
+        csv_quote_style => CsvQuoteStyle::NonNumeric,
+        csv_headers => false,
+    }, {
+        assert_csv_snapshot!(users(), @r###"
+        1;"John Doe";"john@example.com"
+        2;"Jane; Doe";"jane@example.com"
+        "###);
+    });
+}
+
+#[test]
+fn test_csv_columns() {
+    with_settings!({csv_columns => Some(vec!["name".into(), "id".into()])}, {
+        assert_csv_snapshot!(users(), @r###"
+        name,id
+        John Doe,1
+        Jane; Doe,2
+        "###);
+        assert_csv_snapshot!("id,name,email\n1,John,john@example.com", @r###"
+        name,id
+        John,1
+        "###);
+    });
+}
+
+#[cfg(feature = "redactions")]
+#[test]
+fn test_raw_csv_redacted() {
+    assert_csv_snapshot!("id,email\n1,john@example.com\n2,jane@example.com", {
+        "[].email" => "[email]",
+    }, @r###"
+    id,email
+    1,[email]
+    2,[email]
+    "###);
+}
@@ -0,0 +1,72 @@
+use std::error::Error;
+use std::fmt::Write;
+
+use insta::internals::Content;
+use insta::{assert_custom_snapshot, SnapshotSerializer};
+use serde::Serialize;
+
+/// Writes structs as `key = value` lines.
+struct KeyValue;
+
+impl SnapshotSerializer for KeyValue {
+    fn format(&self) -> &str {
+        "key-value"
+    }
+
+    fn serialize(&self, content: &Content) -> Result<String, Box<dyn Error>> {
+        let fields = match content {
+            Content::Struct(_, fields) => fields,
+            _ => return Err("expected a struct".into()),
+        };
+        let mut rv = String::new();
+        for (key, value) in fields {
+            writeln!(rv, "{} = {}", key, serde_json::to_string(value)?)?;
+        }
+        rv.pop();
+        Ok(rv)
+    }
+}
+
+#[derive(Serialize)]
+struct User {
+    id: u32,
+    name: &'static str,
+    roles: Vec<&'static str>,
+}
+
+#[test]
+fn test_custom_format_inline() {
+    let user = User {
+        id: 42,
+        name: "admin",
+        roles: vec!["admin", "user"],
+    };
+    assert_custom_snapshot!(KeyValue, &user, @r###"
+    id = 42
+    name = "admin"
+    roles = ["admin","user"]
+    "###);
+}
+
+#[cfg(feature = "redactions")]
+#[test]
+fn test_custom_format_redacted() {
+    let user = User {
+        id: 42,
+        name: "admin",
+        roles: vec!["admin", "user"],
+    };
+    assert_custom_snapshot!(KeyValue, &user, {
+        ".id" => "[id]",
+    }, @r###"
+    id = "[id]"
+    name = "admin"
+    roles = ["admin","user"]
+    "###);
+}
+
+#[test]
+#[should_panic(expected = "failed to serialize snapshot as key-value: expected a struct")]
+fn test_custom_format_error() {
+    assert_custom_snapshot!(KeyValue, vec![1, 2, 3], @"");
+}
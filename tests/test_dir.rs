@@ -0,0 +1,51 @@
+#![cfg(feature = "dir")]
+
+use std::fs;
+use std::path::PathBuf;
+
+use insta::{assert_dir_snapshot, FileRedaction};
+
+fn make_output_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("insta-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&path).ok();
+    fs::create_dir_all(path.join("src")).unwrap();
+    fs::create_dir_all(path.join("target/debug")).unwrap();
+    fs::write(path.join("Cargo.toml"), "[package]\nname = \"generated\"\n").unwrap();
+    fs::write(path.join("Cargo.lock"), "# changes all the time\n").unwrap();
+    fs::write(path.join("src/main.rs"), "fn main() {}").unwrap();
+    fs::write(path.join("src/empty.rs"), "").unwrap();
+    fs::write(path.join("logo.bin"), b"\x89PNG\x00\xff").unwrap();
+    fs::write(path.join("target/debug/generated"), b"\x7fELF").unwrap();
+    path
+}
+
+#[test]
+fn test_dir_snapshot() {
+    let path = make_output_dir("dir-snapshot");
+    assert_dir_snapshot!(&path, {
+        "target/**" => FileRedaction::Exclude,
+        "*.lock" => "[lockfile]",
+    }, @r###"
+    Cargo.lock
+    Cargo.toml
+    logo.bin
+    src/empty.rs
+    src/main.rs
+
+    === Cargo.lock ===
+    [lockfile]
+
+    === Cargo.toml ===
+    [package]
+    name = "generated"
+
+    === logo.bin ===
+    [binary 6 bytes, fnv1a 3644a773e727b092]
+
+    === src/empty.rs ===
+
+    === src/main.rs ===
+    fn main() {}
+    "###);
+    fs::remove_dir_all(&path).ok();
+}
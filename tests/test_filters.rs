@@ -0,0 +1,63 @@
+#![cfg(feature = "filters")]
+use std::env;
+use std::path::Path;
+
+use insta::{assert_debug_snapshot, assert_snapshot, with_settings, Settings, StandardFilter};
+
+#[test]
+fn test_basic_filter() {
+    with_settings!({filters => vec![(r"\b[[:xdigit:]]{32}\b", "[TOKEN]")]}, {
+        assert_snapshot!("token: 1a79a4d60de6718e8e5b326e338ae533", @"token: [TOKEN]");
+    });
+}
+
+#[test]
+fn test_standard_filters() {
+    let mut settings = Settings::clone_current();
+    settings.add_standard_filters(&[
+        StandardFilter::Uuid,
+        StandardFilter::Timestamp,
+        StandardFilter::TempDir,
+        StandardFilter::HomeDir,
+        StandardFilter::Port,
+        StandardFilter::PathSeparator,
+    ]);
+    settings.bind(|| {
+        let path = env::temp_dir().join("output").join("report.txt");
+        let home_dir = env::var_os("HOME")
+            .or_else(|| env::var_os("USERPROFILE"))
+            .unwrap();
+        let config = Path::new(&home_dir).join(".config");
+        assert_debug_snapshot!(
+            (
+                "4f6a4f93-2f1a-4b57-ae2c-3c42cb2dfd32",
+                "2021-05-01 12:30:00",
+                path,
+                config,
+                "127.0.0.1:43121",
+            ),
+            @r###"
+        (
+            "[UUID]",
+            "[TIMESTAMP]",
+            "[TEMP_DIR]/output/report.txt",
+            "[HOME_DIR]/.config",
+            "127.0.0.1:[PORT]",
+        )
+        "###
+        );
+    });
+}
+
+#[test]
+fn test_placeholders() {
+    with_settings!({allow_placeholders => true}, {
+        assert_snapshot!(
+            format!("pid: {}\nname: insta", std::process::id()),
+            @r###"
+        pid: [[~ regex: \d+ ~]]
+        name: insta
+        "###
+        );
+    });
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "html")]
+
+use insta::{assert_html_snapshot, with_settings};
+
+#[test]
+fn test_html_formatting() {
+    // differently written versions of the same markup match
+    for html in &[
+        "<div class=card id=main><h1>Title</h1><p>Some  text</p></div>",
+        "<DIV id='main' class=\"card\">\n  <h1>Title</h1>\n  <!-- body -->\n  <p>Some\n    text</p>\n</DIV>",
+    ] {
+        assert_html_snapshot!(html, @r###"
+        <div class="card" id="main">
+          <h1>Title</h1>
+          <p>Some text</p>
+        </div>
+        "###);
+    }
+}
+
+#[test]
+fn test_html_removed_attributes() {
+    with_settings!({html_removed_attributes => vec!["nonce".to_string(), "id".to_string()]}, {
+        assert_html_snapshot!(
+            r#"<script nonce="r4nd0m">init();</script><input id=field-17 name=email>"#,
+            @r###"
+        <script>
+          init();
+        </script>
+        <input name="email">
+        "###
+        );
+    });
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "http")]
+
+use insta::{assert_http_snapshot, with_settings, HttpExchange};
+
+fn create_user() -> HttpExchange {
+    HttpExchange::new("POST", "/users")
+        .request_header("Content-Type", "application/json")
+        .request_body(r#"{"name": "John"}"#)
+        .status(201)
+        .response_header("Content-Type", "application/json")
+        .response_header("Date", "Tue, 01 Jun 2021 12:00:00 GMT")
+        .response_header("Set-Cookie", "session=abc")
+        .response_header("Set-Cookie", "theme=dark")
+        .response_body(r#"{"id": 42, "name": "John"}"#)
+}
+
+#[test]
+fn test_http_exchange() {
+    assert_http_snapshot!(create_user(), @r###"
+    ---
+    request:
+      method: POST
+      url: /users
+      headers:
+        content-type: application/json
+      body:
+        name: John
+    response:
+      status: 201
+      headers:
+        content-type: application/json
+        date: "[date]"
+        set-cookie: "[set-cookie]"
+      body:
+        id: 42
+        name: John
+    "###);
+}
+
+#[test]
+fn test_http_selected_headers() {
+    with_settings!({http_headers => Some(vec!["date".to_string()]), http_redacted_headers => vec![]}, {
+        assert_http_snapshot!(
+            HttpExchange::new("GET", "/health")
+                .response_header("Date", "Tue, 01 Jun 2021 12:00:00 GMT")
+                .response_header("Server", "test/1.0")
+                .response_body("ok"),
+            @r###"
+        ---
+        request:
+          method: GET
+          url: /health
+        response:
+          status: 200
+          headers:
+            date: "Tue, 01 Jun 2021 12:00:00 GMT"
+          body: ok
+        "###
+        );
+    });
+}
+
+#[cfg(feature = "redactions")]
+#[test]
+fn test_http_body_redactions() {
+    assert_http_snapshot!(create_user(), {
+        ".response.body.id" => "[id]",
+    }, @r###"
+    ---
+    request:
+      method: POST
+      url: /users
+      headers:
+        content-type: application/json
+      body:
+        name: John
+    response:
+      status: 201
+      headers:
+        content-type: application/json
+        date: "[date]"
+        set-cookie: "[set-cookie]"
+      body:
+        id: "[id]"
+        name: John
+    "###);
+}
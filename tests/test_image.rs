@@ -0,0 +1,40 @@
+#![cfg(feature = "image")]
+
+use insta::{assert_image_snapshot, Settings};
+
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut rv = vec![];
+    {
+        let mut encoder = png::Encoder::new(&mut rv, width, height);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(pixels).unwrap();
+    }
+    rv
+}
+
+#[test]
+fn test_image_snapshot() {
+    let mut pixels = vec![];
+    for idx in 0..16u8 {
+        pixels.extend_from_slice(&[idx * 16, 255 - idx * 16, 128]);
+    }
+    assert_image_snapshot!("gradient", encode_png(4, 4, &pixels));
+
+    // a barely visible change still matches the stored snapshot
+    pixels[0] += 1;
+    assert_image_snapshot!("gradient", encode_png(4, 4, &pixels));
+}
+
+#[test]
+fn test_image_snapshot_threshold() {
+    let mut settings = Settings::clone_current();
+    settings.set_image_threshold(0.5);
+    settings.bind(|| {
+        // a dark gray pixel is close enough to the stored black one
+        let mut pixels = vec![0; 2 * 2 * 3];
+        pixels[..3].copy_from_slice(&[40, 40, 40]);
+        assert_image_snapshot!("black", encode_png(2, 2, &pixels));
+    });
+}
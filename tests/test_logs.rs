@@ -0,0 +1,33 @@
+#![cfg(feature = "log")]
+
+use insta::{assert_log_snapshot, with_settings};
+
+fn connect(url: &str) {
+    log::debug!("resolving {}", url);
+    log::info!("connecting to {}", url);
+    log::warn!("retrying at 2021-06-01T12:00:00.123Z (span_id=7f3a)");
+}
+
+#[test]
+fn test_log_capture() {
+    assert_log_snapshot!(connect("db://localhost"), @r###"
+    DEBUG test_logs: resolving db://localhost
+    INFO test_logs: connecting to db://localhost
+    WARN test_logs: retrying at [TIMESTAMP] (span_id=[SPAN_ID])
+    "###);
+}
+
+#[test]
+fn test_log_level() {
+    with_settings!({log_level => log::LevelFilter::Info}, {
+        assert_log_snapshot!({
+            connect("db://localhost");
+            log::error!("failed:\nno route");
+        }, @r###"
+        INFO test_logs: connecting to db://localhost
+        WARN test_logs: retrying at [TIMESTAMP] (span_id=[SPAN_ID])
+        ERROR test_logs: failed:
+          no route
+        "###);
+    });
+}
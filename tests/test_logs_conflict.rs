@@ -0,0 +1,29 @@
+#![cfg(feature = "log")]
+
+use std::panic;
+
+use log::{Log, Metadata, Record};
+
+struct OtherLogger;
+
+impl Log for OtherLogger {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, _: &Record<'_>) {}
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn test_log_capture_with_other_logger() {
+    log::set_logger(&OtherLogger).unwrap();
+    // the failure is reported the same way every time
+    for _ in 0..2 {
+        let err =
+            panic::catch_unwind(|| insta::assert_log_snapshot!(log::info!("hi"), @"")).unwrap_err();
+        let message = err.downcast_ref::<&str>().unwrap();
+        assert!(message.contains("another logger"), "{}", message);
+    }
+}
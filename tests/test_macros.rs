@@ -0,0 +1,72 @@
+#![cfg(feature = "macros")]
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread;
+
+use insta::{assert_snapshot, Settings};
+
+/// Polls a future to completion, each time on a new unnamed thread.
+fn block_on_many_threads<F: Future<Output = ()> + Send + 'static>(future: F) {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(ptr::null(), &VTABLE)
+    }
+
+    let mut future = Box::pin(future);
+    loop {
+        let (rv, returned) = thread::Builder::new()
+            .spawn(move || {
+                let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+                let rv = future.as_mut().poll(&mut Context::from_waker(&waker));
+                (rv, future)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        if rv.is_ready() {
+            break;
+        }
+        future = returned;
+    }
+}
+
+/// Returns `Pending` once.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            Poll::Pending
+        }
+    }
+}
+
+#[insta::test]
+fn test_sync_attribute() {
+    assert_snapshot!("sync");
+}
+
+#[test]
+fn test_future_moved_between_threads() {
+    block_on_many_threads(insta::_macro_support::run_test_future(
+        "test_moved_future",
+        async {
+            let mut settings = Settings::clone_current();
+            settings.set_snapshot_suffix("moved");
+            settings.bind_to_thread();
+            YieldNow(false).await;
+            assert_snapshot!("moved between threads");
+        },
+    ));
+}
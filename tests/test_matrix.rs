@@ -0,0 +1,47 @@
+#![cfg(feature = "matrix")]
+
+use insta::{assert_matrix_snapshot, with_settings};
+
+#[test]
+fn test_matrix_formatting() {
+    assert_matrix_snapshot!(vec![vec![1.0, 2.5, -3.0], vec![4.0, 5.0, 6.0]], @r###"
+    shape: [2, 3]
+     1.0   2.5  -3.0
+     4.0   5.0   6.0
+    "###);
+    assert_matrix_snapshot!([[[1, 2], [3, 4]], [[5, 6], [7, 8]]], @r###"
+    shape: [2, 2, 2]
+
+    [0, :, :]
+    1  2
+    3  4
+
+    [1, :, :]
+    5  6
+    7  8
+    "###);
+}
+
+#[test]
+fn test_matrix_ndarray_layout() {
+    // the serde representation of an ndarray array
+    let mut array = std::collections::BTreeMap::new();
+    array.insert("v", vec![1]);
+    array.insert("dim", vec![2, 2]);
+    array.insert("data", vec![1, 0, 0, 1]);
+    assert_matrix_snapshot!(array, @r###"
+    shape: [2, 2]
+    1  0
+    0  1
+    "###);
+}
+
+#[test]
+fn test_matrix_tolerance() {
+    with_settings!({float_tolerance => 1e-9}, {
+        assert_matrix_snapshot!(vec![0.1 + 0.2, 1.0 / 3.0], @r###"
+        shape: [2]
+        0.3  0.333333333333
+        "###);
+    });
+}
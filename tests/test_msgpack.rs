@@ -0,0 +1,35 @@
+#![cfg(feature = "msgpack")]
+
+use insta::assert_msgpack_snapshot;
+
+#[test]
+fn test_msgpack_decoding() {
+    // {"id": 300, "name": "peter", "scores": [1.5, -7], "admin": false, "raw": bin [1, 2]}
+    let bytes: &[u8] = b"\x85\xa2id\xcd\x01\x2c\xa4name\xa5peter\xa6scores\x92\xcb\x3f\xf8\x00\x00\x00\x00\x00\x00\xf9\xa5admin\xc2\xa3raw\xc4\x02\x01\x02";
+    assert_msgpack_snapshot!(bytes, @r###"
+    ---
+    id: 300
+    name: peter
+    scores:
+      - 1.5
+      - -7
+    admin: false
+    raw:
+      - 1
+      - 2
+    "###);
+}
+
+#[cfg(feature = "redactions")]
+#[test]
+fn test_msgpack_redactions() {
+    // {"token": "abc123", "user": 7}
+    let bytes = b"\x82\xa5token\xa6abc123\xa4user\x07".to_vec();
+    assert_msgpack_snapshot!(bytes, {
+        ".token" => "[token]",
+    }, @r###"
+    ---
+    token: "[token]"
+    user: 7
+    "###);
+}
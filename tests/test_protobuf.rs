@@ -0,0 +1,42 @@
+#![cfg(feature = "protobuf")]
+
+use insta::assert_protobuf_snapshot;
+
+// message User { uint64 id = 1; string name = 2; repeated string tags = 3; Address address = 4; }
+// message Address { uint32 zip = 1; string city = 2; }
+const USER: &[u8] =
+    b"\x08\x96\x01\x12\x05peter\x1a\x05admin\x1a\x03dev\x22\x0b\x08\xd2\x09\x12\x06Vienna";
+
+#[test]
+fn test_protobuf_decoding() {
+    assert_protobuf_snapshot!(USER, @r###"
+    ---
+    1: 150
+    2: peter
+    3:
+      - admin
+      - dev
+    4:
+      1: 1234
+      2: Vienna
+    "###);
+}
+
+#[cfg(feature = "redactions")]
+#[test]
+fn test_protobuf_redactions() {
+    assert_protobuf_snapshot!(USER.to_vec(), {
+        "[1]" => "[id]",
+        "[4][2]" => "[city]",
+    }, @r###"
+    ---
+    1: "[id]"
+    2: peter
+    3:
+      - admin
+      - dev
+    4:
+      1: 1234
+      2: "[city]"
+    "###);
+}
@@ -272,3 +272,235 @@ fn test_redact_recursive() {
     }
     "###);
 }
+
+#[test]
+fn test_redact_keys() {
+    use std::collections::BTreeMap;
+
+    let mut cache = BTreeMap::new();
+    cache.insert("8f3a5d", "first");
+    cache.insert("1c0b7e", "second");
+    let mut map = BTreeMap::new();
+    map.insert("cache", cache);
+
+    assert_yaml_snapshot!(map, {
+        ".cache.*" => insta::key_redaction(insta::dynamic_redaction(|value, _| {
+            format!("[hash of length {}]", value.as_str().unwrap().len())
+        })),
+    }, @r###"
+    ---
+    cache:
+      "[hash of length 6]": second
+      "[hash of length 6]-2": first
+    "###);
+    assert_yaml_snapshot!(map, {
+        ".cache.*" => insta::key_redaction("[hash]"),
+    }, @r###"
+    ---
+    cache:
+      "[hash]": second
+      "[hash]-2": first
+    "###);
+}
+
+#[test]
+fn test_redact_exclude() {
+    #[derive(Serialize)]
+    pub struct Report {
+        name: &'static str,
+        metrics: Vec<u64>,
+        items: Vec<(u32, &'static str)>,
+    }
+
+    assert_json_snapshot!(Report {
+        name: "report",
+        metrics: vec![1, 2, 3],
+        items: vec![(1, "a"), (2, "b"), (3, "c")],
+    }, {
+        ".metrics" => insta::exclude_redaction(),
+        ".items[1:]" => insta::exclude_redaction(),
+    }, @r###"
+    {
+      "name": "report",
+      "items": [
+        [
+          1,
+          "a"
+        ]
+      ]
+    }
+    "###);
+}
+
+#[test]
+fn test_redact_sorted() {
+    #[derive(Serialize)]
+    pub struct Item {
+        id: u32,
+        name: &'static str,
+    }
+
+    #[derive(Serialize)]
+    pub struct Order {
+        tags: Vec<&'static str>,
+        items: Vec<Item>,
+    }
+
+    assert_yaml_snapshot!(Order {
+        tags: vec!["c", "a", "b"],
+        items: vec![
+            Item { id: 3, name: "three" },
+            Item { id: 1, name: "one" },
+            Item { id: 2, name: "two" },
+        ],
+    }, {
+        ".tags" => insta::sorted_redaction(),
+        ".items" => insta::sorted_redaction_by(".id"),
+    }, @r###"
+    ---
+    tags:
+      - a
+      - b
+      - c
+    items:
+      - id: 1
+        name: one
+      - id: 2
+        name: two
+      - id: 3
+        name: three
+    "###);
+}
+
+#[test]
+fn test_redact_contextual() {
+    #[derive(Serialize)]
+    pub struct Object {
+        id: u32,
+        #[serde(rename = "type")]
+        ty: &'static str,
+    }
+
+    assert_yaml_snapshot!(vec![
+        Object { id: 1, ty: "temp" },
+        Object { id: 2, ty: "user" },
+    ], {
+        "[].id" => insta::contextual_redaction(|value, context| {
+            assert!(context.parent().unwrap().as_slice().is_none());
+            assert!(context.path().to_string().ends_with(".id"));
+            match context.sibling("type").and_then(|x| x.as_str()) {
+                Some("temp") => "[temp id]".into(),
+                _ => value,
+            }
+        }),
+    }, @r###"
+    ---
+    - id: "[temp id]"
+      type: temp
+    - id: 2
+      type: user
+    "###);
+}
+
+#[test]
+fn test_redact_rounded() {
+    #[derive(Serialize)]
+    pub struct Place {
+        name: &'static str,
+        location: (f64, f64),
+        accuracy: f32,
+    }
+
+    let place = Place {
+        name: "Vienna",
+        location: (48.208_176_123, 16.373_819_456),
+        accuracy: 0.987_654,
+    };
+    assert_json_snapshot!(place, {
+        ".location" => insta::rounded_redaction(3),
+        ".accuracy" => insta::rounded_redaction(1),
+    }, @r###"
+    {
+      "name": "Vienna",
+      "location": [
+        48.208,
+        16.374
+      ],
+      "accuracy": 1.0
+    }
+    "###);
+}
+
+#[test]
+fn test_redact_debug() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    pub struct Session {
+        user: &'static str,
+        token: String,
+        score: f64,
+    }
+
+    assert_debug_snapshot!(Session {
+        user: "john_doe",
+        token: "9c1f5e3a".to_string(),
+        score: 0.123_456_7,
+    }, {
+        ".token" => "[token]",
+        ".score" => insta::rounded_redaction(2),
+    }, @r###"
+    Session {
+        user: "john_doe",
+        token: "[token]",
+        score: 0.12,
+    }
+    "###);
+}
+
+#[test]
+fn test_redact_json_pointer() {
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    pub struct Item {
+        id: u32,
+    }
+
+    #[derive(Serialize)]
+    pub struct Document {
+        items: Vec<Item>,
+        #[serde(rename = "a/b")]
+        slashed: &'static str,
+        codes: BTreeMap<&'static str, &'static str>,
+    }
+
+    let mut codes = BTreeMap::new();
+    codes.insert("0", "zero");
+    codes.insert("1", "one");
+
+    assert_json_snapshot!(Document {
+        items: vec![Item { id: 1 }, Item { id: 2 }],
+        slashed: "slash",
+        codes,
+    }, {
+        "/items/0/id" => "[first]",
+        "/a~1b" => "[escaped]",
+        "/codes/1" => "[code]",
+    }, @r###"
+    {
+      "items": [
+        {
+          "id": "[first]"
+        },
+        {
+          "id": 2
+        }
+      ],
+      "a/b": "[escaped]",
+      "codes": {
+        "0": "zero",
+        "1": "[code]"
+      }
+    }
+    "###);
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "schema")]
+
+use std::path::Path;
+
+use insta::{assert_json_snapshot, assert_yaml_snapshot, with_settings, JsonSchema};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct User {
+    id: i64,
+    name: &'static str,
+}
+
+#[test]
+fn test_schema_inline() {
+    let schema = serde_json::json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "id": {"type": "integer"},
+            "name": {"type": "string", "minLength": 1},
+        },
+    });
+    with_settings!({json_schema => Some(JsonSchema::from(schema))}, {
+        assert_json_snapshot!(User { id: 1, name: "john" }, @r###"
+        {
+          "id": 1,
+          "name": "john"
+        }
+        "###);
+    });
+}
+
+#[test]
+fn test_schema_file() {
+    with_settings!({json_schema => Some(JsonSchema::from(Path::new("tests/inputs/user.schema.json")))}, {
+        assert_yaml_snapshot!(User { id: 1, name: "john" }, @r###"
+        ---
+        id: 1
+        name: john
+        "###);
+    });
+}
+
+#[cfg(feature = "redactions")]
+#[test]
+fn test_schema_before_redactions() {
+    with_settings!({json_schema => Some(JsonSchema::from(Path::new("tests/inputs/user.schema.json")))}, {
+        assert_yaml_snapshot!(User { id: 1, name: "john" }, {
+            ".id" => "[id]",
+        }, @r###"
+        ---
+        id: "[id]"
+        name: john
+        "###);
+    });
+}
+
+#[test]
+#[should_panic(expected = "violates the JSON schema")]
+fn test_schema_violation() {
+    // the snapshot matches but the value breaks the contract
+    with_settings!({json_schema => Some(JsonSchema::from(Path::new("tests/inputs/user.schema.json")))}, {
+        assert_yaml_snapshot!(User { id: 0, name: "john" }, @r###"
+        ---
+        id: 0
+        name: john
+        "###);
+    });
+}
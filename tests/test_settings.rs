@@ -1,4 +1,7 @@
-use insta::{assert_yaml_snapshot, with_settings, Settings};
+use insta::{
+    assert_snapshot, assert_yaml_snapshot, with_settings, AnsiEscapes, DiffAlgorithm, Settings,
+    Snapshot, SnapshotLayout,
+};
 use std::collections::HashMap;
 
 #[test]
@@ -74,3 +77,244 @@ fn test_snapshot_no_module_prepending() {
         assert_yaml_snapshot!(vec![1, 2, 3]);
     });
 }
+
+#[test]
+fn test_propagate_to_thread() {
+    let mut map = HashMap::new();
+    map.insert("a", "first value");
+    map.insert("b", "second value");
+    map.insert("c", "third value");
+    map.insert("d", "fourth value");
+
+    with_settings!({sort_maps => true}, {
+        std::thread::spawn(Settings::propagate(move || {
+            assert_yaml_snapshot!(&map, @r###"
+            ---
+            a: first value
+            b: second value
+            c: third value
+            d: fourth value
+            "###);
+        }))
+        .join()
+        .unwrap();
+    });
+}
+
+#[test]
+fn test_float_tolerance() {
+    with_settings!({float_tolerance => 1e-9}, {
+        assert_yaml_snapshot!(vec![0.1 + 0.2, 1.0 / 3.0], @r###"
+        ---
+        - 0.3
+        - 0.333333333333
+        "###);
+    });
+}
+
+#[test]
+fn test_number_precision() {
+    with_settings!({number_precision => Some(4)}, {
+        assert_yaml_snapshot!(vec![0.1 + 0.2, -0.0, 2.0, 1.0 / 3.0], @r###"
+        ---
+        - 0.3
+        - 0
+        - 2
+        - 0.3333
+        "###);
+        // stored numbers are rounded as well when comparing
+        assert_yaml_snapshot!(vec![2.0, 1.0 / 3.0], @r###"
+        ---
+        - 2.0
+        - 0.33333333
+        "###);
+    });
+}
+
+#[test]
+fn test_ansi_escapes() {
+    let output = "\x1b[1;31merror\x1b[0m: file \x1b[4mmain.rs\x1b[24m not found";
+    with_settings!({ansi_escapes => AnsiEscapes::Strip}, {
+        assert_snapshot!(output, @"error: file main.rs not found");
+    });
+    with_settings!({ansi_escapes => AnsiEscapes::Render}, {
+        assert_snapshot!(output, @"<bold><red>error</red></bold>: file <underline>main.rs</underline> not found");
+    });
+}
+
+#[test]
+fn test_structural_json() {
+    let value = serde_json::json!({"name": "John", "scores": [1.0, 2.5]});
+    insta::assert_json_value_snapshot!(value, @r###"
+    {
+      "scores": [1, 2.5],
+      "name": "John"
+    }
+    "###);
+    with_settings!({structural_json => true}, {
+        insta::assert_json_snapshot!(value, @r###"{"scores": [1, 2.50], "name": "John"}"###);
+    });
+}
+
+#[test]
+fn test_diff_context() {
+    let mut settings = Settings::clone_current();
+    settings.set_diff_context(1);
+    settings.bind(|| {
+        assert_eq!(Settings::clone_current().diff_context(), 1);
+    });
+}
+
+#[test]
+fn test_diff_algorithm() {
+    assert_eq!("myers".parse(), Ok(DiffAlgorithm::Myers));
+    assert!("histogram".parse::<DiffAlgorithm>().is_err());
+    let mut settings = Settings::clone_current();
+    settings.set_diff_algorithm(DiffAlgorithm::Lcs);
+    settings.bind(|| {
+        assert_eq!(
+            Settings::clone_current().diff_algorithm(),
+            Some(DiffAlgorithm::Lcs)
+        );
+    });
+}
+
+#[test]
+fn test_max_snapshot_size() {
+    with_settings!({max_snapshot_size => 16}, {
+        assert_yaml_snapshot!(vec![1, 2], @r###"
+        ---
+        - 1
+        - 2
+        "###);
+    });
+}
+
+#[test]
+#[should_panic(expected = "exceeds the maximum snapshot size of 16 bytes")]
+fn test_max_snapshot_size_exceeded() {
+    with_settings!({max_snapshot_size => 16}, {
+        assert_yaml_snapshot!(vec![1, 2, 3, 4, 5], @"");
+    });
+}
+
+#[test]
+fn test_description() {
+    let mut settings = Settings::clone_current();
+    settings.set_description("the numbers from one to three");
+    settings.bind(|| {
+        assert_yaml_snapshot!("description", vec![1, 2, 3]);
+    });
+    let snapshot = Snapshot::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/snapshots/test_settings__description.snap"
+    ))
+    .unwrap();
+    assert_eq!(
+        snapshot.metadata().description(),
+        Some("the numbers from one to three")
+    );
+}
+
+#[test]
+fn test_info() {
+    let mut settings = Settings::clone_current();
+    settings.add_info("ticket", "JIRA-123");
+    settings.add_info("schema_version", 4);
+    settings.bind(|| {
+        assert_yaml_snapshot!("info", vec![1, 2, 3]);
+    });
+    let snapshot = Snapshot::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/snapshots/test_settings__info.snap"
+    ))
+    .unwrap();
+    let info = snapshot.metadata().info();
+    assert_eq!(info["ticket"], "JIRA-123");
+    assert_eq!(info["schema_version"], "4");
+}
+
+#[test]
+fn test_expires() {
+    let mut settings = Settings::clone_current();
+    settings.set_expires("2999-12-31");
+    settings.bind(|| {
+        assert_yaml_snapshot!("expires", vec![1, 2, 3]);
+    });
+    let snapshot = Snapshot::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/snapshots/test_settings__expires.snap"
+    ))
+    .unwrap();
+    assert_eq!(snapshot.metadata().expires(), Some("2999-12-31"));
+    assert!(!snapshot.metadata().is_expired());
+}
+
+#[test]
+#[should_panic(expected = "invalid expiry date")]
+fn test_invalid_expires() {
+    Settings::clone_current().set_expires("next week");
+}
+
+#[test]
+fn test_snapshot_variant() {
+    let mut settings = Settings::clone_current();
+    settings.set_snapshot_variant("alt");
+    settings.bind(|| {
+        assert_yaml_snapshot!("variant", vec![2]);
+    });
+    // variants that are not stored yet pass if any other variant matches
+    settings.set_snapshot_variant("other");
+    settings.bind(|| {
+        assert_yaml_snapshot!("variant", vec![2]);
+    });
+}
+
+#[test]
+fn test_snapshot_nested_layout() {
+    with_settings!({snapshot_layout => SnapshotLayout::Nested}, {
+        assert_yaml_snapshot!("nested", vec![1, 2]);
+    });
+    assert!(std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots/test_settings/nested.snap")
+        .is_file());
+}
+
+#[cfg(feature = "ron")]
+#[test]
+fn test_ron_options() {
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct User {
+        id: u32,
+        roles: Vec<String>,
+    }
+
+    let user = User {
+        id: 42,
+        roles: vec!["admin".into(), "user".into()],
+    };
+    let mut settings = Settings::clone_current();
+    settings.set_ron_struct_names(false);
+    settings.set_ron_indent(4);
+    settings.bind(|| {
+        insta::assert_ron_snapshot!(&user, @r###"
+        (
+            id: 42,
+            roles: [
+                "admin",
+                "user",
+            ],
+        )
+        "###);
+    });
+
+    settings.set_ron_struct_names(true);
+    settings.set_ron_compact_width(Some(40));
+    settings.bind(|| {
+        insta::assert_ron_snapshot!(&user, @r###"User(id: 42, roles: ["admin", "user"])"###);
+    });
+
+    // compact snapshots are still valid RON
+    let compact = "User(id: 42, roles: [\"admin\", \"user\"])";
+    assert_eq!(ron::from_str::<User>(compact).unwrap(), user);
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "sql")]
+
+use insta::{assert_sql_snapshot, with_settings};
+
+#[test]
+fn test_sql_formatting() {
+    // differently formatted versions of the same query match
+    for sql in &[
+        "select id, name from users where active = true order by name",
+        "SELECT id,name\n  FROM users\n WHERE active = TRUE\n ORDER BY name -- admin list",
+    ] {
+        assert_sql_snapshot!(sql, @r###"
+        SELECT
+          id,
+          name
+        FROM
+          users
+        WHERE
+          active = TRUE
+        ORDER BY
+          name
+        "###);
+    }
+}
+
+#[test]
+fn test_sql_strip_literals() {
+    with_settings!({sql_strip_literals => true}, {
+        assert_sql_snapshot!(
+            "insert into events (id, created_at) values (42, '2021-03-04 12:00:00') returning id",
+            @r###"
+        INSERT INTO
+          events (id, created_at)
+        VALUES
+          (?, ?)
+        RETURNING
+          id
+        "###
+        );
+    });
+}
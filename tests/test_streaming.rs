@@ -0,0 +1,37 @@
+use std::io::Write;
+
+use insta::{assert_binary_snapshot, snapshot_writer};
+
+#[test]
+fn test_snapshot_writer() {
+    let mut writer = snapshot_writer!("lines.txt");
+    for idx in 0..1000 {
+        writeln!(writer, "line {}", idx).unwrap();
+    }
+    writer.finish();
+}
+
+#[test]
+fn test_snapshot_writer_matches_binary_snapshot() {
+    let mut writer = snapshot_writer!("bytes.bin");
+    writer.write_all(b"Hello\x00").unwrap();
+    writer.write_all(b"World\xff").unwrap();
+    writer.finish();
+
+    assert_binary_snapshot!("bytes.bin", b"Hello\x00World\xff".to_vec());
+}
+
+#[test]
+fn test_snapshot_writer_clash() {
+    let mut writer = snapshot_writer!("bytes.bin");
+    writer.write_all(b"Hello\x00World\xff").unwrap();
+    writer.finish();
+
+    let err = std::panic::catch_unwind(|| {
+        assert_binary_snapshot!("bytes.bin", b"Goodbye".to_vec());
+    })
+    .unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+    assert!(msg.starts_with("Insta snapshot name clash detected: "));
+    assert!(msg.contains("test_streaming__bytes.snap is asserted with different contents"));
+}
@@ -6,3 +6,21 @@ fn test_basic_suffixes() {
         });
     }
 }
+
+#[test]
+fn test_cfg_suffix() {
+    assert_eq!(insta::cfg_suffix!(test), "test");
+    let expected = if cfg!(feature = "redactions") {
+        "test-redactions"
+    } else {
+        "test"
+    };
+    assert_eq!(insta::cfg_suffix!(test, feature = "redactions"), expected);
+    assert_eq!(insta::cfg_suffix!(debug_assertions, test,), {
+        if cfg!(debug_assertions) {
+            "debug_assertions-test"
+        } else {
+            "test"
+        }
+    });
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "table")]
+
+use std::collections::BTreeMap;
+
+use insta::{assert_table_snapshot, with_settings};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Player {
+    id: u32,
+    name: &'static str,
+    score: Option<f64>,
+}
+
+fn players() -> Vec<Player> {
+    vec![
+        Player {
+            id: 1,
+            name: "peter",
+            score: Some(1.5),
+        },
+        Player {
+            id: 2,
+            name: "john",
+            score: None,
+        },
+        Player {
+            id: 10,
+            name: "anne",
+            score: Some(1.0 / 3.0),
+        },
+    ]
+}
+
+#[test]
+fn test_table_rows() {
+    assert_table_snapshot!(players(), @r###"
+    shape: (3, 3)
+    | id  | name  | score              |
+    | int | str   | float              |
+    |-----|-------|--------------------|
+    |   1 | peter |                1.5 |
+    |   2 | john  |               null |
+    |  10 | anne  | 0.3333333333333333 |
+    "###);
+}
+
+#[test]
+fn test_table_columns() {
+    let mut columns = BTreeMap::new();
+    columns.insert("city", vec!["Vienna", "Graz"]);
+    columns.insert("country", vec!["AT", "AT"]);
+    assert_table_snapshot!(columns, @r###"
+    shape: (2, 2)
+    | city   | country |
+    | str    | str     |
+    |--------|---------|
+    | Vienna | AT      |
+    | Graz   | AT      |
+    "###);
+}
+
+#[test]
+fn test_table_options() {
+    with_settings!({
+        table_max_rows => Some(2),
+        table_float_precision => Some(2),
+        table_excluded_columns => vec!["id".to_string()],
+    }, {
+        assert_table_snapshot!(players(), @r###"
+        shape: (3, 2)
+        | name  | score |
+        | str   | float |
+        |-------|-------|
+        | peter |  1.50 |
+        | john  |  null |
+        ... 1 more row
+        "###);
+    });
+}
+
+#[test]
+fn test_table_polars_dataframe() {
+    // the serde form of a polars `DataFrame`
+    let df = serde_json::json!({
+        "columns": [
+            {"name": "id", "datatype": "UInt32", "values": [1, 2]},
+            {"name": "score", "datatype": "Float64", "values": [0.5, null]},
+            {"name": "tags", "datatype": {"List": "Utf8"}, "values": [["a"], []]},
+        ]
+    });
+    assert_table_snapshot!(df, @r###"
+    shape: (2, 3)
+    | id     | score   | tags            |
+    | UInt32 | Float64 | {"List":"Utf8"} |
+    |--------|---------|-----------------|
+    |      1 |     0.5 | ["a"]           |
+    |      2 |    null | []              |
+    "###);
+}
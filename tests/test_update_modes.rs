@@ -0,0 +1,71 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use insta::Settings;
+
+#[test]
+fn test_new_only() {
+    let old_update_value = env::var("INSTA_UPDATE");
+    let old_force_pass_value = env::var("INSTA_FORCE_PASS");
+    env::set_var("INSTA_UPDATE", "new-only");
+    env::set_var("INSTA_FORCE_PASS", "0");
+
+    let dir = env::temp_dir().join(format!("insta-new-only-{}", std::process::id()));
+    let snapshot_file = dir.join("test_update_modes__value.snap");
+    fs::remove_dir_all(&dir).ok();
+
+    let run = |value: &'static str| {
+        let dir = dir.clone();
+        thread::spawn(move || {
+            let mut settings = Settings::clone_current();
+            settings.set_snapshot_path(dir);
+            settings.bind(|| insta::assert_snapshot!("value", value));
+        })
+        .join()
+    };
+
+    // a missing snapshot is written right away
+    assert!(run("first").is_ok());
+    let written = fs::read_to_string(&snapshot_file).unwrap();
+    // a mismatch fails without touching the snapshot
+    let err = run("second").unwrap_err();
+    let after_mismatch = fs::read_to_string(&snapshot_file).unwrap();
+    let has_new_file = snapshot_file.with_extension("snap.new").exists();
+    fs::remove_dir_all(&dir).ok();
+
+    // new inline snapshots are left to cargo-insta, changed ones are not
+    let pending_file =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/.test_update_modes.rs.pending-snap");
+    fs::remove_file(&pending_file).ok();
+    let inline_mismatch = thread::spawn(|| insta::assert_snapshot!("second", @"first")).join();
+    let pending_after_mismatch = pending_file.exists();
+    let inline_new = thread::spawn(|| insta::assert_snapshot!("first", @"")).join();
+    let pending = fs::read_to_string(&pending_file).unwrap_or_default();
+    fs::remove_file(&pending_file).ok();
+
+    if let Ok(value) = old_update_value {
+        env::set_var("INSTA_UPDATE", value);
+    } else {
+        env::remove_var("INSTA_UPDATE");
+    }
+    if let Ok(value) = old_force_pass_value {
+        env::set_var("INSTA_FORCE_PASS", value);
+    } else {
+        env::remove_var("INSTA_FORCE_PASS");
+    }
+
+    assert!(written.ends_with("first\n"));
+    assert_eq!(
+        err.downcast_ref::<String>().unwrap(),
+        "snapshot assertion for 'value' failed in line 24"
+    );
+    assert_eq!(after_mismatch, written);
+    assert!(!has_new_file);
+
+    assert!(inline_mismatch.is_err());
+    assert!(!pending_after_mismatch);
+    assert!(inline_new.is_err());
+    assert!(pending.contains(r#""snapshot":"first""#));
+}
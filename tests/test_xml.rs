@@ -0,0 +1,66 @@
+#![cfg(feature = "xml")]
+
+use insta::assert_xml_snapshot;
+use serde::Serialize;
+
+#[test]
+fn test_raw_xml_canonicalization() {
+    assert_xml_snapshot!(r#"<?xml version="1.0"?>
+        <config   version="2"   name="demo">
+            <!-- comment is dropped -->
+            <entry key="b">  second
+               value </entry>
+            <entry key="a">first</entry>
+        </config>"#, @r###"
+    <config name="demo" version="2">
+      <entry key="b">second value</entry>
+      <entry key="a">first</entry>
+    </config>
+    "###);
+}
+
+#[test]
+fn test_serialized_xml() {
+    #[derive(Serialize)]
+    pub struct User {
+        id: u32,
+        username: String,
+        roles: Vec<&'static str>,
+    }
+
+    assert_xml_snapshot!(User {
+        id: 42,
+        username: "peter-doe".into(),
+        roles: vec!["admin", "user"],
+    }, @r###"
+    <User>
+      <id>42</id>
+      <username>peter-doe</username>
+      <roles>admin</roles>
+      <roles>user</roles>
+    </User>
+    "###);
+}
+
+#[cfg(feature = "redactions")]
+#[test]
+fn test_xml_redactions() {
+    assert_xml_snapshot!(
+        r#"<session token="abc123"><user id="7">john</user><started>1614500000</started></session>"#,
+        {
+            ".session[\"@token\"]" => "[token]",
+            ".session.started" => "[timestamp]",
+        },
+        @r###"
+    <session token="[token]">
+      <user id="7">john</user>
+      <started>[timestamp]</started>
+    </session>
+    "###
+    );
+}
+
+#[test]
+fn test_invalid_xml_is_text() {
+    assert_xml_snapshot!("<a> 1 < 2", @"<value>&lt;a&gt; 1 &lt; 2</value>");
+}